@@ -0,0 +1,185 @@
+//! The audit log: a line of JSON for every command the orchestrator runs, with its timing,
+//! model, token counts and outcome, for usage accounting and debugging.
+
+use anyhow::Result;
+use rusty_genius_core::protocol::{BrainstemCommand, TokenUsage};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// How large an audit log grows before it is rotated, by default.
+pub const DEFAULT_AUDIT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Where [Orchestrator::set_audit_log](crate::Orchestrator::set_audit_log) records commands.
+/// A log that would grow past its size is moved aside to the same path ending in `.1`,
+/// replacing the one before, and started afresh.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+    max_bytes: u64,
+    redact_prompts: bool,
+}
+
+impl AuditLog {
+    /// A log at `path` (`audit.jsonl` in the config dir, for `ogenius serve`).
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes: DEFAULT_AUDIT_MAX_BYTES,
+            redact_prompts: false,
+        }
+    }
+
+    /// Rotate the log before it grows past `max_bytes`.
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Leave prompts out of the records.
+    pub fn redact_prompts(mut self, redact: bool) -> Self {
+        self.redact_prompts = redact;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Where the log is moved when it is rotated.
+    pub fn rotated_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".1");
+        path.into()
+    }
+
+    /// Append `record`, rotating the log first when it would grow too large.
+    pub fn append(&self, record: &AuditRecord) -> Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let size = std::fs::metadata(&self.path).map_or(0, |meta| meta.len());
+        if size > 0 && size + line.len() as u64 > self.max_bytes {
+            std::fs::rename(&self.path, self.rotated_path())?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// How a command ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditStatus {
+    Ok,
+    Error,
+    Cancelled,
+    /// The engine crashed on it
+    Crashed,
+}
+
+/// A line of the audit log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// When the command started, in milliseconds since the Unix epoch.
+    pub started_ms: u64,
+    pub id: String,
+    /// The command's [name](BrainstemCommand::name).
+    pub command: String,
+    /// The model the command named, or the one it ran on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// The prompts, inputs or query the command was given, unless redacted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prompts: Vec<String>,
+    /// How long the command took; requests that stream are timed until their last event.
+    pub duration_ms: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub status: AuditStatus,
+    /// The first error the command was answered with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The record of a command still running.
+pub(crate) struct Audit {
+    record: AuditRecord,
+    started: Instant,
+}
+
+impl Audit {
+    /// Start the record of `command`, sent as the request `id`.
+    pub(crate) fn start(id: &str, command: &BrainstemCommand, log: &AuditLog) -> Self {
+        let started_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        let prompts = match command {
+            _ if log.redact_prompts => Vec::new(),
+            BrainstemCommand::Infer { prompt, .. } => vec![prompt.clone()],
+            BrainstemCommand::InferBatch { prompts, .. } => prompts.clone(),
+            BrainstemCommand::Embed { input, .. } => vec![input.clone()],
+            BrainstemCommand::EmbedBatch { inputs, .. } => inputs.clone(),
+            BrainstemCommand::Rerank { query, .. } | BrainstemCommand::Rag { query, .. } => {
+                vec![query.clone()]
+            }
+            BrainstemCommand::Chat { message, .. } => vec![message.content.clone()],
+            _ => Vec::new(),
+        };
+        Self {
+            record: AuditRecord {
+                started_ms,
+                id: id.to_string(),
+                command: command.name().to_string(),
+                model: command.model().cloned(),
+                prompts,
+                duration_ms: 0,
+                prompt_tokens: 0,
+                completion_tokens: 0,
+                status: AuditStatus::Ok,
+                error: None,
+            },
+            started: Instant::now(),
+        }
+    }
+
+    /// Note the model the command ran on, when it named none.
+    pub(crate) fn ran_on(&mut self, model: Option<&String>) {
+        if self.record.model.is_none() {
+            self.record.model = model.cloned();
+        }
+    }
+
+    pub(crate) fn count(&mut self, usage: &TokenUsage) {
+        self.record.prompt_tokens += usage.prompt_tokens as u64;
+        self.record.completion_tokens += usage.completion_tokens as u64;
+    }
+
+    /// Note an error the command was answered with; the first one is kept.
+    pub(crate) fn fail(&mut self, error: &str) {
+        self.record.error.get_or_insert_with(|| error.to_string());
+    }
+
+    /// Write the record to `log` as ending with `status`; an error noted makes an `Ok` an
+    /// `Error`.
+    pub(crate) fn finish(mut self, log: &AuditLog, status: AuditStatus) {
+        self.record.duration_ms = self.started.elapsed().as_millis() as u64;
+        self.record.status = match status {
+            AuditStatus::Ok if self.record.error.is_some() => AuditStatus::Error,
+            status => status,
+        };
+        if let Err(e) = log.append(&self.record) {
+            eprintln!(
+                "NOTICE: Audit record of [{}] not written: {}",
+                self.record.id, e
+            );
+        }
+    }
+}
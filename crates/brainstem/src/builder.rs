@@ -0,0 +1,131 @@
+//! Assembling an [Orchestrator] from parts brought by the caller.
+
+use crate::{CortexStrategy, Orchestrator, DEFAULT_CHANNEL_CAPACITY};
+use anyhow::Result;
+use rusty_genius_core::engine::Engine;
+
+#[cfg(feature = "cortex-engine")]
+use facecrab::AssetAuthority;
+#[cfg(feature = "cortex-engine")]
+use futures::FutureExt;
+
+/// Builds an [Orchestrator]; every part left out is made as [Orchestrator::new] makes it.
+///
+/// ```no_run
+/// # use rusty_genius_stem::{CortexStrategy, Orchestrator};
+/// # async fn build(engine: Box<dyn rusty_genius_core::engine::Engine>) -> anyhow::Result<()> {
+/// let orchestrator = Orchestrator::builder()
+///     .engine(engine)
+///     .strategy(CortexStrategy::KeepAlive)
+///     .channel_capacity(16, 256)
+///     .build()
+///     .await?;
+/// let (input_tx, output_rx, run) = orchestrator.connect();
+/// # Ok(())
+/// # }
+/// ```
+pub struct OrchestratorBuilder {
+    engine: Option<Box<dyn Engine>>,
+    #[cfg(feature = "cortex-engine")]
+    asset_authority: Option<AssetAuthority>,
+    strategy: Option<CortexStrategy>,
+    channel_capacity: (usize, usize),
+}
+
+impl Default for OrchestratorBuilder {
+    fn default() -> Self {
+        Self {
+            engine: None,
+            #[cfg(feature = "cortex-engine")]
+            asset_authority: None,
+            strategy: None,
+            channel_capacity: (DEFAULT_CHANNEL_CAPACITY, DEFAULT_CHANNEL_CAPACITY),
+        }
+    }
+}
+
+impl OrchestratorBuilder {
+    /// Run on `engine` instead of the one `create_engine` picks. An engine brought this way
+    /// isn't replaced when it crashes, and `Transcribe` needs
+    /// [Orchestrator::set_transcriber].
+    pub fn engine(mut self, engine: Box<dyn Engine>) -> Self {
+        self.engine = Some(engine);
+        self
+    }
+
+    /// Resolve and download models with `authority`; sessions and the last model loaded are
+    /// kept in its directories.
+    #[cfg(feature = "cortex-engine")]
+    pub fn asset_authority(mut self, authority: AssetAuthority) -> Self {
+        self.asset_authority = Some(authority);
+        self
+    }
+
+    /// When to unload the idle engine; defaults to after 5 minutes.
+    pub fn strategy(mut self, strategy: CortexStrategy) -> Self {
+        self.strategy = Some(strategy);
+        self
+    }
+
+    /// Capacities of the channels [Orchestrator::connect] makes for commands and outputs.
+    pub fn channel_capacity(mut self, input: usize, output: usize) -> Self {
+        self.channel_capacity = (input, output);
+        self
+    }
+
+    #[cfg(feature = "cortex-engine")]
+    pub async fn build(self) -> Result<Orchestrator> {
+        let asset_authority = match self.asset_authority {
+            Some(authority) => authority,
+            None => AssetAuthority::new()?,
+        };
+        let session_dir = asset_authority.session_dir();
+        let last_model_file = asset_authority.config_dir().join("last_model.json");
+
+        let mut orchestrator = match self.engine {
+            Some(engine) => Orchestrator::from_parts(engine, asset_authority),
+            None => {
+                let engine = rusty_genius_cortex::create_engine().await;
+                let mut orchestrator = Orchestrator::from_parts(engine, asset_authority);
+                orchestrator.transcriber = rusty_genius_cortex::create_transcriber();
+                orchestrator.engine_factory =
+                    Some(Box::new(|| rusty_genius_cortex::create_engine().boxed()));
+                orchestrator
+            }
+        };
+        orchestrator.session_dir = Some(session_dir);
+        orchestrator.last_model_file = Some(last_model_file);
+        Ok(Self::finish(
+            orchestrator,
+            self.strategy,
+            self.channel_capacity,
+        ))
+    }
+
+    #[cfg(all(feature = "wllama", not(feature = "cortex-engine")))]
+    pub async fn build(self) -> Result<Orchestrator> {
+        let engine = self.engine.ok_or_else(|| {
+            anyhow::anyhow!(
+                "Give the builder an engine, e.g. WllamaEngine::from_wasm_bytes(...), to create a wllama-backed orchestrator"
+            )
+        })?;
+        Ok(Self::finish(
+            Orchestrator::from_parts(engine),
+            self.strategy,
+            self.channel_capacity,
+        ))
+    }
+
+    /// Apply the settings every build takes.
+    fn finish(
+        mut orchestrator: Orchestrator,
+        strategy: Option<CortexStrategy>,
+        channel_capacity: (usize, usize),
+    ) -> Orchestrator {
+        if let Some(strategy) = strategy {
+            orchestrator.strategy = strategy;
+        }
+        orchestrator.channel_capacity = channel_capacity;
+        orchestrator
+    }
+}
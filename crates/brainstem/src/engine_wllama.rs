@@ -2,7 +2,8 @@ use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use futures::channel::mpsc;
 use futures::sink::SinkExt;
-use rusty_genius_core::engine::Engine;
+use rusty_genius_core::cosine::l2_normalize;
+use rusty_genius_core::engine::{CancellationToken, Engine};
 use rusty_genius_core::manifest::InferenceConfig;
 use rusty_genius_core::protocol::InferenceEvent;
 use std::sync::Mutex;
@@ -11,6 +12,8 @@ use wasmtime::*;
 struct HostState {
     wasi: wasmtime_wasi::p1::WasiP1Ctx,
     token_sender: Option<mpsc::Sender<Result<InferenceEvent>>>,
+    /// Cancellation of the running `infer`; the next `emit_token` traps the guest.
+    cancel: Option<CancellationToken>,
     embedding_buffer: Vec<f32>,
 }
 
@@ -31,6 +34,7 @@ impl WllamaEngine {
         let host_state = HostState {
             wasi: wasmtime_wasi::WasiCtxBuilder::new().build_p1(),
             token_sender: None,
+            cancel: None,
             embedding_buffer: Vec::new(),
         };
         let store = Store::new(&wasm_engine, host_state);
@@ -67,21 +71,30 @@ impl WllamaEngine {
         linker.func_wrap(
             "env",
             "emit_token",
-            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| {
+            |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> wasmtime::Result<()> {
+                if caller
+                    .data()
+                    .cancel
+                    .as_ref()
+                    .is_some_and(CancellationToken::is_cancelled)
+                {
+                    return Err(wasmtime::Error::msg("inference cancelled"));
+                }
                 let memory = match caller.get_export("memory").and_then(|e| e.into_memory()) {
                     Some(m) => m,
-                    None => return,
+                    None => return Ok(()),
                 };
                 let data = memory.data(&caller);
                 let start = ptr as usize;
                 let end = start + len as usize;
                 if end > data.len() {
-                    return;
+                    return Ok(());
                 }
                 let token = String::from_utf8_lossy(&data[start..end]).to_string();
                 if let Some(sender) = caller.data_mut().token_sender.as_mut() {
                     let _ = sender.try_send(Ok(InferenceEvent::Content(token)));
                 }
+                Ok(())
             },
         )?;
 
@@ -115,6 +128,7 @@ impl WllamaEngine {
         let host_state = HostState {
             wasi: wasmtime_wasi::WasiCtxBuilder::new().build_p1(),
             token_sender: None,
+            cancel: None,
             embedding_buffer: Vec::new(),
         };
         let mut store = Store::new(&wasm_engine, host_state);
@@ -249,6 +263,7 @@ impl Engine for WllamaEngine {
         &mut self,
         prompt: &str,
         _config: InferenceConfig,
+        cancel: CancellationToken,
     ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
         let (mut tx, rx) = mpsc::channel(256);
 
@@ -265,6 +280,7 @@ impl Engine for WllamaEngine {
 
         // Set up token sender in host state
         store.data_mut().token_sender = Some(tx.clone());
+        store.data_mut().cancel = Some(cancel.clone());
 
         let prompt_bytes = prompt.as_bytes();
         let ptr = Self::write_to_guest(store, &instance, prompt_bytes)?;
@@ -273,17 +289,24 @@ impl Engine for WllamaEngine {
             .get_typed_func::<(i32, i32, i32), i32>(&mut *store, "infer")
             .map_err(|e| anyhow!("infer export not found: {}", e))?;
 
-        let result = infer_fn
-            .call(
-                &mut *store,
-                (ptr, prompt_bytes.len() as i32, 0), // mode 0 = chat
-            )
-            .map_err(|e| anyhow!("infer call failed: {}", e))?;
-
-        let _ = Self::free_guest(store, &instance, ptr, prompt_bytes.len() as i32);
+        let result = infer_fn.call(
+            &mut *store,
+            (ptr, prompt_bytes.len() as i32, 0), // mode 0 = chat
+        );
 
         // Clear token sender
         store.data_mut().token_sender = None;
+        store.data_mut().cancel = None;
+
+        let result = match result {
+            Ok(result) => {
+                let _ = Self::free_guest(store, &instance, ptr, prompt_bytes.len() as i32);
+                result
+            }
+            // Cancellation traps the guest mid-call, so its prompt buffer is left alone.
+            Err(_) if cancel.is_cancelled() => 0,
+            Err(e) => return Err(anyhow!("infer call failed: {}", e)),
+        };
 
         if result < 0 {
             let _ = tx
@@ -300,7 +323,7 @@ impl Engine for WllamaEngine {
     async fn embed(
         &mut self,
         input: &str,
-        _config: InferenceConfig,
+        config: InferenceConfig,
     ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
         let (mut tx, rx) = mpsc::channel(256);
 
@@ -340,7 +363,10 @@ impl Engine for WllamaEngine {
                 .await;
         } else {
             // Collect embeddings from host state
-            let embeddings = std::mem::take(&mut store.data_mut().embedding_buffer);
+            let mut embeddings = std::mem::take(&mut store.data_mut().embedding_buffer);
+            if config.normalize {
+                l2_normalize(&mut embeddings);
+            }
             let _ = tx.send(Ok(InferenceEvent::Embedding(embeddings))).await;
         }
 
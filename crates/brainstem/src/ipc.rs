@@ -0,0 +1,267 @@
+//! Daemon mode: one orchestrator, and so one warm model, shared by the local clients that
+//! connect to it, e.g. over a Unix domain socket.
+//!
+//! Both sides talk in frames: a big-endian `u32` length, then that many bytes of JSON, a
+//! `BrainstemInput` from the client and a `BrainstemOutput` from the daemon. Each client's
+//! request ids are its own, and outputs without an id go to every client.
+
+use crate::Orchestrator;
+use anyhow::{bail, Result};
+use futures::channel::mpsc;
+use futures::future::{self, BoxFuture, Either, FutureExt, TryFutureExt};
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use futures::sink::SinkExt;
+use futures::stream::{self, BoxStream, SelectAll, Stream};
+use futures::StreamExt;
+use rusty_genius_core::protocol::{
+    BrainstemBody, BrainstemCommand, BrainstemInput, BrainstemOutput,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+/// Frames larger than this are refused, as a peer that doesn't speak the protocol.
+pub const MAX_FRAME_BYTES: usize = 64 * 1024 * 1024;
+
+/// Outputs held for a client that is slow to read them; more are dropped, which it sees as a
+/// gap in `seq`.
+const CLIENT_BUFFER: usize = 256;
+
+/// Write `frame` as JSON behind its length.
+pub async fn write_frame<W, T>(writer: &mut W, frame: &T) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let json = serde_json::to_vec(frame)?;
+    if json.len() > MAX_FRAME_BYTES {
+        bail!("Frame of {} bytes is too large", json.len());
+    }
+    writer.write_all(&(json.len() as u32).to_be_bytes()).await?;
+    writer.write_all(&json).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Read the next frame, or `None` when the peer closed the connection between frames.
+pub async fn read_frame<R, T>(reader: &mut R) -> Result<Option<T>>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let mut len = [0u8; 4];
+    match reader.read_exact(&mut len).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_BYTES {
+        bail!("Frame of {} bytes is too large", len);
+    }
+    let mut json = vec![0u8; len];
+    reader.read_exact(&mut json).await?;
+    Ok(Some(serde_json::from_slice(&json)?))
+}
+
+/// Talk to a daemon over `connection`. Returns channels like those [Orchestrator::run] takes,
+/// and the future that carries them over the connection, to be spawned.
+pub fn connect<C>(
+    connection: C,
+) -> (
+    mpsc::Sender<BrainstemInput>,
+    mpsc::Receiver<BrainstemOutput>,
+    BoxFuture<'static, Result<()>>,
+)
+where
+    C: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (input_tx, mut input_rx) = mpsc::channel::<BrainstemInput>(32);
+    let (mut output_tx, output_rx) = mpsc::channel(32);
+    let (mut reader, mut writer) = connection.split();
+    let send = async move {
+        while let Some(input) = input_rx.next().await {
+            write_frame(&mut writer, &input).await?;
+        }
+        Ok(())
+    };
+    let receive = async move {
+        while let Some(output) = read_frame::<_, BrainstemOutput>(&mut reader).await? {
+            if output_tx.send(output).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    };
+    let carry = future::try_join(send, receive).map_ok(|_| ()).boxed();
+    (input_tx, output_rx, carry)
+}
+
+impl Orchestrator {
+    /// Serve the clients that connect on `connections`, e.g. the streams a Unix socket
+    /// accepts, until a `Stop` or the connections end and every client has gone.
+    pub async fn run_daemon<S, C>(&mut self, connections: S) -> Result<()>
+    where
+        S: Stream<Item = C> + Send + 'static,
+        C: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let (input_tx, input_rx) = mpsc::channel(32);
+        let (output_tx, output_rx) = mpsc::channel(32);
+        let run = self.run(input_rx, output_tx);
+        let hub = serve_clients(connections, input_tx, output_rx);
+        futures::pin_mut!(run, hub);
+        match future::select(run, hub).await {
+            Either::Left((result, _)) => result,
+            // No clients left to send inputs
+            Either::Right(((), run)) => run.await,
+        }
+    }
+}
+
+enum HubEvent<C> {
+    Connected(C),
+    /// An input from a client, or `None` once it has gone
+    Input(u64, Option<Box<BrainstemInput>>),
+    Output(Box<BrainstemOutput>),
+    /// A client's writer finished
+    Written,
+}
+
+/// Forward the clients' inputs to the orchestrator under ids of their own, and route its
+/// outputs back to the client each is for.
+async fn serve_clients<S, C>(
+    connections: S,
+    mut input_tx: mpsc::Sender<BrainstemInput>,
+    output_rx: mpsc::Receiver<BrainstemOutput>,
+) where
+    S: Stream<Item = C> + Send + 'static,
+    C: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let mut events: SelectAll<BoxStream<'static, HubEvent<C>>> = SelectAll::new();
+    events.push(connections.map(HubEvent::Connected).boxed());
+    events.push(
+        output_rx
+            .map(|output| HubEvent::Output(Box::new(output)))
+            .boxed(),
+    );
+    let mut clients: HashMap<u64, mpsc::Sender<BrainstemOutput>> = HashMap::new();
+    let mut next_client = 0;
+    // The ids the orchestrator minted for commands sent without, by client, and the clients
+    // waiting to be told theirs; it tells them in the order the commands came
+    let mut minted: HashMap<String, u64> = HashMap::new();
+    let mut awaiting_ids: VecDeque<u64> = VecDeque::new();
+
+    while let Some(event) = events.next().await {
+        match event {
+            HubEvent::Connected(connection) => {
+                next_client += 1;
+                let client = next_client;
+                let (reader, mut writer) = connection.split();
+                let (output_tx, mut output_rx) = mpsc::channel::<BrainstemOutput>(CLIENT_BUFFER);
+                clients.insert(client, output_tx);
+                events.push(client_inputs(client, reader));
+                let write = async move {
+                    while let Some(output) = output_rx.next().await {
+                        if write_frame(&mut writer, &output).await.is_err() {
+                            break;
+                        }
+                    }
+                };
+                events.push(write.into_stream().map(|()| HubEvent::Written).boxed());
+            }
+            HubEvent::Input(client, Some(mut input)) => {
+                input.id = match input.id {
+                    Some(id) => Some(daemon_id(client, id, &minted)),
+                    None => {
+                        awaiting_ids.push_back(client);
+                        None
+                    }
+                };
+                if let BrainstemCommand::Cancel { id } = &mut input.command {
+                    *id = daemon_id(client, std::mem::take(id), &minted);
+                }
+                if input_tx.send(*input).await.is_err() {
+                    // Stopped
+                    break;
+                }
+            }
+            HubEvent::Input(client, None) => {
+                clients.remove(&client);
+                minted.retain(|_, owner| *owner != client);
+            }
+            HubEvent::Output(output) => {
+                let mut output = *output;
+                let Some(id) = output.id.take() else {
+                    for output_tx in clients.values_mut() {
+                        let _ = output_tx.try_send(output.clone());
+                    }
+                    continue;
+                };
+                let (client, id) = match client_id(&id) {
+                    Some((client, id)) => (client, id.to_string()),
+                    None => {
+                        if matches!(output.body, BrainstemBody::Accepted) {
+                            if let Some(client) = awaiting_ids.pop_front() {
+                                minted.insert(id.clone(), client);
+                            }
+                        }
+                        match minted.get(&id) {
+                            Some(client) => (*client, id),
+                            None => continue,
+                        }
+                    }
+                };
+                if let Some(output_tx) = clients.get_mut(&client) {
+                    output.id = Some(id);
+                    if let Err(e) = output_tx.try_send(output) {
+                        if e.is_disconnected() {
+                            clients.remove(&client);
+                        } else {
+                            let output = e.into_inner();
+                            eprintln!(
+                                "NOTICE: dropped output {} of [{}] for a slow client",
+                                output.seq,
+                                output.id.as_deref().unwrap_or("-")
+                            );
+                        }
+                    }
+                }
+            }
+            HubEvent::Written => {}
+        }
+    }
+}
+
+/// The inputs `client` sends, ending with `None` once it has gone.
+fn client_inputs<C, R>(client: u64, reader: R) -> BoxStream<'static, HubEvent<C>>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    stream::unfold(Some(reader), move |reader| async move {
+        let mut reader = reader?;
+        match read_frame(&mut reader).await {
+            Ok(Some(input)) => Some((HubEvent::Input(client, Some(Box::new(input))), Some(reader))),
+            Ok(None) => Some((HubEvent::Input(client, None), None)),
+            Err(e) => {
+                eprintln!("NOTICE: Client {} disconnected: {}", client, e);
+                Some((HubEvent::Input(client, None), None))
+            }
+        }
+    })
+    .boxed()
+}
+
+/// The id the orchestrator knows a request of `client` by; ids it minted are its own.
+fn daemon_id(client: u64, id: String, minted: &HashMap<String, u64>) -> String {
+    if minted.get(&id) == Some(&client) {
+        id
+    } else {
+        format!("{}/{}", client, id)
+    }
+}
+
+/// The client a daemon id belongs to, and the client's own id.
+fn client_id(id: &str) -> Option<(u64, &str)> {
+    let (client, id) = id.split_once('/')?;
+    Some((client.parse().ok()?, id))
+}
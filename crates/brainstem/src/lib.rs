@@ -1,5 +1,11 @@
+pub mod audit;
+pub mod builder;
 pub mod context_worker;
 pub mod embedder;
+pub mod ipc;
+mod lines;
+pub mod observer;
+pub mod replay;
 // Re-exported from striatum for backward compatibility; Redis access patterns
 // live in rusty-genius-striatum.
 #[cfg(feature = "redis-context")]
@@ -7,36 +13,332 @@ pub use rusty_genius_striatum::RedisContextStore;
 #[cfg(feature = "wllama")]
 pub mod engine_wllama;
 
+pub use audit::AuditLog;
+pub use builder::OrchestratorBuilder;
 pub use context_worker::ContextWorker;
 pub use embedder::BrainstemEmbedder;
 #[cfg(feature = "wllama")]
 pub use engine_wllama::WllamaEngine;
+pub use observer::OrchestratorObserver;
+pub use replay::{Recorder, ReplayEngine};
+pub use rusty_genius_core::protocol::CortexStrategy;
 
 use anyhow::Result;
+use audit::{Audit, AuditStatus};
 use futures::channel::mpsc;
+use futures::future::{self, BoxFuture, FutureExt};
 use futures::sink::SinkExt;
+use futures::stream::{self, AbortHandle, SelectAll, Stream};
 use futures::StreamExt;
-use rusty_genius_core::engine::Engine;
+use rusty_genius_core::chat::ChatTemplate;
+use rusty_genius_core::engine::{CancellationToken, Engine};
+use rusty_genius_core::manifest::ModelDefaults;
 use rusty_genius_core::protocol::{
-    BrainstemBody, BrainstemCommand, BrainstemInput, BrainstemOutput, ModelDescriptor,
+    AdapterConfig, AssetEvent, BenchConfig, BrainstemBody, BrainstemCommand, BrainstemInput,
+    BrainstemOutput, BrainstemState, ChatMessage, ChatRole, InferenceConfig, InferenceEvent,
+    LoadConfig, Metrics, ModelDescriptor, RagConfig, RetrievedDocument, ToolCall,
+    TranscriptionConfig, MINTED_ID_PREFIX,
 };
+use rusty_genius_core::{rag, tools};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 #[cfg(feature = "cortex-engine")]
 use facecrab::AssetAuthority;
 #[cfg(feature = "cortex-engine")]
-use rusty_genius_core::protocol::AssetEvent;
+use rusty_genius_core::runtime;
 
 #[cfg(not(any(feature = "cortex-engine", feature = "wllama")))]
 compile_error!(
     "rusty-genius-stem requires at least one engine feature: `cortex-engine` or `wllama`"
 );
 
-#[derive(Debug, Clone)]
-pub enum CortexStrategy {
-    Immediate,
-    HibernateAfter(Duration),
-    KeepAlive,
+/// How many requests an [Orchestrator] streams at once by default; see
+/// [Orchestrator::set_max_parallel].
+pub const DEFAULT_MAX_PARALLEL: usize = 4;
+
+/// Capacity of the channels [Orchestrator::connect] makes by default; see
+/// [OrchestratorBuilder::channel_capacity].
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 100;
+
+/// How many commands an [Orchestrator] queues by default; see [Orchestrator::set_max_queue].
+pub const DEFAULT_MAX_QUEUE: usize = 64;
+
+/// What an [Orchestrator] does with a command that arrives when its queue is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueFull {
+    /// Fail the new command
+    #[default]
+    Reject,
+    /// Fail the command that has waited longest, and queue the new one
+    DropOldest,
+}
+
+/// How an [Orchestrator] retries a model load or download that failed in a way that may pass:
+/// a busy file, memory a hibernated model hasn't freed yet, a dropped connection. Clients are
+/// told it is `Retrying` before each retry; see [Orchestrator::set_retry_policy]. The command
+/// runs again from the start once its backoff is over, and others are served meanwhile.
+///
+/// The wait before retry `n` (starting at 0) is `backoff * 2^n`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Fail on the first error.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            backoff: Duration::ZERO,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// What errors that may pass when tried again say, in lower case.
+const TRANSIENT_ERRORS: &[&str] = &[
+    "busy",
+    "temporarily unavailable",
+    "out of memory",
+    "failed to allocate",
+    "timed out",
+    "connection reset",
+    "connection closed",
+    "broken pipe",
+    "unexpected eof",
+];
+
+/// Whether a load or download that failed with `error` may succeed if tried again.
+fn is_transient(error: &str) -> bool {
+    let error = error.to_lowercase();
+    TRANSIENT_ERRORS.iter().any(|marker| error.contains(marker))
+}
+
+/// What an `Infer` or `InferTokens` request hands the engine.
+enum Prompt {
+    Text(String),
+    Tokens(Vec<i32>),
+}
+
+/// A request whose events are being relayed to the client.
+struct InFlight {
+    request_id: String,
+    /// Stops the engine, for requests that take a token.
+    cancel: Option<CancellationToken>,
+    abort: AbortHandle,
+    /// Runs on the transcriber rather than the engine.
+    transcribing: bool,
+    /// Whether a `Complete` or an error came; events that end without either were cut off by
+    /// a crash.
+    finished: bool,
+    /// The turn of a `Chat` request, kept in its conversation once the answer completes.
+    chat: Option<ChatTurn>,
+    /// Generation of a request that offered the model tools.
+    tools: Option<ToolRound>,
+    /// The command and when it started, to time it once its events end.
+    timing: Option<(&'static str, Instant)>,
+    /// Index of the prompt, for a prompt of an `InferBatch`.
+    batch_index: Option<usize>,
+    /// Record of the command, when auditing, written once its events end.
+    audit: Option<Audit>,
+}
+
+impl InFlight {
+    /// The output carrying `event`, tagged with the prompt's index in a batch.
+    fn body(&self, event: InferenceEvent) -> BrainstemBody {
+        match self.batch_index {
+            Some(index) => BrainstemBody::Batch { index, event },
+            None => BrainstemBody::Event(event),
+        }
+    }
+
+    /// Record how long the request took, and how it ended in the `audit_log`, when it
+    /// finishes or stops.
+    fn time(&mut self, metrics: &mut Metrics, audit_log: Option<&AuditLog>, status: AuditStatus) {
+        if let Some((command, started)) = self.timing.take() {
+            metrics.record_command(command, started.elapsed());
+        }
+        if let (Some(audit), Some(log)) = (self.audit.take(), audit_log) {
+            audit.finish(log, status);
+        }
+    }
+}
+
+/// A user message being answered, and the answer so far.
+struct ChatTurn {
+    session_id: String,
+    /// The user message, then the tool calls made for it and their results.
+    messages: Vec<ChatMessage>,
+    reply: String,
+}
+
+/// Generation from a prompt that offered the model tools, watched for calls.
+struct ToolRound {
+    /// The prompt, to carry on from after the calls.
+    prompt: String,
+    config: InferenceConfig,
+    /// What the model generated so far.
+    output: String,
+    /// Whether `output` is held back, since it may turn out to be calls.
+    holding: bool,
+}
+
+/// A request waiting for the results of the tools its model called.
+struct ToolWait {
+    /// The model that called them, which carries on.
+    model: Option<String>,
+    prompt: String,
+    /// The model's answer to `prompt`, made of the calls.
+    output: String,
+    config: InferenceConfig,
+    calls: Vec<ToolCall>,
+    /// Results that came back, by call id.
+    results: HashMap<String, String>,
+    chat: Option<ChatTurn>,
+}
+
+/// The prompts of an `InferBatch` request.
+struct Batch {
+    model: Option<String>,
+    config: InferenceConfig,
+    /// Prompts yet to start, with their index.
+    queued: VecDeque<(usize, String)>,
+    /// How many prompts are in flight.
+    running: usize,
+    /// Errors of the prompts that failed, which end the batch in place of `Complete`.
+    failed: Vec<String>,
+    /// When the batch started, recorded once its last prompt finishes.
+    timing: Option<(&'static str, Instant)>,
+    /// Record of the batch, when auditing, written once its last prompt finishes.
+    audit: Option<Audit>,
+}
+
+/// Commands waiting for the model they switch to to download, and to load beside the one
+/// serving.
+struct Preload {
+    waiting: Vec<BrainstemInput>,
+    /// Bytes downloaded so far.
+    downloaded: u64,
+    /// Where the model downloaded to and the memory it takes, while it loads on an engine of
+    /// its own.
+    loading: Option<(String, u64)>,
+}
+
+/// What the loop hears of a model fetched in the background, which only local engines are.
+#[cfg_attr(not(feature = "cortex-engine"), allow(dead_code))]
+enum PreloadEvent {
+    /// Progress of its download.
+    Asset(AssetEvent),
+    /// Its download's events ended.
+    Downloaded,
+    /// It loaded on an engine of its own, or failed to.
+    #[cfg(feature = "cortex-engine")]
+    Loaded(Box<dyn Engine>, Result<()>),
+}
+
+/// An engine that loaded the model at `path` while the current one served; it replaces the
+/// current one when a request switches to that model.
+#[cfg(feature = "cortex-engine")]
+struct Standby {
+    path: String,
+    engine: Box<dyn Engine>,
+    /// Memory the model was estimated to take.
+    memory: u64,
+}
+
+/// Builds an engine to replace one that crashed.
+type EngineFactory = Box<dyn Fn() -> BoxFuture<'static, Box<dyn Engine>> + Send + Sync>;
+
+/// Events of a request in flight, tagged with its key; kept `Sync` so the orchestrator is.
+type EventStream =
+    std::pin::Pin<Box<dyn Stream<Item = (u64, Option<Result<InferenceEvent>>)> + Send + Sync>>;
+
+/// Events of a model fetched in the background, tagged with its name.
+type PreloadStream = std::pin::Pin<Box<dyn Stream<Item = (String, PreloadEvent)> + Send + Sync>>;
+
+/// Wait for the embeddings an engine streams, in input order.
+async fn collect_embeddings(
+    events: Result<mpsc::Receiver<Result<InferenceEvent>>>,
+) -> Result<Vec<Vec<f32>>> {
+    let mut events = events?;
+    let mut embeddings = Vec::new();
+    while let Some(event) = events.next().await {
+        match event? {
+            InferenceEvent::Embedding(embedding) => embeddings.push(embedding),
+            InferenceEvent::IndexedEmbedding { index, embedding } => {
+                if embeddings.len() <= index {
+                    embeddings.resize(index + 1, Vec::new());
+                }
+                embeddings[index] = embedding;
+            }
+            _ => {}
+        }
+    }
+    Ok(embeddings)
+}
+
+/// Number the outputs from `outputs` by request id, as [BrainstemOutput::seq] says, and pass
+/// them on to `output_tx` until it closes.
+async fn number_outputs(
+    mut outputs: mpsc::Receiver<BrainstemOutput>,
+    mut output_tx: mpsc::Sender<BrainstemOutput>,
+    observers: Vec<Arc<dyn OrchestratorObserver>>,
+) {
+    let mut next_seq: HashMap<Option<String>, u64> = HashMap::new();
+    while let Some(mut output) = outputs.next().await {
+        let seq = next_seq.entry(output.id.clone()).or_default();
+        output.seq = *seq;
+        *seq += 1;
+        // A request id may be used again once its request is over
+        if matches!(
+            output.body,
+            BrainstemBody::Event(InferenceEvent::Complete)
+                | BrainstemBody::Error(_)
+                | BrainstemBody::Cancelled
+                | BrainstemBody::ModelList(_)
+                | BrainstemBody::Health(_)
+                | BrainstemBody::Bench(_)
+                | BrainstemBody::Metrics(_)
+        ) && output.id.is_some()
+        {
+            next_seq.remove(&output.id);
+        }
+        observer::observe(&observers, &output);
+        if output_tx.send(output).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// The model last loaded and the settings it was loaded with, remembered across restarts.
+#[derive(Debug, Serialize, Deserialize)]
+struct LastModel {
+    name: String,
+    config: LoadConfig,
+}
+
+/// What the orchestrator's loop wakes up for.
+enum Wake {
+    Input(Option<Box<BrainstemInput>>),
+    /// An event of the request in flight under the key; `None` once its events end.
+    Event(u64, Option<Result<InferenceEvent>>),
+    /// An event of the model named, fetched in the background.
+    Preload(String, PreloadEvent),
+    Timeout,
 }
 
 pub struct Orchestrator {
@@ -46,20 +348,78 @@ pub struct Orchestrator {
     strategy: CortexStrategy,
     last_activity: Instant,
     last_model_name: Option<String>,
+    /// Settings every model is loaded with.
+    load_config: LoadConfig,
+    /// Training context length of the loaded model, read from its GGUF header.
+    model_context_length: Option<u64>,
+    /// Memory the loaded model was estimated to take, which replacing it frees.
+    #[cfg(feature = "cortex-engine")]
+    loaded_memory: u64,
+    /// Engine for `Transcribe` requests, which speech models can't share with the chat engine.
+    transcriber: Option<Box<dyn Engine>>,
+    transcriber_model: Option<String>,
+    /// Where sessions' KV caches are saved when the engine unloads; `None` doesn't keep them.
+    session_dir: Option<PathBuf>,
+    /// Where the last model loaded is remembered; `None` doesn't remember it.
+    last_model_file: Option<PathBuf>,
+    /// Whether `run` starts by loading the model remembered in `last_model_file`.
+    resume: bool,
+    /// Models that never hibernate.
+    keep_warm: HashSet<String>,
+    /// Sessions the engine has a context for since the model was loaded.
+    live_sessions: HashSet<String>,
+    /// Messages of the `Chat` sessions, by session id.
+    conversations: HashMap<String, Vec<ChatMessage>>,
+    /// Requests waiting for tool results, by request id.
+    tool_waits: HashMap<String, ToolWait>,
+    /// `InferBatch` requests with prompts left, by request id.
+    batches: HashMap<String, Batch>,
+    /// Commands waiting for the requests in flight, in the order they arrived.
+    pending: VecDeque<BrainstemInput>,
+    /// Commands switching to a model that downloads in the background meanwhile, by model.
+    preloading: HashMap<String, Preload>,
+    preloads: SelectAll<PreloadStream>,
+    /// Ids of requests whose model finished downloading, which switch to it like any other.
+    preloaded: HashSet<String>,
+    /// The model those requests switch to, loaded already.
+    #[cfg(feature = "cortex-engine")]
+    standby: Option<Standby>,
+    /// Requests streaming events, by key.
+    in_flight: HashMap<u64, InFlight>,
+    /// Events of the requests in flight, tagged with their key.
+    events: SelectAll<EventStream>,
+    next_key: u64,
+    /// How many ids were minted for commands that came without one.
+    minted_ids: u64,
+    max_parallel: usize,
+    /// Most commands `pending` holds, and what happens to the next.
+    max_queue: usize,
+    queue_full: QueueFull,
+    retry_policy: RetryPolicy,
+    /// Commands backing off after a load or download failed, with when they run again.
+    retrying: Vec<(Instant, BrainstemInput)>,
+    /// The backoff the command running asked for with [Self::retry], if any.
+    backoff: Option<Duration>,
+    /// Retries each command has taken, by request id.
+    retries: HashMap<String, u32>,
+    /// Replaces the engine when it crashes; a crashed engine without one is kept.
+    engine_factory: Option<EngineFactory>,
+    /// Capacities of the input and output channels [Orchestrator::connect] makes.
+    channel_capacity: (usize, usize),
+    metrics: Metrics,
+    /// Where every command run is recorded; `None` records nothing.
+    audit_log: Option<AuditLog>,
+    /// What the orchestrator is doing, as it last told its clients.
+    state: BrainstemState,
+    /// Whether the engine crashed for good; cleared by the next model loaded.
+    faulted: bool,
+    observers: Vec<Arc<dyn OrchestratorObserver>>,
 }
 
 impl Orchestrator {
     #[cfg(feature = "cortex-engine")]
     pub async fn new() -> Result<Self> {
-        let engine = rusty_genius_cortex::create_engine().await;
-        let asset_authority = AssetAuthority::new()?;
-        Ok(Self {
-            engine,
-            asset_authority,
-            strategy: CortexStrategy::HibernateAfter(Duration::from_secs(300)),
-            last_activity: Instant::now(),
-            last_model_name: None,
-        })
+        Self::builder().build().await
     }
 
     #[cfg(all(feature = "wllama", not(feature = "cortex-engine")))]
@@ -71,37 +431,264 @@ impl Orchestrator {
 
     /// Create an Orchestrator with a pre-built engine (useful for testing).
     pub fn with_engine(engine: Box<dyn Engine>) -> Self {
+        Self::from_parts(
+            engine,
+            #[cfg(feature = "cortex-engine")]
+            AssetAuthority::new().expect("failed to create asset authority"),
+        )
+    }
+
+    /// Assemble an Orchestrator from its engine, asset authority, strategy and so on.
+    pub fn builder() -> OrchestratorBuilder {
+        OrchestratorBuilder::default()
+    }
+
+    /// An orchestrator on `engine` that keeps nothing on disk and can't restart its engine.
+    fn from_parts(
+        engine: Box<dyn Engine>,
+        #[cfg(feature = "cortex-engine")] asset_authority: AssetAuthority,
+    ) -> Self {
         Self {
             engine,
             #[cfg(feature = "cortex-engine")]
-            asset_authority: AssetAuthority::new().expect("failed to create asset authority"),
+            asset_authority,
             strategy: CortexStrategy::HibernateAfter(Duration::from_secs(300)),
             last_activity: Instant::now(),
             last_model_name: None,
+            load_config: LoadConfig::default(),
+            model_context_length: None,
+            #[cfg(feature = "cortex-engine")]
+            loaded_memory: 0,
+            transcriber: None,
+            transcriber_model: None,
+            session_dir: None,
+            last_model_file: None,
+            resume: false,
+            keep_warm: HashSet::new(),
+            live_sessions: HashSet::new(),
+            conversations: HashMap::new(),
+            tool_waits: HashMap::new(),
+            batches: HashMap::new(),
+            pending: VecDeque::new(),
+            preloading: HashMap::new(),
+            preloads: SelectAll::new(),
+            preloaded: HashSet::new(),
+            #[cfg(feature = "cortex-engine")]
+            standby: None,
+            in_flight: HashMap::new(),
+            events: SelectAll::new(),
+            next_key: 0,
+            minted_ids: 0,
+            max_parallel: DEFAULT_MAX_PARALLEL,
+            max_queue: DEFAULT_MAX_QUEUE,
+            queue_full: QueueFull::Reject,
+            retry_policy: RetryPolicy::default(),
+            retrying: Vec::new(),
+            backoff: None,
+            retries: HashMap::new(),
+            engine_factory: None,
+            channel_capacity: (DEFAULT_CHANNEL_CAPACITY, DEFAULT_CHANNEL_CAPACITY),
+            metrics: Metrics::default(),
+            audit_log: None,
+            state: BrainstemState::Idle,
+            faulted: false,
+            observers: Vec::new(),
         }
     }
 
+    /// Channels to send the orchestrator commands and receive its outputs on, of the
+    /// capacities it was built with, and the future that runs it, for the caller to spawn on
+    /// their runtime.
+    pub fn connect(
+        mut self,
+    ) -> (
+        mpsc::Sender<BrainstemInput>,
+        mpsc::Receiver<BrainstemOutput>,
+        BoxFuture<'static, Result<()>>,
+    ) {
+        let (input_capacity, output_capacity) = self.channel_capacity;
+        let (input_tx, input_rx) = mpsc::channel(input_capacity);
+        let (output_tx, output_rx) = mpsc::channel(output_capacity);
+        let run = async move { self.run(input_rx, output_tx).await }.boxed();
+        (input_tx, output_rx, run)
+    }
+
     pub fn set_strategy(&mut self, strategy: CortexStrategy) {
         self.strategy = strategy;
     }
 
+    /// Stream up to `max` `Infer`, `Embed`, `Rerank` and `Transcribe` requests at once;
+    /// further ones wait for one to finish.
+    pub fn set_max_parallel(&mut self, max: usize) {
+        self.max_parallel = max.max(1);
+    }
+
+    /// Never hibernate `models`, named as requests name them: they stay loaded until another
+    /// model replaces them or a `Reset`, whatever the strategy.
+    pub fn set_keep_warm<I>(&mut self, models: I)
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.keep_warm = models.into_iter().collect();
+    }
+
+    /// Queue up to `max` commands that can't start yet, telling each it is `Busy`; what happens
+    /// to more is up to `when_full`.
+    pub fn set_max_queue(&mut self, max: usize, when_full: QueueFull) {
+        self.max_queue = max;
+        self.queue_full = when_full;
+    }
+
+    /// Retry model loads and downloads that fail transiently as `policy` says, instead of
+    /// failing the request at once.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    /// Replace the engine with one `factory` builds when it crashes: when it panics, or a
+    /// request's events end without a `Complete` or an error. [Orchestrator::new] restarts
+    /// with `create_engine`.
+    pub fn set_engine_factory<F>(&mut self, factory: F)
+    where
+        F: Fn() -> BoxFuture<'static, Box<dyn Engine>> + Send + Sync + 'static,
+    {
+        self.engine_factory = Some(Box::new(factory));
+    }
+
+    /// Load models with `config` (RoPE scaling, batch size, ...) from now on.
+    pub fn set_load_config(&mut self, config: LoadConfig) {
+        self.load_config = config;
+    }
+
+    /// Save sessions' KV caches under `dir` when the engine unloads, and restore them at a
+    /// session's next request; `None` stops keeping them.
+    pub fn set_session_dir(&mut self, dir: Option<PathBuf>) {
+        self.session_dir = dir;
+    }
+
+    /// Remember the last model loaded, and its load settings, in `file`; `None` stops
+    /// remembering it. [Orchestrator::new] keeps it in `last_model.json` in the config dir.
+    pub fn set_last_model_file(&mut self, file: Option<PathBuf>) {
+        self.last_model_file = file;
+    }
+
+    /// With `resume`, `run` starts by loading the remembered model with its settings, so a
+    /// restarted server is warm for the model last used. Requests wait for the load.
+    pub fn set_resume(&mut self, resume: bool) {
+        self.resume = resume;
+    }
+
+    /// Record every command run, with its timing, model, token counts and outcome, in `log`;
+    /// `None` stops recording them.
+    pub fn set_audit_log(&mut self, log: Option<AuditLog>) {
+        self.audit_log = log;
+    }
+
+    /// Tell `observer` of every command, token, completion, download progress and error.
+    pub fn add_observer(&mut self, observer: Arc<dyn OrchestratorObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Run `Transcribe` requests on `engine`.
+    pub fn set_transcriber(&mut self, engine: Box<dyn Engine>) {
+        self.transcriber = Some(engine);
+        self.transcriber_model = None;
+    }
+
+    /// What the orchestrator is doing; every change is sent to clients as a `State` output.
+    pub fn state(&self) -> BrainstemState {
+        self.state
+    }
+
+    /// Answer the commands from `input_rx` on `output_tx` until the inputs close and every
+    /// request has finished, or a `Stop`.
     pub async fn run(
+        &mut self,
+        input_rx: mpsc::Receiver<BrainstemInput>,
+        output_tx: mpsc::Sender<BrainstemOutput>,
+    ) -> Result<()> {
+        // Outputs are numbered on their way out
+        let (numbered_tx, numbered_rx) = mpsc::channel(0);
+        let observers = self.observers.clone();
+        let (result, ()) = future::join(
+            self.serve(input_rx, numbered_tx),
+            number_outputs(numbered_rx, output_tx, observers),
+        )
+        .await;
+        result
+    }
+
+    async fn serve(
         &mut self,
         mut input_rx: mpsc::Receiver<BrainstemInput>,
         mut output_tx: mpsc::Sender<BrainstemOutput>,
     ) -> Result<()> {
-        loop {
+        let mut inputs_open = true;
+        if self.resume {
+            self.queue_resume();
+        }
+        'run: loop {
+            self.requeue_retries();
+            // Start queued commands, in order, as far as the requests in flight allow
+            while let Some(msg) = self.pending.front() {
+                // A switch to another model waits aside while it downloads, and the loaded
+                // model keeps serving
+                #[cfg(feature = "cortex-engine")]
+                if let Some(model) = self.preload_for(msg) {
+                    let msg = self.pending.pop_front().unwrap();
+                    self.preload(model, msg);
+                    continue;
+                }
+                if !self.can_start(msg) {
+                    break;
+                }
+                let msg = self.pending.pop_front().unwrap();
+                if let Some(id) = &msg.id {
+                    self.preloaded.remove(id);
+                }
+                if let BrainstemCommand::Stop = msg.command {
+                    break 'run;
+                }
+                self.execute(msg, &mut output_tx).await;
+            }
+            if !inputs_open
+                && self.pending.is_empty()
+                && self.in_flight.is_empty()
+                && self.preloading.is_empty()
+                && self.retrying.is_empty()
+            {
+                break;
+            }
+
             let timeout_duration = match self.strategy {
                 CortexStrategy::HibernateAfter(duration) => Some(duration),
                 CortexStrategy::Immediate => Some(Duration::ZERO),
                 CortexStrategy::KeepAlive => None,
             };
 
-            let next_activity = if let Some(d) = timeout_duration {
+            // The engine only hibernates once every request has finished
+            let next_activity = if !self.in_flight.is_empty() {
+                None
+            } else if let Some(d) = timeout_duration {
                 let elapsed = self.last_activity.elapsed();
                 if elapsed >= d {
-                    if let Err(e) = self.engine.unload_model().await {
-                        eprintln!("Failed to hibernate engine: {}", e);
+                    if !self.is_warm(self.last_model_name.as_ref()) {
+                        let was_loaded = self.engine.is_loaded();
+                        self.persist_sessions().await;
+                        match self.engine.unload_model().await {
+                            Ok(()) if was_loaded => {
+                                Self::broadcast(BrainstemBody::Hibernated, &mut output_tx).await;
+                            }
+                            Ok(()) => {}
+                            Err(e) => eprintln!("Failed to hibernate engine: {}", e),
+                        }
+                    }
+                    if !self.is_warm(self.transcriber_model.as_ref()) {
+                        if let Some(transcriber) = self.transcriber.as_mut() {
+                            if let Err(e) = transcriber.unload_model().await {
+                                eprintln!("Failed to hibernate transcriber: {}", e);
+                            }
+                        }
                     }
                     None
                 } else {
@@ -111,274 +698,1838 @@ impl Orchestrator {
                 None
             };
 
-            let msg_option = if let Some(wait_time) = next_activity {
-                use futures::future::{self, Either};
-                use futures_timer::Delay;
-
-                let delay = Delay::new(wait_time);
-                futures::pin_mut!(delay);
-                let next = input_rx.next();
-                futures::pin_mut!(next);
-                match future::select(next, delay).await {
-                    Either::Left((msg, _)) => msg,
-                    Either::Right((_, _)) => {
-                        continue;
-                    }
-                }
-            } else {
-                input_rx.next().await
+            // A command backing off wakes the loop when it is due
+            let next_retry = self
+                .retrying
+                .iter()
+                .map(|(at, _)| at.saturating_duration_since(Instant::now()))
+                .min();
+            let timeout = match (next_activity, next_retry) {
+                (Some(activity), Some(retry)) => Some(activity.min(retry)),
+                (activity, retry) => activity.or(retry),
             };
 
-            match msg_option {
-                Some(msg) => {
+            self.settle(&mut output_tx).await;
+            let input_rx = inputs_open.then_some(&mut input_rx);
+            match self.next_wake(input_rx, timeout).await {
+                Wake::Input(Some(mut msg)) => {
                     self.last_activity = Instant::now();
-                    let request_id = msg.id.clone().unwrap_or_else(|| "anon".to_string());
+                    match &msg.id {
+                        None => msg.id = Some(self.accept(&mut output_tx).await),
+                        Some(id) if id.starts_with(MINTED_ID_PREFIX) => {
+                            Self::refuse_minted_id(id, &mut output_tx).await;
+                            continue;
+                        }
+                        Some(_) => {}
+                    }
+                    let request_id = msg.id.clone().unwrap_or_default();
+                    for observer in &self.observers {
+                        observer.on_command(&request_id, &msg.command);
+                    }
                     eprintln!("DEBUG: [orchestrator] command: {:?}", msg.command);
                     eprintln!(
                         "DEBUG: [orchestrator] received command for [{}]: {:?}",
                         request_id, msg.command
                     );
-
+                    // Cancelling, listing, metrics and strategy changes don't wait for anything
                     match msg.command {
-                        BrainstemCommand::LoadModel(name_or_path) => {
-                            self.handle_load_model(name_or_path, &request_id, &mut output_tx)
-                                .await;
-                        }
-                        BrainstemCommand::Infer {
-                            model,
-                            prompt,
-                            config,
-                        } => {
-                            self.handle_infer(model, prompt, config, &request_id, &mut output_tx)
-                                .await;
-                        }
-                        BrainstemCommand::Embed {
-                            model,
-                            input,
-                            config,
-                        } => {
-                            self.handle_embed(model, input, config, &request_id, &mut output_tx)
-                                .await;
-                        }
-                        BrainstemCommand::ListModels => {
-                            self.handle_list_models(&request_id, &mut output_tx).await;
-                        }
-                        BrainstemCommand::Reset => {
-                            if let Err(e) = self.engine.unload_model().await {
-                                let _ = output_tx
-                                    .send(BrainstemOutput {
-                                        id: Some(request_id),
-                                        body: BrainstemBody::Error(e.to_string()),
-                                    })
-                                    .await;
-                            } else {
-                                self.last_model_name = None;
-                                let _ = output_tx
-                                    .send(BrainstemOutput {
-                                        id: Some(request_id),
-                                        body: BrainstemBody::Event(
-                                            rusty_genius_core::protocol::InferenceEvent::Complete,
-                                        ),
-                                    })
-                                    .await;
-                            }
-                        }
-                        BrainstemCommand::Stop => {
-                            break;
+                        BrainstemCommand::Cancel { .. }
+                        | BrainstemCommand::ListModels
+                        | BrainstemCommand::GetMetrics
+                        | BrainstemCommand::SetStrategy(_) => {
+                            self.execute(*msg, &mut output_tx).await;
                         }
+                        _ => self.admit(*msg, &mut output_tx).await,
                     }
                 }
-                None => {
-                    break;
+                Wake::Input(None) => inputs_open = false,
+                Wake::Event(key, event) => self.relay(key, event, &mut output_tx).await,
+                Wake::Preload(model, event) => {
+                    self.preload_event(model, event, &mut output_tx).await
                 }
+                Wake::Timeout => {}
             }
         }
+        self.persist_sessions().await;
         Ok(())
     }
 
-    // ── LoadModel ──
-
-    #[cfg(feature = "cortex-engine")]
-    async fn handle_load_model(
-        &mut self,
-        name_or_path: String,
-        request_id: &str,
-        output_tx: &mut mpsc::Sender<BrainstemOutput>,
-    ) {
-        let mut events = self.asset_authority.ensure_model_stream(&name_or_path);
-        let mut path_to_load = name_or_path.clone();
-
-        while let Some(event) = events.next().await {
-            if let AssetEvent::Complete(path) = &event {
-                path_to_load = path.clone();
-            }
-            if output_tx
-                .send(BrainstemOutput {
-                    id: Some(request_id.to_string()),
-                    body: BrainstemBody::Asset(event),
-                })
-                .await
-                .is_err()
-            {
-                break;
-            }
+    /// Queue the commands whose backoff is over again, ahead of the rest, in the order they
+    /// backed off.
+    fn requeue_retries(&mut self) {
+        let now = Instant::now();
+        let (due, backing_off): (Vec<_>, Vec<_>) = std::mem::take(&mut self.retrying)
+            .into_iter()
+            .partition(|(at, _)| *at <= now);
+        self.retrying = backing_off;
+        for (_, msg) in due.into_iter().rev() {
+            self.pending.push_front(msg);
         }
+    }
 
-        if let Err(e) = self.engine.load_model(&path_to_load).await {
+    /// Queue a command. One that has to wait behind others is told it is `Busy`, or fails
+    /// when the queue is full, as `queue_full` says.
+    async fn admit(&mut self, msg: BrainstemInput, output_tx: &mut mpsc::Sender<BrainstemOutput>) {
+        // Pending commands were left waiting by the last pass through the queue
+        if self.pending.is_empty() && self.can_start(&msg) {
+            self.pending.push_back(msg);
+            return;
+        }
+        if self.pending.len() >= self.max_queue {
+            let drop_oldest = self.queue_full == QueueFull::DropOldest && self.max_queue > 0;
+            let rejected = if drop_oldest {
+                self.pending.pop_front().and_then(|oldest| oldest.id)
+            } else {
+                msg.id.clone()
+            };
             let _ = output_tx
-                .send(BrainstemOutput {
-                    id: Some(request_id.to_string()),
-                    body: BrainstemBody::Error(e.to_string()),
-                })
+                .send(BrainstemOutput::new(
+                    rejected,
+                    BrainstemBody::Error(format!("The queue is full ({} waiting)", self.max_queue)),
+                ))
                 .await;
-        } else {
-            self.last_model_name = Some(name_or_path);
+            if !drop_oldest {
+                return;
+            }
         }
+        let id = msg.id.clone();
+        self.pending.push_back(msg);
+        let queue_position = self.pending.len();
+        let _ = output_tx
+            .send(BrainstemOutput::new(
+                id,
+                BrainstemBody::Busy {
+                    queue_position,
+                    estimated_wait: self.estimate_wait(queue_position),
+                },
+            ))
+            .await;
     }
 
-    #[cfg(not(feature = "cortex-engine"))]
-    async fn handle_load_model(
-        &mut self,
-        name_or_path: String,
-        request_id: &str,
-        output_tx: &mut mpsc::Sender<BrainstemOutput>,
-    ) {
-        if let Err(e) = self.engine.load_model(&name_or_path).await {
-            let _ = output_tx
-                .send(BrainstemOutput {
-                    id: Some(request_id.to_string()),
-                    body: BrainstemBody::Error(e.to_string()),
-                })
-                .await;
-        } else {
-            self.last_model_name = Some(name_or_path);
+    /// How long a command `queue_position` back waits: the average time requests took so
+    /// far, for it and the requests in flight, shared among the requests that run at once.
+    fn estimate_wait(&self, queue_position: usize) -> Option<Duration> {
+        let (sum, count) = self
+            .metrics
+            .commands
+            .values()
+            .fold((0.0, 0), |(sum, count), histogram| {
+                (sum + histogram.sum, count + histogram.count)
+            });
+        if count == 0 {
+            return None;
         }
+        let ahead = (queue_position - 1 + self.in_flight.len()) as f64;
+        Some(Duration::from_secs_f64(
+            sum / count as f64 * ahead / self.max_parallel as f64,
+        ))
     }
 
-    // ── Ensure model loaded (cold reload) ──
+    /// Mint an id for a command that came without one, and tell the client it was
+    /// `Accepted` under it.
+    async fn accept(&mut self, output_tx: &mut mpsc::Sender<BrainstemOutput>) -> String {
+        self.minted_ids += 1;
+        let id = format!("{}{}", MINTED_ID_PREFIX, self.minted_ids);
+        let _ = output_tx
+            .send(BrainstemOutput::new(
+                Some(id.clone()),
+                BrainstemBody::Accepted,
+            ))
+            .await;
+        id
+    }
 
-    #[cfg(feature = "cortex-engine")]
-    async fn ensure_model_loaded(
+    /// Refuse a command whose id `id` could be taken for one the orchestrator minted.
+    async fn refuse_minted_id(id: &str, output_tx: &mut mpsc::Sender<BrainstemOutput>) {
+        let _ = output_tx
+            .send(BrainstemOutput::new(
+                Some(id.to_string()),
+                BrainstemBody::Error(format!(
+                    "Request ids beginning with '{}' are kept for the ids the orchestrator mints",
+                    MINTED_ID_PREFIX
+                )),
+            ))
+            .await;
+    }
+
+    /// Wait for the next input, event of a request in flight, or the end of `timeout`. Inputs
+    /// are not read when `input_rx` is `None`.
+    async fn next_wake(
         &mut self,
-        model: Option<String>,
-        request_id: &str,
-        output_tx: &mut mpsc::Sender<BrainstemOutput>,
-    ) -> bool {
-        if self.engine.is_loaded() {
+        input_rx: Option<&mut mpsc::Receiver<BrainstemInput>>,
+        timeout: Option<Duration>,
+    ) -> Wake {
+        use futures::future::Either;
+        use futures_timer::Delay;
+
+        let input = match input_rx {
+            Some(input_rx) => Either::Left(
+                input_rx
+                    .next()
+                    .map(|input| Wake::Input(input.map(Box::new))),
+            ),
+            None => Either::Right(future::pending()),
+        };
+        let event = if self.events.is_empty() {
+            Either::Right(future::pending())
+        } else {
+            Either::Left(self.events.next().map(|next| match next {
+                Some((key, event)) => Wake::Event(key, event),
+                None => Wake::Timeout,
+            }))
+        };
+        let preload = if self.preloads.is_empty() {
+            Either::Right(future::pending())
+        } else {
+            Either::Left(self.preloads.next().map(|next| match next {
+                Some((model, event)) => Wake::Preload(model, event),
+                None => Wake::Timeout,
+            }))
+        };
+        let delay = match timeout {
+            Some(wait_time) => Either::Left(Delay::new(wait_time).map(|_| Wake::Timeout)),
+            None => Either::Right(future::pending()),
+        };
+        let first = future::select(input, event).map(|either| either.factor_first().0);
+        let second = future::select(first, preload).map(|either| either.factor_first().0);
+        future::select(second, delay).await.factor_first().0
+    }
+
+    /// Whether `command` can start alongside the requests in flight. Requests that stream
+    /// from the loaded model (or the transcriber) run side by side up to `max_parallel`;
+    /// anything else, including a request that needs another model loaded, waits for them
+    /// all to finish.
+    fn can_start(&self, msg: &BrainstemInput) -> bool {
+        if self.in_flight.is_empty() {
             return true;
         }
-        let model_to_load = model
-            .or_else(|| self.last_model_name.clone())
-            .unwrap_or_else(|| self.engine.default_model());
-
-        let start = Instant::now();
-        match self.asset_authority.ensure_model(&model_to_load).await {
-            Ok(path) => {
-                if let Err(e) = self.engine.load_model(path.to_str().unwrap()).await {
-                    let _ = output_tx
-                        .send(BrainstemOutput {
-                            id: Some(request_id.to_string()),
-                            body: BrainstemBody::Error(format!("Cold reload failed: {}", e)),
-                        })
-                        .await;
-                    return false;
-                }
-                self.last_model_name = Some(model_to_load);
-                eprintln!("NOTICE: Model reload took {:?}.", start.elapsed());
-                true
+        let room = self.in_flight.len() < self.max_parallel;
+        match &msg.command {
+            BrainstemCommand::Infer { model, .. }
+            | BrainstemCommand::InferTokens { model, .. }
+            | BrainstemCommand::Embed { model, .. }
+            | BrainstemCommand::EmbedBatch { model, .. }
+            | BrainstemCommand::Rerank { model, .. } => {
+                room && self.engine.is_loaded() && !self.switching(model.as_ref())
             }
-            Err(e) => {
-                let _ = output_tx
-                    .send(BrainstemOutput {
-                        id: Some(request_id.to_string()),
-                        body: BrainstemBody::Error(format!("Cold reload asset fail: {}", e)),
+            // Turns of a conversation take their turn
+            BrainstemCommand::Chat { session_id, .. } => {
+                room && self.engine.is_loaded()
+                    && !self.in_flight.values().any(|request| {
+                        request
+                            .chat
+                            .as_ref()
+                            .is_some_and(|turn| &turn.session_id == session_id)
                     })
-                    .await;
-                false
             }
+            // Carries on with the model that called the tools
+            BrainstemCommand::ToolResult { .. } => {
+                let model = msg
+                    .id
+                    .as_ref()
+                    .and_then(|id| self.tool_waits.get(id))
+                    .and_then(|wait| wait.model.as_ref());
+                room && self.engine.is_loaded() && !self.switching(model)
+            }
+            BrainstemCommand::Transcribe { .. } => room,
+            _ => false,
         }
     }
 
-    #[cfg(not(feature = "cortex-engine"))]
-    async fn ensure_model_loaded(
+    /// Whether `command` may load or download a model, and so back off to run again; prompts
+    /// for the model already loaded don't.
+    fn may_load(&self, command: &BrainstemCommand) -> bool {
+        match command {
+            BrainstemCommand::Infer { model, .. }
+            | BrainstemCommand::InferTokens { model, .. }
+            | BrainstemCommand::InferBatch { model, .. } => {
+                !self.engine.is_loaded() || self.switching(model.as_ref())
+            }
+            _ => true,
+        }
+    }
+
+    /// Run one command; requests that stream go in flight. A panic in the engine restarts it,
+    /// and a load that backs off runs the command again later.
+    async fn execute(
         &mut self,
-        model: Option<String>,
-        request_id: &str,
+        msg: BrainstemInput,
         output_tx: &mut mpsc::Sender<BrainstemOutput>,
-    ) -> bool {
-        if self.engine.is_loaded() {
-            return true;
+    ) {
+        let request_id = msg.id.clone().unwrap_or_else(|| "anon".to_string());
+        let transcribing = matches!(msg.command, BrainstemCommand::Transcribe { .. });
+        let timing = (msg.command.name(), Instant::now());
+        let audit = self
+            .audit_log
+            .as_ref()
+            .map(|log| Audit::start(&request_id, &msg.command, log));
+        let first_key = self.next_key;
+        let again =
+            (self.retry_policy.max_retries > 0 && self.may_load(&msg.command)).then(|| msg.clone());
+        self.backoff = None;
+        let (run, error) = if audit.is_some() {
+            self.run_noting_error(msg, output_tx).await
+        } else {
+            let run = std::panic::AssertUnwindSafe(self.run_command(msg, output_tx))
+                .catch_unwind()
+                .await;
+            (run, None)
+        };
+        if run.is_err() {
+            self.crashed(&request_id, transcribing, output_tx).await;
         }
-        let model_to_load = model
-            .or_else(|| self.last_model_name.clone())
-            .unwrap_or_else(|| self.engine.default_model());
+        // Runs again from the start, audited and timed then
+        if let (Ok(()), Some(delay), Some(msg)) = (&run, self.backoff.take(), again) {
+            self.retrying.push((Instant::now() + delay, msg));
+            return;
+        }
+        self.retries.remove(&request_id);
 
-        if let Err(e) = self.engine.load_model(&model_to_load).await {
-            let _ = output_tx
-                .send(BrainstemOutput {
-                    id: Some(request_id.to_string()),
-                    body: BrainstemBody::Error(format!("Cold reload failed: {}", e)),
-                })
-                .await;
-            return false;
+        // A request that streams is timed and audited until its events end
+        let streaming = (first_key..self.next_key).find(|key| self.in_flight.contains_key(key));
+        let audit = audit.map(|mut audit| {
+            if let Some(error) = &error {
+                audit.fail(error);
+            }
+            if streaming.is_some() {
+                audit.ran_on(match transcribing {
+                    true => self.transcriber_model.as_ref(),
+                    false => self.last_model_name.as_ref(),
+                });
+            }
+            audit
+        });
+        // A batch only once its last prompt finishes
+        if let Some(batch) = self.batches.get_mut(&request_id) {
+            batch.timing = Some(timing);
+            batch.audit = audit;
+            return;
+        }
+        match streaming.and_then(|key| self.in_flight.get_mut(&key)) {
+            Some(request) => {
+                request.timing = Some(timing);
+                request.audit = audit;
+            }
+            None => {
+                self.metrics.record_command(timing.0, timing.1.elapsed());
+                let status = match run {
+                    Ok(()) => AuditStatus::Ok,
+                    Err(_) => AuditStatus::Crashed,
+                };
+                self.audit(audit, status);
+            }
+        }
+    }
+
+    /// Run `msg` as `execute` does, passing its outputs on to `output_tx`, and return the
+    /// first error it was answered with, for the audit.
+    async fn run_noting_error(
+        &mut self,
+        msg: BrainstemInput,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) -> (std::thread::Result<()>, Option<String>) {
+        let id = msg.id.clone();
+        let (mut noted_tx, mut noted_rx) = mpsc::channel::<BrainstemOutput>(0);
+        let run = async move {
+            std::panic::AssertUnwindSafe(self.run_command(msg, &mut noted_tx))
+                .catch_unwind()
+                .await
+        };
+        let pass_on = async move {
+            let mut error = None;
+            while let Some(output) = noted_rx.next().await {
+                if let BrainstemBody::Error(e) = &output.body {
+                    if output.id == id && error.is_none() {
+                        error = Some(e.clone());
+                    }
+                }
+                let _ = output_tx.send(output).await;
+            }
+            error
+        };
+        future::join(run, pass_on).await
+    }
+
+    /// Write the record of a command that ended with `status`, when auditing.
+    fn audit(&self, audit: Option<Audit>, status: AuditStatus) {
+        if let (Some(audit), Some(log)) = (audit, &self.audit_log) {
+            audit.finish(log, status);
+        }
+    }
+
+    /// End the batch `request_id` with `status`.
+    fn end_batch(&mut self, request_id: &str, status: AuditStatus) {
+        if let Some(batch) = self.batches.remove(request_id) {
+            if let Some((command, started)) = batch.timing {
+                self.metrics.record_command(command, started.elapsed());
+            }
+            self.audit(batch.audit, status);
+        }
+    }
+
+    async fn run_command(
+        &mut self,
+        msg: BrainstemInput,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        let request_id = msg.id.unwrap_or_else(|| "anon".to_string());
+        match msg.command {
+            BrainstemCommand::LoadModel(name_or_path) => {
+                let waking = self.begin_wake(output_tx).await;
+                self.handle_load_model(name_or_path, &request_id, output_tx)
+                    .await;
+                self.end_wake(waking, output_tx).await;
+            }
+            BrainstemCommand::Infer {
+                model,
+                prompt,
+                config,
+            } => {
+                self.handle_infer(model, Prompt::Text(prompt), config, &request_id, output_tx)
+                    .await;
+            }
+            BrainstemCommand::InferTokens {
+                model,
+                tokens,
+                config,
+            } => {
+                self.handle_infer(
+                    model,
+                    Prompt::Tokens(tokens),
+                    config,
+                    &request_id,
+                    output_tx,
+                )
+                .await;
+            }
+            BrainstemCommand::InferBatch {
+                model,
+                prompts,
+                config,
+            } => {
+                self.handle_infer_batch(model, prompts, config, &request_id, output_tx)
+                    .await;
+            }
+            BrainstemCommand::Chat {
+                session_id,
+                message,
+                config,
+            } => {
+                self.handle_chat(session_id, message, config, &request_id, output_tx)
+                    .await;
+            }
+            BrainstemCommand::Rag {
+                model,
+                query,
+                documents,
+                embeddings,
+                config,
+            } => {
+                self.handle_rag(
+                    model,
+                    query,
+                    documents,
+                    embeddings,
+                    config,
+                    &request_id,
+                    output_tx,
+                )
+                .await;
+            }
+            BrainstemCommand::ToolResult { call_id, content } => {
+                self.handle_tool_result(call_id, content, &request_id, output_tx)
+                    .await;
+            }
+            BrainstemCommand::Embed {
+                model,
+                input,
+                config,
+            } => {
+                self.handle_embed(model, input, config, &request_id, output_tx)
+                    .await;
+            }
+            BrainstemCommand::EmbedBatch {
+                model,
+                inputs,
+                config,
+            } => {
+                self.handle_embed_batch(model, inputs, config, &request_id, output_tx)
+                    .await;
+            }
+            BrainstemCommand::Rerank {
+                model,
+                query,
+                documents,
+                config,
+            } => {
+                self.handle_rerank(model, query, documents, config, &request_id, output_tx)
+                    .await;
+            }
+            BrainstemCommand::Transcribe {
+                model,
+                audio,
+                config,
+            } => {
+                self.handle_transcribe(model, audio, config, &request_id, output_tx)
+                    .await;
+            }
+            BrainstemCommand::Health => {
+                let body = match self.engine.health().await {
+                    Ok(mut health) => {
+                        health.state = Some(self.state);
+                        BrainstemBody::Health(health)
+                    }
+                    Err(e) => BrainstemBody::Error(e.to_string()),
+                };
+                let _ = output_tx
+                    .send(BrainstemOutput::new(Some(request_id.clone()), body))
+                    .await;
+            }
+            BrainstemCommand::Bench { model, config } => {
+                self.handle_bench(model, config, &request_id, output_tx)
+                    .await;
+            }
+            BrainstemCommand::ListModels => {
+                self.handle_list_models(&request_id, output_tx).await;
+            }
+            BrainstemCommand::CloseSession(session_id) => {
+                self.live_sessions.remove(&session_id);
+                self.conversations.remove(&session_id);
+                if let Some(path) = self.session_file(&session_id) {
+                    let _ = std::fs::remove_file(path);
+                }
+                let body = match self.engine.close_session(&session_id).await {
+                    Ok(()) => {
+                        BrainstemBody::Event(rusty_genius_core::protocol::InferenceEvent::Complete)
+                    }
+                    Err(e) => BrainstemBody::Error(e.to_string()),
+                };
+                let _ = output_tx
+                    .send(BrainstemOutput::new(Some(request_id.clone()), body))
+                    .await;
+            }
+            BrainstemCommand::ApplyAdapter(adapter) => {
+                self.handle_apply_adapter(adapter, &request_id, output_tx)
+                    .await;
+            }
+            BrainstemCommand::RemoveAdapter => {
+                let body = match self.engine.remove_adapter().await {
+                    Ok(()) => {
+                        BrainstemBody::Event(rusty_genius_core::protocol::InferenceEvent::Complete)
+                    }
+                    Err(e) => BrainstemBody::Error(e.to_string()),
+                };
+                let _ = output_tx
+                    .send(BrainstemOutput::new(Some(request_id.clone()), body))
+                    .await;
+            }
+            BrainstemCommand::Cancel { id } => {
+                self.cancel(&id, &request_id, output_tx).await;
+            }
+            BrainstemCommand::GetMetrics => {
+                let _ = output_tx
+                    .send(BrainstemOutput::new(
+                        Some(request_id.clone()),
+                        BrainstemBody::Metrics(self.metrics.clone()),
+                    ))
+                    .await;
+            }
+            BrainstemCommand::SetStrategy(strategy) => {
+                self.strategy = strategy;
+                let _ = output_tx
+                    .send(BrainstemOutput::new(
+                        Some(request_id.clone()),
+                        BrainstemBody::Event(InferenceEvent::Complete),
+                    ))
+                    .await;
+            }
+            BrainstemCommand::Reset => {
+                let was_loaded = self.engine.is_loaded();
+                self.persist_sessions().await;
+                if let Err(e) = self.engine.unload_model().await {
+                    let _ = output_tx
+                        .send(BrainstemOutput::new(
+                            Some(request_id.clone()),
+                            BrainstemBody::Error(e.to_string()),
+                        ))
+                        .await;
+                } else {
+                    self.last_model_name = None;
+                    let _ = output_tx
+                        .send(BrainstemOutput::new(
+                            Some(request_id.clone()),
+                            BrainstemBody::Event(
+                                rusty_genius_core::protocol::InferenceEvent::Complete,
+                            ),
+                        ))
+                        .await;
+                    if was_loaded {
+                        Self::broadcast(BrainstemBody::Hibernated, output_tx).await;
+                    }
+                }
+            }
+            // Stopping is up to run
+            BrainstemCommand::Stop => {}
+        }
+    }
+
+    // ── LoadModel ──
+
+    #[cfg(feature = "cortex-engine")]
+    async fn handle_load_model(
+        &mut self,
+        name_or_path: String,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        let mut path_to_load = name_or_path.clone();
+
+        // Remote engines take the model name as is; there is nothing to download.
+        if !self.engine.is_remote() {
+            let mut events = self.asset_authority.ensure_model_stream(&name_or_path);
+            let mut downloaded = 0;
+            while let Some(event) = events.next().await {
+                self.count_download(&event, &mut downloaded);
+                if let AssetEvent::Progress(..) = event {
+                    self.enter(BrainstemState::Downloading, output_tx).await;
+                }
+                if let AssetEvent::Complete(path) = &event {
+                    path_to_load = path.clone();
+                }
+                if let AssetEvent::Error(e) = &event {
+                    if self.retry(e, request_id, output_tx).await {
+                        return;
+                    }
+                }
+                if output_tx
+                    .send(BrainstemOutput::new(
+                        Some(request_id.to_string()),
+                        BrainstemBody::Asset(event),
+                    ))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        }
+
+        let body = match self
+            .switch_model(name_or_path, &path_to_load, request_id, output_tx)
+            .await
+        {
+            // Loaded again after the backoff
+            Err(_) if self.backoff.is_some() => return,
+            Err(e) => BrainstemBody::Error(e.to_string()),
+            // Local models were announced as they loaded
+            Ok(()) if !self.engine.is_remote() => return,
+            Ok(()) => BrainstemBody::Asset(AssetEvent::Loaded {
+                path: path_to_load,
+                gpu_layers: None,
+            }),
+        };
+        let _ = output_tx
+            .send(BrainstemOutput::new(Some(request_id.to_string()), body))
+            .await;
+    }
+
+    /// Load the model at `path` for requests naming `name` (or none) from now on, telling the
+    /// client it `Loaded`. Errors are the caller's to report.
+    #[cfg(feature = "cortex-engine")]
+    async fn switch_model(
+        &mut self,
+        name: String,
+        path: &str,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) -> Result<()> {
+        let memory = match &self.standby {
+            // Loaded already, beside the model that served
+            Some(standby) if standby.path == path => standby.memory,
+            _ if self.engine.is_remote() => 0,
+            _ => self.check_memory(&name, std::path::Path::new(path))?,
+        };
+        self.persist_sessions().await;
+        self.load_engine(path, request_id, output_tx).await?;
+        self.loaded_memory = memory;
+        self.faulted = false;
+        self.model_context_length = facecrab::inspect(path)
+            .ok()
+            .and_then(|info| info.context_length);
+        self.remember_model(name);
+        if !self.engine.is_remote() {
+            let gpu_layers = self.engine.model_info().and_then(|info| info.gpu_layers);
+            let _ = output_tx
+                .send(BrainstemOutput::new(
+                    Some(request_id.to_string()),
+                    BrainstemBody::Asset(AssetEvent::Loaded {
+                        path: path.to_string(),
+                        gpu_layers,
+                    }),
+                ))
+                .await;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "cortex-engine"))]
+    async fn handle_load_model(
+        &mut self,
+        name_or_path: String,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        self.persist_sessions().await;
+        if let Err(e) = self.load_engine(&name_or_path, request_id, output_tx).await {
+            // Loaded again after the backoff
+            if self.backoff.is_none() {
+                let _ = output_tx
+                    .send(BrainstemOutput::new(
+                        Some(request_id.to_string()),
+                        BrainstemBody::Error(e.to_string()),
+                    ))
+                    .await;
+            }
+        } else {
+            self.faulted = false;
+            let _ = output_tx
+                .send(BrainstemOutput::new(
+                    Some(request_id.to_string()),
+                    BrainstemBody::Asset(AssetEvent::Loaded {
+                        path: name_or_path.clone(),
+                        gpu_layers: self.engine.model_info().and_then(|info| info.gpu_layers),
+                    }),
+                ))
+                .await;
+            self.remember_model(name_or_path);
+        }
+    }
+
+    /// Load the model at `path` into the engine with the load settings. A transient failure
+    /// backs off to be retried; see [Self::retry].
+    async fn load_engine(
+        &mut self,
+        path: &str,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) -> Result<()> {
+        self.enter(BrainstemState::Loading, output_tx).await;
+        // One loaded in the background takes over; any other is dropped, freeing its memory
+        #[cfg(feature = "cortex-engine")]
+        if let Some(standby) = self.standby.take() {
+            if standby.path == path {
+                self.engine = standby.engine;
+                return Ok(());
+            }
+        }
+        let Err(e) = self
+            .engine
+            .load_model_with_config(path, self.load_config.clone())
+            .await
+        else {
+            return Ok(());
+        };
+        self.retry(&format!("{:#}", e), request_id, output_tx).await;
+        Err(e)
+    }
+
+    /// Whether to try the command `request_id` again after its load or download failed with
+    /// `error`: when the error looks transient and the retry policy allows another retry. The
+    /// client is told it is `Retrying`, and the command, which gives up quietly when this
+    /// returns `true`, runs again once the backoff is over while others are served.
+    async fn retry(
+        &mut self,
+        error: &str,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) -> bool {
+        let retries = self.retries.get(request_id).copied().unwrap_or(0);
+        if retries >= self.retry_policy.max_retries || !is_transient(error) {
+            return false;
+        }
+        let delay = self.retry_policy.backoff * 2u32.saturating_pow(retries);
+        self.retries.insert(request_id.to_string(), retries + 1);
+        self.metrics.load_retries += 1;
+        eprintln!("NOTICE: Retrying in {:?} after: {}", delay, error);
+        let _ = output_tx
+            .send(BrainstemOutput::new(
+                Some(request_id.to_string()),
+                BrainstemBody::Retrying {
+                    attempt: retries + 1,
+                    delay,
+                    error: error.to_string(),
+                },
+            ))
+            .await;
+        self.backoff = Some(delay);
+        true
+    }
+
+    // ── Last model ──
+
+    /// Make `name` the model requests that name none run on, and remember it for `resume`.
+    fn remember_model(&mut self, name: String) {
+        if let Some(file) = &self.last_model_file {
+            let last = LastModel {
+                name: name.clone(),
+                config: self.load_config.clone(),
+            };
+            let written = serde_json::to_string_pretty(&last)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| {
+                    if let Some(dir) = file.parent() {
+                        std::fs::create_dir_all(dir)?;
+                    }
+                    Ok(std::fs::write(file, json)?)
+                });
+            if let Err(e) = written {
+                eprintln!("NOTICE: Last model not remembered: {}", e);
+            }
+        }
+        self.last_model_name = Some(name);
+    }
+
+    /// Queue a load of the remembered model, with the settings it was loaded with, ahead of
+    /// any request. Its events go out under the request id `resume`.
+    fn queue_resume(&mut self) {
+        let Some(file) = &self.last_model_file else {
+            return;
+        };
+        let last = match std::fs::read_to_string(file) {
+            Ok(json) => serde_json::from_str::<LastModel>(&json),
+            // Nothing was loaded yet
+            Err(_) => return,
+        };
+        match last {
+            Ok(last) => {
+                self.load_config = last.config;
+                self.pending.push_front(BrainstemInput {
+                    id: Some("resume".to_string()),
+                    command: BrainstemCommand::LoadModel(last.name),
+                });
+            }
+            Err(e) => eprintln!("NOTICE: Last model not resumed: {}", e),
+        }
+    }
+
+    // ── Preloading ──
+
+    /// The model `msg` switches to, when it is a local model to fetch first; one that is
+    /// cached already resolves at once. A request whose model was fetched in the background
+    /// switches as usual.
+    #[cfg(feature = "cortex-engine")]
+    fn preload_for(&self, msg: &BrainstemInput) -> Option<String> {
+        if msg
+            .id
+            .as_ref()
+            .is_some_and(|id| self.preloaded.contains(id))
+        {
+            return None;
+        }
+        let model = msg.command.model()?;
+        (!self.engine.is_remote() && self.switching(Some(model))).then(|| model.clone())
+    }
+
+    /// Hold `msg` until `model` has downloaded, starting the download unless it is under way.
+    #[cfg(feature = "cortex-engine")]
+    fn preload(&mut self, model: String, msg: BrainstemInput) {
+        if let Some(preload) = self.preloading.get_mut(&model) {
+            preload.waiting.push(msg);
+            return;
+        }
+        eprintln!(
+            "NOTICE: Downloading {} while the loaded model serves.",
+            model
+        );
+        let name = model.clone();
+        let events = self.asset_authority.ensure_model_stream(&model);
+        self.preloads.push(Box::pin(
+            events
+                .map(PreloadEvent::Asset)
+                .chain(stream::once(future::ready(PreloadEvent::Downloaded)))
+                .map(move |event| (name.clone(), event)),
+        ));
+        self.preloading.insert(
+            model,
+            Preload {
+                waiting: vec![msg],
+                downloaded: 0,
+                loading: None,
+            },
+        );
+    }
+
+    /// Relay the progress of `model`'s download to the requests waiting for it, then load it
+    /// on an engine of its own when it can be. Once that ends they queue again, ahead of the
+    /// rest, to switch to it; a download or load that failed fails again there, as it would
+    /// have without the preload.
+    async fn preload_event(
+        &mut self,
+        model: String,
+        event: PreloadEvent,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        let Some(mut preload) = self.preloading.remove(&model) else {
+            return;
+        };
+        match event {
+            // The switch reports the path as it loads
+            PreloadEvent::Asset(AssetEvent::Complete(path)) => {
+                let Some(memory) = self.load_beside(&model, &path) else {
+                    return self.requeue_preloaded(preload.waiting);
+                };
+                preload.loading = Some((path, memory));
+            }
+            PreloadEvent::Asset(AssetEvent::Error(_)) => {
+                return self.requeue_preloaded(preload.waiting);
+            }
+            PreloadEvent::Asset(event) => {
+                self.count_download(&event, &mut preload.downloaded);
+                for msg in &preload.waiting {
+                    let _ = output_tx
+                        .send(BrainstemOutput::new(
+                            msg.id.clone(),
+                            BrainstemBody::Asset(event.clone()),
+                        ))
+                        .await;
+                }
+            }
+            PreloadEvent::Downloaded if preload.loading.is_some() => {}
+            PreloadEvent::Downloaded => return self.requeue_preloaded(preload.waiting),
+            #[cfg(feature = "cortex-engine")]
+            PreloadEvent::Loaded(engine, loaded) => {
+                let (path, memory) = preload.loading.take().unwrap_or_default();
+                match loaded {
+                    Ok(()) => {
+                        self.standby = Some(Standby {
+                            path,
+                            engine,
+                            memory,
+                        });
+                    }
+                    Err(e) => eprintln!("NOTICE: Loading {} ahead failed: {:#}", model, e),
+                }
+                return self.requeue_preloaded(preload.waiting);
+            }
+        }
+        self.preloading.insert(model, preload);
+    }
+
+    /// Queue `waiting` again, ahead of the rest, to switch to the model they waited for.
+    fn requeue_preloaded(&mut self, waiting: Vec<BrainstemInput>) {
+        for msg in waiting.into_iter().rev() {
+            if let Some(id) = &msg.id {
+                self.preloaded.insert(id.clone());
+            }
+            self.pending.push_front(msg);
+        }
+    }
+
+    /// Load `model` from `path` on an engine the engine factory builds, off the loop, for
+    /// the switch to it to swap in; returns the memory it takes, if the load started. It needs
+    /// a factory, and room for the model beside the one loaded.
+    #[cfg(feature = "cortex-engine")]
+    fn load_beside(&mut self, model: &str, path: &str) -> Option<u64> {
+        let factory = self.engine_factory.as_ref()?;
+        let memory = self.memory_beside(path)?;
+        eprintln!("NOTICE: Loading {} while the loaded model serves.", model);
+        let engine = factory();
+        let (path, config) = (path.to_string(), self.load_config.clone());
+        // Engines may load on the thread that asks them to
+        let load = runtime::spawn_blocking(move || {
+            futures::executor::block_on(async {
+                let mut engine = engine.await;
+                let loaded = engine.load_model_with_config(&path, config).await;
+                (engine, loaded)
+            })
+        });
+        let name = model.to_string();
+        self.preloads
+            .push(Box::pin(stream::once(load).map(move |(engine, loaded)| {
+                (name.clone(), PreloadEvent::Loaded(engine, loaded))
+            })));
+        Some(memory)
+    }
+
+    #[cfg(not(feature = "cortex-engine"))]
+    fn load_beside(&mut self, _model: &str, _path: &str) -> Option<u64> {
+        None
+    }
+
+    // ── Ensure model loaded (cold reload) ──
+
+    /// Whether `model` is kept warm rather than hibernated.
+    fn is_warm(&self, model: Option<&String>) -> bool {
+        model.is_some_and(|model| self.keep_warm.contains(model))
+    }
+
+    /// Whether a request for `model` has to switch away from the model loaded.
+    fn switching(&self, model: Option<&String>) -> bool {
+        self.engine.is_loaded()
+            && model.is_some_and(|requested| self.last_model_name.as_ref() != Some(requested))
+    }
+
+    /// Load the model a request names, or the last one when it names none; see
+    /// [Self::load_requested_model]. Every client is told the engine is `Waking` when it was
+    /// unloaded, and `Ready` (or still `Hibernated`) once the load ends.
+    async fn ensure_model_loaded(
+        &mut self,
+        model: Option<String>,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) -> bool {
+        let waking = self.begin_wake(output_tx).await;
+        let loaded = self
+            .load_requested_model(model, request_id, output_tx)
+            .await;
+        self.end_wake(waking, output_tx).await;
+        loaded
+    }
+
+    /// Tell every client the engine is `Waking`, if it has no model loaded; returns whether it
+    /// had none.
+    async fn begin_wake(&self, output_tx: &mut mpsc::Sender<BrainstemOutput>) -> bool {
+        let waking = !self.engine.is_loaded();
+        if waking {
+            Self::broadcast(BrainstemBody::Waking, output_tx).await;
+        }
+        waking
+    }
+
+    /// After a load [Self::begin_wake] announced, tell every client whether the engine is
+    /// `Ready` or still `Hibernated`.
+    async fn end_wake(&self, waking: bool, output_tx: &mut mpsc::Sender<BrainstemOutput>) {
+        if waking {
+            let body = if self.engine.is_loaded() {
+                BrainstemBody::Ready
+            } else {
+                BrainstemBody::Hibernated
+            };
+            Self::broadcast(body, output_tx).await;
+        }
+    }
+
+    /// Send every client a change in the engine's state; such outputs carry no request id.
+    async fn broadcast(body: BrainstemBody, output_tx: &mut mpsc::Sender<BrainstemOutput>) {
+        let _ = output_tx.send(BrainstemOutput::new(None, body)).await;
+    }
+
+    // ── State ──
+
+    /// Move to `state`, telling every client of the transition.
+    async fn enter(
+        &mut self,
+        state: BrainstemState,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        if state != self.state {
+            let from = std::mem::replace(&mut self.state, state);
+            Self::broadcast(BrainstemBody::State { from, to: state }, output_tx).await;
+        }
+    }
+
+    /// Move to the state the orchestrator is in between commands: `Generating` while requests
+    /// run, else `Ready` with a model loaded, `Hibernated` with one unloaded, or `Idle`.
+    async fn settle(&mut self, output_tx: &mut mpsc::Sender<BrainstemOutput>) {
+        let state = if self.faulted {
+            BrainstemState::Faulted
+        } else if !self.in_flight.is_empty() {
+            BrainstemState::Generating
+        } else if self.engine.is_loaded() {
+            BrainstemState::Ready
+        } else if self.last_model_name.is_some() {
+            BrainstemState::Hibernated
+        } else {
+            BrainstemState::Idle
+        };
+        self.enter(state, output_tx).await;
+    }
+
+    /// Load the model a request names, or the last one when it names none, relaying the
+    /// download and load to the client as asset events.
+    ///
+    /// A request for a model other than the last one loaded switches to it; the engine keeps
+    /// recently used models resident, so switching back and forth between a chat and an
+    /// embedding model doesn't reload them. A name that can't be resolved fails the request,
+    /// rather than running it on another model.
+    #[cfg(feature = "cortex-engine")]
+    async fn load_requested_model(
+        &mut self,
+        model: Option<String>,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) -> bool {
+        let switching = self.switching(model.as_ref());
+        if self.engine.is_loaded() && !switching {
+            return true;
+        }
+        let model_to_load = model
+            .or_else(|| self.last_model_name.clone())
+            .unwrap_or_else(|| self.engine.default_model());
+
+        let start = Instant::now();
+        let resolved = if self.engine.is_remote() {
+            Ok(model_to_load.clone())
+        } else {
+            self.resolve_model(&model_to_load, request_id, output_tx)
+                .await
+        };
+        match resolved {
+            Ok(path) => {
+                let _ = output_tx
+                    .send(BrainstemOutput::new(
+                        Some(request_id.to_string()),
+                        BrainstemBody::Asset(AssetEvent::Loading(model_to_load.clone())),
+                    ))
+                    .await;
+                let remote = self.engine.is_remote();
+                if let Err(e) = self
+                    .switch_model(model_to_load, &path, request_id, output_tx)
+                    .await
+                {
+                    // Loaded again after the backoff
+                    if self.backoff.is_none() {
+                        let _ = output_tx
+                            .send(BrainstemOutput::new(
+                                Some(request_id.to_string()),
+                                BrainstemBody::Error(format!("Cold reload failed: {}", e)),
+                            ))
+                            .await;
+                    }
+                    return false;
+                }
+                // Local models were announced as they loaded
+                if remote {
+                    let _ = output_tx
+                        .send(BrainstemOutput::new(
+                            Some(request_id.to_string()),
+                            BrainstemBody::Asset(AssetEvent::Loaded {
+                                path,
+                                gpu_layers: None,
+                            }),
+                        ))
+                        .await;
+                }
+                self.metrics.cold_reloads += 1;
+                match self.engine.model_info().and_then(|info| info.gpu_layers) {
+                    Some(layers) => eprintln!(
+                        "NOTICE: Model reload took {:?}, with {} layers on the GPU.",
+                        start.elapsed(),
+                        layers
+                    ),
+                    None => eprintln!("NOTICE: Model reload took {:?}.", start.elapsed()),
+                }
+                true
+            }
+            // Downloaded again after the backoff
+            Err(_) if self.backoff.is_some() => false,
+            Err(e) if switching => {
+                let _ = output_tx
+                    .send(BrainstemOutput::new(
+                        Some(request_id.to_string()),
+                        BrainstemBody::Error(format!(
+                            "Model {} not available: {}",
+                            model_to_load, e
+                        )),
+                    ))
+                    .await;
+                false
+            }
+            Err(e) => {
+                let _ = output_tx
+                    .send(BrainstemOutput::new(
+                        Some(request_id.to_string()),
+                        BrainstemBody::Error(format!("Cold reload asset fail: {}", e)),
+                    ))
+                    .await;
+                false
+            }
+        }
+    }
+
+    /// Download the model `name` if it isn't cached, relaying progress to the client, and
+    /// return its path. A failure is returned rather than relayed, since the caller may carry
+    /// on without the model.
+    #[cfg(feature = "cortex-engine")]
+    async fn resolve_model(
+        &mut self,
+        name: &str,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) -> Result<String> {
+        let mut events = self.asset_authority.ensure_model_stream(name);
+        let mut downloaded = 0;
+        while let Some(event) = events.next().await {
+            self.count_download(&event, &mut downloaded);
+            if let AssetEvent::Progress(..) = event {
+                self.enter(BrainstemState::Downloading, output_tx).await;
+            }
+            let path = match &event {
+                AssetEvent::Error(e) => {
+                    self.retry(e, request_id, output_tx).await;
+                    return Err(anyhow::anyhow!("{}", e));
+                }
+                AssetEvent::Complete(path) => Some(path.clone()),
+                _ => None,
+            };
+            let _ = output_tx
+                .send(BrainstemOutput::new(
+                    Some(request_id.to_string()),
+                    BrainstemBody::Asset(event),
+                ))
+                .await;
+            if let Some(path) = path {
+                return Ok(path);
+            }
+        }
+        Err(anyhow::anyhow!("Download of {} stopped", name))
+    }
+
+    /// Count the bytes a download's `event` reports since the last one, `downloaded` bytes in.
+    fn count_download(&mut self, event: &AssetEvent, downloaded: &mut u64) {
+        match *event {
+            AssetEvent::Resuming { offset, .. } => *downloaded = offset,
+            AssetEvent::Progress(current, _) => {
+                self.metrics.download_bytes += current.saturating_sub(*downloaded);
+                *downloaded = current;
+            }
+            _ => {}
+        }
+    }
+
+    #[cfg(not(feature = "cortex-engine"))]
+    async fn load_requested_model(
+        &mut self,
+        model: Option<String>,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) -> bool {
+        if self.engine.is_loaded() && !self.switching(model.as_ref()) {
+            return true;
+        }
+        let model_to_load = model
+            .or_else(|| self.last_model_name.clone())
+            .unwrap_or_else(|| self.engine.default_model());
+
+        let _ = output_tx
+            .send(BrainstemOutput::new(
+                Some(request_id.to_string()),
+                BrainstemBody::Asset(AssetEvent::Loading(model_to_load.clone())),
+            ))
+            .await;
+        if let Err(e) = self
+            .load_engine(&model_to_load, request_id, output_tx)
+            .await
+        {
+            // Loaded again after the backoff
+            if self.backoff.is_none() {
+                let _ = output_tx
+                    .send(BrainstemOutput::new(
+                        Some(request_id.to_string()),
+                        BrainstemBody::Error(format!("Cold reload failed: {}", e)),
+                    ))
+                    .await;
+            }
+            return false;
+        }
+        let _ = output_tx
+            .send(BrainstemOutput::new(
+                Some(request_id.to_string()),
+                BrainstemBody::Asset(AssetEvent::Loaded {
+                    path: model_to_load.clone(),
+                    gpu_layers: self.engine.model_info().and_then(|info| info.gpu_layers),
+                }),
+            ))
+            .await;
+        self.remember_model(model_to_load);
+        self.metrics.cold_reloads += 1;
+        true
+    }
+
+    // ── Memory ──
+
+    /// Refuse to load the model at `path`, requested as `name`, if it needs more RAM and VRAM
+    /// than are available, counting what the model it replaces frees, with an
+    /// [InsufficientMemory](facecrab::InsufficientMemory) error naming the smallest
+    /// quantization of the model in the registry that would fit. Returns the memory the model
+    /// takes.
+    ///
+    /// The estimate counts the weights and the KV cache of one context of the default request
+    /// size. Paths that aren't GGUF files (remote models, safetensors directories) and machines
+    /// whose memory can't be read pass unchecked.
+    #[cfg(feature = "cortex-engine")]
+    fn check_memory(&self, name: &str, path: &std::path::Path) -> Result<u64> {
+        use facecrab::memory::quant_bits;
+        use facecrab::sources::quant_from_filename;
+
+        let context_size = rusty_genius_core::manifest::InferenceConfig::default().context_size;
+        let Ok(estimate) =
+            facecrab::estimate_memory(path, context_size, self.load_config.kv_cache_type)
+        else {
+            return Ok(0);
+        };
+        let Some(free) = self.free_memory() else {
+            return Ok(estimate.total());
+        };
+        let freed = match self.engine.is_loaded() {
+            true => self.loaded_memory,
+            false => 0,
+        };
+        let available = free + freed;
+        if estimate.total() <= available {
+            return Ok(estimate.total());
+        }
+
+        let bits = path
+            .file_name()
+            .and_then(|file| quant_from_filename(&file.to_string_lossy()))
+            .and_then(|quant| quant_bits(&quant));
+        let suggestion = bits.and_then(|bits| {
+            self.asset_authority
+                .smaller_quants(name, bits)
+                .into_iter()
+                .find(|entry| {
+                    quant_bits(&entry.quantization).is_some_and(|smaller| {
+                        estimate.requantized(bits, smaller).total() <= available
+                    })
+                })
+                .map(|entry| entry.name)
+        });
+        Err(facecrab::InsufficientMemory {
+            model: name.to_string(),
+            required: estimate,
+            available,
+            suggestion,
+        }
+        .into())
+    }
+
+    /// The memory the model at `path` takes, if it fits in what is free beside the model
+    /// loaded; one that can't be estimated takes none, as in [Self::check_memory].
+    #[cfg(feature = "cortex-engine")]
+    fn memory_beside(&self, path: &str) -> Option<u64> {
+        let context_size = rusty_genius_core::manifest::InferenceConfig::default().context_size;
+        let path = std::path::Path::new(path);
+        let Ok(estimate) =
+            facecrab::estimate_memory(path, context_size, self.load_config.kv_cache_type)
+        else {
+            return Some(0);
+        };
+        let fits = self
+            .free_memory()
+            .is_none_or(|free| estimate.total() <= free);
+        fits.then(|| estimate.total())
+    }
+
+    /// Available RAM and free VRAM, when the machine's memory can be read.
+    #[cfg(feature = "cortex-engine")]
+    fn free_memory(&self) -> Option<u64> {
+        let ram = facecrab::system_memory()?;
+        let vram_free: u64 = self
+            .engine
+            .devices()
+            .into_iter()
+            .filter(|device| device.gpu)
+            .map(|gpu| gpu.memory_free)
+            .sum();
+        Some(ram.available + vram_free)
+    }
+
+    /// Cap a requested context size at the loaded model's training context length.
+    fn fit_context(
+        &self,
+        mut config: rusty_genius_core::manifest::InferenceConfig,
+    ) -> rusty_genius_core::manifest::InferenceConfig {
+        if let (Some(requested), Some(max)) = (config.context_size, self.model_context_length) {
+            if requested as u64 > max {
+                config.context_size = Some(max as u32);
+            }
+        }
+        config
+    }
+
+    /// The generation settings the loaded model's registry entry gives it.
+    #[cfg(feature = "cortex-engine")]
+    fn model_defaults(&self) -> ModelDefaults {
+        self.last_model_name
+            .as_ref()
+            .and_then(|name| {
+                self.asset_authority
+                    .list_models()
+                    .into_iter()
+                    .find(|entry| &entry.name == name)
+                    .and_then(|entry| entry.defaults)
+            })
+            .unwrap_or_default()
+    }
+
+    #[cfg(not(feature = "cortex-engine"))]
+    fn model_defaults(&self) -> ModelDefaults {
+        ModelDefaults::default()
+    }
+
+    // ── Adapters ──
+
+    /// Swap an adapter given by registry name for the local path of its GGUF, downloading it
+    /// if needed.
+    #[cfg(feature = "cortex-engine")]
+    async fn resolve_adapter(&self, adapter: &mut AdapterConfig) -> Result<()> {
+        if !std::path::Path::new(&adapter.name).exists() {
+            let path = self.asset_authority.ensure_model(&adapter.name).await?;
+            adapter.name = path.display().to_string();
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "cortex-engine"))]
+    async fn resolve_adapter(&self, _adapter: &mut AdapterConfig) -> Result<()> {
+        Ok(())
+    }
+
+    async fn handle_apply_adapter(
+        &mut self,
+        mut adapter: AdapterConfig,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        if !self.ensure_model_loaded(None, request_id, output_tx).await {
+            return;
+        }
+
+        let result = match self.resolve_adapter(&mut adapter).await {
+            Ok(()) => {
+                self.engine
+                    .apply_adapter(&adapter.name, adapter.scale)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        let body = match result {
+            Ok(()) => BrainstemBody::Event(InferenceEvent::Complete),
+            Err(e) => BrainstemBody::Error(format!("Adapter {}: {}", adapter.name, e)),
+        };
+        let _ = output_tx
+            .send(BrainstemOutput::new(Some(request_id.to_string()), body))
+            .await;
+    }
+
+    // ── Sessions ──
+
+    /// Where the KV cache of `session_id` on the current model is saved.
+    fn session_file(&self, session_id: &str) -> Option<PathBuf> {
+        let model = self.last_model_name.as_deref()?;
+        let file_name = |name: &str| -> String {
+            name.chars()
+                .map(|c| match c {
+                    'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+                    _ => '_',
+                })
+                .collect()
+        };
+        Some(
+            self.session_dir
+                .as_ref()?
+                .join(file_name(model))
+                .join(format!("{}.session", file_name(session_id))),
+        )
+    }
+
+    /// Save the KV cache of every session the engine has a context for, before the engine
+    /// unloads or switches models.
+    async fn persist_sessions(&mut self) {
+        for session_id in std::mem::take(&mut self.live_sessions) {
+            let Some(path) = self.session_file(&session_id) else {
+                continue;
+            };
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            if let Err(e) = self
+                .engine
+                .save_session(&session_id, &path.to_string_lossy())
+                .await
+            {
+                eprintln!("NOTICE: Session {} not saved: {}", session_id, e);
+            }
+        }
+    }
+
+    /// Hand the engine the saved KV cache of a session it has no context for yet.
+    async fn restore_session(&mut self, session_id: &str) {
+        if !self.live_sessions.insert(session_id.to_string()) {
+            return;
+        }
+        let Some(path) = self.session_file(session_id).filter(|path| path.exists()) else {
+            return;
+        };
+        if let Err(e) = self
+            .engine
+            .load_session(session_id, &path.to_string_lossy())
+            .await
+        {
+            eprintln!("NOTICE: Session {} not restored: {}", session_id, e);
         }
-        self.last_model_name = Some(model_to_load);
-        true
     }
 
     // ── Infer ──
 
-    async fn handle_infer(
+    /// Run a prompt on the engine; returns the key of the request in flight, if it started.
+    async fn handle_infer(
+        &mut self,
+        model: Option<String>,
+        prompt: Prompt,
+        config: rusty_genius_core::manifest::InferenceConfig,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) -> Option<u64> {
+        if !self.ensure_model_loaded(model, request_id, output_tx).await {
+            return None;
+        }
+
+        let mut config = self.fit_context(self.model_defaults().apply(config));
+        if let Some(session_id) = &config.session_id {
+            self.restore_session(session_id).await;
+        }
+        if let Some(adapter) = config.adapter.as_mut() {
+            if let Err(e) = self.resolve_adapter(adapter).await {
+                let _ = output_tx
+                    .send(BrainstemOutput::new(
+                        Some(request_id.to_string()),
+                        BrainstemBody::Error(format!("Adapter {}: {}", adapter.name, e)),
+                    ))
+                    .await;
+                return None;
+            }
+        }
+        let cancel = CancellationToken::new();
+        let mut round = None;
+        let events = match prompt {
+            Prompt::Text(text) => {
+                if !config.tools.is_empty() {
+                    round = Some(ToolRound {
+                        prompt: text.clone(),
+                        config: config.clone(),
+                        output: String::new(),
+                        holding: true,
+                    });
+                }
+                self.engine.infer(&text, config, cancel.clone()).await
+            }
+            Prompt::Tokens(tokens) => {
+                self.engine
+                    .infer_tokens(tokens, config, cancel.clone())
+                    .await
+            }
+        };
+        let key = self
+            .dispatch(events, Some(cancel), false, request_id, output_tx)
+            .await?;
+        if let Some(request) = self.in_flight.get_mut(&key) {
+            request.tools = round;
+        }
+        Some(key)
+    }
+
+    async fn handle_infer_batch(
+        &mut self,
+        model: Option<String>,
+        prompts: Vec<String>,
+        config: InferenceConfig,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        if !config.tools.is_empty() {
+            let _ = output_tx
+                .send(BrainstemOutput::new(
+                    Some(request_id.to_string()),
+                    BrainstemBody::Error("Batches can't offer tools".to_string()),
+                ))
+                .await;
+            return;
+        }
+        let batch = Batch {
+            model,
+            config,
+            queued: prompts.into_iter().enumerate().collect(),
+            running: 0,
+            failed: Vec::new(),
+            timing: None,
+            audit: None,
+        };
+        self.batches.insert(request_id.to_string(), batch);
+        self.run_batch(request_id, output_tx).await;
+    }
+
+    /// Start prompts of the batch `request_id` while there's room for them, and end it once
+    /// they have all finished: with `Complete`, or with an `Error` naming the prompts that
+    /// failed.
+    async fn run_batch(&mut self, request_id: &str, output_tx: &mut mpsc::Sender<BrainstemOutput>) {
+        while self.in_flight.len() < self.max_parallel {
+            let Some(batch) = self.batches.get_mut(request_id) else {
+                return;
+            };
+            let Some((index, prompt)) = batch.queued.pop_front() else {
+                break;
+            };
+            let key = self
+                .start_prompt(index, prompt, request_id, output_tx)
+                .await;
+            let Some(batch) = self.batches.get_mut(request_id) else {
+                return;
+            };
+            match key.and_then(|key| self.in_flight.get_mut(&key)) {
+                Some(request) => {
+                    request.batch_index = Some(index);
+                    batch.running += 1;
+                }
+                // Runs again from the start after the backoff
+                None if self.backoff.is_some() => {
+                    self.batches.remove(request_id);
+                    return;
+                }
+                // The model couldn't load or the engine refused; so will the rest, and the
+                // batch ends with the prompts already running
+                None => {
+                    batch.queued.clear();
+                    break;
+                }
+            }
+        }
+        let Some(failed) = self
+            .batches
+            .get(request_id)
+            .filter(|batch| batch.queued.is_empty() && batch.running == 0)
+            .map(|batch| batch.failed.join("; "))
+        else {
+            return;
+        };
+        let (status, body) = if failed.is_empty() {
+            (
+                AuditStatus::Ok,
+                BrainstemBody::Event(InferenceEvent::Complete),
+            )
+        } else {
+            (AuditStatus::Error, BrainstemBody::Error(failed))
+        };
+        self.end_batch(request_id, status);
+        let _ = output_tx
+            .send(BrainstemOutput::new(Some(request_id.to_string()), body))
+            .await;
+    }
+
+    /// Start the prompt `index` of the batch `request_id` as [Self::handle_infer] does,
+    /// keeping the errors it is refused with for the end of the batch rather than passing
+    /// them on; returns the key of the prompt in flight, if it started.
+    async fn start_prompt(
+        &mut self,
+        index: usize,
+        prompt: String,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) -> Option<u64> {
+        let batch = self.batches.get(request_id)?;
+        let (model, config) = (batch.model.clone(), batch.config.clone());
+        let (mut noted_tx, mut noted_rx) = mpsc::channel::<BrainstemOutput>(0);
+        let start = async {
+            let key = self
+                .handle_infer(
+                    model,
+                    Prompt::Text(prompt),
+                    config,
+                    request_id,
+                    &mut noted_tx,
+                )
+                .await;
+            drop(noted_tx);
+            key
+        };
+        let pass_on = async {
+            let mut errors = Vec::new();
+            while let Some(output) = noted_rx.next().await {
+                match &output.body {
+                    BrainstemBody::Error(e) if output.id.as_deref() == Some(request_id) => {
+                        errors.push(format!("Prompt {}: {}", index, e));
+                    }
+                    _ => {
+                        let _ = output_tx.send(output).await;
+                    }
+                }
+            }
+            errors
+        };
+        let (key, errors) = future::join(start, pass_on).await;
+        if let Some(batch) = self.batches.get_mut(request_id) {
+            batch.failed.extend(errors);
+        }
+        key
+    }
+
+    // ── Chat ──
+
+    /// Record `message` in the conversation `session_id`; a user message is answered from the
+    /// whole conversation, in the engine context kept for the session.
+    async fn handle_chat(
+        &mut self,
+        session_id: String,
+        message: ChatMessage,
+        mut config: rusty_genius_core::manifest::InferenceConfig,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        if message.role != ChatRole::User {
+            self.conversations
+                .entry(session_id)
+                .or_default()
+                .push(message);
+            let _ = output_tx
+                .send(BrainstemOutput::new(
+                    Some(request_id.to_string()),
+                    BrainstemBody::Event(InferenceEvent::Complete),
+                ))
+                .await;
+            return;
+        }
+        // The template comes from the model
+        if !self.ensure_model_loaded(None, request_id, output_tx).await {
+            return;
+        }
+
+        let mut messages = self
+            .conversations
+            .get(&session_id)
+            .cloned()
+            .unwrap_or_default();
+        messages.push(message.clone());
+        // Tools are described in the system message
+        if !config.tools.is_empty() {
+            let tools = tools::tools_prompt(&config.tools);
+            match messages.first_mut() {
+                Some(system) if system.role == ChatRole::System => {
+                    system.content = format!("{}\n\n{}", system.content, tools);
+                }
+                _ => messages.insert(0, ChatMessage::system(tools)),
+            }
+        }
+        let prompt = self.chat_template().render(&messages, true);
+        config.session_id = Some(session_id.clone());
+        let key = self
+            .handle_infer(None, Prompt::Text(prompt), config, request_id, output_tx)
+            .await;
+        if let Some(request) = key.and_then(|key| self.in_flight.get_mut(&key)) {
+            request.chat = Some(ChatTurn {
+                session_id,
+                messages: vec![message],
+                reply: String::new(),
+            });
+        }
+    }
+
+    /// The chat template of the loaded model, unless its registry entry names another.
+    #[cfg(feature = "cortex-engine")]
+    fn chat_template(&self) -> ChatTemplate {
+        let override_template = self.last_model_name.as_ref().and_then(|name| {
+            self.asset_authority
+                .list_models()
+                .into_iter()
+                .find(|entry| &entry.name == name)
+                .and_then(|entry| entry.chat_template)
+        });
+        ChatTemplate::for_engine(self.engine.as_ref(), override_template.as_deref())
+    }
+
+    #[cfg(not(feature = "cortex-engine"))]
+    fn chat_template(&self) -> ChatTemplate {
+        ChatTemplate::for_engine(self.engine.as_ref(), None)
+    }
+
+    // ── Rag ──
+
+    /// Answer `query` from the documents most similar to it: embed it (and the documents
+    /// without `embeddings`) with the embedding model, then prompt the answering model with
+    /// the best of them.
+    #[allow(clippy::too_many_arguments)]
+    async fn handle_rag(
         &mut self,
         model: Option<String>,
-        prompt: String,
-        config: rusty_genius_core::manifest::InferenceConfig,
+        query: String,
+        documents: Vec<String>,
+        embeddings: Vec<Vec<f32>>,
+        config: RagConfig,
         request_id: &str,
         output_tx: &mut mpsc::Sender<BrainstemOutput>,
     ) {
+        let embedding_model = config.embedding_model.clone().or_else(|| model.clone());
         if !self
-            .ensure_model_loaded(model, request_id, output_tx)
+            .ensure_model_loaded(embedding_model, request_id, output_tx)
             .await
         {
             return;
         }
-
-        match self.engine.infer(&prompt, config).await {
-            Ok(mut event_rx) => {
-                while let Some(event_res) = event_rx.next().await {
-                    match event_res {
-                        Ok(event) => {
-                            if output_tx
-                                .send(BrainstemOutput {
-                                    id: Some(request_id.to_string()),
-                                    body: BrainstemBody::Event(event),
-                                })
-                                .await
-                                .is_err()
-                            {
-                                break;
-                            }
-                        }
-                        Err(e) => {
-                            let _ = output_tx
-                                .send(BrainstemOutput {
-                                    id: Some(request_id.to_string()),
-                                    body: BrainstemBody::Error(e.to_string()),
-                                })
-                                .await;
-                        }
-                    }
-                }
-            }
+        let retrieved = match self.retrieve(&query, &documents, embeddings, &config).await {
+            Ok(retrieved) => retrieved,
             Err(e) => {
                 let _ = output_tx
-                    .send(BrainstemOutput {
-                        id: Some(request_id.to_string()),
-                        body: BrainstemBody::Error(e.to_string()),
-                    })
+                    .send(BrainstemOutput::new(
+                        Some(request_id.to_string()),
+                        BrainstemBody::Error(format!("Retrieval failed: {}", e)),
+                    ))
                     .await;
+                return;
             }
+        };
+
+        // The template comes from the answering model
+        if !self
+            .ensure_model_loaded(model.clone(), request_id, output_tx)
+            .await
+        {
+            return;
+        }
+        let messages = rag::messages(&query, &documents, &retrieved);
+        let prompt = self.chat_template().render(&messages, true);
+        let _ = output_tx
+            .send(BrainstemOutput::new(
+                Some(request_id.to_string()),
+                BrainstemBody::Event(InferenceEvent::Retrieved(retrieved)),
+            ))
+            .await;
+        self.handle_infer(
+            model,
+            Prompt::Text(prompt),
+            config.inference,
+            request_id,
+            output_tx,
+        )
+        .await;
+    }
+
+    /// Embed `query`, and `documents` unless their `embeddings` are given, on the engine and
+    /// rank the documents by their similarity to it.
+    async fn retrieve(
+        &mut self,
+        query: &str,
+        documents: &[String],
+        mut embeddings: Vec<Vec<f32>>,
+        config: &RagConfig,
+    ) -> Result<Vec<RetrievedDocument>> {
+        let embed_config = self.fit_context(InferenceConfig::default());
+        let query_embedding =
+            collect_embeddings(self.engine.embed(query, embed_config.clone()).await)
+                .await?
+                .pop()
+                .ok_or_else(|| anyhow::anyhow!("The engine gave no embedding of the query"))?;
+        if embeddings.is_empty() {
+            embeddings =
+                collect_embeddings(self.engine.embed_batch(documents, embed_config).await).await?;
+        }
+        if embeddings.len() != documents.len() {
+            return Err(anyhow::anyhow!(
+                "{} embeddings for {} documents",
+                embeddings.len(),
+                documents.len()
+            ));
         }
+        Ok(rag::rank(&query_embedding, &embeddings, config.top_k))
     }
 
     // ── Embed ──
@@ -391,49 +2542,636 @@ impl Orchestrator {
         request_id: &str,
         output_tx: &mut mpsc::Sender<BrainstemOutput>,
     ) {
-        if !self
-            .ensure_model_loaded(model, request_id, output_tx)
-            .await
-        {
+        if !self.ensure_model_loaded(model, request_id, output_tx).await {
             return;
         }
 
-        match self.engine.embed(&input, config).await {
-            Ok(mut event_rx) => {
-                while let Some(event_res) = event_rx.next().await {
-                    match event_res {
-                        Ok(event) => {
-                            if output_tx
-                                .send(BrainstemOutput {
-                                    id: Some(request_id.to_string()),
-                                    body: BrainstemBody::Event(event),
-                                })
-                                .await
-                                .is_err()
-                            {
-                                break;
-                            }
-                        }
-                        Err(e) => {
-                            let _ = output_tx
-                                .send(BrainstemOutput {
-                                    id: Some(request_id.to_string()),
-                                    body: BrainstemBody::Error(e.to_string()),
-                                })
-                                .await;
+        let config = self.fit_context(config);
+        let events = self.engine.embed(&input, config).await;
+        self.dispatch(events, None, false, request_id, output_tx)
+            .await;
+    }
+
+    async fn handle_embed_batch(
+        &mut self,
+        model: Option<String>,
+        inputs: Vec<String>,
+        config: rusty_genius_core::manifest::InferenceConfig,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        if !self.ensure_model_loaded(model, request_id, output_tx).await {
+            return;
+        }
+
+        let config = self.fit_context(config);
+        let events = self.engine.embed_batch(&inputs, config).await;
+        self.dispatch(events, None, false, request_id, output_tx)
+            .await;
+    }
+
+    async fn handle_rerank(
+        &mut self,
+        model: Option<String>,
+        query: String,
+        documents: Vec<String>,
+        config: rusty_genius_core::manifest::InferenceConfig,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        if !self.ensure_model_loaded(model, request_id, output_tx).await {
+            return;
+        }
+
+        let config = self.fit_context(config);
+        let events = self.engine.rerank(&query, &documents, config).await;
+        self.dispatch(events, None, false, request_id, output_tx)
+            .await;
+    }
+
+    // ── Bench ──
+
+    async fn handle_bench(
+        &mut self,
+        model: Option<String>,
+        config: BenchConfig,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        if !self.ensure_model_loaded(model, request_id, output_tx).await {
+            return;
+        }
+
+        let body = match self.engine.bench(config).await {
+            Ok(report) => BrainstemBody::Bench(report),
+            Err(e) => BrainstemBody::Error(e.to_string()),
+        };
+        let _ = output_tx
+            .send(BrainstemOutput::new(Some(request_id.to_string()), body))
+            .await;
+    }
+
+    // ── Transcribe ──
+
+    async fn handle_transcribe(
+        &mut self,
+        model: Option<String>,
+        audio: Vec<u8>,
+        config: TranscriptionConfig,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        let Some(transcriber) = self.transcriber.as_mut() else {
+            let _ = output_tx
+                .send(BrainstemOutput::new(
+                    Some(request_id.to_string()),
+                    BrainstemBody::Error(
+                        "No transcription engine; build with the `whisper` feature".to_string(),
+                    ),
+                ))
+                .await;
+            return;
+        };
+
+        let model_to_load = model
+            .or_else(|| self.transcriber_model.clone())
+            .unwrap_or_else(|| transcriber.default_model());
+        if !transcriber.is_loaded() || self.transcriber_model.as_ref() != Some(&model_to_load) {
+            // A path to a model file is loaded as is; a name is resolved and downloaded.
+            #[cfg(feature = "cortex-engine")]
+            let resolved = if std::path::Path::new(&model_to_load).is_file() {
+                Ok(model_to_load.clone())
+            } else {
+                self.asset_authority
+                    .ensure_model(&model_to_load)
+                    .await
+                    .map(|path| path.to_string_lossy().into_owned())
+            };
+            #[cfg(not(feature = "cortex-engine"))]
+            let resolved: Result<String> = Ok(model_to_load.clone());
+
+            let loaded = match resolved {
+                Ok(path) => transcriber.load_model(&path).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = loaded {
+                let _ = output_tx
+                    .send(BrainstemOutput::new(
+                        Some(request_id.to_string()),
+                        BrainstemBody::Error(format!("Transcription model failed: {}", e)),
+                    ))
+                    .await;
+                return;
+            }
+            self.transcriber_model = Some(model_to_load);
+        }
+
+        let events = match rusty_genius_core::audio::decode_wav(&audio) {
+            Ok(samples) => transcriber.transcribe(&samples, config).await,
+            Err(e) => Err(e),
+        };
+        self.dispatch(events, None, true, request_id, output_tx)
+            .await;
+    }
+
+    /// Put a request's events in flight, to relay to the client as they arrive; returns its
+    /// key, or `None` when the engine refused it.
+    async fn dispatch(
+        &mut self,
+        events: Result<mpsc::Receiver<Result<InferenceEvent>>>,
+        cancel: Option<CancellationToken>,
+        transcribing: bool,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) -> Option<u64> {
+        match events {
+            Ok(event_rx) => {
+                let key = self.next_key;
+                self.next_key += 1;
+                // `None` marks the end of the events, aborted or not
+                let (events, abort) = stream::abortable(event_rx.map(Some));
+                self.events.push(Box::pin(
+                    events
+                        .chain(stream::once(future::ready(None)))
+                        .map(move |event| (key, event)),
+                ));
+                self.in_flight.insert(
+                    key,
+                    InFlight {
+                        request_id: request_id.to_string(),
+                        cancel,
+                        abort,
+                        transcribing,
+                        finished: false,
+                        chat: None,
+                        tools: None,
+                        timing: None,
+                        batch_index: None,
+                        audit: None,
+                    },
+                );
+                Some(key)
+            }
+            Err(e) => {
+                let _ = output_tx
+                    .send(BrainstemOutput::new(
+                        Some(request_id.to_string()),
+                        BrainstemBody::Error(e.to_string()),
+                    ))
+                    .await;
+                None
+            }
+        }
+    }
+
+    /// Send the client an event of the request in flight under `key`.
+    async fn relay(
+        &mut self,
+        key: u64,
+        event: Option<Result<InferenceEvent>>,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        // Events still on their way from a cancelled request
+        if !self.in_flight.contains_key(&key) {
+            return;
+        }
+        if let Some(Ok(InferenceEvent::Complete)) = event {
+            self.complete(key, output_tx).await;
+            return;
+        }
+        let request = self.in_flight.get_mut(&key).unwrap();
+        let body = match event {
+            Some(Ok(InferenceEvent::Content(text))) => {
+                let text = match request.tools.as_mut() {
+                    Some(round) => {
+                        round.output.push_str(&text);
+                        if !round.holding {
+                            text
+                        } else if tools::may_be_tool_call(&round.output) {
+                            return;
+                        } else {
+                            // Not a call after all; show what was held back
+                            round.holding = false;
+                            round.output.clone()
                         }
                     }
+                    None => text,
+                };
+                if let Some(turn) = request.chat.as_mut() {
+                    turn.reply.push_str(&text);
                 }
+                request.body(InferenceEvent::Content(text))
             }
-            Err(e) => {
+            Some(Ok(event)) => {
+                if let InferenceEvent::Usage(usage) = &event {
+                    self.metrics.prompt_tokens += usage.prompt_tokens as u64;
+                    self.metrics.generated_tokens += usage.completion_tokens as u64;
+                    if let Some(audit) = request.audit.as_mut() {
+                        audit.count(usage);
+                    }
+                    if let Some(audit) = self
+                        .batches
+                        .get_mut(&request.request_id)
+                        .and_then(|batch| batch.audit.as_mut())
+                    {
+                        audit.count(usage);
+                    }
+                }
+                request.body(event)
+            }
+            Some(Err(e)) => {
+                request.finished = true;
+                let error = match request.batch_index {
+                    Some(index) => format!("Prompt {}: {}", index, e),
+                    None => e.to_string(),
+                };
+                if let Some(audit) = request.audit.as_mut() {
+                    audit.fail(&error);
+                }
+                if let Some(audit) = self
+                    .batches
+                    .get_mut(&request.request_id)
+                    .and_then(|batch| batch.audit.as_mut())
+                {
+                    audit.fail(&error);
+                }
+                request.time(
+                    &mut self.metrics,
+                    self.audit_log.as_ref(),
+                    AuditStatus::Error,
+                );
+                // A batch reports the errors of its prompts once the last one ends
+                if let Some(batch) = request
+                    .batch_index
+                    .and(self.batches.get_mut(&request.request_id))
+                {
+                    batch.failed.push(error);
+                    return;
+                }
+                BrainstemBody::Error(error)
+            }
+            None => {
+                let mut request = self.in_flight.remove(&key).unwrap();
+                request.time(
+                    &mut self.metrics,
+                    self.audit_log.as_ref(),
+                    AuditStatus::Crashed,
+                );
+                self.last_activity = Instant::now();
+                if !request.finished {
+                    self.crashed(&request.request_id, request.transcribing, output_tx)
+                        .await;
+                }
+                if request.batch_index.is_some() {
+                    if let Some(batch) = self.batches.get_mut(&request.request_id) {
+                        batch.running -= 1;
+                    }
+                    self.run_batch(&request.request_id, output_tx).await;
+                }
+                return;
+            }
+        };
+        let output = BrainstemOutput::new(Some(request.request_id.clone()), body);
+        if output_tx.send(output).await.is_err() {
+            // Nobody is listening any more; stop generating.
+            self.abort(key);
+        }
+    }
+
+    /// Finish the request in flight under `key` with `Complete`, unless its output was tool
+    /// calls: then it sends them and waits for their results.
+    async fn complete(&mut self, key: u64, output_tx: &mut mpsc::Sender<BrainstemOutput>) {
+        let request = self.in_flight.get_mut(&key).unwrap();
+        request.finished = true;
+        request.time(&mut self.metrics, self.audit_log.as_ref(), AuditStatus::Ok);
+        let id = Some(request.request_id.clone());
+        if let Some(round) = request.tools.take().filter(|round| round.holding) {
+            let calls = tools::parse_tool_calls(&round.output);
+            if !calls.is_empty() {
+                for call in &calls {
+                    let _ = output_tx
+                        .send(BrainstemOutput::new(
+                            id.clone(),
+                            BrainstemBody::Event(InferenceEvent::ToolCall(call.clone())),
+                        ))
+                        .await;
+                }
                 let _ = output_tx
-                    .send(BrainstemOutput {
-                        id: Some(request_id.to_string()),
-                        body: BrainstemBody::Error(e.to_string()),
-                    })
+                    .send(BrainstemOutput::new(id, BrainstemBody::AwaitingTools))
+                    .await;
+                let wait = ToolWait {
+                    model: self.last_model_name.clone(),
+                    prompt: round.prompt,
+                    output: round.output,
+                    config: round.config,
+                    calls,
+                    results: HashMap::new(),
+                    chat: request.chat.take(),
+                };
+                self.tool_waits.insert(request.request_id.clone(), wait);
+                return;
+            }
+            if !round.output.is_empty() {
+                if let Some(turn) = request.chat.as_mut() {
+                    turn.reply.push_str(&round.output);
+                }
+                let _ = output_tx
+                    .send(BrainstemOutput::new(
+                        id.clone(),
+                        BrainstemBody::Event(InferenceEvent::Content(round.output)),
+                    ))
+                    .await;
+            }
+        }
+        // A turn joins its conversation once answered in full
+        if let Some(turn) = request.chat.take() {
+            let conversation = self.conversations.entry(turn.session_id).or_default();
+            conversation.extend(turn.messages);
+            conversation.push(ChatMessage::assistant(turn.reply));
+        }
+        let output = BrainstemOutput::new(id, request.body(InferenceEvent::Complete));
+        if output_tx.send(output).await.is_err() {
+            self.abort(key);
+        }
+    }
+
+    /// Take the result of a tool call of the request `request_id`; once every call has one,
+    /// carry on generating from the prompt, the calls and their results.
+    async fn handle_tool_result(
+        &mut self,
+        call_id: String,
+        content: String,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        // A call the request didn't make is reported under the call's id, so the request
+        // still waits on the calls it did make
+        let error = match self.tool_waits.get_mut(request_id) {
+            None => Some((
+                request_id.to_string(),
+                format!("Request '{}' is not waiting for tool results", request_id),
+            )),
+            Some(wait) if !wait.calls.iter().any(|call| call.id == call_id) => Some((
+                call_id.clone(),
+                format!("Request '{}' made no call '{}'", request_id, call_id),
+            )),
+            Some(wait) => {
+                wait.results.insert(call_id, content);
+                if wait.results.len() < wait.calls.len() {
+                    return;
+                }
+                None
+            }
+        };
+        if let Some((id, error)) = error {
+            let _ = output_tx
+                .send(BrainstemOutput::new(Some(id), BrainstemBody::Error(error)))
+                .await;
+            return;
+        }
+
+        let mut wait = self.tool_waits.remove(request_id).unwrap();
+        // The template comes from the model
+        if !self
+            .ensure_model_loaded(wait.model.clone(), request_id, output_tx)
+            .await
+        {
+            // Still waiting when it runs again after the backoff
+            if self.backoff.is_some() {
+                self.tool_waits.insert(request_id.to_string(), wait);
+            }
+            return;
+        }
+        let results: Vec<ChatMessage> = wait
+            .calls
+            .iter()
+            .map(|call| ChatMessage::tool(wait.results.remove(&call.id).unwrap_or_default()))
+            .collect();
+        let template = self.chat_template();
+        let prompt = format!(
+            "{}{}{}{}",
+            wait.prompt,
+            wait.output,
+            template.end_of_turn(),
+            template.render(&results, true)
+        );
+        if let Some(turn) = wait.chat.as_mut() {
+            turn.messages.push(ChatMessage::assistant(wait.output));
+            turn.messages.extend(results);
+        }
+        let key = self
+            .handle_infer(
+                wait.model,
+                Prompt::Text(prompt),
+                wait.config,
+                request_id,
+                output_tx,
+            )
+            .await;
+        if let Some(request) = key.and_then(|key| self.in_flight.get_mut(&key)) {
+            request.chat = wait.chat;
+        }
+    }
+
+    /// Stop the request in flight under `key`: fire its token, for engines that take one, and
+    /// drop its events, which stops the rest.
+    fn abort(&mut self, key: u64) -> Option<InFlight> {
+        let mut request = self.in_flight.remove(&key)?;
+        if let Some(cancel) = &request.cancel {
+            cancel.cancel();
+        }
+        request.abort.abort();
+        request.time(
+            &mut self.metrics,
+            self.audit_log.as_ref(),
+            AuditStatus::Cancelled,
+        );
+        Some(request)
+    }
+
+    // ── Crashes ──
+
+    /// Fail the request `request_id`, which the engine (or the transcriber, when
+    /// `transcribing`) crashed on, and restart the engine.
+    async fn crashed(
+        &mut self,
+        request_id: &str,
+        transcribing: bool,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        let engine = if transcribing {
+            "transcriber"
+        } else {
+            "engine"
+        };
+        let _ = output_tx
+            .send(BrainstemOutput::new(
+                Some(request_id.to_string()),
+                BrainstemBody::Error(format!("The {} crashed", engine)),
+            ))
+            .await;
+        if transcribing {
+            // Loaded afresh by the next request
+            self.transcriber_model = None;
+        } else {
+            self.restart_engine(request_id, output_tx).await;
+        }
+    }
+
+    /// Replace a crashed engine with a fresh one, reload the model it had (relaying the load
+    /// to `request_id`, and backing off to retry it as a `LoadModel` if it fails transiently)
+    /// and tell every client it `Restarted`, so they can retry. Requests in
+    /// flight on the old engine fail, as do those waiting on tool results.
+    async fn restart_engine(
+        &mut self,
+        request_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        let keys: Vec<u64> = self
+            .in_flight
+            .iter()
+            .filter(|(_, request)| !request.transcribing)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in keys {
+            if let Some(request) = self.in_flight.get_mut(&key) {
+                request.time(
+                    &mut self.metrics,
+                    self.audit_log.as_ref(),
+                    AuditStatus::Crashed,
+                );
+            }
+            // Requests that already completed only wait for their events to end, and the
+            // prompts of a batch fail with it, below
+            if let Some(request) = self
+                .abort(key)
+                .filter(|request| !request.finished && request.batch_index.is_none())
+            {
+                let _ = output_tx
+                    .send(BrainstemOutput::new(
+                        Some(request.request_id),
+                        BrainstemBody::Error("The engine crashed".to_string()),
+                    ))
+                    .await;
+            }
+        }
+        // Requests waiting on tool results would carry on without their session
+        let waiting: Vec<String> = self.tool_waits.drain().map(|(id, _)| id).collect();
+        for id in waiting {
+            let _ = output_tx
+                .send(BrainstemOutput::new(
+                    Some(id),
+                    BrainstemBody::Error("The engine crashed".to_string()),
+                ))
+                .await;
+        }
+        // Every batch has a prompt in flight, so they all fail, once each; the one that
+        // crashed was already told
+        let batches: Vec<String> = self.batches.keys().cloned().collect();
+        for batch in batches {
+            self.end_batch(&batch, AuditStatus::Crashed);
+            if batch != request_id {
+                let _ = output_tx
+                    .send(BrainstemOutput::new(
+                        Some(batch),
+                        BrainstemBody::Error("The engine crashed".to_string()),
+                    ))
                     .await;
             }
         }
+
+        let Some(engine) = self.engine_factory.as_ref().map(|factory| factory()) else {
+            eprintln!("NOTICE: The engine crashed and can't be restarted.");
+            self.faulted = true;
+            return;
+        };
+        eprintln!("NOTICE: The engine crashed; restarting it.");
+        self.metrics.engine_restarts += 1;
+        self.engine = engine.await;
+        self.live_sessions.clear();
+        if let Some(model) = self.last_model_name.clone() {
+            let reload = std::panic::AssertUnwindSafe(self.handle_load_model(
+                model.clone(),
+                request_id,
+                output_tx,
+            ))
+            .catch_unwind()
+            .await;
+            if let Some(delay) = self.backoff.take() {
+                let reload = BrainstemInput {
+                    id: Some(request_id.to_string()),
+                    command: BrainstemCommand::LoadModel(model),
+                };
+                self.retrying.push((Instant::now() + delay, reload));
+            }
+            if reload.is_err() {
+                eprintln!(
+                    "NOTICE: Reloading the model crashed the engine again; leaving it unloaded."
+                );
+                self.faulted = true;
+                if let Some(engine) = self.engine_factory.as_ref().map(|factory| factory()) {
+                    self.engine = engine.await;
+                }
+            }
+        }
+        Self::broadcast(BrainstemBody::Restarted, output_tx).await;
+    }
+
+    /// Cancel the request `id`, in flight or queued, or tell the sender of the `Cancel`
+    /// (`cancel_id`) that no such request is running.
+    async fn cancel(
+        &mut self,
+        id: &str,
+        cancel_id: &str,
+        output_tx: &mut mpsc::Sender<BrainstemOutput>,
+    ) {
+        // Every prompt of a batch
+        let running: Vec<u64> = self
+            .in_flight
+            .iter()
+            .filter(|(_, request)| request.request_id == id)
+            .map(|(key, _)| *key)
+            .collect();
+        let queued = self
+            .pending
+            .iter()
+            .position(|input| input.id.as_deref() == Some(id));
+        // Waiting for tool results or for its model to download
+        let mut waiting = self.tool_waits.remove(id).is_some();
+        // Or backing off before its load is retried
+        let backing_off = self.retrying.len();
+        self.retrying
+            .retain(|(_, msg)| msg.id.as_deref() != Some(id));
+        waiting |= self.retrying.len() < backing_off;
+        self.retries.remove(id);
+        self.preloaded.remove(id);
+        for preload in self.preloading.values_mut() {
+            let held = preload.waiting.len();
+            preload.waiting.retain(|msg| msg.id.as_deref() != Some(id));
+            waiting |= preload.waiting.len() < held;
+        }
+        self.end_batch(id, AuditStatus::Cancelled);
+        let output = match (running.first(), queued) {
+            (Some(_), _) => {
+                eprintln!("DEBUG: [orchestrator] cancelled [{}]", id);
+                for key in running {
+                    self.abort(key);
+                }
+                BrainstemOutput::new(Some(id.to_string()), BrainstemBody::Cancelled)
+            }
+            (None, Some(index)) => {
+                self.pending.remove(index);
+                BrainstemOutput::new(Some(id.to_string()), BrainstemBody::Cancelled)
+            }
+            (None, None) if waiting => {
+                BrainstemOutput::new(Some(id.to_string()), BrainstemBody::Cancelled)
+            }
+            (None, None) => BrainstemOutput::new(
+                Some(cancel_id.to_string()),
+                BrainstemBody::Error(format!("No request '{}' is running", id)),
+            ),
+        };
+        let _ = output_tx.send(output).await;
     }
 
     // ── ListModels ──
@@ -444,20 +3182,38 @@ impl Orchestrator {
         request_id: &str,
         output_tx: &mut mpsc::Sender<BrainstemOutput>,
     ) {
-        let models = self
+        let cached = self.asset_authority.list_cached();
+        let mut models: Vec<ModelDescriptor> = self
             .asset_authority
             .list_models()
             .into_iter()
-            .map(|m| ModelDescriptor {
-                id: m.name,
-                purpose: format!("{:?}", m.purpose),
+            .map(|m| {
+                let file = cached.iter().find(|c| c.model.as_ref() == Some(&m.name));
+                let mut tags = m.all_tags();
+                if file.is_some() {
+                    tags.push("cached".to_string());
+                }
+                // The loaded model answers from the engine; others from their GGUF header
+                let info = if self.loaded_model() == Some(&m.name) {
+                    self.engine.model_info()
+                } else {
+                    file.and_then(|c| facecrab::inspect(&c.path).ok())
+                        .map(Into::into)
+                };
+                ModelDescriptor {
+                    tags,
+                    id: m.name,
+                    purpose: format!("{:?}", m.purpose),
+                    info,
+                }
             })
             .collect();
+        self.add_loaded_model(&mut models);
         let _ = output_tx
-            .send(BrainstemOutput {
-                id: Some(request_id.to_string()),
-                body: BrainstemBody::ModelList(models),
-            })
+            .send(BrainstemOutput::new(
+                Some(request_id.to_string()),
+                BrainstemBody::ModelList(models),
+            ))
             .await;
     }
 
@@ -467,11 +3223,37 @@ impl Orchestrator {
         request_id: &str,
         output_tx: &mut mpsc::Sender<BrainstemOutput>,
     ) {
+        let mut models = Vec::new();
+        self.add_loaded_model(&mut models);
         let _ = output_tx
-            .send(BrainstemOutput {
-                id: Some(request_id.to_string()),
-                body: BrainstemBody::ModelList(vec![]),
-            })
+            .send(BrainstemOutput::new(
+                Some(request_id.to_string()),
+                BrainstemBody::ModelList(models),
+            ))
             .await;
     }
+
+    /// Name of the model the engine has loaded, if any.
+    fn loaded_model(&self) -> Option<&String> {
+        self.last_model_name
+            .as_ref()
+            .filter(|_| self.engine.is_loaded())
+    }
+
+    /// Tag the loaded model in `models` as `loaded`, adding it when no registry entry names it
+    /// (a local path, or a model served by a remote engine).
+    fn add_loaded_model(&self, models: &mut Vec<ModelDescriptor>) {
+        let Some(name) = self.loaded_model() else {
+            return;
+        };
+        match models.iter_mut().find(|m| &m.id == name) {
+            Some(model) => model.tags.push("loaded".to_string()),
+            None => models.push(ModelDescriptor {
+                id: name.clone(),
+                purpose: "Inference".to_string(),
+                tags: vec!["loaded".to_string()],
+                info: self.engine.model_info(),
+            }),
+        }
+    }
 }
@@ -0,0 +1,79 @@
+//! JSON lines transport: the orchestrator driven over a pair of byte streams, one
+//! `BrainstemInput` per line in and one `BrainstemOutput` per line out, so a host in any
+//! language can run it as a subprocess on its stdin and stdout.
+
+use crate::Orchestrator;
+use anyhow::Result;
+use futures::channel::mpsc;
+use futures::future::{self, Either};
+use futures::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use futures::sink::SinkExt;
+use futures::StreamExt;
+use rusty_genius_core::protocol::{BrainstemInput, BrainstemOutput};
+
+impl Orchestrator {
+    /// [Run](Orchestrator::run) on JSON lines: each line of `reader` is a `BrainstemInput`,
+    /// and each output is written to `writer` as a line, flushed at once.
+    ///
+    /// Blank lines are skipped, and lines that aren't an input are reported on stderr, so
+    /// `writer` carries nothing but outputs. Returns once `reader` ends and every request has
+    /// finished, or on a `Stop`.
+    pub async fn run_json_lines<R, W>(&mut self, reader: R, writer: W) -> Result<()>
+    where
+        R: AsyncBufRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let (input_tx, input_rx) = mpsc::channel(32);
+        let (output_tx, output_rx) = mpsc::channel(32);
+        let serve = future::join(
+            self.run(input_rx, output_tx),
+            write_outputs(output_rx, writer),
+        );
+        let read = read_inputs(reader, input_tx);
+        futures::pin_mut!(serve, read);
+        // A `Stop` ends the run without waiting for the reader to end
+        match future::select(read, serve).await {
+            Either::Left((read, serve)) => {
+                let (result, written) = serve.await;
+                read.and(result).and(written)
+            }
+            Either::Right(((result, written), _)) => result.and(written),
+        }
+    }
+}
+
+async fn read_inputs<R: AsyncBufRead + Unpin>(
+    reader: R,
+    mut input_tx: mpsc::Sender<BrainstemInput>,
+) -> Result<()> {
+    let mut lines = reader.lines();
+    while let Some(line) = lines.next().await {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<BrainstemInput>(&line) {
+            Ok(input) => {
+                if input_tx.send(input).await.is_err() {
+                    // Stopped
+                    break;
+                }
+            }
+            Err(e) => eprintln!("NOTICE: Skipped input line: {}", e),
+        }
+    }
+    Ok(())
+}
+
+async fn write_outputs<W: AsyncWrite + Unpin>(
+    mut output_rx: mpsc::Receiver<BrainstemOutput>,
+    mut writer: W,
+) -> Result<()> {
+    while let Some(output) = output_rx.next().await {
+        let mut line = serde_json::to_vec(&output)?;
+        line.push(b'\n');
+        writer.write_all(&line).await?;
+        writer.flush().await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! Hooks for applications to follow what the orchestrator does, to feed their own metrics or
+//! tracing, without reading its output channel.
+
+use rusty_genius_core::protocol::{
+    AssetEvent, BrainstemBody, BrainstemCommand, BrainstemOutput, InferenceEvent,
+};
+use std::sync::Arc;
+
+/// Told of the orchestrator's work as it happens, once registered with
+/// [Orchestrator::add_observer](crate::Orchestrator::add_observer). Every method does nothing
+/// unless implemented. They are called on the orchestrator's task, so should return quickly.
+pub trait OrchestratorObserver: Send + Sync {
+    /// A command arrived, under the id its outputs carry.
+    fn on_command(&self, _id: &str, _command: &BrainstemCommand) {}
+
+    /// The request `id` generated `text`, in any prompt of a batch.
+    fn on_token(&self, _id: &str, _text: &str) {}
+
+    /// The request `id` completed.
+    fn on_complete(&self, _id: &str) {}
+
+    /// `downloaded` of `total` bytes of a model have been fetched, for the request `id`, or for
+    /// a background download without one.
+    fn on_asset_progress(&self, _id: Option<&str>, _downloaded: u64, _total: u64) {}
+
+    /// An error was sent to the request `id`, or to every client without one.
+    fn on_error(&self, _id: Option<&str>, _error: &str) {}
+
+    /// Any output, as it is sent; called before the hooks above.
+    fn on_output(&self, _output: &BrainstemOutput) {}
+}
+
+/// Tell `observers` of `output`, on its way to the client.
+pub(crate) fn observe(observers: &[Arc<dyn OrchestratorObserver>], output: &BrainstemOutput) {
+    if observers.is_empty() {
+        return;
+    }
+    let id = output.id.as_deref();
+    for observer in observers {
+        observer.on_output(output);
+        match (&output.body, id) {
+            (BrainstemBody::Event(InferenceEvent::Content(text)), Some(id))
+            | (
+                BrainstemBody::Batch {
+                    event: InferenceEvent::Content(text),
+                    ..
+                },
+                Some(id),
+            ) => observer.on_token(id, text),
+            (BrainstemBody::Event(InferenceEvent::Complete), Some(id)) => observer.on_complete(id),
+            (BrainstemBody::Asset(AssetEvent::Progress(downloaded, total)), id) => {
+                observer.on_asset_progress(id, *downloaded, *total)
+            }
+            (BrainstemBody::Error(error), id) => observer.on_error(id, error),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! Record and replay: a [Recorder] writes every input the orchestrator takes and every output
+//! it sends to a file, and a [ReplayEngine] answers from such a recording, so end-to-end tests
+//! run deterministically without a model or the network.
+
+use crate::observer::OrchestratorObserver;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use rusty_genius_core::engine::{CancellationToken, Engine};
+use rusty_genius_core::manifest::InferenceConfig;
+use rusty_genius_core::protocol::{
+    BrainstemBody, BrainstemCommand, BrainstemInput, BrainstemOutput, InferenceEvent,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// A line of a recording, in the order the orchestrator took and sent them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Recorded {
+    /// A command, under the id its outputs carry
+    Input(Box<BrainstemInput>),
+    Output(Box<BrainstemOutput>),
+}
+
+/// Read the recording at `path`.
+pub fn read_recording(path: impl AsRef<Path>) -> Result<Vec<Recorded>> {
+    let text = std::fs::read_to_string(path)?;
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| Ok(serde_json::from_str(line)?))
+        .collect()
+}
+
+/// Writes a recording as JSON lines once added with
+/// [Orchestrator::add_observer](crate::Orchestrator::add_observer).
+pub struct Recorder {
+    file: Mutex<std::fs::File>,
+}
+
+impl Recorder {
+    /// Record to `path`, replacing what was there.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        if let Some(dir) = path.as_ref().parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        Ok(Self {
+            file: Mutex::new(std::fs::File::create(path)?),
+        })
+    }
+
+    fn write(&self, recorded: &Recorded) {
+        let result = serde_json::to_string(recorded).map(|mut line| {
+            line.push('\n');
+            line
+        });
+        let written = match result {
+            Ok(line) => self.file.lock().unwrap().write_all(line.as_bytes()),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = written {
+            eprintln!("NOTICE: Recording not written: {}", e);
+        }
+    }
+}
+
+impl OrchestratorObserver for Recorder {
+    fn on_command(&self, id: &str, command: &BrainstemCommand) {
+        self.write(&Recorded::Input(Box::new(BrainstemInput {
+            id: Some(id.to_string()),
+            command: command.clone(),
+        })));
+    }
+
+    fn on_output(&self, output: &BrainstemOutput) {
+        self.write(&Recorded::Output(Box::new(output.clone())));
+    }
+}
+
+/// The events recorded for one text: the prompt of an `Infer`, the input of an `Embed` or the
+/// message of a `Chat`.
+type Answers = HashMap<String, VecDeque<Vec<InferenceEvent>>>;
+
+enum Asked {
+    Prompt,
+    Chat,
+    Embed,
+}
+
+/// Answers prompts and embeds with the events recorded for them, in the order they were
+/// recorded; the last answer to a text is given again once the others are used up.
+///
+/// `Infer` prompts and `Embed` inputs are matched exactly. Chats are rendered into prompts
+/// before they reach an engine, so a prompt no `Infer` answers is matched to the recorded chat
+/// message that comes latest in it.
+pub struct ReplayEngine {
+    loaded: Option<String>,
+    prompts: Answers,
+    chats: Answers,
+    embeds: Answers,
+    default_model: String,
+}
+
+impl ReplayEngine {
+    /// Replay the recording at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::from_recording(read_recording(path)?))
+    }
+
+    pub fn from_recording(recording: impl IntoIterator<Item = Recorded>) -> Self {
+        let (mut prompts, mut chats, mut embeds) = (Answers::new(), Answers::new(), Answers::new());
+        // The requests whose events are still being collected, by id
+        let mut pending: HashMap<String, (Asked, String, Vec<InferenceEvent>)> = HashMap::new();
+        let mut default_model = None;
+        for recorded in recording {
+            match recorded {
+                Recorded::Input(input) => {
+                    let BrainstemInput {
+                        id: Some(id),
+                        command,
+                    } = *input
+                    else {
+                        continue;
+                    };
+                    let (kind, text, model) = match command {
+                        BrainstemCommand::Infer { prompt, model, .. } => {
+                            (Asked::Prompt, prompt, model)
+                        }
+                        BrainstemCommand::Chat { message, .. } => {
+                            (Asked::Chat, message.content, None)
+                        }
+                        BrainstemCommand::Embed { input, model, .. } => {
+                            (Asked::Embed, input, model)
+                        }
+                        BrainstemCommand::LoadModel(model) => {
+                            default_model.get_or_insert(model);
+                            continue;
+                        }
+                        _ => continue,
+                    };
+                    if let Some(model) = model {
+                        default_model.get_or_insert(model);
+                    }
+                    pending.insert(id, (kind, text, Vec::new()));
+                }
+                Recorded::Output(output) => {
+                    let BrainstemOutput {
+                        id: Some(id),
+                        body: BrainstemBody::Event(event),
+                        ..
+                    } = *output
+                    else {
+                        continue;
+                    };
+                    let complete = matches!(event, InferenceEvent::Complete);
+                    if let Some((_, _, events)) = pending.get_mut(&id) {
+                        events.push(event);
+                    }
+                    if complete {
+                        if let Some((kind, text, events)) = pending.remove(&id) {
+                            let answers = match kind {
+                                Asked::Prompt => &mut prompts,
+                                Asked::Chat => &mut chats,
+                                Asked::Embed => &mut embeds,
+                            };
+                            answers.entry(text).or_default().push_back(events);
+                        }
+                    }
+                }
+            }
+        }
+        Self {
+            loaded: None,
+            prompts,
+            chats,
+            embeds,
+            default_model: default_model.unwrap_or_else(|| "replay".to_string()),
+        }
+    }
+
+    /// The events to answer `prompt` with.
+    fn answer_prompt(&mut self, prompt: &str) -> Option<Vec<InferenceEvent>> {
+        if self.prompts.contains_key(prompt) {
+            return next_answer(&mut self.prompts, prompt);
+        }
+        let message = self
+            .chats
+            .keys()
+            .filter_map(|message| prompt.rfind(message.as_str()).map(|at| (at, message)))
+            .max_by_key(|(at, message)| (*at, message.len()))
+            .map(|(_, message)| message.clone())?;
+        next_answer(&mut self.chats, &message)
+    }
+}
+
+/// The next answer recorded for `text`, keeping the last.
+fn next_answer(answers: &mut Answers, text: &str) -> Option<Vec<InferenceEvent>> {
+    let queue = answers.get_mut(text)?;
+    if queue.len() > 1 {
+        queue.pop_front()
+    } else {
+        queue.front().cloned()
+    }
+}
+
+fn replay(events: Vec<InferenceEvent>) -> mpsc::Receiver<Result<InferenceEvent>> {
+    let (mut tx, rx) = mpsc::channel(events.len());
+    for event in events {
+        let _ = tx.try_send(Ok(event));
+    }
+    rx
+}
+
+#[async_trait]
+impl Engine for ReplayEngine {
+    async fn load_model(&mut self, model_path: &str) -> Result<()> {
+        self.loaded = Some(model_path.to_string());
+        Ok(())
+    }
+
+    async fn unload_model(&mut self) -> Result<()> {
+        self.loaded = None;
+        Ok(())
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.loaded.is_some()
+    }
+
+    /// Nothing to download
+    fn is_remote(&self) -> bool {
+        true
+    }
+
+    fn default_model(&self) -> String {
+        self.default_model.clone()
+    }
+
+    async fn infer(
+        &mut self,
+        prompt: &str,
+        _config: InferenceConfig,
+        _cancel: CancellationToken,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        let events = self
+            .answer_prompt(prompt)
+            .ok_or_else(|| anyhow!("No recorded answer to the prompt {:?}", prompt))?;
+        Ok(replay(events))
+    }
+
+    async fn embed(
+        &mut self,
+        input: &str,
+        _config: InferenceConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        let events = next_answer(&mut self.embeds, input)
+            .ok_or_else(|| anyhow!("No recorded embedding of {:?}", input))?;
+        Ok(replay(events))
+    }
+}
@@ -0,0 +1,399 @@
+#![cfg(feature = "cortex-engine")]
+
+mod common;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use common::{infer, start, Call, Calls, Harness, MockEngine, Reply};
+use facecrab::sources::AssetStream;
+use facecrab::{AssetAuthority, AssetSource};
+use futures::channel::oneshot;
+use rusty_genius_core::manifest::{InferenceConfig, ModelSpec};
+use rusty_genius_core::protocol::{BrainstemBody, BrainstemCommand, InferenceEvent};
+use rusty_genius_stem::{CortexStrategy, Orchestrator};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// An authority keeping its config and cache in `dir`, fetching from `source` when given one.
+fn authority(dir: &Path, source: Option<GatedSource>) -> AssetAuthority {
+    let builder = AssetAuthority::builder()
+        .config_dir(dir)
+        .cache_dir(dir.join("cache"))
+        .use_hub_cache(false);
+    match source {
+        Some(source) => builder.source(source),
+        None => builder,
+    }
+    .build()
+    .unwrap()
+}
+
+// ── Building ──
+
+#[test]
+fn test_builder_runs_on_the_parts_it_is_given() {
+    smol::block_on(async {
+        let dir = common::temp_dir("builder");
+        let orchestrator = Orchestrator::builder()
+            .engine(Box::new(MockEngine::new("mock")))
+            .asset_authority(authority(&dir, None))
+            .strategy(CortexStrategy::KeepAlive)
+            .channel_capacity(1, 4)
+            .build()
+            .await
+            .unwrap();
+        let mut harness = start(orchestrator);
+
+        harness.answer("request", infer("hello")).await;
+        harness.stop().await;
+
+        // The model was remembered in the authority's config dir
+        let last = std::fs::read_to_string(dir.join("last_model.json")).unwrap();
+        assert!(last.contains("\"mock\""));
+        let _ = std::fs::remove_dir_all(&dir);
+    });
+}
+
+// ── Registry defaults ──
+
+const MANIFEST: &str = r#"
+schema_version = 1
+
+[[models]]
+name = "mock"
+repo = "example/mock-GGUF"
+filename = "mock.gguf"
+quantization = "Q4_K_M"
+
+[models.defaults]
+temperature = 0.1
+max_tokens = 32
+end_tokens = ["<|eot|>"]
+"#;
+
+#[test]
+fn test_registry_defaults_go_under_request_settings() {
+    smol::block_on(async {
+        let dir = common::temp_dir("model-defaults");
+        std::fs::write(dir.join("manifest.toml"), MANIFEST).unwrap();
+        let engine = MockEngine::new("mock");
+        let calls = engine.calls();
+        let orchestrator = Orchestrator::builder()
+            .engine(Box::new(engine))
+            .asset_authority(authority(&dir, None))
+            .build()
+            .await
+            .unwrap();
+        let mut harness = start(orchestrator);
+
+        let requests = [
+            InferenceConfig::default(),
+            InferenceConfig {
+                temperature: 0.9,
+                end_tokens: vec!["###".to_string()],
+                ..Default::default()
+            },
+        ];
+        for config in requests {
+            let command = BrainstemCommand::Infer {
+                model: None,
+                prompt: "hello".to_string(),
+                config,
+            };
+            harness.answer("request", command).await;
+        }
+        harness.stop().await;
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let configs: Vec<_> = calls
+            .all()
+            .into_iter()
+            .filter_map(|call| match call {
+                Call::Infer { config, .. } => Some(config),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(configs[0].temperature, 0.1);
+        assert_eq!(configs[0].max_tokens, Some(32));
+        assert_eq!(configs[0].end_tokens, vec!["<|eot|>"]);
+        // What the request set wins
+        assert_eq!(configs[1].temperature, 0.9);
+        assert_eq!(configs[1].max_tokens, Some(32));
+        assert_eq!(configs[1].end_tokens, vec!["###", "<|eot|>"]);
+    });
+}
+
+// ── Memory ──
+
+/// A GGUF header, with no tensors or metadata, grown sparsely to `size` bytes.
+fn write_sparse_gguf(path: &Path, size: u64) {
+    let mut header = b"GGUF".to_vec();
+    header.extend_from_slice(&3u32.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    std::fs::write(path, header).unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_len(size)
+        .unwrap();
+}
+
+#[test]
+fn test_model_too_large_is_refused_with_a_smaller_quant() {
+    let Some(memory) = facecrab::system_memory() else {
+        return;
+    };
+    smol::block_on(async {
+        let dir = common::temp_dir("memory");
+        let cache = dir.join("cache");
+        std::fs::create_dir_all(&cache).unwrap();
+        let entry = |name: &str, quant: &str| {
+            format!(
+                "[[models]]\nname = \"huge-{}\"\nrepo = \"local/huge\"\nfilename = \"huge.{}.gguf\"\nquantization = \"{}\"\n",
+                name, quant, quant
+            )
+        };
+        let manifest = [entry("q8", "Q8_0"), entry("q2", "Q2_K")].concat();
+        std::fs::write(dir.join("manifest.toml"), manifest).unwrap();
+        // Twice what is available at 8 bits is half of it at 2
+        write_sparse_gguf(&cache.join("huge.Q8_0.gguf"), memory.available * 2);
+
+        let engine = MockEngine::new("huge-q8").local();
+        let calls = engine.calls();
+        let orchestrator = Orchestrator::builder()
+            .engine(Box::new(engine))
+            .asset_authority(authority(&dir, None))
+            .build()
+            .await
+            .unwrap();
+        let mut harness = start(orchestrator);
+
+        harness
+            .send("load", BrainstemCommand::LoadModel("huge-q8".to_string()))
+            .await;
+        let error = loop {
+            if let BrainstemBody::Error(e) = harness.next().await.body {
+                break e;
+            }
+        };
+        assert!(
+            error.starts_with("Not enough memory for huge-q8: it needs about"),
+            "{}",
+            error
+        );
+        assert!(
+            error.ends_with("; try the smaller quantization huge-q2"),
+            "{}",
+            error
+        );
+        assert!(calls.loads().is_empty());
+
+        harness.stop().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    });
+}
+
+// ── Downloads alongside generation ──
+
+/// Serves `gate/...` files at once, except `slow.gguf`, which waits for the gate to open.
+struct GatedSource {
+    gate: Mutex<Option<oneshot::Receiver<()>>>,
+}
+
+#[async_trait]
+impl AssetSource for GatedSource {
+    fn handles(&self, spec: &ModelSpec) -> bool {
+        spec.repo.starts_with("gate/")
+    }
+
+    fn url(&self, spec: &ModelSpec) -> String {
+        format!("{}/{}", spec.repo, spec.filename)
+    }
+
+    async fn open(
+        &self,
+        spec: &ModelSpec,
+        _client: &surf::Client,
+        _offset: u64,
+    ) -> Result<AssetStream> {
+        if spec.filename == "slow.gguf" {
+            let gate = self.gate.lock().unwrap().take();
+            if let Some(gate) = gate {
+                let _ = gate.await;
+            }
+        }
+        Ok(AssetStream {
+            reader: Box::new(futures::io::Cursor::new(b"GGUF".to_vec())),
+            total: Some(4),
+            offset: 0,
+            revision: None,
+        })
+    }
+}
+
+/// An orchestrator on local engines answering with the file name of the model they have
+/// loaded, downloading from a [GatedSource] behind `gate` and loading `slow.gguf` behind
+/// `load_gate`; returns it with the engines' calls.
+async fn start_gated(
+    dir: &Path,
+    gate: Option<oneshot::Receiver<()>>,
+    load_gate: Option<oneshot::Receiver<()>>,
+) -> (Harness, Calls) {
+    let mut engine = MockEngine::new("gate/store:fast.gguf")
+        .local()
+        .answer(|prompt| {
+            let name = Path::new(prompt.model).file_name().unwrap_or_default();
+            Reply::text(&[&name.to_string_lossy()])
+        });
+    if let Some(load_gate) = load_gate {
+        engine = engine.gated_load("slow.gguf", load_gate);
+    }
+    let calls = engine.calls();
+    let source = GatedSource {
+        gate: Mutex::new(gate),
+    };
+    let mut orchestrator = Orchestrator::builder()
+        .engine(Box::new(engine.clone()))
+        .asset_authority(authority(dir, Some(source)))
+        .build()
+        .await
+        .unwrap();
+    engine.restart(&mut orchestrator);
+    (start(orchestrator), calls)
+}
+
+fn ask(model: Option<&str>) -> BrainstemCommand {
+    BrainstemCommand::Infer {
+        model: model.map(str::to_string),
+        prompt: "which model?".to_string(),
+        config: InferenceConfig::default(),
+    }
+}
+
+/// The answer of the next request to complete, and its id.
+async fn next_answer(harness: &mut Harness) -> (String, String) {
+    let mut answers = std::collections::HashMap::new();
+    loop {
+        let output = harness.next().await;
+        let id = output.id.unwrap_or_default();
+        match output.body {
+            BrainstemBody::Event(InferenceEvent::Content(text)) => {
+                answers.insert(id, text);
+            }
+            BrainstemBody::Event(InferenceEvent::Complete) => {
+                let answer = answers.remove(&id).unwrap_or_default();
+                return (id, answer);
+            }
+            BrainstemBody::Error(e) => panic!("{}: {}", id, e),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_loaded_model_serves_while_the_next_downloads() {
+    smol::block_on(async {
+        let dir = common::temp_dir("preload");
+        let (open_gate, gate) = oneshot::channel();
+        let (mut harness, _) = start_gated(&dir, Some(gate), None).await;
+
+        harness.send("first", ask(None)).await;
+        assert_eq!(
+            next_answer(&mut harness).await,
+            ("first".to_string(), "fast.gguf".to_string())
+        );
+
+        // The switch waits for its download; the loaded model answers meanwhile
+        harness
+            .send("switch", ask(Some("gate/store:slow.gguf")))
+            .await;
+        harness.send("meanwhile", ask(None)).await;
+        assert_eq!(
+            next_answer(&mut harness).await,
+            ("meanwhile".to_string(), "fast.gguf".to_string())
+        );
+
+        open_gate.send(()).unwrap();
+        assert_eq!(
+            next_answer(&mut harness).await,
+            ("switch".to_string(), "slow.gguf".to_string())
+        );
+
+        harness.stop().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    });
+}
+
+#[test]
+fn test_loaded_model_serves_while_the_next_loads() {
+    smol::block_on(async {
+        let dir = common::temp_dir("preload-load");
+        let (open_gate, load_gate) = oneshot::channel();
+        let (mut harness, calls) = start_gated(&dir, None, Some(load_gate)).await;
+
+        harness.send("first", ask(None)).await;
+        next_answer(&mut harness).await;
+
+        // The switch waits for its model to load on an engine of its own
+        harness
+            .send("switch", ask(Some("gate/store:slow.gguf")))
+            .await;
+        while !calls
+            .loads()
+            .iter()
+            .any(|model| model.ends_with("slow.gguf"))
+        {
+            smol::Timer::after(Duration::from_millis(5)).await;
+        }
+        harness.send("meanwhile", ask(None)).await;
+        assert_eq!(
+            next_answer(&mut harness).await,
+            ("meanwhile".to_string(), "fast.gguf".to_string())
+        );
+
+        open_gate.send(()).unwrap();
+        assert_eq!(
+            next_answer(&mut harness).await,
+            ("switch".to_string(), "slow.gguf".to_string())
+        );
+        // Swapped in rather than loaded again
+        assert_eq!(calls.loads().len(), 2);
+
+        harness.stop().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    });
+}
+
+#[test]
+fn test_unavailable_model_fails_the_request() {
+    smol::block_on(async {
+        let dir = common::temp_dir("unavailable");
+        let (mut harness, _) = start_gated(&dir, None, None).await;
+
+        harness.send("first", ask(None)).await;
+        next_answer(&mut harness).await;
+
+        // Not run on the model already loaded instead
+        harness.send("switch", ask(Some("no-such-model"))).await;
+        let error = loop {
+            match harness.next().await.body {
+                BrainstemBody::Error(e) => break e,
+                BrainstemBody::Event(InferenceEvent::Content(text)) => {
+                    panic!("answered with {}", text)
+                }
+                _ => {}
+            }
+        };
+        assert!(
+            error.contains("Model no-such-model not available"),
+            "{}",
+            error
+        );
+
+        harness.stop().await;
+        let _ = std::fs::remove_dir_all(&dir);
+    });
+}
@@ -0,0 +1,584 @@
+//! The mock engine and orchestrator harness the brainstem tests share.
+#![allow(dead_code)]
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::channel::{mpsc, oneshot};
+use futures::sink::SinkExt;
+use futures::{FutureExt, StreamExt};
+use rusty_genius_core::engine::{CancellationToken, Engine};
+use rusty_genius_core::manifest::{BenchConfig, InferenceConfig, LoadConfig, TranscriptionConfig};
+use rusty_genius_core::protocol::{
+    BenchReport, BrainstemBody, BrainstemCommand, BrainstemInput, BrainstemOutput, InferenceEvent,
+    ModelInfo, TranscriptSegment,
+};
+use rusty_genius_stem::Orchestrator;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Something the mock engine was asked to do.
+#[derive(Clone, Debug)]
+pub enum Call {
+    Load {
+        model: String,
+        config: LoadConfig,
+    },
+    Infer {
+        prompt: String,
+        config: InferenceConfig,
+        cancel: CancellationToken,
+    },
+    Embed(String),
+    SaveSession(String),
+    /// A session loaded back, with what was saved for it.
+    LoadSession {
+        session_id: String,
+        saved: String,
+    },
+}
+
+/// As "load model", "infer prompt", "embed input", "save session" or "load session from
+/// saved".
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Call::Load { model, .. } => write!(f, "load {}", model),
+            Call::Infer { prompt, .. } => write!(f, "infer {}", prompt),
+            Call::Embed(input) => write!(f, "embed {}", input),
+            Call::SaveSession(session_id) => write!(f, "save {}", session_id),
+            Call::LoadSession { session_id, saved } => {
+                write!(f, "load {} from {}", session_id, saved)
+            }
+        }
+    }
+}
+
+/// The calls of a mock engine, and of the engines built to replace it, in the order made.
+#[derive(Clone, Default)]
+pub struct Calls(Arc<Mutex<Vec<Call>>>);
+
+impl Calls {
+    pub fn all(&self) -> Vec<Call> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// The models loaded, and tried.
+    pub fn loads(&self) -> Vec<String> {
+        self.all()
+            .into_iter()
+            .filter_map(|call| match call {
+                Call::Load { model, .. } => Some(model),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn prompts(&self) -> Vec<String> {
+        self.all()
+            .into_iter()
+            .filter_map(|call| match call {
+                Call::Infer { prompt, .. } => Some(prompt),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Every call, described.
+    pub fn describe(&self) -> Vec<String> {
+        self.all().iter().map(Call::to_string).collect()
+    }
+
+    fn push(&self, call: Call) {
+        self.0.lock().unwrap().push(call);
+    }
+}
+
+/// Generations answered with [Reply::Held], open until [Held::finish].
+#[derive(Clone, Default)]
+pub struct Held(Arc<Mutex<Vec<mpsc::Sender<Result<InferenceEvent>>>>>);
+
+impl Held {
+    /// Complete the generations held open.
+    pub fn finish(&self) {
+        for mut stream in self.0.lock().unwrap().drain(..) {
+            let _ = stream.try_send(Ok(InferenceEvent::Complete));
+        }
+    }
+}
+
+/// A prompt put to the mock engine.
+pub struct Prompt<'a> {
+    pub text: &'a str,
+    pub config: &'a InferenceConfig,
+    /// The model loaded, as it was named to the engine.
+    pub model: &'a str,
+    /// How many prompts the engine has been given, this one included.
+    pub count: usize,
+}
+
+/// How the mock engine answers a prompt.
+pub enum Reply {
+    /// Stream these events and close the stream; without a `Complete`, the events are cut
+    /// off as those of a worker thread that panicked would be.
+    Events(Vec<InferenceEvent>),
+    /// Stream an error.
+    Fail(String),
+    /// Refuse the prompt before streaming anything.
+    Refuse(String),
+    /// Panic, as an engine whose state is poisoned.
+    Panic,
+    /// Stream the prompt over and over until cancelled.
+    Endless,
+    /// Stream "started", and hold the stream open until [Held::finish].
+    Held,
+}
+
+impl Reply {
+    /// `pieces` as content, then `Complete`.
+    pub fn text(pieces: &[&str]) -> Self {
+        let mut events: Vec<_> = pieces
+            .iter()
+            .map(|piece| InferenceEvent::Content(piece.to_string()))
+            .collect();
+        events.push(InferenceEvent::Complete);
+        Reply::Events(events)
+    }
+}
+
+type Answer = Arc<dyn Fn(&Prompt) -> Reply + Send + Sync>;
+type Embedding = Arc<dyn Fn(&str) -> Vec<f32> + Send + Sync>;
+/// The model a load waits on the gate for, until the load takes it.
+type LoadGate = Arc<Mutex<Option<(String, oneshot::Receiver<()>)>>>;
+
+/// An engine that answers as the test tells it, noting its calls. By default it is remote,
+/// so models load by name without a download, and echoes every prompt back.
+#[derive(Clone)]
+pub struct MockEngine {
+    name: String,
+    remote: bool,
+    loaded: Option<String>,
+    answer: Answer,
+    embedding: Option<Embedding>,
+    info: Option<ModelInfo>,
+    failing_loads: usize,
+    load_error: String,
+    load_gate: LoadGate,
+    takes_tokens: bool,
+    benches: bool,
+    transcribes: bool,
+    prompts: usize,
+    calls: Calls,
+    held: Held,
+}
+
+impl MockEngine {
+    /// A mock whose default model is `name`.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            remote: true,
+            loaded: None,
+            answer: Arc::new(|prompt: &Prompt| Reply::text(&[prompt.text])),
+            embedding: None,
+            info: None,
+            failing_loads: 0,
+            load_error: String::new(),
+            load_gate: Arc::default(),
+            takes_tokens: false,
+            benches: false,
+            transcribes: false,
+            prompts: 0,
+            calls: Calls::default(),
+            held: Held::default(),
+        }
+    }
+
+    /// Load models from files, downloading them first.
+    pub fn local(mut self) -> Self {
+        self.remote = false;
+        self
+    }
+
+    /// Start with the default model loaded.
+    pub fn loaded(mut self) -> Self {
+        self.loaded = Some(self.name.clone());
+        self
+    }
+
+    pub fn answer(mut self, answer: impl Fn(&Prompt) -> Reply + Send + Sync + 'static) -> Self {
+        self.answer = Arc::new(answer);
+        self
+    }
+
+    /// Embed inputs with `embedding`, rather than failing.
+    pub fn embeds(mut self, embedding: impl Fn(&str) -> Vec<f32> + Send + Sync + 'static) -> Self {
+        self.embedding = Some(Arc::new(embedding));
+        self
+    }
+
+    /// Report `info` while a model is loaded.
+    pub fn info(mut self, info: ModelInfo) -> Self {
+        self.info = Some(info);
+        self
+    }
+
+    /// Fail the first `failures` loads with `error`.
+    pub fn failing_loads(mut self, failures: usize, error: &str) -> Self {
+        self.failing_loads = failures;
+        self.load_error = error.to_string();
+        self
+    }
+
+    /// Hold the first load of a model whose path ends with `model` until `gate` opens, on
+    /// whichever copy of this engine makes it.
+    pub fn gated_load(self, model: &str, gate: oneshot::Receiver<()>) -> Self {
+        *self.load_gate.lock().unwrap() = Some((model.to_string(), gate));
+        self
+    }
+
+    /// Answer token prompts with their ids.
+    pub fn takes_tokens(mut self) -> Self {
+        self.takes_tokens = true;
+        self
+    }
+
+    /// Report every benchmark as taking a second.
+    pub fn benches(mut self) -> Self {
+        self.benches = true;
+        self
+    }
+
+    /// Transcribe any audio as the number of samples it got.
+    pub fn transcribes(mut self) -> Self {
+        self.transcribes = true;
+        self
+    }
+
+    pub fn calls(&self) -> Calls {
+        self.calls.clone()
+    }
+
+    pub fn held(&self) -> Held {
+        self.held.clone()
+    }
+
+    /// Restart `orchestrator` on copies of this engine, as it is now, when its engine crashes;
+    /// returns the count of engines built.
+    pub fn restart(&self, orchestrator: &mut Orchestrator) -> Arc<AtomicUsize> {
+        let builds = Arc::new(AtomicUsize::new(0));
+        let (engine, built) = (self.clone(), builds.clone());
+        orchestrator.set_engine_factory(move || {
+            built.fetch_add(1, Ordering::SeqCst);
+            let engine: Box<dyn Engine> = Box::new(engine.clone());
+            async move { engine }.boxed()
+        });
+        builds
+    }
+}
+
+#[async_trait]
+impl Engine for MockEngine {
+    async fn load_model(&mut self, model_path: &str) -> Result<()> {
+        self.load_model_with_config(model_path, LoadConfig::default())
+            .await
+    }
+
+    async fn load_model_with_config(&mut self, model_path: &str, config: LoadConfig) -> Result<()> {
+        self.calls.push(Call::Load {
+            model: model_path.to_string(),
+            config,
+        });
+        if self.failing_loads > 0 {
+            self.failing_loads -= 1;
+            return Err(anyhow!("{}", self.load_error));
+        }
+        let gate = {
+            let mut gate = self.load_gate.lock().unwrap();
+            match gate.take() {
+                Some((model, open)) if model_path.ends_with(&model) => Some(open),
+                other => {
+                    *gate = other;
+                    None
+                }
+            }
+        };
+        if let Some(open) = gate {
+            let _ = open.await;
+        }
+        self.loaded = Some(model_path.to_string());
+        Ok(())
+    }
+
+    async fn unload_model(&mut self) -> Result<()> {
+        self.loaded = None;
+        Ok(())
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.loaded.is_some()
+    }
+
+    fn is_remote(&self) -> bool {
+        self.remote
+    }
+
+    fn default_model(&self) -> String {
+        self.name.clone()
+    }
+
+    fn model_info(&self) -> Option<ModelInfo> {
+        self.info.clone().filter(|_| self.is_loaded())
+    }
+
+    async fn infer(
+        &mut self,
+        prompt: &str,
+        config: InferenceConfig,
+        cancel: CancellationToken,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        self.calls.push(Call::Infer {
+            prompt: prompt.to_string(),
+            config: config.clone(),
+            cancel: cancel.clone(),
+        });
+        self.prompts += 1;
+        let reply = (self.answer)(&Prompt {
+            text: prompt,
+            config: &config,
+            model: self.loaded.as_deref().unwrap_or_default(),
+            count: self.prompts,
+        });
+        match reply {
+            Reply::Events(events) => Ok(stream(events.into_iter().map(Ok).collect())),
+            Reply::Fail(error) => Ok(stream(vec![Err(anyhow!("{}", error))])),
+            Reply::Refuse(error) => Err(anyhow!("{}", error)),
+            Reply::Panic => panic!("engine state poisoned"),
+            Reply::Endless => {
+                let (mut tx, rx) = mpsc::channel(4);
+                let prompt = prompt.to_string();
+                std::thread::spawn(move || {
+                    while !cancel.is_cancelled() {
+                        let content = Ok(InferenceEvent::Content(prompt.clone()));
+                        if futures::executor::block_on(tx.send(content)).is_err() {
+                            break;
+                        }
+                        std::thread::sleep(Duration::from_millis(5));
+                    }
+                });
+                Ok(rx)
+            }
+            Reply::Held => {
+                let (mut tx, rx) = mpsc::channel(2);
+                tx.send(Ok(InferenceEvent::Content("started".to_string())))
+                    .await?;
+                self.held.0.lock().unwrap().push(tx);
+                Ok(rx)
+            }
+        }
+    }
+
+    async fn infer_tokens(
+        &mut self,
+        tokens: Vec<i32>,
+        _config: InferenceConfig,
+        _cancel: CancellationToken,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        if !self.takes_tokens {
+            return Err(anyhow!("This engine does not support token prompts"));
+        }
+        Ok(stream(vec![
+            Ok(InferenceEvent::Content(format!("{:?}", tokens))),
+            Ok(InferenceEvent::Complete),
+        ]))
+    }
+
+    async fn embed(
+        &mut self,
+        input: &str,
+        _config: InferenceConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        self.calls.push(Call::Embed(input.to_string()));
+        let embedding = self
+            .embedding
+            .as_ref()
+            .ok_or_else(|| anyhow!("no embeddings"))?;
+        Ok(stream(vec![
+            Ok(InferenceEvent::Embedding(embedding(input))),
+            Ok(InferenceEvent::Complete),
+        ]))
+    }
+
+    async fn save_session(&mut self, session_id: &str, path: &str) -> Result<()> {
+        std::fs::write(path, session_id)?;
+        self.calls.push(Call::SaveSession(session_id.to_string()));
+        Ok(())
+    }
+
+    async fn load_session(&mut self, session_id: &str, path: &str) -> Result<()> {
+        let saved = std::fs::read_to_string(path)?;
+        self.calls.push(Call::LoadSession {
+            session_id: session_id.to_string(),
+            saved,
+        });
+        Ok(())
+    }
+
+    async fn transcribe(
+        &mut self,
+        samples: &[f32],
+        _config: TranscriptionConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        if !self.transcribes {
+            return Err(anyhow!("This engine does not support transcription"));
+        }
+        Ok(stream(vec![
+            Ok(InferenceEvent::Transcript(TranscriptSegment {
+                start_ms: 0,
+                end_ms: 1000,
+                text: format!("{} samples", samples.len()),
+            })),
+            Ok(InferenceEvent::Complete),
+        ]))
+    }
+
+    async fn bench(&mut self, config: BenchConfig) -> Result<BenchReport> {
+        if !self.benches {
+            return Err(anyhow!("This engine does not support benchmarking"));
+        }
+        Ok(BenchReport::new(&config, 1000.0, 1000.0))
+    }
+}
+
+/// A stream of `events`, already sent.
+fn stream(events: Vec<Result<InferenceEvent>>) -> mpsc::Receiver<Result<InferenceEvent>> {
+    let (mut tx, rx) = mpsc::channel(events.len());
+    for event in events {
+        let _ = tx.try_send(event);
+    }
+    rx
+}
+
+/// An orchestrator running on a task, with the ends of its channels.
+pub struct Harness {
+    pub in_tx: mpsc::Sender<BrainstemInput>,
+    pub out_rx: mpsc::Receiver<BrainstemOutput>,
+    run: smol::Task<Result<()>>,
+}
+
+/// Run `orchestrator` on a task of its own.
+pub fn start(orchestrator: Orchestrator) -> Harness {
+    let (in_tx, out_rx, run) = orchestrator.connect();
+    Harness {
+        in_tx,
+        out_rx,
+        run: smol::spawn(run),
+    }
+}
+
+/// Run an orchestrator on `engine` with its defaults.
+pub fn start_with(engine: MockEngine) -> Harness {
+    start(Orchestrator::with_engine(Box::new(engine)))
+}
+
+impl Harness {
+    /// Send `command` as the request `id`.
+    pub async fn send(&mut self, id: &str, command: BrainstemCommand) {
+        self.in_tx.send(input(id, command)).await.unwrap();
+    }
+
+    /// Send `command` without an id, for the orchestrator to mint one.
+    pub async fn send_unnamed(&mut self, command: BrainstemCommand) {
+        let input = BrainstemInput { id: None, command };
+        self.in_tx.send(input).await.unwrap();
+    }
+
+    /// The next output that isn't a state transition.
+    pub async fn next(&mut self) -> BrainstemOutput {
+        loop {
+            let output = self.out_rx.next().await.expect("orchestrator stopped");
+            if !matches!(output.body, BrainstemBody::State { .. }) {
+                return output;
+            }
+        }
+    }
+
+    /// The outputs for the request `id` until it completes or fails, without state
+    /// transitions.
+    pub async fn until_done(&mut self, id: &str) -> Vec<BrainstemBody> {
+        let mut bodies = Vec::new();
+        loop {
+            let output = self.next().await;
+            if output.id.as_deref() != Some(id) {
+                continue;
+            }
+            let done = is_done(&output.body);
+            bodies.push(output.body);
+            if done {
+                return bodies;
+            }
+        }
+    }
+
+    /// Send `command` as `id` and return the text it answered with, panicking if it fails.
+    pub async fn answer(&mut self, id: &str, command: BrainstemCommand) -> String {
+        self.send(id, command).await;
+        let bodies = self.until_done(id).await;
+        if let Some(BrainstemBody::Error(e)) = bodies.last() {
+            panic!("{}: {}", id, e);
+        }
+        text(&bodies)
+    }
+
+    /// Close the inputs and wait for the orchestrator to finish what it was running.
+    pub async fn stop(self) {
+        let Harness { in_tx, out_rx, run } = self;
+        drop(in_tx);
+        run.await.unwrap();
+        drop(out_rx);
+    }
+}
+
+pub fn input(id: &str, command: BrainstemCommand) -> BrainstemInput {
+    BrainstemInput {
+        id: Some(id.to_string()),
+        command,
+    }
+}
+
+pub fn infer(prompt: &str) -> BrainstemCommand {
+    BrainstemCommand::Infer {
+        model: None,
+        prompt: prompt.to_string(),
+        config: InferenceConfig::default(),
+    }
+}
+
+/// Whether `body` ends its request.
+pub fn is_done(body: &BrainstemBody) -> bool {
+    matches!(
+        body,
+        BrainstemBody::Event(InferenceEvent::Complete) | BrainstemBody::Error(_)
+    )
+}
+
+/// The content among `bodies`, joined.
+pub fn text(bodies: &[BrainstemBody]) -> String {
+    bodies
+        .iter()
+        .filter_map(|body| match body {
+            BrainstemBody::Event(InferenceEvent::Content(text)) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// An empty directory of the test process's own, under the system's temporary one.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rusty-genius-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,680 @@
+mod common;
+
+use common::{infer, start, start_with, Harness, MockEngine, Reply};
+use rusty_genius_core::manifest::{InferenceConfig, RagConfig, ToolDefinition};
+use rusty_genius_core::protocol::{BrainstemBody, BrainstemCommand, ChatMessage, InferenceEvent};
+use rusty_genius_stem::Orchestrator;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+// ── Batches ──
+
+/// Run an `InferBatch` of `prompts` and collect the text of each prompt by index, whether
+/// each prompt completed, and the error the batch ended with, until it ends. Prompts are
+/// echoed back in two pieces; "fail" fails, and "refuse" is refused.
+async fn run_batch(prompts: &[&str]) -> (BTreeMap<usize, (String, bool)>, Option<String>) {
+    let engine = MockEngine::new("echo").answer(|prompt| match prompt.text {
+        "refuse" => Reply::Refuse("too busy".to_string()),
+        "fail" => Reply::Fail("cannot answer".to_string()),
+        text => Reply::text(&[text, "!"]),
+    });
+    let mut orchestrator = Orchestrator::with_engine(Box::new(engine));
+    orchestrator.set_max_parallel(2);
+    let mut harness = start(orchestrator);
+
+    let batch = BrainstemCommand::InferBatch {
+        model: None,
+        prompts: prompts.iter().map(|prompt| prompt.to_string()).collect(),
+        config: InferenceConfig::default(),
+    };
+    harness.send("batch", batch).await;
+
+    let mut answers: BTreeMap<usize, (String, bool)> = BTreeMap::new();
+    loop {
+        let output = harness.next().await;
+        // Lifecycle outputs carry no id
+        if output.id.is_none() {
+            continue;
+        }
+        assert_eq!(output.id.as_deref(), Some("batch"));
+        match output.body {
+            BrainstemBody::Batch { index, event } => {
+                let answer = answers.entry(index).or_default();
+                assert!(!answer.1, "prompt {} had events after Complete", index);
+                match event {
+                    InferenceEvent::Content(text) => answer.0.push_str(&text),
+                    InferenceEvent::Complete => answer.1 = true,
+                    other => panic!("unexpected event {:?}", other),
+                }
+            }
+            BrainstemBody::Event(InferenceEvent::Complete) => return (answers, None),
+            BrainstemBody::Error(e) => return (answers, Some(e)),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_batch_tags_events_with_prompt_index() {
+    smol::block_on(async {
+        let prompts = ["one", "two", "three", "four", "five"];
+        let (answers, error) = run_batch(&prompts).await;
+        assert_eq!(error, None);
+        assert_eq!(answers.len(), prompts.len());
+        for (index, prompt) in prompts.iter().enumerate() {
+            assert_eq!(answers[&index], (format!("{}!", prompt), true));
+        }
+    });
+}
+
+#[test]
+fn test_batch_carries_on_past_a_failed_prompt() {
+    smol::block_on(async {
+        let (answers, error) = run_batch(&["one", "fail", "three"]).await;
+        // The failure ends the batch, once the other prompts have
+        assert_eq!(error.as_deref(), Some("Prompt 1: cannot answer"));
+        assert_eq!(answers[&0], ("one!".to_string(), true));
+        assert_eq!(answers[&2], ("three!".to_string(), true));
+        assert!(!answers.contains_key(&1));
+    });
+}
+
+#[test]
+fn test_refused_prompt_ends_the_batch_after_those_running() {
+    smol::block_on(async {
+        let (answers, error) = run_batch(&["one", "refuse", "three"]).await;
+        assert_eq!(error.as_deref(), Some("Prompt 1: too busy"));
+        // The prompt ahead finishes first; those behind never start
+        assert_eq!(answers[&0], ("one!".to_string(), true));
+        assert_eq!(answers.len(), 1);
+    });
+}
+
+#[test]
+fn test_empty_batch_completes() {
+    smol::block_on(async {
+        let (answers, error) = run_batch(&[]).await;
+        assert!(answers.is_empty());
+        assert_eq!(error, None);
+    });
+}
+
+#[test]
+fn test_crash_ends_the_batch_once() {
+    smol::block_on(async {
+        let engine = MockEngine::new("crashing").answer(|prompt| match prompt.text {
+            "hold" => Reply::Held,
+            "panic" => Reply::Panic,
+            text => Reply::text(&[text]),
+        });
+        let mut orchestrator = Orchestrator::with_engine(Box::new(engine.clone()));
+        orchestrator.set_max_parallel(3);
+        engine.restart(&mut orchestrator);
+        let mut harness = start(orchestrator);
+        let batch = |prompts: &[&str]| BrainstemCommand::InferBatch {
+            model: None,
+            prompts: prompts.iter().map(|prompt| prompt.to_string()).collect(),
+            config: InferenceConfig::default(),
+        };
+
+        // A batch with prompts in flight when another request crashes the engine, then one
+        // that crashes it itself with a prompt of its own in flight
+        harness.send("held", batch(&["hold", "hold"])).await;
+        harness.send("other", infer("panic")).await;
+        harness
+            .send("crashing", batch(&["hold", "panic", "three"]))
+            .await;
+        harness.send("after", infer("after")).await;
+
+        let mut ends = Vec::new();
+        loop {
+            let output = harness.next().await;
+            match (output.id.as_deref(), output.body) {
+                (Some("after"), BrainstemBody::Event(InferenceEvent::Complete)) => break,
+                (Some(id), BrainstemBody::Error(e)) => ends.push(format!("{}: {}", id, e)),
+                (Some(id), BrainstemBody::Event(InferenceEvent::Complete)) => {
+                    ends.push(format!("{}: Complete", id))
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(
+            ends,
+            vec![
+                "other: The engine crashed",
+                "held: The engine crashed",
+                "crashing: The engine crashed",
+            ]
+        );
+
+        harness.stop().await;
+    });
+}
+
+// ── Token prompts ──
+
+#[test]
+fn test_infer_tokens_reaches_engine() {
+    smol::block_on(async {
+        let mut harness = start_with(MockEngine::new("echo").loaded().takes_tokens());
+        let tokens = BrainstemCommand::InferTokens {
+            model: None,
+            tokens: vec![1, 15043, 2],
+            config: InferenceConfig::default(),
+        };
+        assert_eq!(harness.answer("t1", tokens).await, "[1, 15043, 2]");
+        harness.stop().await;
+    });
+}
+
+#[test]
+fn test_infer_tokens_unsupported_errors() {
+    smol::block_on(async {
+        let mut harness = start_with(MockEngine::new("echo").loaded());
+        let tokens = BrainstemCommand::InferTokens {
+            model: None,
+            tokens: vec![1],
+            config: InferenceConfig::default(),
+        };
+        harness.send("t1", tokens).await;
+        assert!(matches!(
+            harness.until_done("t1").await.as_slice(),
+            [BrainstemBody::Error(e)] if e == "This engine does not support token prompts"
+        ));
+        harness.stop().await;
+    });
+}
+
+// ── Chat sessions ──
+
+fn chat(session_id: &str, message: ChatMessage) -> BrainstemCommand {
+    BrainstemCommand::Chat {
+        session_id: session_id.to_string(),
+        message,
+        config: InferenceConfig::default(),
+    }
+}
+
+#[test]
+fn test_chat_sessions_keep_their_history() {
+    smol::block_on(async {
+        // Answers with the number of prompts so far
+        let engine = MockEngine::new("chat")
+            .answer(|prompt| Reply::text(&["Reply ", &prompt.count.to_string()]));
+        let calls = engine.calls();
+        let mut harness = start_with(engine);
+
+        let turns = [
+            ("a", ChatMessage::system("Be brief.")),
+            ("a", ChatMessage::user("Hi")),
+            ("b", ChatMessage::user("Other")),
+            ("a", ChatMessage::user("Bye")),
+        ];
+        for (session_id, message) in turns {
+            harness.answer("request", chat(session_id, message)).await;
+        }
+        let close = BrainstemCommand::CloseSession("a".into());
+        harness.answer("request", close).await;
+        harness
+            .answer("request", chat("a", ChatMessage::user("Again")))
+            .await;
+
+        // The system message is recorded without an answer
+        let sessions: Vec<_> = calls
+            .all()
+            .into_iter()
+            .filter_map(|call| match call {
+                common::Call::Infer { config, .. } => Some(config.session_id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            sessions,
+            vec![
+                Some("a".to_string()),
+                Some("b".to_string()),
+                Some("a".to_string()),
+                Some("a".to_string())
+            ]
+        );
+        let prompts = calls.prompts();
+        assert_eq!(
+            prompts[2],
+            "<|im_start|>system\nBe brief.<|im_end|>\n\
+             <|im_start|>user\nHi<|im_end|>\n\
+             <|im_start|>assistant\nReply 1<|im_end|>\n\
+             <|im_start|>user\nBye<|im_end|>\n\
+             <|im_start|>assistant\n"
+        );
+        assert_eq!(
+            prompts[1],
+            "<|im_start|>user\nOther<|im_end|>\n<|im_start|>assistant\n"
+        );
+        // Closing the session forgot it
+        assert_eq!(
+            prompts[3],
+            "<|im_start|>user\nAgain<|im_end|>\n<|im_start|>assistant\n"
+        );
+
+        harness.stop().await;
+    });
+}
+
+// ── Retrieval ──
+
+/// Embeds text by whether it mentions cats or dogs, and answers with its prompt.
+fn pet_engine() -> MockEngine {
+    MockEngine::new("pets").embeds(|input| {
+        let mentions = |pet: &str| if input.contains(pet) { 1.0 } else { 0.0 };
+        vec![mentions("cat"), mentions("dog"), 0.1]
+    })
+}
+
+/// Run `command` and return the documents it retrieved and the answer, and the inputs
+/// embedded.
+async fn rag(command: BrainstemCommand) -> (Vec<usize>, String, Vec<String>) {
+    let engine = pet_engine();
+    let calls = engine.calls();
+    let mut harness = start_with(engine);
+
+    harness.send("rag", command).await;
+    let (mut retrieved, mut answer) = (Vec::new(), String::new());
+    for body in harness.until_done("rag").await {
+        match body {
+            BrainstemBody::Event(InferenceEvent::Retrieved(documents)) => {
+                assert!(answer.is_empty(), "retrieved after the answer began");
+                retrieved = documents.iter().map(|d| d.index).collect();
+            }
+            BrainstemBody::Event(InferenceEvent::Content(text)) => answer.push_str(&text),
+            BrainstemBody::Error(e) => panic!("{}", e),
+            _ => {}
+        }
+    }
+    harness.stop().await;
+    let embedded = calls
+        .describe()
+        .into_iter()
+        .filter_map(|call| call.strip_prefix("embed ").map(str::to_string))
+        .collect();
+    (retrieved, answer, embedded)
+}
+
+fn documents() -> Vec<String> {
+    vec![
+        "Our dog barks at the mail carrier.".to_string(),
+        "The cat sleeps on the radiator.".to_string(),
+        "Rent is due on the first.".to_string(),
+    ]
+}
+
+#[test]
+fn test_rag_answers_from_the_closest_documents() {
+    smol::block_on(async {
+        let command = BrainstemCommand::Rag {
+            model: None,
+            query: "Where does the cat sleep?".to_string(),
+            documents: documents(),
+            embeddings: Vec::new(),
+            config: RagConfig {
+                top_k: 1,
+                ..Default::default()
+            },
+        };
+        let (retrieved, answer, embedded) = rag(command).await;
+
+        assert_eq!(retrieved, vec![1]);
+        assert!(answer.starts_with("<|im_start|>system\n"));
+        assert!(answer.contains(
+            "[1] The cat sleeps on the radiator.\n\nQuestion: Where does the cat sleep?"
+        ));
+        assert!(!answer.contains("dog"));
+        // The query, then every document
+        assert_eq!(embedded.len(), 4);
+    });
+}
+
+#[test]
+fn test_rag_uses_given_embeddings() {
+    smol::block_on(async {
+        let command = BrainstemCommand::Rag {
+            model: None,
+            query: "Is there a dog?".to_string(),
+            documents: documents(),
+            embeddings: vec![
+                vec![0.0, 1.0, 0.0],
+                vec![1.0, 0.0, 0.0],
+                vec![0.0, 0.0, 1.0],
+            ],
+            config: RagConfig::default(),
+        };
+        let (retrieved, _, embedded) = rag(command).await;
+
+        assert_eq!(retrieved, vec![0, 2, 1]);
+        assert_eq!(embedded, vec!["Is there a dog?"]);
+    });
+}
+
+// ── Tool calls ──
+
+const CALL: &str =
+    "<tool_call>\n{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Oslo\"}}\n</tool_call>";
+
+/// Calls `get_weather` until it is given a tool response, then answers with it; panics at the
+/// prompt `crash`. Restarts when it does.
+fn start_weather() -> (Harness, common::Calls) {
+    let engine = MockEngine::new("weather").answer(|prompt| {
+        if prompt.text == "crash" {
+            return Reply::Panic;
+        }
+        let answer = if prompt.config.tools.is_empty() {
+            "No tools."
+        } else if prompt
+            .text
+            .ends_with("</tool_response><|im_end|>\n<|im_start|>assistant\n")
+        {
+            "It is sunny."
+        } else {
+            CALL
+        };
+        // In pieces, as tokens come
+        let (first, rest) = answer.split_at(5);
+        Reply::text(&[first, rest])
+    });
+    let calls = engine.calls();
+    let mut orchestrator = Orchestrator::with_engine(Box::new(engine.clone()));
+    engine.restart(&mut orchestrator);
+    (start(orchestrator), calls)
+}
+
+fn weather_tools() -> InferenceConfig {
+    InferenceConfig {
+        tools: vec![ToolDefinition {
+            name: "get_weather".to_string(),
+            description: Some("Current weather in a city".to_string()),
+            parameters: json!({"type": "object", "properties": {"city": {"type": "string"}}}),
+        }],
+        ..Default::default()
+    }
+}
+
+/// Send `command` as the request "request" and collect its events until it completes or
+/// waits on its tool calls.
+async fn converse(harness: &mut Harness, command: BrainstemCommand) -> Vec<InferenceEvent> {
+    harness.send("request", command).await;
+    let mut events = Vec::new();
+    loop {
+        match harness.next().await.body {
+            BrainstemBody::Event(event) => {
+                let done = matches!(event, InferenceEvent::Complete);
+                events.push(event);
+                if done {
+                    return events;
+                }
+            }
+            BrainstemBody::AwaitingTools => return events,
+            BrainstemBody::Error(e) => panic!("{}", e),
+            _ => {}
+        }
+    }
+}
+
+/// The next error sent to the request `id`.
+async fn next_error(harness: &mut Harness, id: &str) -> String {
+    loop {
+        let output = harness.next().await;
+        if let BrainstemBody::Error(e) = output.body {
+            if output.id.as_deref() == Some(id) {
+                return e;
+            }
+        }
+    }
+}
+
+fn describe(events: &[InferenceEvent]) -> Vec<String> {
+    events
+        .iter()
+        .map(|event| match event {
+            InferenceEvent::Content(text) => text.clone(),
+            InferenceEvent::ToolCall(call) => {
+                format!("{} {} {}", call.id, call.name, call.arguments)
+            }
+            other => format!("{:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn test_infer_calls_tools_and_carries_on() {
+    smol::block_on(async {
+        let (mut harness, calls) = start_weather();
+        let prompt = "<|im_start|>user\nWeather in Oslo?<|im_end|>\n<|im_start|>assistant\n";
+        let ask = BrainstemCommand::Infer {
+            model: None,
+            prompt: prompt.to_string(),
+            config: weather_tools(),
+        };
+
+        // The call is held back from the content
+        let events = converse(&mut harness, ask).await;
+        assert_eq!(
+            describe(&events),
+            vec![r#"call_0 get_weather {"city":"Oslo"}"#]
+        );
+
+        let result = BrainstemCommand::ToolResult {
+            call_id: "call_0".to_string(),
+            content: "Sunny, 21C".to_string(),
+        };
+        // An answer that is no call streams as it comes
+        let events = converse(&mut harness, result).await;
+        assert_eq!(describe(&events), vec!["It is", " sunny.", "Complete"]);
+        assert_eq!(
+            calls.prompts()[1],
+            format!(
+                "{}{}<|im_end|>\n<|im_start|>user\n<tool_response>\nSunny, 21C\n</tool_response><|im_end|>\n<|im_start|>assistant\n",
+                prompt, CALL
+            )
+        );
+
+        let events = converse(&mut harness, infer(prompt)).await;
+        assert_eq!(describe(&events), vec!["No to", "ols.", "Complete"]);
+    });
+}
+
+#[test]
+fn test_unexpected_tool_result_fails() {
+    smol::block_on(async {
+        let (mut harness, _) = start_weather();
+        let result = BrainstemCommand::ToolResult {
+            call_id: "call_0".to_string(),
+            content: "Sunny".to_string(),
+        };
+        harness.send("nobody", result).await;
+        let output = harness.next().await;
+        assert_eq!(output.id.as_deref(), Some("nobody"));
+        assert!(matches!(output.body, BrainstemBody::Error(_)));
+    });
+}
+
+#[test]
+fn test_unknown_call_id_leaves_the_request_waiting() {
+    smol::block_on(async {
+        let (mut harness, _) = start_weather();
+        let ask = BrainstemCommand::Infer {
+            model: None,
+            prompt: "Weather in Oslo?".to_string(),
+            config: weather_tools(),
+        };
+        converse(&mut harness, ask).await;
+
+        let result = |call_id: &str| BrainstemCommand::ToolResult {
+            call_id: call_id.to_string(),
+            content: "Sunny, 21C".to_string(),
+        };
+        harness.send("request", result("call_9")).await;
+        let output = harness.next().await;
+        assert_eq!(output.id.as_deref(), Some("call_9"));
+        assert!(matches!(
+            output.body,
+            BrainstemBody::Error(e) if e == "Request 'request' made no call 'call_9'"
+        ));
+
+        // The request's stream carries on where it was
+        harness.send("request", result("call_0")).await;
+        let mut seqs = Vec::new();
+        loop {
+            let output = harness.next().await;
+            assert_eq!(output.id.as_deref(), Some("request"));
+            seqs.push(output.seq);
+            match output.body {
+                BrainstemBody::Event(InferenceEvent::Complete) => break,
+                BrainstemBody::Error(e) => panic!("{}", e),
+                _ => {}
+            }
+        }
+        assert!(seqs[0] > 0, "{:?}", seqs);
+    });
+}
+
+#[test]
+fn test_chat_keeps_tool_calls_in_its_history() {
+    smol::block_on(async {
+        let (mut harness, calls) = start_weather();
+        let chat = |message: ChatMessage| BrainstemCommand::Chat {
+            session_id: "weather".to_string(),
+            message,
+            config: weather_tools(),
+        };
+
+        let events = converse(&mut harness, chat(ChatMessage::user("Weather in Oslo?"))).await;
+        let InferenceEvent::ToolCall(call) = &events[0] else {
+            panic!("expected a tool call, got {:?}", events);
+        };
+        let result = BrainstemCommand::ToolResult {
+            call_id: call.id.clone(),
+            content: "Sunny".to_string(),
+        };
+        converse(&mut harness, result).await;
+        converse(&mut harness, chat(ChatMessage::user("Thanks"))).await;
+
+        let prompts = calls.prompts();
+        assert!(prompts[0].starts_with("<|im_start|>system\nYou may call"));
+        assert!(prompts[0].contains("\"name\":\"get_weather\""));
+        assert!(prompts[2].ends_with(&format!(
+            "<|im_start|>user\nWeather in Oslo?<|im_end|>\n\
+             <|im_start|>assistant\n{}<|im_end|>\n\
+             <|im_start|>user\n<tool_response>\nSunny\n</tool_response><|im_end|>\n\
+             <|im_start|>assistant\nIt is sunny.<|im_end|>\n\
+             <|im_start|>user\nThanks<|im_end|>\n<|im_start|>assistant\n",
+            CALL
+        )));
+    });
+}
+
+#[test]
+fn test_crash_fails_requests_waiting_on_tools() {
+    smol::block_on(async {
+        let (mut harness, _) = start_weather();
+        let prompt = "<|im_start|>user\nWeather in Oslo?<|im_end|>\n<|im_start|>assistant\n";
+        let ask = BrainstemCommand::Infer {
+            model: None,
+            prompt: prompt.to_string(),
+            config: weather_tools(),
+        };
+        let events = converse(&mut harness, ask).await;
+        assert!(matches!(events[0], InferenceEvent::ToolCall(_)));
+
+        harness.send("crash", infer("crash")).await;
+        let error = next_error(&mut harness, "request").await;
+        assert_eq!(error, "The engine crashed");
+
+        // Not carried on against the new engine
+        let result = BrainstemCommand::ToolResult {
+            call_id: "call_0".to_string(),
+            content: "Sunny".to_string(),
+        };
+        harness.send("request", result).await;
+        let error = next_error(&mut harness, "request").await;
+        assert!(error.contains("not waiting for tool results"), "{}", error);
+    });
+}
+
+// ── Transcription ──
+
+/// A 16 kHz mono 16-bit WAV file of `samples` silent samples.
+#[cfg(feature = "cortex-engine")]
+fn silent_wav(samples: usize) -> Vec<u8> {
+    let data_len = samples as u32 * 2;
+    let mut bytes = b"RIFF".to_vec();
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&16_000u32.to_le_bytes());
+    bytes.extend_from_slice(&32_000u32.to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    bytes.resize(bytes.len() + data_len as usize, 0);
+    bytes
+}
+
+/// Send one Transcribe request and collect its outputs up to Complete or an error.
+#[cfg(feature = "cortex-engine")]
+async fn transcribe(mut orchestrator: Orchestrator, model: Option<String>) -> Vec<BrainstemBody> {
+    use rusty_genius_core::manifest::TranscriptionConfig;
+    use rusty_genius_stem::CortexStrategy;
+
+    orchestrator.set_strategy(CortexStrategy::KeepAlive);
+    let mut harness = start(orchestrator);
+    let command = BrainstemCommand::Transcribe {
+        model,
+        audio: silent_wav(1600),
+        config: TranscriptionConfig::default(),
+    };
+    harness.send("t1", command).await;
+    let bodies = harness.until_done("t1").await;
+    harness.stop().await;
+    bodies
+}
+
+#[cfg(feature = "cortex-engine")]
+#[test]
+fn test_transcribe_without_transcriber_errors() {
+    smol::block_on(async {
+        let engine = MockEngine::new("counting").local().transcribes();
+        let bodies = transcribe(Orchestrator::with_engine(Box::new(engine)), None).await;
+        assert!(matches!(bodies.last(), Some(BrainstemBody::Error(_))));
+    });
+}
+
+#[cfg(feature = "cortex-engine")]
+#[test]
+fn test_transcribe_runs_on_transcriber() {
+    smol::block_on(async {
+        let model = std::env::temp_dir().join("rusty-genius-transcribe-test.bin");
+        std::fs::write(&model, b"weights").unwrap();
+
+        let mut orchestrator =
+            Orchestrator::with_engine(Box::new(MockEngine::new("counting").local()));
+        orchestrator.set_transcriber(Box::new(MockEngine::new("counting").local().transcribes()));
+        let bodies = transcribe(orchestrator, Some(model.to_string_lossy().into_owned())).await;
+
+        let texts: Vec<_> = bodies
+            .iter()
+            .filter_map(|body| match body {
+                BrainstemBody::Event(InferenceEvent::Transcript(segment)) => {
+                    Some(segment.text.as_str())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts, vec!["1600 samples"]);
+        assert!(matches!(
+            bodies.last(),
+            Some(BrainstemBody::Event(InferenceEvent::Complete))
+        ));
+    });
+}
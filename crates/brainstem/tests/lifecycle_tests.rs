@@ -0,0 +1,494 @@
+mod common;
+
+use common::{infer, start, start_with, Calls, Harness, MockEngine, Reply};
+use futures::StreamExt;
+use rusty_genius_core::manifest::InferenceConfig;
+use rusty_genius_core::protocol::{
+    AssetEvent, BrainstemBody, BrainstemCommand, BrainstemState, EngineHealth, InferenceEvent,
+};
+use rusty_genius_stem::{CortexStrategy, Orchestrator, RetryPolicy};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// ── Hibernation ──
+
+fn start_quick(strategy: CortexStrategy) -> Harness {
+    let mut orchestrator = Orchestrator::with_engine(Box::new(MockEngine::new("quick")));
+    orchestrator.set_strategy(strategy);
+    start(orchestrator)
+}
+
+/// Send `command` and return the lifecycle outputs until it completes.
+async fn lifecycle_of(harness: &mut Harness, command: BrainstemCommand) -> Vec<String> {
+    harness.send("request", command).await;
+    let mut lifecycle = Vec::new();
+    loop {
+        let output = harness.next().await;
+        match output.body {
+            BrainstemBody::Event(InferenceEvent::Complete) => return lifecycle,
+            BrainstemBody::Error(e) => panic!("{}", e),
+            body if output.id.is_none() => lifecycle.push(format!("{:?}", body)),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_hibernation_and_wake_are_broadcast() {
+    smol::block_on(async {
+        let mut harness = start_quick(CortexStrategy::HibernateAfter(Duration::from_millis(100)));
+
+        let lifecycle = lifecycle_of(&mut harness, infer("hello")).await;
+        assert_eq!(lifecycle, vec!["Waking", "Ready"]);
+        assert!(lifecycle_of(&mut harness, infer("hello")).await.is_empty());
+
+        // Idle past the timeout
+        let output = harness.next().await;
+        assert_eq!(output.id, None);
+        assert!(matches!(output.body, BrainstemBody::Hibernated));
+
+        let lifecycle = lifecycle_of(&mut harness, infer("hello")).await;
+        assert_eq!(lifecycle, vec!["Waking", "Ready"]);
+
+        // A reset unloads too
+        let lifecycle = lifecycle_of(&mut harness, BrainstemCommand::Reset).await;
+        assert!(lifecycle.is_empty());
+        let output = harness.next().await;
+        assert!(matches!(output.body, BrainstemBody::Hibernated));
+
+        harness.stop().await;
+    });
+}
+
+#[test]
+fn test_set_strategy_takes_effect_while_running() {
+    smol::block_on(async {
+        let mut harness = start_quick(CortexStrategy::KeepAlive);
+
+        let lifecycle = lifecycle_of(&mut harness, infer("hello")).await;
+        assert_eq!(lifecycle, vec!["Waking", "Ready"]);
+
+        let strategy = BrainstemCommand::SetStrategy(CortexStrategy::Immediate);
+        assert!(lifecycle_of(&mut harness, strategy).await.is_empty());
+        let output = harness.next().await;
+        assert!(matches!(output.body, BrainstemBody::Hibernated));
+
+        harness.stop().await;
+    });
+}
+
+#[test]
+fn test_cold_reload_is_reported_to_the_request() {
+    smol::block_on(async {
+        let mut harness = start_with(MockEngine::new("quick"));
+
+        harness.send("request", infer("hello")).await;
+        let mut assets = Vec::new();
+        loop {
+            let output = harness.next().await;
+            match output.body {
+                BrainstemBody::Asset(event) => {
+                    assert_eq!(output.id.as_deref(), Some("request"));
+                    assets.push(format!("{:?}", event));
+                }
+                BrainstemBody::Event(InferenceEvent::Complete) => break,
+                _ => {}
+            }
+        }
+        assert_eq!(
+            assets,
+            vec![
+                r#"Loading("quick")"#,
+                r#"Loaded { path: "quick", gpu_layers: None }"#
+            ]
+        );
+
+        harness.stop().await;
+    });
+}
+
+#[test]
+fn test_load_model_ends_with_loaded() {
+    smol::block_on(async {
+        let mut harness = start_with(MockEngine::new("quick"));
+
+        let load = BrainstemCommand::LoadModel("remote-model".to_string());
+        harness.send("load", load).await;
+        let loaded = loop {
+            let output = harness.next().await;
+            match output.body {
+                BrainstemBody::Asset(event @ AssetEvent::Loaded { .. }) => {
+                    assert_eq!(output.id.as_deref(), Some("load"));
+                    break format!("{:?}", event);
+                }
+                BrainstemBody::Error(e) => panic!("{}", e),
+                _ => {}
+            }
+        };
+        assert_eq!(
+            loaded,
+            r#"Loaded { path: "remote-model", gpu_layers: None }"#
+        );
+
+        harness.stop().await;
+    });
+}
+
+#[test]
+fn test_warm_models_never_hibernate() {
+    smol::block_on(async {
+        let mut orchestrator = Orchestrator::with_engine(Box::new(MockEngine::new("quick")));
+        orchestrator.set_strategy(CortexStrategy::Immediate);
+        orchestrator.set_keep_warm(["warm".to_string()]);
+        let mut harness = start(orchestrator);
+
+        let infer_with = |model: &str| BrainstemCommand::Infer {
+            model: Some(model.to_string()),
+            prompt: "hello".to_string(),
+            config: InferenceConfig::default(),
+        };
+        let lifecycle = lifecycle_of(&mut harness, infer_with("warm")).await;
+        assert_eq!(lifecycle, vec!["Waking", "Ready"]);
+        // Still loaded, though the strategy unloads at once
+        assert!(lifecycle_of(&mut harness, infer("hello")).await.is_empty());
+
+        // Other models hibernate as usual
+        lifecycle_of(&mut harness, infer_with("cold")).await;
+        let output = harness.next().await;
+        assert!(matches!(output.body, BrainstemBody::Hibernated));
+
+        harness.stop().await;
+    });
+}
+
+// ── States ──
+
+/// Completes at once; panics on the prompt "panic".
+fn fragile_engine() -> MockEngine {
+    MockEngine::new("quick").answer(|prompt| match prompt.text {
+        "panic" => Reply::Panic,
+        _ => Reply::Events(vec![InferenceEvent::Complete]),
+    })
+}
+
+/// Read outputs until the orchestrator enters `until`, returning the states it went through.
+async fn states_until(harness: &mut Harness, until: BrainstemState) -> Vec<BrainstemState> {
+    let mut states = Vec::new();
+    loop {
+        let output = harness.out_rx.next().await.expect("orchestrator stopped");
+        if let BrainstemBody::State { from, to } = output.body {
+            assert_eq!(output.id, None);
+            assert_eq!(states.last().copied().unwrap_or(BrainstemState::Idle), from);
+            states.push(to);
+            if to == until {
+                return states;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_transitions_are_broadcast() {
+    smol::block_on(async {
+        let mut orchestrator = Orchestrator::with_engine(Box::new(fragile_engine()));
+        orchestrator.set_strategy(CortexStrategy::HibernateAfter(Duration::from_millis(100)));
+        let mut harness = start(orchestrator);
+
+        harness.send("request", infer("hello")).await;
+        assert_eq!(
+            states_until(&mut harness, BrainstemState::Hibernated).await,
+            vec![
+                BrainstemState::Loading,
+                BrainstemState::Generating,
+                BrainstemState::Ready,
+                BrainstemState::Hibernated
+            ]
+        );
+
+        harness.stop().await;
+    });
+}
+
+#[test]
+fn test_crash_without_restart_faults() {
+    smol::block_on(async {
+        let mut harness = start_with(fragile_engine());
+
+        harness.send("request", infer("panic")).await;
+        // No factory to restart the engine with
+        states_until(&mut harness, BrainstemState::Faulted).await;
+
+        harness.send("health", BrainstemCommand::Health).await;
+        let health = loop {
+            if let BrainstemBody::Health(health) = harness.next().await.body {
+                break health;
+            }
+        };
+        assert_eq!(health.state, Some(BrainstemState::Faulted));
+        assert!(!health.is_healthy());
+
+        harness.stop().await;
+    });
+}
+
+// ── Crashes ──
+
+/// An orchestrator on an engine that panics on the prompt "panic", and drops the events of
+/// "die" half way, as a worker thread that panicked would; with `restartable` it restarts it.
+struct Crashing {
+    harness: Harness,
+    calls: Calls,
+    builds: Arc<AtomicUsize>,
+    restartable: bool,
+}
+
+fn start_crashing(restartable: bool) -> Crashing {
+    let engine = MockEngine::new("fragile").answer(|prompt| match prompt.text {
+        "panic" => Reply::Panic,
+        "die" => Reply::Events(vec![InferenceEvent::Content(prompt.text.to_string())]),
+        text => Reply::text(&[text]),
+    });
+    let calls = engine.calls();
+    let mut orchestrator = Orchestrator::with_engine(Box::new(engine.clone()));
+    let builds = match restartable {
+        true => engine.restart(&mut orchestrator),
+        false => Arc::default(),
+    };
+    Crashing {
+        harness: start(orchestrator),
+        calls,
+        builds,
+        restartable,
+    }
+}
+
+impl Crashing {
+    /// Send `command` and collect the outputs other than content until it completes or the
+    /// engine restarts (or, when it can't, until it fails).
+    async fn send(&mut self, command: BrainstemCommand) -> Vec<String> {
+        self.harness.send("request", command).await;
+        let mut outputs = Vec::new();
+        loop {
+            let output = self.harness.next().await;
+            let done = match &output.body {
+                BrainstemBody::Event(InferenceEvent::Complete) | BrainstemBody::Restarted => true,
+                BrainstemBody::Error(_) => !self.restartable,
+                _ => false,
+            };
+            if !matches!(
+                output.body,
+                BrainstemBody::Event(InferenceEvent::Content(_))
+            ) {
+                outputs.push(format!("{:?}", output.body));
+            }
+            if done {
+                return outputs;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_panicking_engine_is_restarted_with_its_model() {
+    smol::block_on(async {
+        let mut crashing = start_crashing(true);
+        let load = BrainstemCommand::LoadModel("chat-model".into());
+        crashing.harness.send_unnamed(load).await;
+        crashing.send(infer("hello")).await;
+
+        assert_eq!(
+            crashing.send(infer("panic")).await,
+            vec![
+                r#"Error("The engine crashed")"#,
+                r#"Asset(Loaded { path: "chat-model", gpu_layers: None })"#,
+                "Restarted"
+            ]
+        );
+        assert_eq!(crashing.builds.load(Ordering::SeqCst), 1);
+        assert_eq!(crashing.calls.loads(), vec!["chat-model", "chat-model"]);
+
+        // The new engine serves the retry
+        assert_eq!(crashing.send(infer("hello")).await, vec!["Event(Complete)"]);
+    });
+}
+
+#[test]
+fn test_events_cut_off_restart_the_engine() {
+    smol::block_on(async {
+        let mut crashing = start_crashing(true);
+        assert_eq!(
+            crashing.send(infer("die")).await,
+            vec![
+                "Waking",
+                r#"Asset(Loading("fragile"))"#,
+                r#"Asset(Loaded { path: "fragile", gpu_layers: None })"#,
+                "Ready",
+                r#"Error("The engine crashed")"#,
+                r#"Asset(Loaded { path: "fragile", gpu_layers: None })"#,
+                "Restarted"
+            ]
+        );
+        assert_eq!(crashing.builds.load(Ordering::SeqCst), 1);
+        assert_eq!(crashing.send(infer("hello")).await, vec!["Event(Complete)"]);
+    });
+}
+
+#[test]
+fn test_crash_without_factory_keeps_running() {
+    smol::block_on(async {
+        let mut crashing = start_crashing(false);
+        assert_eq!(
+            crashing.send(infer("panic")).await,
+            vec![
+                "Waking",
+                r#"Asset(Loading("fragile"))"#,
+                r#"Asset(Loaded { path: "fragile", gpu_layers: None })"#,
+                "Ready",
+                r#"Error("The engine crashed")"#
+            ]
+        );
+        assert_eq!(crashing.send(infer("hello")).await, vec!["Event(Complete)"]);
+    });
+}
+
+// ── Health ──
+
+/// Ask an orchestrator on `engine`, which answers with one token or, `broken`, fails every
+/// request, for the engine's health.
+async fn health(engine: MockEngine, broken: bool) -> EngineHealth {
+    let engine = engine.answer(move |_| match broken {
+        true => Reply::Refuse("out of memory".to_string()),
+        false => Reply::text(&["Hi"]),
+    });
+    let mut harness = start_with(engine);
+    harness.send("h1", BrainstemCommand::Health).await;
+    let output = harness.next().await;
+    harness.stop().await;
+    match output.body {
+        BrainstemBody::Health(health) => health,
+        other => panic!("expected a health report, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_health_without_model() {
+    smol::block_on(async {
+        let report = health(MockEngine::new("tiny").local(), true).await;
+        assert!(report.smoke_test.is_none());
+        assert!(report.is_healthy());
+    });
+}
+
+#[test]
+fn test_health_runs_smoke_test() {
+    smol::block_on(async {
+        let report = health(MockEngine::new("tiny").local().loaded(), false).await;
+        assert!(report.smoke_test.as_ref().unwrap().passed);
+        assert!(report.is_healthy());
+
+        let report = health(MockEngine::new("tiny").local().loaded(), true).await;
+        let smoke_test = report.smoke_test.as_ref().unwrap();
+        assert!(!smoke_test.passed);
+        assert_eq!(smoke_test.error.as_deref(), Some("out of memory"));
+        assert!(!report.is_healthy());
+    });
+}
+
+// ── Retries ──
+
+/// An orchestrator retrying twice, after `backoff`, on an engine whose first `failures` loads
+/// fail with `error`.
+fn start_flaky(failures: usize, error: &str, backoff: Duration) -> (Harness, Calls) {
+    let engine = MockEngine::new("flaky").failing_loads(failures, error);
+    let calls = engine.calls();
+    let mut orchestrator = Orchestrator::with_engine(Box::new(engine));
+    orchestrator.set_retry_policy(RetryPolicy {
+        max_retries: 2,
+        backoff,
+    });
+    (start(orchestrator), calls)
+}
+
+/// Run one `Infer` on an engine whose first `failures` loads fail with `error`, returning the
+/// request's `Retrying` attempts, its error if it failed, and the loads tried.
+fn infer_on_flaky(failures: usize, error: &str) -> (Vec<u32>, Option<String>, usize) {
+    smol::block_on(async {
+        let (mut harness, calls) = start_flaky(failures, error, Duration::from_millis(1));
+        harness.send("1", infer("hello")).await;
+        let mut attempts = Vec::new();
+        let error = loop {
+            match harness.next().await.body {
+                BrainstemBody::Retrying {
+                    attempt,
+                    error: retried,
+                    ..
+                } => {
+                    assert_eq!(retried, error);
+                    attempts.push(attempt);
+                }
+                BrainstemBody::Event(InferenceEvent::Complete) => break None,
+                BrainstemBody::Error(e) => break Some(e),
+                _ => {}
+            }
+        };
+
+        harness.stop().await;
+        (attempts, error, calls.loads().len())
+    })
+}
+
+#[test]
+fn test_transient_load_failure_is_retried() {
+    let (attempts, error, loads) = infer_on_flaky(2, "Device or resource busy");
+    assert_eq!(attempts, vec![1, 2]);
+    assert_eq!(error, None);
+    assert_eq!(loads, 3);
+}
+
+#[test]
+fn test_retries_run_out() {
+    let (attempts, error, loads) = infer_on_flaky(5, "CUDA error: out of memory");
+    assert_eq!(attempts, vec![1, 2]);
+    assert!(error.unwrap().contains("out of memory"));
+    assert_eq!(loads, 3);
+}
+
+#[test]
+fn test_lasting_load_failure_is_not_retried() {
+    let (attempts, error, loads) = infer_on_flaky(1, "unknown model architecture");
+    assert!(attempts.is_empty());
+    assert!(error.unwrap().contains("unknown model architecture"));
+    assert_eq!(loads, 1);
+}
+
+#[test]
+fn test_commands_are_served_while_a_load_backs_off() {
+    smol::block_on(async {
+        let (mut harness, calls) =
+            start_flaky(1, "Device or resource busy", Duration::from_secs(3600));
+
+        let load = BrainstemCommand::LoadModel("flaky".to_string());
+        harness.send("1", load).await;
+        loop {
+            if let BrainstemBody::Retrying { .. } = harness.next().await.body {
+                break;
+            }
+        }
+
+        // Answered during the hour-long backoff, which the cancel ends
+        harness
+            .send("2", BrainstemCommand::Cancel { id: "1".into() })
+            .await;
+        loop {
+            let output = harness.next().await;
+            if let BrainstemBody::Cancelled = output.body {
+                assert_eq!(output.id.as_deref(), Some("1"));
+                break;
+            }
+        }
+
+        harness.stop().await;
+        assert_eq!(calls.loads().len(), 1);
+    })
+}
@@ -0,0 +1,257 @@
+mod common;
+
+use common::{start, start_with, Call, MockEngine, Reply};
+use rusty_genius_core::manifest::{InferenceConfig, LoadConfig};
+use rusty_genius_core::protocol::{
+    BrainstemBody, BrainstemCommand, InferenceEvent, ModelDescriptor, ModelInfo,
+};
+use rusty_genius_stem::{CortexStrategy, Orchestrator};
+use std::path::Path;
+
+fn infer_on(model: Option<&str>, config: InferenceConfig) -> BrainstemCommand {
+    BrainstemCommand::Infer {
+        model: model.map(str::to_string),
+        prompt: "Hello".to_string(),
+        config,
+    }
+}
+
+// ── Routing ──
+
+#[test]
+fn test_requests_run_on_the_model_they_name() {
+    smol::block_on(async {
+        // Answers with the name of the model it was loaded with
+        let engine = MockEngine::new("default-model").answer(|prompt| Reply::text(&[prompt.model]));
+        let calls = engine.calls();
+        let mut harness = start_with(engine);
+
+        let requested = [
+            Some("chat-model"),
+            Some("code-model"),
+            None,
+            Some("chat-model"),
+        ];
+        let mut ran_on = Vec::new();
+        for model in requested {
+            harness
+                .send_unnamed(infer_on(model, InferenceConfig::default()))
+                .await;
+            loop {
+                match harness.next().await.body {
+                    BrainstemBody::Event(InferenceEvent::Content(model)) => ran_on.push(model),
+                    BrainstemBody::Event(InferenceEvent::Complete) => break,
+                    BrainstemBody::Error(e) => panic!("{}", e),
+                    _ => {}
+                }
+            }
+        }
+
+        // A request naming no model stays on the last one
+        assert_eq!(
+            ran_on,
+            vec!["chat-model", "code-model", "code-model", "chat-model"]
+        );
+        assert_eq!(
+            calls.loads(),
+            vec!["chat-model", "code-model", "chat-model"]
+        );
+
+        harness.stop().await;
+    });
+}
+
+// ── Listing ──
+
+/// Run `commands` and return the model list the last of them answers with.
+async fn list_models(commands: Vec<BrainstemCommand>) -> Vec<ModelDescriptor> {
+    let engine = MockEngine::new("served-model").info(ModelInfo {
+        architecture: Some("llama".to_string()),
+        ..Default::default()
+    });
+    let mut harness = start_with(engine);
+
+    for command in commands {
+        harness.send_unnamed(command).await;
+    }
+    harness.send("models", BrainstemCommand::ListModels).await;
+    let models = loop {
+        match harness.next().await.body {
+            BrainstemBody::ModelList(models) => break models,
+            BrainstemBody::Error(e) => panic!("{}", e),
+            _ => {}
+        }
+    };
+    harness.stop().await;
+    models
+}
+
+#[test]
+fn test_list_models_includes_loaded_model() {
+    smol::block_on(async {
+        let models = list_models(vec![]).await;
+        assert!(models.iter().all(|m| m.id != "served-model"));
+        assert!(models
+            .iter()
+            .all(|m| !m.tags.contains(&"loaded".to_string())));
+
+        let models = list_models(vec![BrainstemCommand::LoadModel("served-model".into())]).await;
+        let served = models.iter().find(|m| m.id == "served-model").unwrap();
+        assert_eq!(served.tags, vec!["loaded"]);
+        assert_eq!(
+            served.info.as_ref().unwrap().architecture.as_deref(),
+            Some("llama")
+        );
+    });
+}
+
+// ── Resuming ──
+
+/// Run `commands` on a fresh orchestrator remembering its model in `file`, until it has
+/// answered them all and stopped; returns the loads of its engine and how.
+async fn run_remembering(
+    file: &Path,
+    resume: bool,
+    config: LoadConfig,
+    commands: Vec<BrainstemCommand>,
+) -> Vec<(String, LoadConfig)> {
+    let engine = MockEngine::new("default");
+    let calls = engine.calls();
+    let mut orchestrator = Orchestrator::with_engine(Box::new(engine));
+    orchestrator.set_last_model_file(Some(file.to_path_buf()));
+    orchestrator.set_resume(resume);
+    orchestrator.set_load_config(config);
+    let mut harness = start(orchestrator);
+
+    for command in commands {
+        harness.answer("request", command).await;
+    }
+    harness.stop().await;
+    calls
+        .all()
+        .into_iter()
+        .filter_map(|call| match call {
+            Call::Load { model, config } => Some((model, config)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_resume_loads_the_last_model_with_its_config() {
+    smol::block_on(async {
+        let file = common::temp_dir("resume").join("last_model.json");
+        let config = LoadConfig {
+            batch_size: Some(64),
+            ..Default::default()
+        };
+        let named = |model| infer_on(Some(model), InferenceConfig::default());
+
+        // The model used last is the one remembered
+        run_remembering(
+            &file,
+            false,
+            config.clone(),
+            vec![named("first"), named("second")],
+        )
+        .await;
+
+        // Without resume nothing loads until asked
+        let loads = run_remembering(&file, false, LoadConfig::default(), vec![]).await;
+        assert!(loads.is_empty());
+
+        let loads = run_remembering(&file, true, LoadConfig::default(), vec![]).await;
+        assert_eq!(loads, vec![("second".to_string(), config.clone())]);
+
+        // Requests naming no model run on it, without another load
+        let unnamed = infer_on(None, InferenceConfig::default());
+        let loads = run_remembering(&file, true, LoadConfig::default(), vec![unnamed]).await;
+        assert_eq!(loads, vec![("second".to_string(), config)]);
+
+        let _ = std::fs::remove_dir_all(file.parent().unwrap());
+    });
+}
+
+// ── Saved sessions ──
+
+/// Run `commands` on a fresh orchestrator on `engine` saving sessions in `dir`, until it has
+/// answered them all and stopped.
+async fn run_saving(engine: MockEngine, dir: &Path, commands: Vec<BrainstemCommand>) {
+    let mut orchestrator = Orchestrator::with_engine(Box::new(engine));
+    orchestrator.set_strategy(CortexStrategy::KeepAlive);
+    orchestrator.set_session_dir(Some(dir.to_path_buf()));
+    let mut harness = start(orchestrator);
+
+    for command in commands {
+        // A remote engine loads without a word
+        let silent = matches!(command, BrainstemCommand::LoadModel(_));
+        harness.send_unnamed(command).await;
+        if silent {
+            continue;
+        }
+        loop {
+            match harness.next().await.body {
+                BrainstemBody::Event(InferenceEvent::Complete) => break,
+                BrainstemBody::Error(e) => panic!("{}", e),
+                _ => {}
+            }
+        }
+    }
+    harness.stop().await;
+}
+
+fn infer_in(session_id: &str) -> BrainstemCommand {
+    let config = InferenceConfig {
+        session_id: Some(session_id.to_string()),
+        ..Default::default()
+    };
+    infer_on(None, config)
+}
+
+#[test]
+fn test_sessions_survive_restart() {
+    smol::block_on(async {
+        let dir = common::temp_dir("sessions");
+        // Every run on a fresh copy, noting its calls in one place
+        let engine = MockEngine::new("sessions");
+        let calls = engine.calls();
+        let sessions = || {
+            calls
+                .all()
+                .iter()
+                .filter(|call| matches!(call, Call::SaveSession(_) | Call::LoadSession { .. }))
+                .map(Call::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        run_saving(
+            engine.clone(),
+            &dir,
+            vec![
+                BrainstemCommand::LoadModel("org/model".into()),
+                infer_in("chat/1"),
+            ],
+        )
+        .await;
+        let file = dir.join("org_model").join("chat_1.session");
+        assert!(file.exists());
+        assert_eq!(sessions(), vec!["save chat/1"]);
+
+        // The saved session is handed back once, at its first request after the restart
+        run_saving(
+            engine.clone(),
+            &dir,
+            vec![
+                BrainstemCommand::LoadModel("org/model".into()),
+                infer_in("chat/1"),
+                infer_in("chat/1"),
+                BrainstemCommand::CloseSession("chat/1".into()),
+            ],
+        )
+        .await;
+        assert_eq!(sessions(), vec!["save chat/1", "load chat/1 from chat/1"]);
+        assert!(!file.exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    });
+}
@@ -0,0 +1,405 @@
+mod common;
+
+use common::{infer, start, start_with, MockEngine, Reply};
+use rusty_genius_core::manifest::{BenchConfig, InferenceConfig};
+use rusty_genius_core::protocol::{
+    BrainstemBody, BrainstemCommand, ChatMessage, InferenceEvent, TokenUsage,
+};
+use rusty_genius_stem::audit::{AuditRecord, AuditStatus};
+use rusty_genius_stem::replay::read_recording;
+use rusty_genius_stem::{AuditLog, Orchestrator, OrchestratorObserver, Recorder, ReplayEngine};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Answers "ok" with `prompt_tokens` prompt and `completion_tokens` generated tokens; fails
+/// on the prompt "fail".
+fn usage_engine(name: &str, prompt_tokens: usize, completion_tokens: usize) -> MockEngine {
+    MockEngine::new(name).answer(move |prompt| match prompt.text {
+        "fail" => Reply::Fail("cannot answer".to_string()),
+        _ => Reply::Events(vec![
+            InferenceEvent::Content("ok".to_string()),
+            InferenceEvent::Usage(TokenUsage {
+                prompt_tokens,
+                completion_tokens,
+                ..Default::default()
+            }),
+            InferenceEvent::Complete,
+        ]),
+    })
+}
+
+// ── Audit log ──
+
+fn log_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rusty-genius-audit-{}", std::process::id()));
+    let path = dir.join(name);
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn read_records(path: &Path) -> Vec<AuditRecord> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+/// Run `commands` one after another, each as the request named with it, waiting for each
+/// to end.
+async fn run_audited(log: AuditLog, commands: Vec<(&str, BrainstemCommand)>) {
+    let mut orchestrator = Orchestrator::with_engine(Box::new(usage_engine("usage", 5, 2)));
+    orchestrator.set_audit_log(Some(log));
+    let mut harness = start(orchestrator);
+
+    for (id, command) in commands {
+        harness.send(id, command).await;
+        loop {
+            let output = harness.next().await;
+            if output.id.as_deref() != Some(id) {
+                continue;
+            }
+            if common::is_done(&output.body) || matches!(output.body, BrainstemBody::Metrics(_)) {
+                break;
+            }
+        }
+    }
+    harness.stop().await;
+}
+
+#[test]
+fn test_audit_records_commands_and_outcomes() {
+    smol::block_on(async {
+        let path = log_path("audit.jsonl");
+        let commands = vec![
+            ("answer", infer("hello")),
+            ("broken", infer("fail")),
+            ("metrics", BrainstemCommand::GetMetrics),
+        ];
+        run_audited(AuditLog::new(&path), commands).await;
+
+        let records = read_records(&path);
+        assert_eq!(records.len(), 3, "{:?}", records);
+        let answer = &records[0];
+        assert_eq!(
+            (answer.id.as_str(), answer.command.as_str()),
+            ("answer", "infer")
+        );
+        assert_eq!(answer.model.as_deref(), Some("usage"));
+        assert_eq!(answer.prompts, vec!["hello".to_string()]);
+        assert_eq!((answer.prompt_tokens, answer.completion_tokens), (5, 2));
+        assert_eq!(answer.status, AuditStatus::Ok);
+
+        assert_eq!(records[1].status, AuditStatus::Error);
+        assert_eq!(records[1].error.as_deref(), Some("cannot answer"));
+
+        assert_eq!(records[2].command, "get_metrics");
+        assert_eq!(records[2].model, None);
+        assert_eq!(records[2].status, AuditStatus::Ok);
+    });
+}
+
+#[test]
+fn test_audit_redacts_prompts_and_rotates() {
+    smol::block_on(async {
+        let path = log_path("rotated.jsonl");
+        let log = AuditLog::new(&path).redact_prompts(true).max_bytes(300);
+        let _ = std::fs::remove_file(log.rotated_path());
+        let commands = vec![
+            ("one", infer("secret")),
+            ("two", infer("secret")),
+            ("three", infer("secret")),
+        ];
+        run_audited(log.clone(), commands).await;
+
+        let old = read_records(&log.rotated_path());
+        let new = read_records(&path);
+        assert!(!old.is_empty() && !new.is_empty());
+        assert_eq!(old.len() + new.len(), 3);
+        assert_eq!(new.last().unwrap().id, "three");
+        for record in old.iter().chain(&new) {
+            assert!(record.prompts.is_empty());
+        }
+        assert!(std::fs::metadata(&path).unwrap().len() <= 300);
+    });
+}
+
+// ── Metrics ──
+
+#[test]
+fn test_metrics_count_commands_tokens_and_reloads() {
+    smol::block_on(async {
+        let engine = usage_engine("counting", 7, 3).embeds(|_| vec![1.0]);
+        let mut harness = start_with(engine);
+
+        let embed = BrainstemCommand::Embed {
+            model: None,
+            input: "text".to_string(),
+            config: InferenceConfig::default(),
+        };
+        for command in [infer("hello"), infer("hello"), embed] {
+            harness.answer("request", command).await;
+        }
+
+        harness.send("metrics", BrainstemCommand::GetMetrics).await;
+        let metrics = loop {
+            if let BrainstemBody::Metrics(metrics) = harness.next().await.body {
+                break metrics;
+            }
+        };
+        assert_eq!(metrics.commands["infer"].count, 2);
+        assert_eq!(metrics.commands["embed"].count, 1);
+        assert!(!metrics.commands.contains_key("get_metrics"));
+        assert_eq!(metrics.prompt_tokens, 14);
+        assert_eq!(metrics.generated_tokens, 6);
+        // Only the first request loaded the model
+        assert_eq!(metrics.cold_reloads, 1);
+
+        harness.stop().await;
+    });
+}
+
+#[test]
+fn test_batch_is_timed_until_its_last_prompt() {
+    smol::block_on(async {
+        let engine = MockEngine::new("held").answer(|prompt| match prompt.text {
+            "hold" => Reply::Held,
+            text => Reply::text(&[text]),
+        });
+        let held = engine.held();
+        let mut orchestrator = Orchestrator::with_engine(Box::new(engine));
+        orchestrator.set_max_parallel(2);
+        let mut harness = start(orchestrator);
+
+        let batch = BrainstemCommand::InferBatch {
+            model: None,
+            prompts: vec!["quick".to_string(), "hold".to_string()],
+            config: InferenceConfig::default(),
+        };
+        harness.send("batch", batch).await;
+        // The quick prompt is done long before the batch
+        loop {
+            let body = harness.next().await.body;
+            if let BrainstemBody::Batch {
+                index: 1,
+                event: InferenceEvent::Content(_),
+            } = body
+            {
+                break;
+            }
+        }
+        smol::Timer::after(Duration::from_millis(50)).await;
+        held.finish();
+        harness.until_done("batch").await;
+
+        harness.send("metrics", BrainstemCommand::GetMetrics).await;
+        let metrics = loop {
+            if let BrainstemBody::Metrics(metrics) = harness.next().await.body {
+                break metrics;
+            }
+        };
+        let batches = &metrics.commands["infer_batch"];
+        assert_eq!(batches.count, 1);
+        assert!(batches.sum >= 0.05, "{}", batches.sum);
+
+        harness.stop().await;
+    });
+}
+
+// ── Observers ──
+
+/// Notes every call, in order.
+#[derive(Default)]
+struct Observed {
+    calls: Mutex<Vec<String>>,
+}
+
+impl OrchestratorObserver for Observed {
+    fn on_command(&self, id: &str, command: &BrainstemCommand) {
+        let call = format!("command {} {}", id, command.name());
+        self.calls.lock().unwrap().push(call);
+    }
+
+    fn on_token(&self, id: &str, text: &str) {
+        let call = format!("token {} {}", id, text);
+        self.calls.lock().unwrap().push(call);
+    }
+
+    fn on_complete(&self, id: &str) {
+        self.calls.lock().unwrap().push(format!("complete {}", id));
+    }
+
+    fn on_error(&self, id: Option<&str>, error: &str) {
+        let call = format!("error {} {}", id.unwrap_or("-"), error);
+        self.calls.lock().unwrap().push(call);
+    }
+}
+
+#[test]
+fn test_observer_follows_requests() {
+    smol::block_on(async {
+        // Answers in two tokens; refuses the prompt "fail"
+        let engine = MockEngine::new("two").answer(|prompt| match prompt.text {
+            "fail" => Reply::Refuse("cannot answer".to_string()),
+            _ => Reply::text(&["Hel", "lo"]),
+        });
+        let observed = Arc::new(Observed::default());
+        let mut orchestrator = Orchestrator::with_engine(Box::new(engine));
+        orchestrator.add_observer(observed.clone());
+        let mut harness = start(orchestrator);
+
+        harness.answer("a", infer("hello")).await;
+        harness.send("b", infer("fail")).await;
+        harness.until_done("b").await;
+
+        assert_eq!(
+            *observed.calls.lock().unwrap(),
+            vec![
+                "command a infer",
+                "token a Hel",
+                "token a lo",
+                "complete a",
+                "command b infer",
+                "error b cannot answer",
+            ]
+        );
+
+        harness.stop().await;
+    });
+}
+
+// ── Recording and replay ──
+
+fn commands() -> Vec<BrainstemCommand> {
+    let chat = |content: &str| BrainstemCommand::Chat {
+        session_id: "session".to_string(),
+        message: ChatMessage::user(content),
+        config: InferenceConfig::default(),
+    };
+    vec![
+        infer("hello"),
+        BrainstemCommand::Embed {
+            model: None,
+            input: "some text".to_string(),
+            config: InferenceConfig::default(),
+        },
+        chat("Hi there"),
+        chat("And again"),
+    ]
+}
+
+/// Send each of `commands` in turn, returning the events each was answered with.
+async fn session(orchestrator: Orchestrator) -> Vec<Vec<InferenceEvent>> {
+    let mut harness = start(orchestrator);
+
+    let mut answers = Vec::new();
+    for (n, command) in commands().into_iter().enumerate() {
+        let id = format!("request-{}", n);
+        harness.send(&id, command).await;
+        let mut events = Vec::new();
+        for body in harness.until_done(&id).await {
+            match body {
+                BrainstemBody::Event(InferenceEvent::Complete) => {}
+                BrainstemBody::Event(event) => events.push(event),
+                BrainstemBody::Error(e) => panic!("{}", e),
+                _ => {}
+            }
+        }
+        answers.push(events);
+    }
+    harness.stop().await;
+    answers
+}
+
+#[test]
+fn test_replay_answers_as_recorded() {
+    smol::block_on(async {
+        let path = std::env::temp_dir().join(format!(
+            "rusty-genius-replay-test-{}.jsonl",
+            std::process::id()
+        ));
+        // Counts its answers, so a replay can't be told apart only by chance
+        let engine = MockEngine::new("counting")
+            .answer(|prompt| {
+                let answer = format!("answer {} to {} chars", prompt.count, prompt.text.len());
+                Reply::text(&[&answer])
+            })
+            .embeds(|input| vec![input.len() as f32, 1.0]);
+        let mut orchestrator = Orchestrator::with_engine(Box::new(engine));
+        orchestrator.add_observer(Arc::new(Recorder::create(&path).unwrap()));
+        let recorded = session(orchestrator).await;
+
+        let recording = read_recording(&path).unwrap();
+        assert!(!recording.is_empty());
+        let engine = ReplayEngine::open(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let replayed = session(Orchestrator::with_engine(Box::new(engine))).await;
+        assert_eq!(format!("{:?}", replayed), format!("{:?}", recorded));
+    });
+}
+
+#[test]
+fn test_unrecorded_prompt_fails() {
+    smol::block_on(async {
+        let engine = ReplayEngine::from_recording(Vec::new());
+        let mut harness = start(Orchestrator::with_engine(Box::new(engine)));
+
+        harness.send("new", commands().remove(0)).await;
+        let error = loop {
+            if let BrainstemBody::Error(e) = harness.next().await.body {
+                break e;
+            }
+        };
+        assert!(error.contains("No recorded answer"), "{}", error);
+
+        harness.stop().await;
+    });
+}
+
+// ── Benchmarks ──
+
+/// Send one Bench request to an orchestrator on `engine` and return the response.
+async fn bench(engine: MockEngine, config: BenchConfig) -> BrainstemBody {
+    let mut harness = start_with(engine);
+    let command = BrainstemCommand::Bench {
+        model: None,
+        config,
+    };
+    harness.send("b1", command).await;
+    let output = harness.next().await;
+    assert_eq!(output.id.as_deref(), Some("b1"));
+    harness.stop().await;
+    output.body
+}
+
+#[test]
+fn test_bench_returns_report() {
+    smol::block_on(async {
+        let config = BenchConfig {
+            prompt_tokens: 64,
+            generated_tokens: 16,
+            repetitions: 1,
+        };
+        let engine = MockEngine::new("bench").local().loaded().benches();
+        match bench(engine, config).await {
+            BrainstemBody::Bench(report) => {
+                assert_eq!(report.prompt_tokens_per_sec, 64.0);
+                assert_eq!(report.tokens_per_sec, 16.0);
+                assert_eq!(report.repetitions, 1);
+            }
+            other => panic!("expected a bench report, got {:?}", other),
+        }
+    });
+}
+
+#[test]
+fn test_bench_unsupported_engine_errors() {
+    smol::block_on(async {
+        let engine = MockEngine::new("bench").local().loaded();
+        let body = bench(engine, BenchConfig::default()).await;
+        assert!(
+            matches!(body, BrainstemBody::Error(e) if e.contains("does not support benchmarking"))
+        );
+    });
+}
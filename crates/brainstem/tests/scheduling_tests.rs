@@ -0,0 +1,431 @@
+mod common;
+
+use common::{infer, input, start, start_with, Call, Harness, MockEngine, Reply};
+use futures::sink::SinkExt;
+use futures::StreamExt;
+use rusty_genius_core::manifest::InferenceConfig;
+use rusty_genius_core::protocol::{
+    BrainstemBody, BrainstemCommand, BrainstemInput, InferenceEvent,
+};
+use rusty_genius_stem::{Orchestrator, QueueFull};
+use std::collections::{HashMap, HashSet};
+
+fn cancel(id: &str) -> BrainstemCommand {
+    BrainstemCommand::Cancel { id: id.to_string() }
+}
+
+// ── Cancellation ──
+
+/// Read outputs until `id` is cancelled, noting the requests that streamed in `streamed`.
+async fn until_cancelled(harness: &mut Harness, id: &str, streamed: &mut HashSet<String>) {
+    loop {
+        let output = harness.next().await;
+        let output_id = output.id.unwrap_or_default();
+        match output.body {
+            BrainstemBody::Cancelled if output_id == id => return,
+            BrainstemBody::Event(InferenceEvent::Content(_)) => {
+                streamed.insert(output_id);
+            }
+            BrainstemBody::Error(e) => panic!("{}", e),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_cancel_running_and_queued_requests() {
+    smol::block_on(async {
+        let engine = MockEngine::new("endless").answer(|_| Reply::Endless);
+        let calls = engine.calls();
+        let mut orchestrator = Orchestrator::with_engine(Box::new(engine));
+        orchestrator.set_max_parallel(1);
+        let mut harness = start(orchestrator);
+
+        // "next" and "queued" wait behind "first"; "queued" never starts
+        for id in ["first", "next", "queued"] {
+            harness.send(id, infer(id)).await;
+        }
+        harness.send("cancel-queued", cancel("queued")).await;
+        let mut streamed = HashSet::new();
+        until_cancelled(&mut harness, "queued", &mut streamed).await;
+
+        harness.send("cancel-first", cancel("first")).await;
+        until_cancelled(&mut harness, "first", &mut streamed).await;
+
+        // Once "next" is streaming
+        while harness.out_rx.next().await.unwrap().id.as_deref() != Some("next") {}
+        harness.send("cancel-next", cancel("next")).await;
+        until_cancelled(&mut harness, "next", &mut streamed).await;
+        assert!(!streamed.contains("queued"));
+
+        let generations: Vec<_> = calls
+            .all()
+            .into_iter()
+            .filter_map(|call| match call {
+                Call::Infer { prompt, cancel, .. } => Some((prompt, cancel)),
+                _ => None,
+            })
+            .collect();
+        let prompts: Vec<&str> = generations.iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(prompts, vec!["first", "next"]);
+        assert!(generations.iter().all(|(_, token)| token.is_cancelled()));
+
+        harness.send("cancel-first", cancel("first")).await;
+        let output = harness.next().await;
+        assert_eq!(output.id.as_deref(), Some("cancel-first"));
+        assert!(matches!(output.body, BrainstemBody::Error(_)));
+
+        harness.stop().await;
+    });
+}
+
+// ── Requests side by side ──
+
+/// Generates until cancelled and embeds at once.
+fn endless_engine() -> MockEngine {
+    MockEngine::new("first-model")
+        .answer(|_| Reply::Endless)
+        .embeds(|_| vec![1.0])
+}
+
+/// Read outputs until one for `id` matches `done`.
+async fn wait_for(harness: &mut Harness, id: &str, done: impl Fn(&BrainstemBody) -> bool) {
+    loop {
+        let output = harness.next().await;
+        if let BrainstemBody::Error(e) = &output.body {
+            panic!("{}", e);
+        }
+        if output.id.as_deref() == Some(id) && done(&output.body) {
+            return;
+        }
+    }
+}
+
+fn streaming(body: &BrainstemBody) -> bool {
+    matches!(body, BrainstemBody::Event(InferenceEvent::Content(_)))
+}
+
+#[test]
+fn test_requests_run_alongside_a_generation() {
+    smol::block_on(async {
+        let engine = endless_engine();
+        let calls = engine.calls();
+        let mut harness = start_with(engine);
+
+        // An embedding and the model list are answered while "slow" generates
+        harness.send("slow", infer("slow")).await;
+        let embed = BrainstemCommand::Embed {
+            model: None,
+            input: "text".to_string(),
+            config: InferenceConfig::default(),
+        };
+        harness.send("embed", embed).await;
+        wait_for(&mut harness, "embed", |body| {
+            matches!(body, BrainstemBody::Event(InferenceEvent::Complete))
+        })
+        .await;
+        harness.send("models", BrainstemCommand::ListModels).await;
+        wait_for(&mut harness, "models", |body| {
+            matches!(body, BrainstemBody::ModelList(_))
+        })
+        .await;
+
+        // Loading waits for "slow" to finish, and "after" for the load
+        let load = BrainstemCommand::LoadModel("second-model".to_string());
+        harness.send("load", load).await;
+        harness.send("after", infer("after")).await;
+        harness.send("cancel", cancel("slow")).await;
+        wait_for(&mut harness, "after", streaming).await;
+
+        assert_eq!(
+            calls.describe(),
+            vec![
+                "load first-model",
+                "infer slow",
+                "embed text",
+                "load second-model",
+                "infer after",
+            ]
+        );
+
+        harness.send("cancel", cancel("after")).await;
+        harness.stop().await;
+    });
+}
+
+#[test]
+fn test_parallel_limit_queues_requests() {
+    smol::block_on(async {
+        let engine = endless_engine();
+        let calls = engine.calls();
+        let mut orchestrator = Orchestrator::with_engine(Box::new(engine));
+        orchestrator.set_max_parallel(2);
+        let mut harness = start(orchestrator);
+
+        for id in ["one", "two", "three"] {
+            harness.send(id, infer(id)).await;
+        }
+        wait_for(&mut harness, "two", streaming).await;
+        assert_eq!(
+            calls.describe(),
+            vec!["load first-model", "infer one", "infer two"]
+        );
+
+        // "three" starts once "one" makes room
+        harness.send("cancel", cancel("one")).await;
+        wait_for(&mut harness, "three", streaming).await;
+        assert_eq!(calls.describe().last().unwrap(), "infer three");
+
+        for id in ["two", "three"] {
+            harness.send("cancel", cancel(id)).await;
+        }
+        harness.stop().await;
+    });
+}
+
+// ── The queue ──
+
+/// An orchestrator streaming one request at a time, which keeps streaming until the test
+/// finishes it, with room for one more in its queue.
+fn start_held(when_full: QueueFull) -> (Harness, common::Held) {
+    let engine = MockEngine::new("held").answer(|_| Reply::Held);
+    let held = engine.held();
+    let mut orchestrator = Orchestrator::with_engine(Box::new(engine));
+    orchestrator.set_max_parallel(1);
+    orchestrator.set_max_queue(1, when_full);
+    (start(orchestrator), held)
+}
+
+/// The next output other than the engine's lifecycle and model load, as "id body".
+async fn next_described(harness: &mut Harness) -> String {
+    loop {
+        let output = harness.next().await;
+        match (output.id, output.body) {
+            (None, _) | (_, BrainstemBody::Asset(_)) => {}
+            (Some(id), body) => return format!("{} {:?}", id, body),
+        }
+    }
+}
+
+#[test]
+fn test_full_queue_rejects_new_requests() {
+    smol::block_on(async {
+        let (mut harness, held) = start_held(QueueFull::Reject);
+        harness.send("a", infer("hello")).await;
+        assert_eq!(
+            next_described(&mut harness).await,
+            r#"a Event(Content("started"))"#
+        );
+
+        harness.send("b", infer("hello")).await;
+        assert_eq!(
+            next_described(&mut harness).await,
+            "b Busy { queue_position: 1, estimated_wait: None }"
+        );
+        harness.send("c", infer("hello")).await;
+        assert_eq!(
+            next_described(&mut harness).await,
+            r#"c Error("The queue is full (1 waiting)")"#
+        );
+
+        // The queued request starts once the first finishes
+        held.finish();
+        assert_eq!(next_described(&mut harness).await, "a Event(Complete)");
+        assert_eq!(
+            next_described(&mut harness).await,
+            r#"b Event(Content("started"))"#
+        );
+    });
+}
+
+#[test]
+fn test_full_queue_can_drop_the_oldest_request() {
+    smol::block_on(async {
+        let (mut harness, held) = start_held(QueueFull::DropOldest);
+        harness.send("a", infer("hello")).await;
+        assert_eq!(
+            next_described(&mut harness).await,
+            r#"a Event(Content("started"))"#
+        );
+
+        harness.send("b", infer("hello")).await;
+        assert!(next_described(&mut harness).await.starts_with("b Busy"));
+        harness.send("c", infer("hello")).await;
+        assert_eq!(
+            next_described(&mut harness).await,
+            r#"b Error("The queue is full (1 waiting)")"#
+        );
+        assert!(next_described(&mut harness)
+            .await
+            .starts_with("c Busy { queue_position: 1"));
+
+        held.finish();
+        assert_eq!(next_described(&mut harness).await, "a Event(Complete)");
+        assert_eq!(
+            next_described(&mut harness).await,
+            r#"c Event(Content("started"))"#
+        );
+    });
+}
+
+// ── Output numbering ──
+
+/// Streams a few words for every prompt but `fail`, which it refuses.
+fn words_engine() -> MockEngine {
+    MockEngine::new("words").answer(|prompt| match prompt.text {
+        "fail" => Reply::Refuse("The prompt failed".to_string()),
+        _ => Reply::text(&["one ", "two ", "three"]),
+    })
+}
+
+#[test]
+fn test_outputs_are_numbered_per_request() {
+    smol::block_on(async {
+        let mut harness = start_with(words_engine());
+
+        // Two requests side by side, then the first id again
+        for id in ["a", "b"] {
+            harness.send(id, infer("count")).await;
+        }
+        let mut seqs: HashMap<Option<String>, Vec<u64>> = HashMap::new();
+        let mut completed = 0;
+        while completed < 2 {
+            let output = harness.out_rx.next().await.unwrap();
+            if matches!(output.body, BrainstemBody::Event(InferenceEvent::Complete)) {
+                completed += 1;
+            }
+            seqs.entry(output.id).or_default().push(output.seq);
+        }
+        for id in ["a", "b"] {
+            let seqs = &seqs[&Some(id.to_string())];
+            let expected: Vec<u64> = (0..seqs.len() as u64).collect();
+            assert_eq!(seqs, &expected, "outputs of {}", id);
+        }
+        // Lifecycle outputs are numbered together
+        let lifecycle = &seqs[&None];
+        let expected: Vec<u64> = (0..lifecycle.len() as u64).collect();
+        assert_eq!(lifecycle, &expected);
+
+        harness.send("a", infer("count")).await;
+        let output = loop {
+            let output = harness.out_rx.next().await.unwrap();
+            if output.id.is_some() {
+                break output;
+            }
+        };
+        assert_eq!(output.id.as_deref(), Some("a"));
+        assert_eq!(output.seq, 0);
+
+        harness.stop().await;
+    });
+}
+
+#[test]
+fn test_numbering_starts_over_after_an_error() {
+    smol::block_on(async {
+        let mut harness = start_with(words_engine());
+
+        // The id of a request that failed may be used again, as one that completed may
+        let mut first = Vec::new();
+        for prompt in ["fail", "count"] {
+            harness.send("a", infer(prompt)).await;
+            let mut seqs = Vec::new();
+            loop {
+                let output = harness.out_rx.next().await.unwrap();
+                if output.id.is_none() {
+                    continue;
+                }
+                seqs.push(output.seq);
+                if common::is_done(&output.body) {
+                    break;
+                }
+            }
+            first.push(seqs[0]);
+        }
+        assert_eq!(first, vec![0, 0]);
+
+        harness.stop().await;
+    });
+}
+
+// ── Request ids ──
+
+/// Send `inputs` and close the inputs, returning the outputs `keep` picks as "id body".
+async fn outputs_of(
+    inputs: Vec<BrainstemInput>,
+    keep: impl Fn(&BrainstemBody) -> bool,
+) -> Vec<String> {
+    let mut harness = start_with(MockEngine::new("echo"));
+    for input in inputs {
+        harness.in_tx.send(input).await.unwrap();
+    }
+    drop(harness.in_tx);
+
+    let mut outputs = Vec::new();
+    while let Some(output) = harness.out_rx.next().await {
+        if keep(&output.body) {
+            outputs.push(format!("{} {:?}", output.id.unwrap(), output.body));
+        }
+    }
+    outputs
+}
+
+fn unnamed(command: BrainstemCommand) -> BrainstemInput {
+    BrainstemInput { id: None, command }
+}
+
+#[test]
+fn test_commands_without_an_id_are_given_one() {
+    smol::block_on(async {
+        let inputs = vec![
+            unnamed(infer("first")),
+            unnamed(infer("second")),
+            input("mine", infer("third")),
+        ];
+        let mut outputs = outputs_of(inputs, |body| {
+            matches!(
+                body,
+                BrainstemBody::Accepted | BrainstemBody::Event(InferenceEvent::Content(_))
+            )
+        })
+        .await;
+
+        outputs.sort();
+        assert_eq!(
+            outputs,
+            vec![
+                "anon-1 Accepted",
+                r#"anon-1 Event(Content("first"))"#,
+                "anon-2 Accepted",
+                r#"anon-2 Event(Content("second"))"#,
+                r#"mine Event(Content("third"))"#,
+            ]
+        );
+    });
+}
+
+#[test]
+fn test_ids_like_minted_ones_are_refused() {
+    smol::block_on(async {
+        // Would answer for the command minted "anon-1" after it
+        let inputs = vec![input("anon-1", infer("mine")), unnamed(infer("minted"))];
+        let outputs = outputs_of(inputs, |body| {
+            matches!(
+                body,
+                BrainstemBody::Accepted
+                    | BrainstemBody::Error(_)
+                    | BrainstemBody::Event(InferenceEvent::Content(_))
+            )
+        })
+        .await;
+
+        assert_eq!(
+            outputs,
+            vec![
+                r#"anon-1 Error("Request ids beginning with 'anon-' are kept for the ids the orchestrator mints")"#,
+                "anon-1 Accepted",
+                r#"anon-1 Event(Content("minted"))"#,
+            ]
+        );
+    });
+}
@@ -0,0 +1,198 @@
+mod common;
+
+use common::{MockEngine, Reply};
+use futures::io::Cursor;
+use futures::{stream, StreamExt, TryStreamExt};
+use rusty_genius_core::manifest::InferenceConfig;
+use rusty_genius_core::protocol::{BrainstemBody, BrainstemOutput, InferenceEvent};
+use rusty_genius_stem::Orchestrator;
+
+// ── IPC ──
+
+#[cfg(unix)]
+mod ipc {
+    use super::common::{self, Calls, MockEngine};
+    use futures::channel::mpsc;
+    use futures::sink::SinkExt;
+    use futures::StreamExt;
+    use rusty_genius_core::protocol::{
+        BrainstemBody, BrainstemCommand, BrainstemInput, BrainstemOutput, InferenceEvent,
+    };
+    use rusty_genius_stem::{ipc, Orchestrator};
+    use smol::net::unix::UnixStream;
+
+    struct Client {
+        in_tx: mpsc::Sender<BrainstemInput>,
+        out_rx: mpsc::Receiver<BrainstemOutput>,
+    }
+
+    impl Client {
+        /// Infer `prompt` as `id` and return the answer's id and text once it completes.
+        async fn infer(&mut self, id: Option<&str>, prompt: &str) -> (String, String) {
+            let input = BrainstemInput {
+                id: id.map(str::to_string),
+                command: common::infer(prompt),
+            };
+            self.in_tx.send(input).await.unwrap();
+            let mut text = String::new();
+            loop {
+                let output = self.out_rx.next().await.expect("daemon closed");
+                let Some(output_id) = output.id else {
+                    continue;
+                };
+                match output.body {
+                    BrainstemBody::Event(InferenceEvent::Content(piece)) => text.push_str(&piece),
+                    BrainstemBody::Event(InferenceEvent::Complete) => return (output_id, text),
+                    BrainstemBody::Error(e) => panic!("{}", e),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Start a daemon on an echoing engine, returning the sender of its connections and the
+    /// engine's calls.
+    fn start() -> (mpsc::UnboundedSender<UnixStream>, Calls) {
+        let engine = MockEngine::new("echo");
+        let calls = engine.calls();
+        let mut orchestrator = Orchestrator::with_engine(Box::new(engine));
+        let (connections_tx, connections) = mpsc::unbounded();
+        smol::spawn(async move { orchestrator.run_daemon(connections).await }).detach();
+        (connections_tx, calls)
+    }
+
+    fn connect(connections: &mpsc::UnboundedSender<UnixStream>) -> Client {
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        connections.unbounded_send(theirs).unwrap();
+        let (in_tx, out_rx, carry) = ipc::connect(ours);
+        smol::spawn(carry).detach();
+        Client { in_tx, out_rx }
+    }
+
+    #[test]
+    fn test_clients_share_one_model() {
+        smol::block_on(async {
+            let (connections, calls) = start();
+            let mut first = connect(&connections);
+            let mut second = connect(&connections);
+
+            // Each sees its own ids, even when they clash
+            assert_eq!(
+                first.infer(Some("x"), "one").await,
+                ("x".to_string(), "one".to_string())
+            );
+            assert_eq!(
+                second.infer(Some("x"), "two").await,
+                ("x".to_string(), "two".to_string())
+            );
+            assert_eq!(calls.loads().len(), 1);
+
+            // Ids minted for commands sent without reach the client that sent them
+            let (id, text) = second.infer(None, "three").await;
+            assert!(id.starts_with("anon-"), "{}", id);
+            assert_eq!(text, "three");
+
+            // A client that went doesn't hold the others up
+            drop(first);
+            assert_eq!(second.infer(Some("y"), "four").await.1, "four");
+        });
+    }
+
+    #[test]
+    fn test_frames_round_trip() {
+        smol::block_on(async {
+            let (mut ours, mut theirs) = UnixStream::pair().unwrap();
+            let input = common::input("a", BrainstemCommand::ListModels);
+            ipc::write_frame(&mut ours, &input).await.unwrap();
+            drop(ours);
+            let read: BrainstemInput = ipc::read_frame(&mut theirs).await.unwrap().unwrap();
+            assert_eq!(read.id.as_deref(), Some("a"));
+            assert!(matches!(read.command, BrainstemCommand::ListModels));
+            // Closed between frames
+            assert!(ipc::read_frame::<_, BrainstemInput>(&mut theirs)
+                .await
+                .unwrap()
+                .is_none());
+        });
+    }
+}
+
+// ── JSON lines ──
+
+/// An orchestrator answering every prompt with "hi".
+fn hi_orchestrator() -> Orchestrator {
+    let engine = MockEngine::new("hi").answer(|_| Reply::text(&["hi"]));
+    Orchestrator::with_engine(Box::new(engine))
+}
+
+fn infer_line(id: &str) -> String {
+    let config = serde_json::to_value(InferenceConfig::default()).unwrap();
+    serde_json::json!({
+        "id": id,
+        "command": {"Infer": {"model": null, "prompt": "hello", "config": config}}
+    })
+    .to_string()
+}
+
+fn parse(written: &[u8]) -> Vec<BrainstemOutput> {
+    String::from_utf8(written.to_vec())
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_json_lines_in_and_out() {
+    smol::block_on(async {
+        let mut orchestrator = hi_orchestrator();
+        let input = format!("{}\n\nnot an input\n{}\n", infer_line("a"), infer_line("b"));
+        let mut written = Vec::new();
+        orchestrator
+            .run_json_lines(Cursor::new(input.into_bytes()), &mut written)
+            .await
+            .unwrap();
+
+        let outputs = parse(&written);
+        for id in ["a", "b"] {
+            let text: String = outputs
+                .iter()
+                .filter(|output| output.id.as_deref() == Some(id))
+                .filter_map(|output| match &output.body {
+                    BrainstemBody::Event(InferenceEvent::Content(text)) => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(text, "hi", "answer to {}", id);
+            assert!(outputs.iter().any(|output| output.id.as_deref() == Some(id)
+                && matches!(output.body, BrainstemBody::Event(InferenceEvent::Complete))));
+        }
+        // The bad line is only reported on stderr
+        assert!(!outputs
+            .iter()
+            .any(|output| matches!(output.body, BrainstemBody::Error(_))));
+    });
+}
+
+#[test]
+fn test_stop_ends_run_with_input_still_open() {
+    smol::block_on(async {
+        let mut orchestrator = hi_orchestrator();
+        let lines = format!(
+            "{}\n{{\"id\":\"s\",\"command\":\"Stop\"}}\n",
+            infer_line("a")
+        );
+        // Never ends, as a host's stdin that stays open
+        let reader = stream::iter([Ok::<_, std::io::Error>(lines.into_bytes())])
+            .chain(stream::pending())
+            .into_async_read();
+        let mut written = Vec::new();
+        orchestrator
+            .run_json_lines(reader, &mut written)
+            .await
+            .unwrap();
+        assert!(parse(&written)
+            .iter()
+            .any(|output| output.id.as_deref() == Some("a")));
+    });
+}
@@ -3,7 +3,7 @@
 use futures::channel::mpsc;
 use futures::sink::SinkExt;
 use futures::StreamExt;
-use rusty_genius_core::engine::Engine;
+use rusty_genius_core::engine::{CancellationToken, Engine};
 use rusty_genius_core::manifest::InferenceConfig;
 use rusty_genius_core::protocol::{
     BrainstemBody, BrainstemCommand, BrainstemInput, BrainstemOutput, InferenceEvent,
@@ -50,7 +50,10 @@ fn test_wllama_engine_infer_streaming() {
         engine.load_model("test-model").await.unwrap();
 
         let config = InferenceConfig::default();
-        let mut rx = engine.infer("hello world test", config).await.unwrap();
+        let mut rx = engine
+            .infer("hello world test", config, CancellationToken::new())
+            .await
+            .unwrap();
 
         let mut events = vec![];
         while let Some(event) = rx.next().await {
@@ -100,7 +103,10 @@ fn test_wllama_engine_infer_without_load() {
         // Do NOT load a model
 
         let config = InferenceConfig::default();
-        let mut rx = engine.infer("hello", config).await.unwrap();
+        let mut rx = engine
+            .infer("hello", config, CancellationToken::new())
+            .await
+            .unwrap();
 
         // Should get ProcessStart, then an error (guest returns -1), then Complete
         let mut events = vec![];
@@ -7,12 +7,15 @@ mod tests {
     use rusty_genius_core::protocol::{
         AssetEvent, BrainstemBody, BrainstemCommand, BrainstemInput, InferenceEvent, ThoughtEvent,
     };
-    use rusty_genius_stem::Orchestrator;
+    use rusty_genius_stem::replay::{read_recording, Recorded};
+    use rusty_genius_stem::{Orchestrator, Recorder, ReplayEngine};
     use std::fs;
     use std::path::{Path, PathBuf};
+    use std::sync::Arc;
     use std::time::Duration;
 
     #[derive(Debug)]
+    #[allow(dead_code)]
     struct Fixture {
         path: PathBuf,
         org: String,
@@ -78,8 +81,13 @@ mod tests {
         let fixtures = scan_fixtures(&fixture_root);
         println!("Found {} fixtures.", fixtures.len());
 
-        // 2. Setup Orchestrator
+        // 2. Setup Orchestrator, recording the run next to the fixture with
+        // BRAINTEASER_RECORD=1 for test_replayed_inference_flow
         let mut orchestrator = Orchestrator::new().await?;
+        if let (Ok(_), Some(fixture)) = (std::env::var("BRAINTEASER_RECORD"), fixtures.first()) {
+            let recorder = Recorder::create(fixture.path.with_extension("jsonl"))?;
+            orchestrator.add_observer(Arc::new(recorder));
+        }
         let (mut input_tx, input_rx) = mpsc::channel(100);
         let (output_tx, mut output_rx) = mpsc::channel(100);
 
@@ -156,7 +164,23 @@ mod tests {
                     BrainstemBody::Error(e) => {
                         return Err(anyhow::anyhow!("Received error from brainstem: {}", e));
                     }
-                    BrainstemBody::ModelList(_) => {
+                    BrainstemBody::Cancelled => {
+                        return Err(anyhow::anyhow!("Request was cancelled"));
+                    }
+                    BrainstemBody::Accepted
+                    | BrainstemBody::Busy { .. }
+                    | BrainstemBody::Batch { .. }
+                    | BrainstemBody::AwaitingTools
+                    | BrainstemBody::ModelList(_)
+                    | BrainstemBody::Health(_)
+                    | BrainstemBody::Bench(_)
+                    | BrainstemBody::Hibernated
+                    | BrainstemBody::Waking
+                    | BrainstemBody::Ready
+                    | BrainstemBody::Restarted
+                    | BrainstemBody::Retrying { .. }
+                    | BrainstemBody::State { .. }
+                    | BrainstemBody::Metrics(_) => {
                         // Ignored in test harness
                     }
                 },
@@ -172,7 +196,7 @@ mod tests {
                 command: BrainstemCommand::Stop,
             })
             .await?;
-        let _ = orchestrator_handle.await?;
+        orchestrator_handle.await?;
 
         println!("Collected Output: {}", collected_output);
 
@@ -199,4 +223,93 @@ mod tests {
 
         Ok(())
     }
+    /// The text the request that sent `prompt` was answered with in `recording`.
+    fn recorded_answer(recording: &[Recorded], prompt: &str) -> String {
+        let id = recording.iter().find_map(|recorded| match recorded {
+            Recorded::Input(input) => match &input.command {
+                BrainstemCommand::Infer { prompt: sent, .. } if sent == prompt => input.id.clone(),
+                _ => None,
+            },
+            _ => None,
+        });
+        recording
+            .iter()
+            .filter_map(|recorded| match recorded {
+                Recorded::Output(output) if output.id.is_some() && output.id == id => {
+                    match &output.body {
+                        BrainstemBody::Event(InferenceEvent::Content(c)) => Some(c.as_str()),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Runs the fixtures recorded by test_inference_flow again from their recordings, so the
+    /// whole flow is tested without a model or the network.
+    #[async_std::test]
+    async fn test_replayed_inference_flow() -> Result<()> {
+        let manifest_dir = std::env::var("CARGO_MANIFEST_DIR")?;
+        let fixtures = scan_fixtures(&PathBuf::from(manifest_dir).join("fixtures"));
+        let mut replayed = 0;
+        for fixture in fixtures {
+            let recording_path = fixture.path.with_extension("jsonl");
+            if !recording_path.exists() {
+                continue;
+            }
+            let prompt = fs::read_to_string(&fixture.path)?;
+            let expected = recorded_answer(&read_recording(&recording_path)?, &prompt);
+
+            let engine = ReplayEngine::open(&recording_path)?;
+            let mut orchestrator = Orchestrator::with_engine(Box::new(engine));
+            let (mut input_tx, input_rx) = mpsc::channel(100);
+            let (output_tx, mut output_rx) = mpsc::channel(100);
+            let orchestrator_handle =
+                async_std::task::spawn(async move { orchestrator.run(input_rx, output_tx).await });
+
+            input_tx
+                .send(BrainstemInput {
+                    id: None,
+                    command: BrainstemCommand::LoadModel("qwen-2.5-3b-instruct".to_string()),
+                })
+                .await?;
+            input_tx
+                .send(BrainstemInput {
+                    id: None,
+                    command: BrainstemCommand::Infer {
+                        model: Some("qwen-2.5-3b-instruct".to_string()),
+                        prompt,
+                        config: Default::default(),
+                    },
+                })
+                .await?;
+
+            let mut collected_output = String::new();
+            while let Some(output) = output_rx.next().await {
+                match output.body {
+                    BrainstemBody::Event(InferenceEvent::Content(c)) => {
+                        collected_output.push_str(&c)
+                    }
+                    BrainstemBody::Event(InferenceEvent::Complete) => break,
+                    BrainstemBody::Error(e) => {
+                        return Err(anyhow::anyhow!("Received error from brainstem: {}", e));
+                    }
+                    _ => {}
+                }
+            }
+            drop(input_tx);
+            orchestrator_handle.await?;
+
+            assert!(!expected.is_empty(), "{:?} answers nothing", recording_path);
+            assert_eq!(
+                collected_output, expected,
+                "replay of {}",
+                fixture.test_name
+            );
+            replayed += 1;
+        }
+        assert!(replayed > 0, "no recorded fixtures");
+        Ok(())
+    }
 }
@@ -0,0 +1,164 @@
+//! Audio decoding for speech-to-text engines.
+
+use anyhow::{anyhow, Result};
+
+/// Sample rate speech models (Whisper) expect.
+pub const SAMPLE_RATE: u32 = 16_000;
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Decode a WAV file into mono `f32` samples at [SAMPLE_RATE].
+///
+/// Integer PCM (8, 16, 24 or 32 bit) and 32-bit float are supported. Channels are averaged
+/// and other sample rates are resampled linearly.
+pub fn decode_wav(bytes: &[u8]) -> Result<Vec<f32>> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(anyhow!("Not a WAV file"));
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let id = &bytes[pos..pos + 4];
+        let size = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let body = &bytes[pos + 8..(pos + 8).saturating_add(size).min(bytes.len())];
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let mut tag = u16::from_le_bytes([body[0], body[1]]);
+                if tag == FORMAT_EXTENSIBLE && body.len() >= 26 {
+                    // The real format is the first two bytes of the sub-format GUID.
+                    tag = u16::from_le_bytes([body[24], body[25]]);
+                }
+                format = Some(WavFormat {
+                    tag,
+                    channels: u16::from_le_bytes([body[2], body[3]]),
+                    sample_rate: u32::from_le_bytes(body[4..8].try_into().unwrap()),
+                    bits: u16::from_le_bytes([body[14], body[15]]),
+                });
+            }
+            b"data" => data = Some(body),
+            _ => {}
+        }
+        // Chunks are padded to an even size.
+        pos += 8 + size + (size & 1);
+    }
+
+    let format = format.ok_or_else(|| anyhow!("WAV file has no fmt chunk"))?;
+    let data = data.ok_or_else(|| anyhow!("WAV file has no data chunk"))?;
+    if format.channels == 0 || format.sample_rate == 0 {
+        return Err(anyhow!("WAV file has no channels"));
+    }
+
+    let width = (format.bits / 8) as usize;
+    let decode: fn(&[u8]) -> f32 = match (format.tag, format.bits) {
+        (FORMAT_PCM, 8) => |s| (s[0] as f32 - 128.0) / 128.0,
+        (FORMAT_PCM, 16) => |s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32_768.0,
+        (FORMAT_PCM, 24) => {
+            |s| (i32::from_le_bytes([0, s[0], s[1], s[2]]) >> 8) as f32 / 8_388_608.0
+        }
+        (FORMAT_PCM, 32) => {
+            |s| i32::from_le_bytes([s[0], s[1], s[2], s[3]]) as f32 / 2_147_483_648.0
+        }
+        (FORMAT_FLOAT, 32) => |s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]),
+        (tag, bits) => {
+            return Err(anyhow!(
+                "Unsupported WAV encoding (format {}, {} bits)",
+                tag,
+                bits
+            ))
+        }
+    };
+
+    let channels = format.channels as usize;
+    let mono: Vec<f32> = data
+        .chunks_exact(width * channels)
+        .map(|frame| frame.chunks_exact(width).map(decode).sum::<f32>() / channels as f32)
+        .collect();
+
+    Ok(resample(&mono, format.sample_rate, SAMPLE_RATE))
+}
+
+struct WavFormat {
+    tag: u16,
+    channels: u16,
+    sample_rate: u32,
+    bits: u16,
+}
+
+/// Linearly interpolate `samples` from rate `from` to rate `to`.
+fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let len = (samples.len() as u64 * to as u64 / from as u64) as usize;
+    let step = from as f64 / to as f64;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position as usize;
+            let fraction = (position - index as f64) as f32;
+            let current = samples[index];
+            let next = samples.get(index + 1).copied().unwrap_or(current);
+            current + (next - current) * fraction
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav(tag: u16, channels: u16, sample_rate: u32, bits: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&tag.to_le_bytes());
+        bytes.extend_from_slice(&channels.to_le_bytes());
+        bytes.extend_from_slice(&sample_rate.to_le_bytes());
+        let block = channels * bits / 8;
+        bytes.extend_from_slice(&(sample_rate * block as u32).to_le_bytes());
+        bytes.extend_from_slice(&block.to_le_bytes());
+        bytes.extend_from_slice(&bits.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn test_decode_pcm16_mono() {
+        let data: Vec<u8> = [0i16, 16_384, -32_768]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let samples = decode_wav(&wav(FORMAT_PCM, 1, SAMPLE_RATE, 16, &data)).unwrap();
+        assert_eq!(samples, vec![0.0, 0.5, -1.0]);
+    }
+
+    #[test]
+    fn test_decode_float_stereo_downmix() {
+        let data: Vec<u8> = [0.5f32, -0.5, 1.0, 0.0]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let samples = decode_wav(&wav(FORMAT_FLOAT, 2, SAMPLE_RATE, 32, &data)).unwrap();
+        assert_eq!(samples, vec![0.0, 0.5]);
+    }
+
+    #[test]
+    fn test_decode_resamples() {
+        let data: Vec<u8> = [0i16; 320].iter().flat_map(|s| s.to_le_bytes()).collect();
+        let samples = decode_wav(&wav(FORMAT_PCM, 1, 32_000, 16, &data)).unwrap();
+        assert_eq!(samples.len(), 160);
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        assert!(decode_wav(b"ID3\x03not a wav file").is_err());
+        assert!(decode_wav(&wav(2, 1, SAMPLE_RATE, 4, &[0; 8])).is_err());
+    }
+}
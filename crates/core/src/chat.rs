@@ -0,0 +1,227 @@
+//! Chat prompt rendering.
+//!
+//! Instruct models expect their conversation wrapped in the special tokens they were trained
+//! with (`<|im_start|>user ...` for ChatML, `[INST] ... [/INST]` for Mistral, ...). GGUF files
+//! ship that format as a Jinja template in `tokenizer.chat_template`; rather than evaluating
+//! Jinja, [ChatTemplate::detect] recognises the family the template belongs to and
+//! [ChatTemplate::render] formats the messages the same way.
+
+use crate::engine::Engine;
+pub use crate::protocol::{ChatMessage, ChatRole};
+
+/// The prompt formats of common instruct model families.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChatTemplate {
+    /// `<|im_start|>role\n...<|im_end|>`: Qwen, Yi, Hermes and many fine-tunes.
+    ChatMl,
+    /// `<|start_header_id|>role<|end_header_id|>\n\n...<|eot_id|>`: Llama 3.
+    Llama3,
+    /// `[INST] <<SYS>>\n...\n<</SYS>>\n\n... [/INST]`: Llama 2 chat.
+    Llama2,
+    /// `[INST] ... [/INST]`: Mistral and Mixtral instruct.
+    Mistral,
+    /// `<start_of_turn>role\n...<end_of_turn>`: Gemma.
+    Gemma,
+    /// `<|role|>\n...<|end|>`: Phi-3.
+    Phi3,
+}
+
+impl ChatTemplate {
+    /// Look a template up by name, as written in a registry override: `chatml`, `llama3`,
+    /// `llama2`, `mistral`, `gemma` or `phi3`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "chatml" => Some(Self::ChatMl),
+            "llama3" => Some(Self::Llama3),
+            "llama2" => Some(Self::Llama2),
+            "mistral" => Some(Self::Mistral),
+            "gemma" => Some(Self::Gemma),
+            "phi3" => Some(Self::Phi3),
+            _ => None,
+        }
+    }
+
+    /// Recognise the family of a Jinja chat template by the special tokens it emits.
+    pub fn detect(source: &str) -> Option<Self> {
+        let has = |needle: &str| source.contains(needle);
+        if has("<|im_start|>") {
+            Some(Self::ChatMl)
+        } else if has("<|start_header_id|>") && has("<|end_header_id|>") {
+            Some(Self::Llama3)
+        } else if has("<start_of_turn>") {
+            Some(Self::Gemma)
+        } else if has("<|assistant|>") && has("<|end|>") {
+            Some(Self::Phi3)
+        } else if has("[INST]") && has("<<SYS>>") {
+            Some(Self::Llama2)
+        } else if has("[INST]") {
+            Some(Self::Mistral)
+        } else {
+            None
+        }
+    }
+
+    /// Pick the template for a model: the registry override (a template name or Jinja source)
+    /// wins over the template in the model's metadata. Falls back to ChatML, which most
+    /// recent instruct models understand.
+    pub fn resolve(override_template: Option<&str>, metadata_template: Option<&str>) -> Self {
+        override_template
+            .and_then(|t| Self::from_name(t).or_else(|| Self::detect(t)))
+            .or_else(|| metadata_template.and_then(Self::detect))
+            .unwrap_or(Self::ChatMl)
+    }
+
+    /// The template for the model currently loaded in `engine`.
+    pub fn for_engine(engine: &dyn Engine, override_template: Option<&str>) -> Self {
+        Self::resolve(override_template, engine.chat_template().as_deref())
+    }
+
+    /// Render `messages` into a prompt. With `add_generation_prompt` the prompt ends with the
+    /// opening of an assistant turn so the model answers instead of continuing the user.
+    ///
+    /// The beginning-of-sequence token is left out; engines add it when tokenizing.
+    pub fn render(&self, messages: &[ChatMessage], add_generation_prompt: bool) -> String {
+        let mut out = String::new();
+        match self {
+            Self::ChatMl => {
+                for msg in messages {
+                    // Qwen and Hermes take tool results back as the user
+                    let content = match msg.role {
+                        ChatRole::Tool => {
+                            format!("<tool_response>\n{}\n</tool_response>", msg.content)
+                        }
+                        _ => msg.content.clone(),
+                    };
+                    out.push_str(&format!(
+                        "<|im_start|>{}\n{}<|im_end|>\n",
+                        role_name(msg.role),
+                        content
+                    ));
+                }
+                if add_generation_prompt {
+                    out.push_str("<|im_start|>assistant\n");
+                }
+            }
+            Self::Llama3 => {
+                for msg in messages {
+                    let role = match msg.role {
+                        ChatRole::Tool => "ipython",
+                        role => role_name(role),
+                    };
+                    out.push_str(&format!(
+                        "<|start_header_id|>{}<|end_header_id|>\n\n{}<|eot_id|>",
+                        role,
+                        msg.content.trim()
+                    ));
+                }
+                if add_generation_prompt {
+                    out.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n");
+                }
+            }
+            Self::Llama2 => {
+                let mut system = None;
+                for msg in messages {
+                    match msg.role {
+                        ChatRole::System => system = Some(msg.content.trim()),
+                        ChatRole::User | ChatRole::Tool => {
+                            out.push_str("[INST] ");
+                            if let Some(system) = system.take() {
+                                out.push_str(&format!("<<SYS>>\n{}\n<</SYS>>\n\n", system));
+                            }
+                            out.push_str(&format!("{} [/INST]", msg.content.trim()));
+                        }
+                        ChatRole::Assistant => {
+                            out.push_str(&format!(" {} </s><s>", msg.content.trim()));
+                        }
+                    }
+                }
+            }
+            Self::Mistral => {
+                let mut system = None;
+                for msg in messages {
+                    match msg.role {
+                        ChatRole::System => system = Some(msg.content.trim()),
+                        ChatRole::User | ChatRole::Tool => match system.take() {
+                            Some(system) => out.push_str(&format!(
+                                "[INST] {}\n\n{} [/INST]",
+                                system,
+                                msg.content.trim()
+                            )),
+                            None => out.push_str(&format!("[INST] {} [/INST]", msg.content.trim())),
+                        },
+                        ChatRole::Assistant => {
+                            out.push_str(&format!(" {}</s>", msg.content.trim()));
+                        }
+                    }
+                }
+            }
+            Self::Gemma => {
+                // Gemma has no system role; its instructions go at the top of the first user turn.
+                let mut system = None;
+                for msg in messages {
+                    let (role, content) = match msg.role {
+                        ChatRole::System => {
+                            system = Some(msg.content.trim());
+                            continue;
+                        }
+                        ChatRole::User | ChatRole::Tool => match system.take() {
+                            Some(system) => {
+                                ("user", format!("{}\n\n{}", system, msg.content.trim()))
+                            }
+                            None => ("user", msg.content.trim().to_string()),
+                        },
+                        ChatRole::Assistant => ("model", msg.content.trim().to_string()),
+                    };
+                    out.push_str(&format!(
+                        "<start_of_turn>{}\n{}<end_of_turn>\n",
+                        role, content
+                    ));
+                }
+                if add_generation_prompt {
+                    out.push_str("<start_of_turn>model\n");
+                }
+            }
+            Self::Phi3 => {
+                for msg in messages {
+                    out.push_str(&format!(
+                        "<|{}|>\n{}<|end|>\n",
+                        role_name(msg.role),
+                        msg.content
+                    ));
+                }
+                if add_generation_prompt {
+                    out.push_str("<|assistant|>\n");
+                }
+            }
+        }
+        out
+    }
+
+    /// What closes an assistant turn: appended to a rendered prompt and the model's answer to
+    /// render more messages after it.
+    pub fn end_of_turn(&self) -> &'static str {
+        match self {
+            Self::ChatMl => "<|im_end|>\n",
+            Self::Llama3 => "<|eot_id|>",
+            Self::Llama2 => " </s><s>",
+            Self::Mistral => "</s>",
+            Self::Gemma => "<end_of_turn>\n",
+            Self::Phi3 => "<|end|>\n",
+        }
+    }
+}
+
+fn role_name(role: ChatRole) -> &'static str {
+    match role {
+        ChatRole::System => "system",
+        // Templates without a tool role take results back as the user
+        ChatRole::User | ChatRole::Tool => "user",
+        ChatRole::Assistant => "assistant",
+    }
+}
+
+/// Render `messages` with the chat template of the model loaded in `engine`, ready to pass
+/// to [Engine::infer].
+pub fn build_prompt(engine: &dyn Engine, messages: &[ChatMessage]) -> String {
+    ChatTemplate::for_engine(engine, None).render(messages, true)
+}
@@ -1,32 +1,131 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use futures::channel::mpsc;
+use futures::StreamExt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-use crate::manifest::InferenceConfig;
-use crate::protocol::InferenceEvent;
+use crate::manifest::{BenchConfig, InferenceConfig, LoadConfig, TranscriptionConfig};
+use crate::protocol::{
+    BenchReport, DeviceInfo, EngineHealth, InferenceEvent, ModelInfo, SmokeTest,
+};
+
+/// Stops a running [Engine::infer] early, e.g. when the client that asked for it went away.
+///
+/// Clones share the same flag, so the caller keeps one and hands another to the engine.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the engine to stop generating.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
 
 #[async_trait]
 pub trait Engine: Send + Sync {
     /// Load a model from a path
     async fn load_model(&mut self, model_path: &str) -> Result<()>;
 
+    /// Load a model with RoPE, batch and other load-time settings. Engines without any ignore
+    /// `config`.
+    async fn load_model_with_config(
+        &mut self,
+        model_path: &str,
+        _config: LoadConfig,
+    ) -> Result<()> {
+        self.load_model(model_path).await
+    }
+
     /// Unload the currently loaded model to free resources
     async fn unload_model(&mut self) -> Result<()>;
 
     /// Check if a model is currently loaded
     fn is_loaded(&self) -> bool;
 
+    /// Whether models are named on a remote server rather than loaded from files. The
+    /// orchestrator hands such names to [Engine::load_model] without downloading anything.
+    fn is_remote(&self) -> bool {
+        false
+    }
+
     /// Get the default model name for this engine
     fn default_model(&self) -> String;
 
+    /// The Jinja chat template embedded in the loaded model (`tokenizer.chat_template`), if any
+    fn chat_template(&self) -> Option<String> {
+        None
+    }
+
+    /// Architecture, size, quantization and other details of the loaded model
+    fn model_info(&self) -> Option<ModelInfo> {
+        None
+    }
+
     /// Run inference
     /// Returns a channel of InferenceEvents
+    ///
+    /// Once `cancel` is cancelled the engine stops before the next token and ends the
+    /// stream with `Complete`. Engines also stop when the receiver is dropped.
     async fn infer(
         &mut self,
         prompt: &str,
         config: InferenceConfig,
+        cancel: CancellationToken,
     ) -> Result<mpsc::Receiver<Result<InferenceEvent>>>;
 
+    /// Run inference on a prompt that is already tokenized, e.g. one templated and counted by
+    /// the caller or replayed from a recorded session
+    ///
+    /// The tokens are evaluated exactly as given; nothing (not even BOS) is added.
+    async fn infer_tokens(
+        &mut self,
+        _tokens: Vec<i32>,
+        _config: InferenceConfig,
+        _cancel: CancellationToken,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        Err(anyhow!("This engine does not support token prompts"))
+    }
+
+    /// Drop the context kept for a session (see [InferenceConfig::session_id])
+    async fn close_session(&mut self, _session_id: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Write a session's KV cache, and the tokens in it, to the file at `path`, so a long
+    /// conversation survives the engine unloading or the process restarting
+    async fn save_session(&mut self, _session_id: &str, _path: &str) -> Result<()> {
+        Err(anyhow!("This engine does not support saving sessions"))
+    }
+
+    /// Restore a session from a file written by [Engine::save_session] for the same model and
+    /// adapter, so its next request only evaluates what is new. Engines may defer reading the
+    /// file until that request, and fall back to evaluating the whole prompt if it is unusable.
+    async fn load_session(&mut self, _session_id: &str, _path: &str) -> Result<()> {
+        Err(anyhow!("This engine does not support loading sessions"))
+    }
+
+    /// Apply the LoRA adapter at `path` to the loaded model with strength `scale`, replacing
+    /// any adapter applied before. It stays applied until [Engine::remove_adapter] or the next
+    /// model load.
+    async fn apply_adapter(&mut self, _path: &str, _scale: f32) -> Result<()> {
+        Err(anyhow!("This engine does not support LoRA adapters"))
+    }
+
+    /// Go back to the plain base model
+    async fn remove_adapter(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     /// Generate embeddings
     /// Returns a channel of InferenceEvents (will emit Embedding event)
     async fn embed(
@@ -34,4 +133,107 @@ pub trait Engine: Send + Sync {
         input: &str,
         config: InferenceConfig,
     ) -> Result<mpsc::Receiver<Result<InferenceEvent>>>;
+
+    /// Generate embeddings for several inputs
+    /// Returns a channel of InferenceEvents with an IndexedEmbedding per input
+    ///
+    /// The default embeds the inputs one at a time; engines that can evaluate several
+    /// sequences at once override it.
+    async fn embed_batch(
+        &mut self,
+        inputs: &[String],
+        config: InferenceConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        let (mut tx, rx) = mpsc::channel(inputs.len() + 2);
+        let _ = tx.try_send(Ok(InferenceEvent::ProcessStart));
+        for (index, input) in inputs.iter().enumerate() {
+            let mut events = self.embed(input, config.clone()).await?;
+            while let Some(event) = events.next().await {
+                if let InferenceEvent::Embedding(embedding) = event? {
+                    let _ = tx.try_send(Ok(InferenceEvent::IndexedEmbedding { index, embedding }));
+                }
+            }
+        }
+        let _ = tx.try_send(Ok(InferenceEvent::Complete));
+        Ok(rx)
+    }
+
+    /// Score how relevant each document is to `query`, with a cross-encoder (reranker) model
+    /// Returns a channel of InferenceEvents with a RerankScore per document
+    async fn rerank(
+        &mut self,
+        _query: &str,
+        _documents: &[String],
+        _config: InferenceConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        Err(anyhow!("This engine does not support reranking"))
+    }
+
+    /// Transcribe speech, given as mono samples at [audio::SAMPLE_RATE](crate::audio::SAMPLE_RATE)
+    /// Returns a channel of InferenceEvents with a Transcript per segment
+    async fn transcribe(
+        &mut self,
+        _samples: &[f32],
+        _config: TranscriptionConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        Err(anyhow!("This engine does not support transcription"))
+    }
+
+    /// Devices the engine computes on, with their memory; empty when it doesn't say.
+    fn devices(&self) -> Vec<DeviceInfo> {
+        Vec::new()
+    }
+
+    /// Report the backend, devices and loaded model, and check that the model generates (see
+    /// [smoke_test]). Engines with a native backend override it to fill in the build and
+    /// device details.
+    async fn health(&mut self) -> Result<EngineHealth> {
+        let smoke_test = if self.is_loaded() {
+            Some(smoke_test(self).await)
+        } else {
+            None
+        };
+        Ok(EngineHealth {
+            devices: self.devices(),
+            model: self.model_info(),
+            smoke_test,
+            ..Default::default()
+        })
+    }
+
+    /// Time prompt processing and generation on the loaded model with synthetic tokens, so
+    /// quantizations and offload settings can be compared
+    async fn bench(&mut self, _config: BenchConfig) -> Result<BenchReport> {
+        Err(anyhow!("This engine does not support benchmarking"))
+    }
+}
+
+/// Generate one token with the loaded model and report whether that worked and how long it
+/// took.
+pub async fn smoke_test<E: Engine + ?Sized>(engine: &mut E) -> SmokeTest {
+    let start = std::time::Instant::now();
+    let config = InferenceConfig {
+        temperature: 0.0,
+        max_tokens: Some(1),
+        ..Default::default()
+    };
+    let error = match engine
+        .infer("Hello", config, CancellationToken::new())
+        .await
+    {
+        Ok(mut events) => loop {
+            match events.next().await {
+                Some(Ok(InferenceEvent::Complete)) => break None,
+                Some(Ok(_)) => {}
+                Some(Err(e)) => break Some(e.to_string()),
+                None => break Some("The generation ended without completing".to_string()),
+            }
+        },
+        Err(e) => Some(e.to_string()),
+    };
+    SmokeTest {
+        passed: error.is_none(),
+        elapsed_ms: start.elapsed().as_millis() as u64,
+        error,
+    }
 }
@@ -0,0 +1,85 @@
+/// Name of a llama.cpp `general.file_type` value, e.g. `Q4_K_M` for 15.
+pub fn file_type_name(file_type: u64) -> Option<&'static str> {
+    Some(match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        36 => "TQ1_0",
+        37 => "TQ2_0",
+        _ => return None,
+    })
+}
+
+/// Every part of a split model (`gguf-split` output such as `model-00002-of-00005.gguf`),
+/// first to last, given the name of any part; `None` for a single-file model.
+///
+/// llama.cpp loads a split model from its first part and finds the others next to it by
+/// this naming.
+pub fn split_parts(filename: &str) -> Option<Vec<String>> {
+    let stem = filename.strip_suffix(".gguf")?;
+    let (rest, count) = stem.rsplit_once("-of-")?;
+    let (prefix, index) = rest.rsplit_once('-')?;
+    let width = count.len();
+    if index.len() != width || !is_digits(index) || !is_digits(count) {
+        return None;
+    }
+    let count: u32 = count.parse().ok()?;
+    if count == 0 {
+        return None;
+    }
+    Some(
+        (1..=count)
+            .map(|i| format!("{}-{:0width$}-of-{:0width$}.gguf", prefix, i, count))
+            .collect(),
+    )
+}
+
+fn is_digits(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_parts() {
+        assert_eq!(
+            split_parts("Qwen2.5-72B-Q4_K_M-00002-of-00003.gguf").unwrap(),
+            vec![
+                "Qwen2.5-72B-Q4_K_M-00001-of-00003.gguf",
+                "Qwen2.5-72B-Q4_K_M-00002-of-00003.gguf",
+                "Qwen2.5-72B-Q4_K_M-00003-of-00003.gguf",
+            ]
+        );
+        assert_eq!(split_parts("qwen2.5-0.5b-instruct-q4_k_m.gguf"), None);
+        assert_eq!(split_parts("model-of-00003.gguf"), None);
+    }
+}
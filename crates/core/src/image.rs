@@ -0,0 +1,101 @@
+//! Image attachments for vision models.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Where an image goes in a prompt. Engines put the images of
+/// [InferenceConfig::images](crate::manifest::InferenceConfig::images) at these markers, in
+/// order; llama.cpp's multimodal tokenizer uses the same one.
+pub const IMAGE_MARKER: &str = "<__media__>";
+
+/// An image file (PNG, JPEG, ...) attached to a prompt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageInput {
+    /// The file's contents, base64-encoded.
+    Base64(String),
+    /// Path of the file on the machine the engine runs on.
+    Path(PathBuf),
+}
+
+impl ImageInput {
+    /// The image in a `data:` URL, e.g. `data:image/png;base64,iVBOR...`; `None` for any other
+    /// URL.
+    pub fn from_data_url(url: &str) -> Option<Self> {
+        let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+        header
+            .ends_with(";base64")
+            .then(|| ImageInput::Base64(data.to_string()))
+    }
+
+    /// The image file's contents.
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            ImageInput::Base64(data) => decode_base64(data),
+            ImageInput::Path(path) => std::fs::read(path)
+                .map_err(|e| anyhow!("Failed to read image {}: {}", path.display(), e)),
+        }
+    }
+}
+
+/// Decode standard or URL-safe base64, with or without padding. Whitespace is skipped.
+pub fn decode_base64(data: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in data.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            c if c.is_ascii_whitespace() => continue,
+            c => return Err(anyhow!("Invalid base64 character {:?}", c as char)),
+        };
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_base64() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGVsbG8").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGVs\nbG8h").unwrap(), b"hello!");
+        assert_eq!(decode_base64("-_8").unwrap(), [0xfb, 0xff]);
+        assert!(decode_base64("a*b").is_err());
+    }
+
+    #[test]
+    fn test_from_data_url() {
+        assert_eq!(
+            ImageInput::from_data_url("data:image/png;base64,aGk="),
+            Some(ImageInput::Base64("aGk=".to_string()))
+        );
+        assert_eq!(ImageInput::from_data_url("data:text/plain,hi"), None);
+        assert_eq!(
+            ImageInput::from_data_url("https://example.com/cat.png"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_serde_names() {
+        let json = serde_json::to_value(ImageInput::Path("cat.png".into())).unwrap();
+        assert_eq!(json, serde_json::json!({ "path": "cat.png" }));
+        let image: ImageInput = serde_json::from_str(r#"{"base64":"aGk="}"#).unwrap();
+        assert_eq!(image.bytes().unwrap(), b"hi");
+    }
+}
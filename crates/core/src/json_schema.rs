@@ -0,0 +1,353 @@
+//! JSON Schema constrained output.
+//!
+//! [to_grammar] compiles a schema into a GBNF grammar for engines that constrain decoding, and
+//! [validate] checks the finished output against the same schema.
+//!
+//! Supported keywords are `type` (a name or a list of names), `properties`, `required`,
+//! `items`, `enum`, `const`, `anyOf` and `oneOf`. Objects with `properties` only accept the
+//! listed properties, emitted in key order. Other keywords are ignored; `$ref` is rejected.
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// Rules for JSON values the schema doesn't narrow down.
+const PRIMITIVES: &str = r#"ws ::= | " " | "\n" [ \t]{0,20}
+boolean ::= "true" | "false"
+null ::= "null"
+integer ::= "-"? ([0-9] | [1-9] [0-9]{0,15})
+number ::= integer ("." [0-9]+)? ([eE] [-+]? [0-9]+)?
+string ::= "\"" char* "\""
+char ::= [^"\\\x7F\x00-\x1F] | "\\" (["\\/bfnrt] | "u" [0-9a-fA-F]{4})
+value ::= object | array | string | number | boolean | null
+object ::= "{" ws (string ws ":" ws value ws ("," ws string ws ":" ws value ws)*)? "}"
+array ::= "[" ws (value ws ("," ws value ws)*)? "]"
+"#;
+
+const RESERVED: &[&str] = &[
+    "root", "ws", "boolean", "null", "integer", "number", "string", "char", "value", "object",
+    "array",
+];
+
+/// Compile `schema` into a GBNF grammar whose `root` rule matches exactly the JSON documents
+/// the schema accepts.
+pub fn to_grammar(schema: &Value) -> Result<String> {
+    let mut compiler = Compiler::default();
+    let root = compiler.visit(schema, "schema")?;
+    let mut grammar = format!("root ::= {}\n", root);
+    for rule in &compiler.rules {
+        grammar.push_str(rule);
+        grammar.push('\n');
+    }
+    grammar.push_str(PRIMITIVES);
+    Ok(grammar)
+}
+
+#[derive(Default)]
+struct Compiler {
+    rules: Vec<String>,
+    names: Vec<String>,
+}
+
+impl Compiler {
+    /// Add a rule named after `name`, made unique, and return a reference to it.
+    fn rule(&mut self, name: &str, body: String) -> String {
+        let base: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let mut unique = base.clone();
+        let mut n = 1;
+        while RESERVED.contains(&unique.as_str()) || self.names.contains(&unique) {
+            n += 1;
+            unique = format!("{}-{}", base, n);
+        }
+        self.names.push(unique.clone());
+        self.rules.push(format!("{} ::= {}", unique, body));
+        unique
+    }
+
+    fn visit(&mut self, schema: &Value, name: &str) -> Result<String> {
+        let schema = match schema {
+            Value::Bool(true) => return Ok("value".to_string()),
+            Value::Object(schema) => schema,
+            _ => return Err(anyhow!("Schema at `{}` accepts no value", name)),
+        };
+        if schema.contains_key("$ref") {
+            return Err(anyhow!("`$ref` is not supported (at `{}`)", name));
+        }
+
+        if let Some(value) = schema.get("const") {
+            return Ok(literal(&value.to_string()));
+        }
+        if let Some(values) = schema.get("enum") {
+            let values = values
+                .as_array()
+                .filter(|values| !values.is_empty())
+                .ok_or_else(|| anyhow!("`enum` at `{}` must be a non-empty array", name))?;
+            let choices: Vec<String> = values.iter().map(|v| literal(&v.to_string())).collect();
+            return Ok(format!("({})", choices.join(" | ")));
+        }
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(variants) = schema.get(keyword).and_then(Value::as_array) {
+                let choices = variants
+                    .iter()
+                    .enumerate()
+                    .map(|(i, variant)| self.visit(variant, &format!("{}-{}", name, i)))
+                    .collect::<Result<Vec<_>>>()?;
+                return Ok(format!("({})", choices.join(" | ")));
+            }
+        }
+
+        match schema.get("type") {
+            None => Ok("value".to_string()),
+            Some(Value::String(ty)) => self.visit_type(schema, ty, name),
+            Some(Value::Array(types)) => {
+                let choices = types
+                    .iter()
+                    .map(|ty| {
+                        let ty = ty
+                            .as_str()
+                            .ok_or_else(|| anyhow!("Invalid `type` at `{}`", name))?;
+                        self.visit_type(schema, ty, &format!("{}-{}", name, ty))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(format!("({})", choices.join(" | ")))
+            }
+            Some(_) => Err(anyhow!("Invalid `type` at `{}`", name)),
+        }
+    }
+
+    fn visit_type(
+        &mut self,
+        schema: &serde_json::Map<String, Value>,
+        ty: &str,
+        name: &str,
+    ) -> Result<String> {
+        match ty {
+            "string" | "number" | "integer" | "boolean" | "null" => Ok(ty.to_string()),
+            "array" => {
+                let item = match schema.get("items") {
+                    Some(items) => self.visit(items, &format!("{}-item", name))?,
+                    None => "value".to_string(),
+                };
+                let body = format!(
+                    "\"[\" ws ({item} ws (\",\" ws {item} ws)*)? \"]\"",
+                    item = item
+                );
+                Ok(self.rule(name, body))
+            }
+            "object" => {
+                let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+                    return Ok("object".to_string());
+                };
+                let required: Vec<&str> = schema
+                    .get("required")
+                    .and_then(Value::as_array)
+                    .map(|names| names.iter().filter_map(Value::as_str).collect())
+                    .unwrap_or_default();
+
+                let mut required_kvs = Vec::new();
+                let mut optional_kvs = Vec::new();
+                for (key, property) in properties {
+                    let value = self.visit(property, &format!("{}-{}", name, key))?;
+                    let kv = format!(
+                        "{} ws \":\" ws {} ws",
+                        literal(&Value::String(key.clone()).to_string()),
+                        value
+                    );
+                    if required.contains(&key.as_str()) {
+                        required_kvs.push(kv);
+                    } else {
+                        optional_kvs.push(kv);
+                    }
+                }
+                for key in required {
+                    if !properties.contains_key(key) {
+                        required_kvs.push(format!(
+                            "{} ws \":\" ws value ws",
+                            literal(&Value::String(key.to_string()).to_string())
+                        ));
+                    }
+                }
+
+                let mut body = format!("\"{{\" ws {}", required_kvs.join(" \",\" ws "));
+                if !optional_kvs.is_empty() {
+                    let rest = self.optional_rules(name, &optional_kvs);
+                    if required_kvs.is_empty() {
+                        body.push_str(&format!("({})?", rest));
+                    } else {
+                        body.push_str(&format!(" (\",\" ws {})?", rest));
+                    }
+                }
+                body.push_str(" \"}\"");
+                Ok(self.rule(name, body))
+            }
+            other => Err(anyhow!("Unknown type `{}` at `{}`", other, name)),
+        }
+    }
+
+    /// Rules matching any subsequence of `kvs`, in order and comma separated. Returns the rule
+    /// for the whole list.
+    fn optional_rules(&mut self, name: &str, kvs: &[String]) -> String {
+        let mut next: Option<String> = None;
+        let mut firsts = Vec::new();
+        for (i, kv) in kvs.iter().enumerate().rev() {
+            let choice = match &next {
+                Some(rest) => format!("{} (\",\" ws {})?", kv, rest),
+                None => kv.clone(),
+            };
+            firsts.insert(0, choice);
+            next = Some(self.rule(&format!("{}-rest-{}", name, i), firsts.join(" | ")));
+        }
+        next.expect("at least one optional property")
+    }
+}
+
+/// A GBNF string literal matching `text` exactly.
+fn literal(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Check that `value` conforms to `schema`, naming the first offending location otherwise.
+pub fn validate(schema: &Value, value: &Value) -> Result<()> {
+    check(schema, value, "$")
+}
+
+fn check(schema: &Value, value: &Value, path: &str) -> Result<()> {
+    let schema = match schema {
+        Value::Bool(false) => return Err(anyhow!("{}: no value is allowed", path)),
+        Value::Object(schema) => schema,
+        _ => return Ok(()),
+    };
+
+    if let Some(expected) = schema.get("const") {
+        if value != expected {
+            return Err(anyhow!("{}: expected {}", path, expected));
+        }
+    }
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        if !values.contains(value) {
+            return Err(anyhow!(
+                "{}: {} is not one of the allowed values",
+                path,
+                value
+            ));
+        }
+    }
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(variants) = schema.get(keyword).and_then(Value::as_array) {
+            if !variants
+                .iter()
+                .any(|variant| check(variant, value, path).is_ok())
+            {
+                return Err(anyhow!(
+                    "{}: matches none of the `{}` schemas",
+                    path,
+                    keyword
+                ));
+            }
+        }
+    }
+
+    let types: Vec<&str> = match schema.get("type") {
+        Some(Value::String(ty)) => vec![ty.as_str()],
+        Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|ty| has_type(value, ty)) {
+        return Err(anyhow!(
+            "{}: expected {}, found {}",
+            path,
+            types.join(" or "),
+            value
+        ));
+    }
+
+    match value {
+        Value::Object(object) => {
+            if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+                for (key, property) in properties {
+                    if let Some(v) = object.get(key) {
+                        check(property, v, &format!("{}.{}", path, key))?;
+                    }
+                }
+            }
+            if let Some(required) = schema.get("required").and_then(Value::as_array) {
+                for key in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(key) {
+                        return Err(anyhow!("{}: missing required property `{}`", path, key));
+                    }
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(item_schema, item, &format!("{}[{}]", path, i))?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn has_type(value: &Value, ty: &str) -> bool {
+    match ty {
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn person() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "age": { "type": "integer" },
+                "tags": { "type": "array", "items": { "enum": ["a", "b"] } }
+            },
+            "required": ["name"]
+        })
+    }
+
+    #[test]
+    fn test_object_grammar() {
+        let grammar = to_grammar(&person()).unwrap();
+        assert!(grammar.starts_with("root ::= schema\n"));
+        assert!(grammar.contains(
+            r#"schema ::= "{" ws "\"name\"" ws ":" ws string ws ("," ws schema-rest-0)? "}""#
+        ));
+        assert!(grammar.contains(r#"schema-tags ::= "[" ws (("\"a\"" | "\"b\"") ws"#));
+        // Optional properties can be skipped: the first rest rule offers either one.
+        assert!(grammar.contains(
+            r#"schema-rest-0 ::= "\"age\"" ws ":" ws integer ws ("," ws schema-rest-1)? | "\"tags\"""#
+        ));
+        assert!(to_grammar(&json!({ "$ref": "#/defs/x" })).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = person();
+        assert!(validate(&schema, &json!({ "name": "Ada", "age": 36, "tags": ["a"] })).is_ok());
+
+        let err = validate(&schema, &json!({ "age": 36 })).unwrap_err();
+        assert!(err.to_string().contains("missing required property `name`"));
+        let err = validate(&schema, &json!({ "name": "Ada", "tags": ["c"] })).unwrap_err();
+        assert!(err.to_string().starts_with("$.tags[0]"));
+        assert!(validate(&schema, &json!({ "name": "Ada", "age": 3.5 })).is_err());
+    }
+}
@@ -1,16 +1,28 @@
+pub mod audio;
+pub mod chat;
 pub mod context;
 pub mod cosine;
 pub mod engine;
 pub mod error;
+pub mod gguf;
+pub mod image;
+pub mod json_schema;
 pub mod manifest;
 pub mod memory;
+pub mod metrics;
 pub mod protocol;
+pub mod rag;
+#[cfg(any(feature = "async-std", feature = "tokio"))]
+pub mod runtime;
+pub mod tools;
+pub mod utf8;
 
 pub use context::{ContextStore, InMemoryContextStore};
-pub use cosine::cosine_similarity;
-pub use engine::Engine;
+pub use cosine::{cosine_similarity, l2_normalize};
+pub use engine::{CancellationToken, Engine};
 pub use error::GeniusError;
 pub use memory::{
     EmbeddingProvider, InMemoryMemoryStore, MemoryObject, MemoryObjectType, MemoryStore,
     MockEmbeddingProvider,
 };
+pub use utf8::Utf8Buffer;
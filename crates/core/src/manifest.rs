@@ -1,4 +1,7 @@
+pub use crate::image::{ImageInput, IMAGE_MARKER};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserManifest {
@@ -21,6 +24,33 @@ pub struct ModelSpec {
     pub repo: String,
     pub filename: String,
     pub quantization: String,
+    /// Commit hash or tag to download from; `None` follows the default branch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    /// Extra files fetched alongside the model, such as `tokenizer_config.json`. A plain path
+    /// is taken from the same repo and revision; `org/repo[@revision]:path` names another repo
+    /// (e.g. the original non-GGUF model).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aux_files: Vec<String>,
+}
+
+/// A downloaded model: the GGUF weights plus any auxiliary files from [ModelSpec::aux_files].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelBundle {
+    pub gguf: PathBuf,
+    /// Local paths of the auxiliary files, by their path in the repo.
+    #[serde(default)]
+    pub aux: BTreeMap<String, PathBuf>,
+}
+
+impl ModelBundle {
+    /// The auxiliary file whose repo path is or ends with `name`, e.g. `tokenizer_config.json`.
+    pub fn aux_file(&self, name: &str) -> Option<&Path> {
+        self.aux
+            .iter()
+            .find(|(path, _)| *path == name || path.ends_with(&format!("/{}", name)))
+            .map(|(_, local)| local.as_path())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +62,181 @@ pub struct InferenceConfig {
     pub max_tokens: Option<usize>,
     pub context_size: Option<u32>,
     pub show_thinking: bool,
+    /// The tags the model wraps its thinking in; `None` is `<think>` and `</think>`.
+    #[serde(default)]
+    pub thinking_tags: Option<ThinkingTags>,
+    /// Keep the engine's context between requests with the same id, so a follow-up turn reuses
+    /// the already evaluated conversation instead of starting over. Engines without sessions
+    /// ignore it.
+    #[serde(default)]
+    pub session_id: Option<String>,
+    /// GBNF grammar the output is constrained to, starting at its `root` rule. Engines that
+    /// can't constrain decoding ignore it.
+    #[serde(default)]
+    pub grammar: Option<String>,
+    /// JSON Schema the output must conform to. Engines with grammar support compile it with
+    /// [json_schema::to_grammar](crate::json_schema::to_grammar), in place of `grammar`, and
+    /// check the result before `Complete`.
+    #[serde(default)]
+    pub json_schema: Option<serde_json::Value>,
+    /// Emit an `InferenceEvent::Logprob` for every generated token, listing this many most
+    /// likely alternatives. Engines that don't expose probabilities ignore it.
+    #[serde(default)]
+    pub logprobs: Option<u32>,
+    /// Scale embeddings to unit length, as cosine-similarity pipelines expect.
+    #[serde(default)]
+    pub normalize: bool,
+    /// LoRA adapter to apply to the loaded model for this request, in place of the one set
+    /// with `Engine::apply_adapter`.
+    #[serde(default)]
+    pub adapter: Option<AdapterConfig>,
+    /// What to do when the prompt doesn't fit the context.
+    #[serde(default)]
+    pub context_overflow: ContextOverflow,
+    /// Drop tokens less likely than this fraction of the most likely one.
+    #[serde(default)]
+    pub min_p: Option<f32>,
+    /// Sample with Mirostat, which keeps the output's perplexity near a target, in place of
+    /// top-k, top-p and min-p.
+    #[serde(default)]
+    pub mirostat: Option<MirostatConfig>,
+    /// Images for a vision model to look at, placed at the [IMAGE_MARKER]s in the prompt in
+    /// order; images without a marker go before the prompt. Engines without image support
+    /// ignore them.
+    #[serde(default)]
+    pub images: Vec<ImageInput>,
+    /// Tokens that end the generation besides the model's own end-of-generation tokens, by
+    /// their text (e.g. `<|im_end|>`), for finetunes whose metadata misses one.
+    #[serde(default)]
+    pub end_tokens: Vec<String>,
+    /// Render special tokens such as `<|im_start|>` in `Content`, for debugging templates. Off
+    /// by default, which strips them from the output.
+    #[serde(default)]
+    pub render_special_tokens: bool,
+    /// Functions the model may call. The orchestrator watches the output for calls, sends them
+    /// as `InferenceEvent::ToolCall`s and carries on once their results come back. `Chat`
+    /// describes them to the model itself; an `Infer` prompt should (see
+    /// [tools::tools_prompt](crate::tools::tools_prompt)).
+    #[serde(default)]
+    pub tools: Vec<ToolDefinition>,
+}
+
+/// The tags around a model's thinking, which is sent as `Thought` events rather than content.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThinkingTags {
+    pub start: String,
+    pub end: String,
+}
+
+impl Default for ThinkingTags {
+    fn default() -> Self {
+        Self {
+            start: "<think>".to_string(),
+            end: "</think>".to_string(),
+        }
+    }
+}
+
+/// A function a model may call, described for it the way OpenAI's `tools` are.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// JSON Schema of the arguments object.
+    #[serde(default)]
+    pub parameters: serde_json::Value,
+}
+
+/// How an engine handles a prompt longer than its context.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextOverflow {
+    /// Fail the request.
+    #[default]
+    Error,
+    /// Drop the oldest prompt tokens (keeping the first) until the prompt fits with room to
+    /// generate.
+    TruncateOldest,
+    /// Truncate like `TruncateOldest`, and when generation fills the context, discard the
+    /// oldest half of it and carry on.
+    SlidingWindow,
+}
+
+impl ContextOverflow {
+    /// How many prompt tokens to drop, after the first, so a prompt of `prompt` tokens fits a
+    /// context of `n_ctx`. A prompt that doesn't fit is cut down to leave `reserve` tokens
+    /// (at most half the context) to generate into.
+    pub fn tokens_to_drop(
+        self,
+        prompt: usize,
+        n_ctx: usize,
+        reserve: usize,
+    ) -> Result<usize, String> {
+        if prompt < n_ctx {
+            return Ok(0);
+        }
+        match self {
+            ContextOverflow::Error => Err(format!(
+                "Prompt of {} tokens does not fit the context of {}",
+                prompt, n_ctx
+            )),
+            ContextOverflow::TruncateOldest | ContextOverflow::SlidingWindow => {
+                let keep = n_ctx.saturating_sub(reserve.min(n_ctx / 2)).max(1);
+                Ok(prompt - keep)
+            }
+        }
+    }
+}
+
+/// Mirostat sampling settings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MirostatConfig {
+    pub version: MirostatVersion,
+    /// Target surprise (cross-entropy); lower values give more focused output.
+    #[serde(default = "default_mirostat_tau")]
+    pub tau: f32,
+    /// Learning rate of the feedback towards `tau`.
+    #[serde(default = "default_mirostat_eta")]
+    pub eta: f32,
+}
+
+impl Default for MirostatConfig {
+    fn default() -> Self {
+        Self {
+            version: MirostatVersion::V2,
+            tau: default_mirostat_tau(),
+            eta: default_mirostat_eta(),
+        }
+    }
+}
+
+fn default_mirostat_tau() -> f32 {
+    5.0
+}
+
+fn default_mirostat_eta() -> f32 {
+    0.1
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MirostatVersion {
+    V1,
+    V2,
+}
+
+/// A LoRA adapter and the strength it is applied with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdapterConfig {
+    /// Registry name or local path of the adapter's GGUF file.
+    pub name: String,
+    #[serde(default = "default_adapter_scale")]
+    pub scale: f32,
+}
+
+fn default_adapter_scale() -> f32 {
+    1.0
 }
 
 impl Default for InferenceConfig {
@@ -44,10 +249,312 @@ impl Default for InferenceConfig {
             max_tokens: None,
             context_size: Some(2048),
             show_thinking: true,
+            thinking_tags: None,
+            session_id: None,
+            grammar: None,
+            json_schema: None,
+            logprobs: None,
+            normalize: false,
+            adapter: None,
+            context_overflow: ContextOverflow::default(),
+            min_p: None,
+            mirostat: None,
+            images: Vec::new(),
+            end_tokens: Vec::new(),
+            render_special_tokens: false,
+            tools: Vec::new(),
+        }
+    }
+}
+
+/// How an engine loads a model and sets up its contexts. Every field left `None` keeps the
+/// model's own value or the engine's default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoadConfig {
+    /// Base frequency of the rotary position embeddings.
+    #[serde(default)]
+    pub rope_freq_base: Option<f32>,
+    /// Factor positions are scaled by; `0.25` stretches a model trained on 8k tokens to 32k.
+    #[serde(default)]
+    pub rope_freq_scale: Option<f32>,
+    /// How positions past the trained context are scaled.
+    #[serde(default)]
+    pub rope_scaling: Option<RopeScaling>,
+    /// YaRN settings, used with [RopeScaling::Yarn].
+    #[serde(default)]
+    pub yarn: YarnConfig,
+    /// Most tokens evaluated in one decode (`n_batch`). Longer prompts are evaluated in
+    /// several. Engines default to the context size, so a prompt that fits takes one decode.
+    #[serde(default)]
+    pub batch_size: Option<u32>,
+    /// Most tokens the backend computes at once within a decode (`n_ubatch`). Larger values
+    /// speed up prompt evaluation on big GPUs at the cost of compute buffer memory.
+    #[serde(default)]
+    pub ubatch_size: Option<u32>,
+    /// Compute attention with flash attention, which needs less memory for long contexts.
+    #[serde(default)]
+    pub flash_attention: Option<bool>,
+    /// Precision the KV cache is stored in. Quantized types roughly halve (`q8_0`) or quarter
+    /// (`q4_0`) its size; llama.cpp needs flash attention for a quantized V cache.
+    #[serde(default)]
+    pub kv_cache_type: Option<KvCacheType>,
+    /// Map the model file into memory instead of reading it in; on by default.
+    #[serde(default)]
+    pub use_mmap: Option<bool>,
+    /// Lock the model's memory so the OS can't page it out.
+    #[serde(default)]
+    pub use_mlock: Option<bool>,
+    /// How many of the model's layers are offloaded to the GPU; engines default to all.
+    #[serde(default)]
+    pub gpu_layers: Option<GpuLayers>,
+    /// Threads used while generating.
+    #[serde(default)]
+    pub n_threads: Option<u32>,
+    /// Threads used while evaluating prompts and other batches.
+    #[serde(default)]
+    pub n_threads_batch: Option<u32>,
+    /// Multimodal projector (`mmproj` GGUF) loaded alongside the model, which lets a vision
+    /// model take [InferenceConfig::images].
+    #[serde(default)]
+    pub mmproj: Option<String>,
+}
+
+/// Element type of the KV cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KvCacheType {
+    F16,
+    Q8_0,
+    Q4_0,
+}
+
+/// How many of a model's layers live in VRAM. In JSON, `"auto"`, `"all"` or a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GpuLayers {
+    /// As many as fit in the free VRAM, leaving room for the KV cache and compute buffers.
+    Auto,
+    All,
+    #[serde(untagged)]
+    Count(u32),
+}
+
+impl std::str::FromStr for GpuLayers {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "all" => Ok(Self::All),
+            n => n
+                .parse()
+                .map(Self::Count)
+                .map_err(|_| format!("expected auto, all or a number of layers, got '{}'", n)),
+        }
+    }
+}
+
+/// RoPE scaling method for running a model past the context it was trained on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RopeScaling {
+    None,
+    Linear,
+    /// YaRN; see [YarnConfig].
+    Yarn,
+}
+
+/// Parameters of YaRN RoPE scaling, as in llama.cpp's `--yarn-*` options. Every field left
+/// `None` keeps the model's own value.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct YarnConfig {
+    /// Context length the model was trained with.
+    #[serde(default)]
+    pub orig_ctx: Option<u32>,
+    /// Extrapolation mix factor; `0.0` is full interpolation.
+    #[serde(default)]
+    pub ext_factor: Option<f32>,
+    /// Magnitude scaling of attention.
+    #[serde(default)]
+    pub attn_factor: Option<f32>,
+    /// Low correction dimension.
+    #[serde(default)]
+    pub beta_fast: Option<f32>,
+    /// High correction dimension.
+    #[serde(default)]
+    pub beta_slow: Option<f32>,
+}
+
+/// How a speech-to-text engine transcribes audio.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptionConfig {
+    /// ISO-639-1 code of the spoken language, e.g. `en`; detected when `None`.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Translate the speech into English instead of transcribing it.
+    #[serde(default)]
+    pub translate: bool,
+    /// Text the audio follows, to carry over spelling and style.
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub temperature: f32,
+    #[serde(default)]
+    pub n_threads: Option<u32>,
+}
+
+/// Workloads of [Engine::bench](crate::engine::Engine::bench), in the style of llama-bench's
+/// `pp512` and `tg128`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchConfig {
+    /// Length of the prompt evaluated in the prompt-processing test; `0` skips it.
+    #[serde(default = "default_bench_prompt_tokens")]
+    pub prompt_tokens: usize,
+    /// Tokens generated one at a time in the generation test; `0` skips it.
+    #[serde(default = "default_bench_generated_tokens")]
+    pub generated_tokens: usize,
+    /// Times each test runs; the report has the mean.
+    #[serde(default = "default_bench_repetitions")]
+    pub repetitions: usize,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            prompt_tokens: default_bench_prompt_tokens(),
+            generated_tokens: default_bench_generated_tokens(),
+            repetitions: default_bench_repetitions(),
+        }
+    }
+}
+
+fn default_bench_prompt_tokens() -> usize {
+    512
+}
+
+fn default_bench_generated_tokens() -> usize {
+    128
+}
+
+fn default_bench_repetitions() -> usize {
+    3
+}
+
+/// How a `Rag` request retrieves documents and answers from them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RagConfig {
+    /// Model embedding the query and documents; `None` embeds with the answering model.
+    #[serde(default)]
+    pub embedding_model: Option<String>,
+    /// How many of the documents most similar to the query go in the prompt.
+    #[serde(default = "default_rag_top_k")]
+    pub top_k: usize,
+    /// How the answer is generated.
+    #[serde(default)]
+    pub inference: InferenceConfig,
+}
+
+impl Default for RagConfig {
+    fn default() -> Self {
+        Self {
+            embedding_model: None,
+            top_k: default_rag_top_k(),
+            inference: InferenceConfig::default(),
         }
     }
 }
 
+fn default_rag_top_k() -> usize {
+    3
+}
+
+/// Generation settings a model's registry entry gives it, for requests that leave them at
+/// their defaults.
+///
+/// ```toml
+/// [models.defaults]
+/// temperature = 0.6
+/// end_tokens = ["<|eot_id|>"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelDefaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repetition_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_size: Option<u32>,
+    /// Stop strings, added to a request's [InferenceConfig::end_tokens].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub end_tokens: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub show_thinking: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thinking_tags: Option<ThinkingTags>,
+}
+
+impl ModelDefaults {
+    /// `config` with these defaults in place of the settings it leaves at
+    /// [InferenceConfig::default]'s; settings a request changed win.
+    pub fn apply(&self, mut config: InferenceConfig) -> InferenceConfig {
+        fn fill<T: Clone + PartialEq>(setting: &mut T, unset: T, default: &Option<T>) {
+            if let Some(default) = default {
+                if *setting == unset {
+                    *setting = default.clone();
+                }
+            }
+        }
+        let unset = InferenceConfig::default();
+        fill(
+            &mut config.temperature,
+            unset.temperature,
+            &self.temperature,
+        );
+        fill(&mut config.top_p, unset.top_p, &self.top_p.map(Some));
+        fill(&mut config.top_k, unset.top_k, &self.top_k.map(Some));
+        fill(&mut config.min_p, unset.min_p, &self.min_p.map(Some));
+        fill(
+            &mut config.repetition_penalty,
+            unset.repetition_penalty,
+            &self.repetition_penalty.map(Some),
+        );
+        fill(
+            &mut config.max_tokens,
+            unset.max_tokens,
+            &self.max_tokens.map(Some),
+        );
+        fill(
+            &mut config.context_size,
+            unset.context_size,
+            &self.context_size.map(Some),
+        );
+        fill(
+            &mut config.show_thinking,
+            unset.show_thinking,
+            &self.show_thinking,
+        );
+        fill(
+            &mut config.thinking_tags,
+            unset.thinking_tags,
+            &self.thinking_tags.clone().map(Some),
+        );
+        for end in &self.end_tokens {
+            if !config.end_tokens.contains(end) {
+                config.end_tokens.push(end.clone());
+            }
+        }
+        config
+    }
+}
+
 impl UserManifest {
     pub fn merge(&self, other: &Self) -> Self {
         Self {
@@ -64,3 +571,120 @@ impl UserManifest {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fitting_prompt_is_kept() {
+        for policy in [
+            ContextOverflow::Error,
+            ContextOverflow::TruncateOldest,
+            ContextOverflow::SlidingWindow,
+        ] {
+            assert_eq!(policy.tokens_to_drop(100, 2048, 512), Ok(0));
+        }
+    }
+
+    #[test]
+    fn test_overflow_policies() {
+        assert!(ContextOverflow::Error
+            .tokens_to_drop(3000, 2048, 512)
+            .unwrap_err()
+            .contains("3000 tokens"));
+        // Cut down to leave the reserve free
+        assert_eq!(
+            ContextOverflow::TruncateOldest.tokens_to_drop(3000, 2048, 512),
+            Ok(3000 - 1536)
+        );
+        // The reserve never takes more than half the context
+        assert_eq!(
+            ContextOverflow::SlidingWindow.tokens_to_drop(3000, 2048, 4096),
+            Ok(3000 - 1024)
+        );
+    }
+
+    #[test]
+    fn test_overflow_serde_names() {
+        let policy: ContextOverflow = serde_json::from_str("\"truncate_oldest\"").unwrap();
+        assert_eq!(policy, ContextOverflow::TruncateOldest);
+        assert_eq!(
+            InferenceConfig::default().context_overflow,
+            ContextOverflow::Error
+        );
+    }
+
+    #[test]
+    fn test_mirostat_defaults() {
+        let config: MirostatConfig = serde_json::from_str(r#"{"version": "v1"}"#).unwrap();
+        assert_eq!(config.version, MirostatVersion::V1);
+        assert_eq!(config.tau, 5.0);
+        assert_eq!(config.eta, 0.1);
+        assert!(InferenceConfig::default().mirostat.is_none());
+    }
+
+    #[test]
+    fn test_model_defaults_fill_what_requests_leave_unset() {
+        let defaults: ModelDefaults = serde_json::from_str(
+            r#"{"temperature": 0.2, "top_k": 20, "max_tokens": 64, "end_tokens": ["<|eot_id|>"],
+                "thinking_tags": {"start": "<reasoning>", "end": "</reasoning>"}}"#,
+        )
+        .unwrap();
+        let config = defaults.apply(InferenceConfig::default());
+        assert_eq!(config.temperature, 0.2);
+        assert_eq!(config.top_k, Some(20));
+        assert_eq!(config.max_tokens, Some(64));
+        assert_eq!(config.top_p, InferenceConfig::default().top_p);
+        assert_eq!(config.end_tokens, vec!["<|eot_id|>"]);
+        assert_eq!(config.thinking_tags.unwrap().start, "<reasoning>");
+
+        // What a request sets wins; stop strings add up
+        let request = InferenceConfig {
+            temperature: 1.0,
+            max_tokens: Some(8),
+            end_tokens: vec!["###".to_string()],
+            ..Default::default()
+        };
+        let config = defaults.apply(request);
+        assert_eq!(config.temperature, 1.0);
+        assert_eq!(config.max_tokens, Some(8));
+        assert_eq!(config.top_k, Some(20));
+        assert_eq!(config.end_tokens, vec!["###", "<|eot_id|>"]);
+    }
+
+    #[test]
+    fn test_load_config_defaults() {
+        let config: LoadConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config, LoadConfig::default());
+
+        let config: LoadConfig = serde_json::from_str(
+            r#"{"rope_freq_scale": 0.25, "rope_scaling": "yarn", "yarn": {"orig_ctx": 8192}}"#,
+        )
+        .unwrap();
+        assert_eq!(config.rope_freq_scale, Some(0.25));
+        assert_eq!(config.rope_scaling, Some(RopeScaling::Yarn));
+        assert_eq!(config.yarn.orig_ctx, Some(8192));
+        assert_eq!(config.yarn.ext_factor, None);
+        assert_eq!(config.batch_size, None);
+        assert_eq!(config.ubatch_size, None);
+
+        let config: LoadConfig =
+            serde_json::from_str(r#"{"flash_attention": true, "kv_cache_type": "q8_0"}"#).unwrap();
+        assert_eq!(config.flash_attention, Some(true));
+        assert_eq!(config.kv_cache_type, Some(KvCacheType::Q8_0));
+    }
+
+    #[test]
+    fn test_gpu_layers_forms() {
+        let config: LoadConfig = serde_json::from_str(r#"{"gpu_layers": "auto"}"#).unwrap();
+        assert_eq!(config.gpu_layers, Some(GpuLayers::Auto));
+        let config: LoadConfig = serde_json::from_str(r#"{"gpu_layers": 20}"#).unwrap();
+        assert_eq!(config.gpu_layers, Some(GpuLayers::Count(20)));
+        assert_eq!(serde_json::to_string(&GpuLayers::All).unwrap(), "\"all\"");
+
+        assert_eq!("all".parse(), Ok(GpuLayers::All));
+        assert_eq!("0".parse(), Ok(GpuLayers::Count(0)));
+        assert!("most".parse::<GpuLayers>().is_err());
+    }
+}
@@ -0,0 +1,140 @@
+//! Counters and latencies the orchestrator keeps, reported by `GetMetrics`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the latency histogram buckets.
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0,
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metrics {
+    /// How long commands took, by [name](crate::protocol::BrainstemCommand::name); requests
+    /// that stream are timed until their last event.
+    pub commands: BTreeMap<String, LatencyHistogram>,
+    /// Prompt tokens evaluated, as engines that report usage count them.
+    pub prompt_tokens: u64,
+    pub generated_tokens: u64,
+    /// Models loaded because a request needed one that wasn't.
+    pub cold_reloads: u64,
+    pub download_bytes: u64,
+    /// Times a crashed engine was replaced.
+    pub engine_restarts: u64,
+    /// Model loads and downloads tried again after a transient failure.
+    pub load_retries: u64,
+}
+
+/// Latencies counted into the [LATENCY_BUCKETS].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencyHistogram {
+    /// Observations at or under each bucket's bound, so every count includes the ones before.
+    pub buckets: Vec<u64>,
+    /// Total of the observations, in seconds.
+    pub sum: f64,
+    pub count: u64,
+}
+
+impl LatencyHistogram {
+    pub fn observe(&mut self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        self.buckets.resize(LATENCY_BUCKETS.len(), 0);
+        for (count, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+impl Metrics {
+    /// Count a command `name` that took `latency`.
+    pub fn record_command(&mut self, name: &str, latency: Duration) {
+        self.commands
+            .entry(name.to_string())
+            .or_default()
+            .observe(latency);
+    }
+
+    /// The metrics in the Prometheus text exposition format, named `genius_*`.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# TYPE genius_command_duration_seconds histogram");
+        for (command, histogram) in &self.commands {
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(&histogram.buckets) {
+                let _ = writeln!(
+                    out,
+                    "genius_command_duration_seconds_bucket{{command=\"{}\",le=\"{}\"}} {}",
+                    command, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "genius_command_duration_seconds_bucket{{command=\"{}\",le=\"+Inf\"}} {}",
+                command, histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "genius_command_duration_seconds_sum{{command=\"{}\"}} {}",
+                command, histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "genius_command_duration_seconds_count{{command=\"{}\"}} {}",
+                command, histogram.count
+            );
+        }
+        let counters = [
+            ("genius_prompt_tokens_total", self.prompt_tokens),
+            ("genius_generated_tokens_total", self.generated_tokens),
+            ("genius_cold_reloads_total", self.cold_reloads),
+            ("genius_download_bytes_total", self.download_bytes),
+            ("genius_engine_restarts_total", self.engine_restarts),
+            ("genius_load_retries_total", self.load_retries),
+        ];
+        for (name, value) in counters {
+            let _ = writeln!(out, "# TYPE {} counter\n{} {}", name, name, value);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_buckets_are_cumulative() {
+        let mut histogram = LatencyHistogram::default();
+        histogram.observe(Duration::from_millis(30));
+        histogram.observe(Duration::from_secs(2));
+        assert_eq!(histogram.count, 2);
+        assert!((histogram.sum - 2.03).abs() < 1e-9);
+        // le 0.025 -> 0, le 0.05 -> 1, ..., le 2.5 -> 2
+        assert_eq!(histogram.buckets[2], 0);
+        assert_eq!(histogram.buckets[3], 1);
+        assert_eq!(histogram.buckets[8], 2);
+    }
+
+    #[test]
+    fn test_prometheus_text() {
+        let mut metrics = Metrics {
+            generated_tokens: 42,
+            ..Default::default()
+        };
+        metrics.record_command("infer", Duration::from_millis(200));
+        let text = metrics.to_prometheus();
+        assert!(text
+            .contains("genius_command_duration_seconds_bucket{command=\"infer\",le=\"0.25\"} 1\n"));
+        assert!(text
+            .contains("genius_command_duration_seconds_bucket{command=\"infer\",le=\"0.1\"} 0\n"));
+        assert!(text.contains("genius_command_duration_seconds_count{command=\"infer\"} 1\n"));
+        assert!(text.contains(
+            "# TYPE genius_generated_tokens_total counter\ngenius_generated_tokens_total 42\n"
+        ));
+    }
+}
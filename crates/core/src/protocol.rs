@@ -1,6 +1,11 @@
-pub use crate::manifest::InferenceConfig;
+pub use crate::manifest::{
+    AdapterConfig, BenchConfig, ContextOverflow, ImageInput, InferenceConfig, LoadConfig,
+    RagConfig, ToolDefinition, TranscriptionConfig, IMAGE_MARKER,
+};
 use crate::memory::{MemoryObject, MemoryObjectType};
+pub use crate::metrics::Metrics;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 // ── Context protocol types ──
 
@@ -38,10 +43,196 @@ pub enum InferenceEvent {
     ProcessStart,
     Thought(ThoughtEvent),
     Content(String),
+    /// Log probabilities of the next generated token, sent before its text when
+    /// [InferenceConfig::logprobs] is set.
+    Logprob(TokenLogprob),
     Embedding(Vec<f32>),
+    /// The embedding of input `index` of a batch embed.
+    IndexedEmbedding {
+        index: usize,
+        embedding: Vec<f32>,
+    },
+    /// Relevance of document `index` of a rerank to the query; higher is more relevant.
+    RerankScore {
+        index: usize,
+        score: f32,
+    },
+    /// `dropped` of the oldest tokens were cut to fit the context, under
+    /// [InferenceConfig::context_overflow].
+    ContextTruncated {
+        dropped: usize,
+    },
+    /// Token counts and timings of a finished generation, sent just before `Complete` by
+    /// engines that measure them.
+    Usage(TokenUsage),
+    /// Latency and throughput of a finished generation, sent after `Usage` by engines that
+    /// measure them.
+    Stats(InferenceStats),
+    /// A stretch of transcribed speech, in the order spoken.
+    Transcript(TranscriptSegment),
+    /// The documents a `Rag` request put in the prompt, most relevant first; sent before the
+    /// answer.
+    Retrieved(Vec<RetrievedDocument>),
+    /// The model called a tool offered in [InferenceConfig::tools]. The request waits, without
+    /// a `Complete`, for a `ToolResult` for each of its calls and then carries on.
+    ToolCall(ToolCall),
     Complete,
 }
 
+/// A document a `Rag` request retrieved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetrievedDocument {
+    /// Position of the document in the request.
+    pub index: usize,
+    /// Cosine similarity of its embedding to the query's.
+    pub score: f32,
+}
+
+/// A call of one of the tools in [InferenceConfig::tools].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Names the call in its `ToolResult`.
+    pub id: String,
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptSegment {
+    /// Offset of the segment into the audio.
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: usize,
+    /// Prompt tokens whose state was reused from an earlier request of the same session.
+    pub cached_tokens: usize,
+    pub completion_tokens: usize,
+    /// Wall-clock time spent evaluating the prompt.
+    pub prompt_ms: u64,
+    /// Wall-clock time spent generating the completion.
+    pub completion_ms: u64,
+}
+
+/// How fast a generation went.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InferenceStats {
+    /// From the request reaching the engine to its first generated token, including any wait
+    /// for a free slot and the prompt's evaluation.
+    pub time_to_first_token_ms: u64,
+    pub prompt_eval_ms: u64,
+    /// Prompt tokens evaluated per second; tokens reused from the cache don't count.
+    pub prompt_tokens_per_sec: f64,
+    pub generation_ms: u64,
+    /// Tokens generated per second.
+    pub tokens_per_sec: f64,
+}
+
+impl InferenceStats {
+    /// Stats for a generation with the counts and timings of `usage`.
+    pub fn new(usage: &TokenUsage, time_to_first_token_ms: u64) -> Self {
+        let per_sec = |tokens: usize, ms: u64| match ms {
+            0 => 0.0,
+            ms => tokens as f64 * 1000.0 / ms as f64,
+        };
+        Self {
+            time_to_first_token_ms,
+            prompt_eval_ms: usage.prompt_ms,
+            prompt_tokens_per_sec: per_sec(
+                usage.prompt_tokens.saturating_sub(usage.cached_tokens),
+                usage.prompt_ms,
+            ),
+            generation_ms: usage.completion_ms,
+            tokens_per_sec: per_sec(usage.completion_tokens, usage.completion_ms),
+        }
+    }
+}
+
+impl std::fmt::Display for InferenceStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.1} tok/s, first token after {} ms (prompt {} ms at {:.1} tok/s)",
+            self.tokens_per_sec,
+            self.time_to_first_token_ms,
+            self.prompt_eval_ms,
+            self.prompt_tokens_per_sec
+        )
+    }
+}
+
+/// Results of [Engine::bench](crate::engine::Engine::bench).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BenchReport {
+    pub prompt_tokens: usize,
+    /// Mean time to evaluate the prompt.
+    pub prompt_ms: f64,
+    pub prompt_tokens_per_sec: f64,
+    pub generated_tokens: usize,
+    /// Mean time to generate the tokens.
+    pub generation_ms: f64,
+    pub tokens_per_sec: f64,
+    pub repetitions: usize,
+}
+
+impl BenchReport {
+    /// The report for tests that took `prompt_ms` and `generation_ms` on average.
+    pub fn new(config: &BenchConfig, prompt_ms: f64, generation_ms: f64) -> Self {
+        let per_sec = |tokens: usize, ms: f64| {
+            if ms > 0.0 {
+                tokens as f64 * 1000.0 / ms
+            } else {
+                0.0
+            }
+        };
+        Self {
+            prompt_tokens: config.prompt_tokens,
+            prompt_ms,
+            prompt_tokens_per_sec: per_sec(config.prompt_tokens, prompt_ms),
+            generated_tokens: config.generated_tokens,
+            generation_ms,
+            tokens_per_sec: per_sec(config.generated_tokens, generation_ms),
+            repetitions: config.repetitions,
+        }
+    }
+}
+
+impl std::fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut tests = Vec::new();
+        if self.prompt_tokens > 0 {
+            tests.push(format!(
+                "pp{}: {:.2} tok/s",
+                self.prompt_tokens, self.prompt_tokens_per_sec
+            ));
+        }
+        if self.generated_tokens > 0 {
+            tests.push(format!(
+                "tg{}: {:.2} tok/s",
+                self.generated_tokens, self.tokens_per_sec
+            ));
+        }
+        write!(f, "{}", tests.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    pub token: String,
+    pub logprob: f32,
+    /// The most likely tokens at this position, most likely first.
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopLogprob {
+    pub token: String,
+    pub logprob: f32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ThoughtEvent {
     Start,
@@ -49,43 +240,358 @@ pub enum ThoughtEvent {
     Stop,
 }
 
+/// Who wrote a [ChatMessage].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    System,
+    User,
+    Assistant,
+    /// The result of a tool the assistant called.
+    Tool,
+}
+
+/// One turn of a conversation, rendered into a prompt with the model's chat template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn system(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::System,
+            content: content.into(),
+        }
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::User,
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::Assistant,
+            content: content.into(),
+        }
+    }
+
+    pub fn tool(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::Tool,
+            content: content.into(),
+        }
+    }
+}
+
+/// When the orchestrator unloads an idle engine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum CortexStrategy {
+    /// As soon as no request is running
+    Immediate,
+    /// Once nothing has come in for the duration
+    HibernateAfter(Duration),
+    /// Never
+    KeepAlive,
+}
+
+/// What the orchestrator is doing, as its `State` outputs announce.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BrainstemState {
+    /// No model has been loaded yet, or since a `Reset`
+    #[default]
+    Idle,
+    /// Downloading a model that requests wait for
+    Downloading,
+    /// Loading a model into the engine
+    Loading,
+    /// A model is loaded and no request is running
+    Ready,
+    /// Requests are running
+    Generating,
+    /// The model was unloaded after inactivity; the next request reloads it
+    Hibernated,
+    /// The engine crashed and couldn't be restarted, or crashed again reloading its model;
+    /// the next model loaded clears it
+    Faulted,
+}
+
+/// How the ids the orchestrator mints begin; commands whose ids begin so are refused, so a
+/// client's id never answers for a minted one.
+pub const MINTED_ID_PREFIX: &str = "anon-";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BrainstemInput {
+    /// Tags the outputs answering the command; the orchestrator mints one for commands
+    /// without, and tells the client with `Accepted`. Ids beginning with [MINTED_ID_PREFIX]
+    /// are refused with an `Error`
     pub id: Option<String>,
     pub command: BrainstemCommand,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BrainstemCommand {
+    /// Load a model by name or path, answered by its `Asset` events ending in `Loaded`, or an
+    /// `Error`
     LoadModel(String),
     Infer {
         model: Option<String>,
         prompt: String,
         config: InferenceConfig,
     },
+    /// Like Infer, for a prompt already tokenized with the model's vocabulary
+    InferTokens {
+        model: Option<String>,
+        tokens: Vec<i32>,
+        config: InferenceConfig,
+    },
+    /// Generate from each of `prompts`, side by side as far as the engine allows. Their events
+    /// come as `Batch` outputs tagged with the prompt's index, each ending with its own
+    /// `Complete`. Once the last prompt has ended, a plain `Complete` ends the batch, or an
+    /// `Error` naming the prompts that failed; a prompt the engine refuses leaves those after
+    /// it unstarted. Tools aren't offered to batches
+    InferBatch {
+        model: Option<String>,
+        prompts: Vec<String>,
+        config: InferenceConfig,
+    },
     Embed {
         model: Option<String>,
         input: String,
         config: InferenceConfig,
     },
+    EmbedBatch {
+        model: Option<String>,
+        inputs: Vec<String>,
+        config: InferenceConfig,
+    },
+    Rerank {
+        model: Option<String>,
+        query: String,
+        documents: Vec<String>,
+        config: InferenceConfig,
+    },
+    /// Transcribe a WAV file with a speech-to-text model
+    Transcribe {
+        model: Option<String>,
+        audio: Vec<u8>,
+        config: TranscriptionConfig,
+    },
+    /// Add `message` to the conversation `session_id`, which the orchestrator keeps. A user
+    /// message is answered by the model, prompted with the whole conversation in its chat
+    /// template, and the answer is kept too; system and assistant messages are only recorded
+    Chat {
+        session_id: String,
+        message: ChatMessage,
+        config: InferenceConfig,
+    },
+    /// Answer `query` from `documents`: embed the query (and the documents, unless their
+    /// `embeddings` are given, in order), put the `top_k` most similar documents in the model's
+    /// chat template with the question, and stream the answer after a `Retrieved` event naming
+    /// them
+    Rag {
+        model: Option<String>,
+        query: String,
+        documents: Vec<String>,
+        #[serde(default)]
+        embeddings: Vec<Vec<f32>>,
+        config: RagConfig,
+    },
     ListModels,
+    /// Free the engine context kept for a session id, and forget its saved state and
+    /// conversation
+    CloseSession(String),
+    /// Report the engine's backend, devices and loaded model, and check it generates
+    Health,
+    /// Measure prompt-processing and generation speed
+    Bench {
+        model: Option<String>,
+        config: BenchConfig,
+    },
+    /// Apply a LoRA adapter to the loaded model for requests that don't pick their own
+    ApplyAdapter(AdapterConfig),
+    RemoveAdapter,
+    /// The result of the call `call_id` a request made with `InferenceEvent::ToolCall`, sent
+    /// with the id of that request. Generation carries on once every call has its result; a
+    /// `call_id` the request didn't make is answered with an `Error` under that call id
+    ToolResult {
+        call_id: String,
+        content: String,
+    },
+    /// Abort the request with id `id`, whether it is running or waiting behind another
+    Cancel {
+        id: String,
+    },
+    /// Report the orchestrator's counters and latencies; answered at once
+    GetMetrics,
+    /// Change when the engine unloads while idle; answered at once with `Complete`
+    SetStrategy(CortexStrategy),
     Reset,
     Stop,
 }
 
+impl BrainstemCommand {
+    /// The command's name in snake case, e.g. `infer`, as metrics label it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BrainstemCommand::LoadModel(_) => "load_model",
+            BrainstemCommand::Infer { .. } => "infer",
+            BrainstemCommand::InferTokens { .. } => "infer_tokens",
+            BrainstemCommand::InferBatch { .. } => "infer_batch",
+            BrainstemCommand::Embed { .. } => "embed",
+            BrainstemCommand::EmbedBatch { .. } => "embed_batch",
+            BrainstemCommand::Rerank { .. } => "rerank",
+            BrainstemCommand::Transcribe { .. } => "transcribe",
+            BrainstemCommand::Chat { .. } => "chat",
+            BrainstemCommand::Rag { .. } => "rag",
+            BrainstemCommand::ListModels => "list_models",
+            BrainstemCommand::CloseSession(_) => "close_session",
+            BrainstemCommand::Health => "health",
+            BrainstemCommand::Bench { .. } => "bench",
+            BrainstemCommand::ApplyAdapter(_) => "apply_adapter",
+            BrainstemCommand::RemoveAdapter => "remove_adapter",
+            BrainstemCommand::ToolResult { .. } => "tool_result",
+            BrainstemCommand::Cancel { .. } => "cancel",
+            BrainstemCommand::GetMetrics => "get_metrics",
+            BrainstemCommand::SetStrategy(_) => "set_strategy",
+            BrainstemCommand::Reset => "reset",
+            BrainstemCommand::Stop => "stop",
+        }
+    }
+
+    /// The model the command names to run on, or loads.
+    pub fn model(&self) -> Option<&String> {
+        match self {
+            BrainstemCommand::LoadModel(model) => Some(model),
+            BrainstemCommand::Infer { model, .. }
+            | BrainstemCommand::InferTokens { model, .. }
+            | BrainstemCommand::InferBatch { model, .. }
+            | BrainstemCommand::Embed { model, .. }
+            | BrainstemCommand::EmbedBatch { model, .. }
+            | BrainstemCommand::Rerank { model, .. }
+            | BrainstemCommand::Rag { model, .. }
+            | BrainstemCommand::Bench { model, .. } => model.as_ref(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelDescriptor {
     pub id: String,
     pub purpose: String,
+    /// The registry's tags, then `cached` once downloaded and `loaded` while loaded.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Details of the model, for models that are loaded or downloaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<ModelInfo>,
+}
+
+/// Details of a model, read from its GGUF metadata.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    /// `general.architecture`, e.g. `llama` or `qwen2`.
+    pub architecture: Option<String>,
+    pub parameter_count: u64,
+    /// e.g. `Q4_K_M`.
+    pub quantization: Option<String>,
+    /// Context length the model was trained with.
+    pub context_length: Option<u64>,
+    /// Size of the model's embeddings (hidden state).
+    pub embedding_length: Option<u64>,
+    pub vocab_size: Option<u64>,
+    /// Jinja chat template (`tokenizer.chat_template`).
+    pub chat_template: Option<String>,
+    /// Layers the engine offloaded to the GPU when it loaded the model.
+    #[serde(default)]
+    pub gpu_layers: Option<u32>,
+}
+
+/// What [Engine::health](crate::engine::Engine::health) found.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EngineHealth {
+    /// The inference backend, e.g. `llama.cpp`.
+    pub backend: String,
+    /// Whether the backend was built with GPU offload (CUDA, Metal, Vulkan, ...).
+    pub gpu_offload: bool,
+    /// Devices the backend can compute on.
+    #[serde(default)]
+    pub devices: Vec<DeviceInfo>,
+    /// The loaded model; `None` when nothing is loaded.
+    pub model: Option<ModelInfo>,
+    /// Result of generating a token with the loaded model; `None` when nothing is loaded.
+    pub smoke_test: Option<SmokeTest>,
+    /// What the orchestrator was doing when asked; engines leave it to the orchestrator.
+    #[serde(default)]
+    pub state: Option<BrainstemState>,
+}
+
+impl EngineHealth {
+    /// Whether the engine works: no smoke test failed and the orchestrator isn't `Faulted`.
+    pub fn is_healthy(&self) -> bool {
+        self.smoke_test.as_ref().is_none_or(|test| test.passed)
+            && self.state != Some(BrainstemState::Faulted)
+    }
+}
+
+/// A compute device, e.g. `CUDA0`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub name: String,
+    /// e.g. `NVIDIA GeForce RTX 4090`.
+    pub description: String,
+    pub memory_total: u64,
+    pub memory_free: u64,
+    /// Whether model layers can be offloaded to it (a discrete or integrated GPU).
+    #[serde(default)]
+    pub gpu: bool,
+}
+
+/// Outcome of a one-token generation run to check an engine.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SmokeTest {
+    pub passed: bool,
+    pub elapsed_ms: u64,
+    pub error: Option<String>,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum AssetEvent {
     /// Starting resolution and download process
     Started(String),
+    /// Waiting for a download slot; `position` is the number of downloads ahead
+    Queued { position: usize },
+    /// Continuing a partial download from `offset` bytes (of `total`)
+    Resuming { offset: u64, total: u64 },
     /// Download progress in bytes (current, total)
     Progress(u64, u64),
+    /// Measured transfer rate and estimated seconds remaining (if the size is known)
+    Rate {
+        bytes_per_sec: u64,
+        eta_secs: Option<u64>,
+    },
+    /// Download finished; checking the file before it is made available
+    Verifying(String),
+    /// An abandoned partial download at this path was deleted
+    Discarded(String),
+    /// The model's license (EULA text or URL) must be accepted before it can be downloaded
+    LicenseRequired { model: String, license: String },
     /// Successfully downloaded
     Complete(String),
+    /// A request needs the model, named or at this path, loaded before it runs; `Loaded`
+    /// follows once the engine has it
+    Loading(String),
+    /// The engine loaded the model at `path`, offloading `gpu_layers` of its layers to the
+    /// GPU if it says how many
+    Loaded {
+        path: String,
+        gpu_layers: Option<u32>,
+    },
     /// Error during asset handling
     Error(String),
 }
@@ -94,16 +600,76 @@ pub enum AssetEvent {
 pub struct BrainstemOutput {
     pub id: Option<String>,
     pub body: BrainstemBody,
+    /// Counts the outputs of each request from 0, in the order the orchestrator sent them, so
+    /// a consumer can tell when one was lost or reordered; an id used again after its request
+    /// completed or failed counts afresh. Lifecycle outputs, which carry no id, are counted
+    /// together
+    #[serde(default)]
+    pub seq: u64,
+}
+
+impl BrainstemOutput {
+    /// An output for the orchestrator to number as it sends it.
+    pub fn new(id: Option<String>, body: BrainstemBody) -> Self {
+        Self { id, body, seq: 0 }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum BrainstemBody {
+    /// A command came without an id and its outputs carry the id of this output; sent as soon
+    /// as it arrives
+    Accepted,
+    /// The command is queued behind others, `queue_position` commands back (`1` is next).
+    /// `estimated_wait` extrapolates from how long requests took so far; `None` until some
+    /// have finished
+    Busy {
+        queue_position: usize,
+        estimated_wait: Option<Duration>,
+    },
     /// Standard inference and thought events
     Event(InferenceEvent),
+    /// An event of prompt `index` of an `InferBatch`
+    Batch { index: usize, event: InferenceEvent },
     /// Progress/status of asset management
     Asset(AssetEvent),
     /// List of available models
     ModelList(Vec<ModelDescriptor>),
+    /// Results of a health check
+    Health(EngineHealth),
+    /// Results of a benchmark
+    Bench(BenchReport),
+    /// The orchestrator's counters and latencies, answering `GetMetrics`
+    Metrics(Metrics),
+    /// The request was cancelled before it finished; sent in place of `Complete`
+    Cancelled,
+    /// Follows the request's `ToolCall`s, every one it made; it waits for their `ToolResult`s
+    /// before carrying on
+    AwaitingTools,
+    /// The engine unloaded its model, after inactivity or a `Reset`; the next request reloads
+    /// it. Lifecycle outputs like this one carry no request id
+    Hibernated,
+    /// The engine is loading a model after being unloaded; requests wait for it
+    Waking,
+    /// The engine has loaded its model and serves requests without a reload
+    Ready,
+    /// The engine crashed and was replaced, with its model reloaded; requests that failed with
+    /// it can be retried
+    Restarted,
+    /// The orchestrator went from one state to another; the state machine behind the
+    /// lifecycle outputs above
+    State {
+        from: BrainstemState,
+        to: BrainstemState,
+    },
+    /// Loading the request's model failed with `error`, which looks transient (a busy file,
+    /// memory not yet freed, a dropped download); retry `attempt` (from `1`) starts after
+    /// `delay`. The request fails once the retries are spent
+    Retrying {
+        attempt: u32,
+        delay: Duration,
+        error: String,
+    },
     /// Catch-all for engine or orchestrator errors
     Error(String),
 }
@@ -157,3 +723,54 @@ pub enum MemoryBody {
     Ack,
     Error(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_rates() {
+        let usage = TokenUsage {
+            prompt_tokens: 120,
+            cached_tokens: 20,
+            completion_tokens: 50,
+            prompt_ms: 200,
+            completion_ms: 2_000,
+        };
+        let stats = InferenceStats::new(&usage, 250);
+        assert_eq!(stats.time_to_first_token_ms, 250);
+        assert_eq!(stats.prompt_tokens_per_sec, 500.0);
+        assert_eq!(stats.tokens_per_sec, 25.0);
+        assert_eq!(
+            stats.to_string(),
+            "25.0 tok/s, first token after 250 ms (prompt 200 ms at 500.0 tok/s)"
+        );
+    }
+
+    #[test]
+    fn test_bench_report() {
+        let report = BenchReport::new(&BenchConfig::default(), 256.0, 4_000.0);
+        assert_eq!(report.prompt_tokens_per_sec, 2_000.0);
+        assert_eq!(report.tokens_per_sec, 32.0);
+        assert_eq!(
+            report.to_string(),
+            "pp512: 2000.00 tok/s, tg128: 32.00 tok/s"
+        );
+
+        let config = BenchConfig {
+            prompt_tokens: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            BenchReport::new(&config, 0.0, 4_000.0).to_string(),
+            "tg128: 32.00 tok/s"
+        );
+    }
+
+    #[test]
+    fn test_stats_without_timings() {
+        let stats = InferenceStats::new(&TokenUsage::default(), 0);
+        assert_eq!(stats.prompt_tokens_per_sec, 0.0);
+        assert_eq!(stats.tokens_per_sec, 0.0);
+    }
+}
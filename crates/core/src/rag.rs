@@ -0,0 +1,75 @@
+//! Retrieval-augmented generation: picking the documents most relevant to a question and
+//! prompting a model to answer from them.
+
+use crate::cosine::cosine_similarity;
+use crate::protocol::{ChatMessage, RetrievedDocument};
+
+const INSTRUCTIONS: &str = "Answer the question using the numbered documents below. \
+     Say so if they don't hold the answer.";
+
+/// The `top_k` of `documents` (embeddings, by index) most similar to `query`, most similar
+/// first.
+pub fn rank(query: &[f32], documents: &[Vec<f32>], top_k: usize) -> Vec<RetrievedDocument> {
+    let mut ranked: Vec<RetrievedDocument> = documents
+        .iter()
+        .enumerate()
+        .map(|(index, embedding)| RetrievedDocument {
+            index,
+            score: cosine_similarity(query, embedding),
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    ranked.truncate(top_k);
+    ranked
+}
+
+/// The conversation asking `query` of the `retrieved` ones of `documents`, numbered from 1 in
+/// the order retrieved.
+pub fn messages(
+    query: &str,
+    documents: &[String],
+    retrieved: &[RetrievedDocument],
+) -> Vec<ChatMessage> {
+    let context = retrieved
+        .iter()
+        .enumerate()
+        .map(|(n, document)| format!("[{}] {}", n + 1, documents[document.index].trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    vec![
+        ChatMessage::system(INSTRUCTIONS),
+        ChatMessage::user(format!(
+            "Documents:\n{}\n\nQuestion: {}",
+            context,
+            query.trim()
+        )),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_keeps_the_most_similar() {
+        let documents = vec![vec![0.0, 1.0], vec![1.0, 0.1], vec![1.0, 0.0]];
+        let ranked = rank(&[1.0, 0.0], &documents, 2);
+        let indices: Vec<_> = ranked.iter().map(|d| d.index).collect();
+        assert_eq!(indices, vec![2, 1]);
+        assert!((ranked[0].score - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_messages_number_the_documents() {
+        let documents = vec!["Cats purr.".to_string(), "Dogs bark.".to_string()];
+        let retrieved = [RetrievedDocument {
+            index: 1,
+            score: 0.9,
+        }];
+        let messages = messages("What do dogs do?", &documents, &retrieved);
+        assert_eq!(
+            messages[1].content,
+            "Documents:\n[1] Dogs bark.\n\nQuestion: What do dogs do?"
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! The async runtime the stack spawns its tasks on: async-std with the `async-std` feature, or
+//! the application's own tokio runtime with `tokio`, which wins when both are enabled. Crates
+//! spawn and sleep through here, so a tokio application doesn't run a second runtime beside
+//! its own. With tokio, these must be called from within its runtime.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+/// A spawned task; awaiting it gives its output, and dropping it leaves it running.
+pub struct Task<T> {
+    #[cfg(feature = "tokio")]
+    handle: tokio::task::JoinHandle<T>,
+    #[cfg(not(feature = "tokio"))]
+    handle: async_std::task::JoinHandle<T>,
+}
+
+impl<T> Future for Task<T> {
+    type Output = T;
+
+    #[cfg(feature = "tokio")]
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        // A task that panicked panics its awaiter too, as with async-std
+        Pin::new(&mut self.handle)
+            .poll(cx)
+            .map(|joined| match joined {
+                Ok(output) => output,
+                Err(e) => match e.try_into_panic() {
+                    Ok(panic) => std::panic::resume_unwind(panic),
+                    Err(e) => panic!("{}", e),
+                },
+            })
+    }
+
+    #[cfg(not(feature = "tokio"))]
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        Pin::new(&mut self.handle).poll(cx)
+    }
+}
+
+/// Run `future` in the background.
+pub fn spawn<F>(future: F) -> Task<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(feature = "tokio")]
+    let handle = tokio::task::spawn(future);
+    #[cfg(not(feature = "tokio"))]
+    let handle = async_std::task::spawn(future);
+    Task { handle }
+}
+
+/// Run `f` on a thread where blocking is fine, such as a model's decode loop.
+pub fn spawn_blocking<F, T>(f: F) -> Task<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    #[cfg(feature = "tokio")]
+    let handle = tokio::task::spawn_blocking(f);
+    #[cfg(not(feature = "tokio"))]
+    let handle = async_std::task::spawn_blocking(f);
+    Task { handle }
+}
+
+/// Wait for `duration`.
+pub async fn sleep(duration: Duration) {
+    #[cfg(feature = "tokio")]
+    tokio::time::sleep(duration).await;
+    #[cfg(not(feature = "tokio"))]
+    async_std::task::sleep(duration).await;
+}
+
+// The tests run on async-std
+#[cfg(all(test, not(feature = "tokio")))]
+mod tests {
+    use super::*;
+
+    #[async_std::test]
+    async fn test_tasks_give_their_output() {
+        let task = spawn(async { 1 + 1 });
+        let blocking = spawn_blocking(|| "done");
+        sleep(Duration::from_millis(1)).await;
+        assert_eq!(task.await, 2);
+        assert_eq!(blocking.await, "done");
+    }
+}
@@ -0,0 +1,149 @@
+//! Tool calling.
+//!
+//! Models are told about their tools with [tools_prompt], in the Hermes format most tool-tuned
+//! models were trained on, and answer with calls wrapped in `<tool_call>` tags. [parse_tool_calls]
+//! reads those back, along with the bare JSON calls of Llama 3 and OpenAI-style function objects.
+
+use crate::manifest::ToolDefinition;
+use crate::protocol::ToolCall;
+use serde_json::{json, Value};
+
+const CALL_START: &str = "<tool_call>";
+const CALL_END: &str = "</tool_call>";
+
+/// Instructions describing `tools` to a model, for its system prompt.
+pub fn tools_prompt(tools: &[ToolDefinition]) -> String {
+    let mut prompt = String::from(
+        "You may call one or more functions to help with the user query. \
+         The functions you can call are described in <tools></tools> XML tags:\n<tools>\n",
+    );
+    for tool in tools {
+        let function = json!({
+            "type": "function",
+            "function": {
+                "name": tool.name,
+                "description": tool.description.clone().unwrap_or_default(),
+                "parameters": tool.parameters,
+            }
+        });
+        prompt.push_str(&function.to_string());
+        prompt.push('\n');
+    }
+    prompt.push_str(
+        "</tools>\n\nFor each function call, answer with a JSON object holding the function \
+         name and its arguments within <tool_call></tool_call> XML tags:\n\
+         <tool_call>\n{\"name\": <function-name>, \"arguments\": <args-json-object>}\n</tool_call>",
+    );
+    prompt
+}
+
+/// Whether a model's output so far could still turn out to be a tool call, and so shouldn't
+/// be shown yet.
+pub fn may_be_tool_call(output: &str) -> bool {
+    let output = output.trim_start();
+    output.starts_with(CALL_START)
+        || CALL_START.starts_with(output)
+        || output.starts_with('{')
+        || output.starts_with('[')
+}
+
+/// The tool calls a model's output consists of, numbered `call_0`, `call_1`, ... in order;
+/// empty when it isn't made of calls. Takes calls in `<tool_call>` tags, or a bare JSON call
+/// or list of calls, with the arguments under `arguments` or `parameters`.
+pub fn parse_tool_calls(output: &str) -> Vec<ToolCall> {
+    let output = output.trim();
+    let values: Vec<Value> = if output.starts_with(CALL_START) {
+        let mut values = Vec::new();
+        for block in output.split(CALL_START).skip(1) {
+            let block = block.split(CALL_END).next().unwrap_or_default();
+            match serde_json::from_str(block.trim()) {
+                Ok(value) => values.push(value),
+                Err(_) => return Vec::new(),
+            }
+        }
+        values
+    } else {
+        match serde_json::from_str(output) {
+            Ok(Value::Array(values)) => values,
+            Ok(value) => vec![value],
+            Err(_) => return Vec::new(),
+        }
+    };
+
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| to_call(value, index))
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default()
+}
+
+fn to_call(mut value: Value, index: usize) -> Option<ToolCall> {
+    // OpenAI nests the call in a `function` object
+    if let Some(function) = value.get_mut("function").map(Value::take) {
+        value = function;
+    }
+    let name = value.get("name")?.as_str()?.to_string();
+    let arguments = match value.get("arguments").or_else(|| value.get("parameters")) {
+        // Some models write the arguments as a JSON string
+        Some(Value::String(text)) => serde_json::from_str(text).ok()?,
+        Some(arguments) => arguments.clone(),
+        None => json!({}),
+    };
+    Some(ToolCall {
+        id: format!("call_{}", index),
+        name,
+        arguments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tagged_calls() {
+        let output = "<tool_call>\n{\"name\": \"get_weather\", \"arguments\": {\"city\": \"Oslo\"}}\n</tool_call>\n\
+                      <tool_call>\n{\"name\": \"get_time\", \"arguments\": \"{}\"}\n</tool_call>";
+        let calls = parse_tool_calls(output);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].id, "call_0");
+        assert_eq!(calls[0].name, "get_weather");
+        assert_eq!(calls[0].arguments, json!({"city": "Oslo"}));
+        assert_eq!(calls[1].name, "get_time");
+        assert_eq!(calls[1].arguments, json!({}));
+    }
+
+    #[test]
+    fn test_parse_bare_calls() {
+        let llama3 = r#"{"name": "search", "parameters": {"query": "rust"}}"#;
+        assert_eq!(
+            parse_tool_calls(llama3)[0].arguments,
+            json!({"query": "rust"})
+        );
+
+        let openai = r#"[{"type": "function", "function": {"name": "a"}}, {"name": "b"}]"#;
+        let names: Vec<_> = parse_tool_calls(openai)
+            .into_iter()
+            .map(|c| c.name)
+            .collect();
+        assert_eq!(names, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn test_text_is_not_a_call() {
+        assert!(parse_tool_calls("The weather is fine.").is_empty());
+        assert!(parse_tool_calls(r#"{"answer": 42}"#).is_empty());
+        assert!(parse_tool_calls("<tool_call>{broken</tool_call>").is_empty());
+    }
+
+    #[test]
+    fn test_may_be_tool_call() {
+        assert!(may_be_tool_call(""));
+        assert!(may_be_tool_call("\n<tool"));
+        assert!(may_be_tool_call("<tool_call>{\"na"));
+        assert!(may_be_tool_call(" {\"name\""));
+        assert!(!may_be_tool_call("<think"));
+        assert!(!may_be_tool_call("Sure"));
+    }
+}
@@ -0,0 +1,96 @@
+/// Turns a stream of byte chunks into text without splitting characters.
+///
+/// Tokenizers cut text at byte boundaries, so one token can end partway through a multi-byte
+/// character (CJK, emoji) that the next token completes. [Utf8Buffer::push] returns only the
+/// complete characters and holds an unfinished one back until the rest arrives. Invalid bytes
+/// come out as U+FFFD.
+#[derive(Debug, Default)]
+pub struct Utf8Buffer {
+    pending: Vec<u8>,
+}
+
+impl Utf8Buffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `bytes` and return the text they complete, which may be empty.
+    pub fn push(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+
+        let mut text = String::new();
+        let mut rest = self.pending.as_slice();
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    rest = &[];
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[len..];
+                        }
+                        // An unfinished character at the end: wait for its remaining bytes
+                        None => {
+                            rest = after;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.pending = rest.to_vec();
+        text
+    }
+
+    /// Return whatever is held back, once no more bytes will come.
+    pub fn finish(&mut self) -> String {
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passes_ascii_through() {
+        let mut buffer = Utf8Buffer::new();
+        assert_eq!(buffer.push(b"hello"), "hello");
+        assert_eq!(buffer.finish(), "");
+    }
+
+    #[test]
+    fn joins_split_characters() {
+        let bytes = "日本🙂".as_bytes();
+        let mut buffer = Utf8Buffer::new();
+        let mut text = String::new();
+        for byte in bytes {
+            text.push_str(&buffer.push(&[*byte]));
+        }
+        assert_eq!(text, "日本🙂");
+
+        // Nothing is emitted until the emoji's last byte arrives
+        let mut buffer = Utf8Buffer::new();
+        assert_eq!(buffer.push(&"🙂".as_bytes()[..2]), "");
+        assert_eq!(buffer.push(&"🙂".as_bytes()[2..]), "🙂");
+    }
+
+    #[test]
+    fn replaces_invalid_bytes() {
+        let mut buffer = Utf8Buffer::new();
+        assert_eq!(buffer.push(b"a\xffb"), "a\u{fffd}b");
+
+        // A character cut off for good is flushed as a replacement
+        assert_eq!(buffer.push(&"é".as_bytes()[..1]), "");
+        assert_eq!(buffer.finish(), "\u{fffd}");
+    }
+}
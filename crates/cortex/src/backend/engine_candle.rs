@@ -0,0 +1,405 @@
+#![cfg(feature = "candle-engine")]
+
+use crate::bridge::EventSender;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::generation::{LogitsProcessor, Sampling};
+use candle_transformers::models::llama::{Cache, Config, Llama, LlamaConfig, LlamaEosToks};
+use candle_transformers::utils::apply_repeat_penalty;
+use futures::channel::mpsc;
+use rusty_genius_core::engine::{CancellationToken, Engine};
+use rusty_genius_core::manifest::InferenceConfig;
+use rusty_genius_core::protocol::{InferenceEvent, ModelInfo, TokenUsage};
+use rusty_genius_core::runtime;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokenizers::Tokenizer;
+
+/// Most tokens generated for one request.
+const MAX_GENERATED_TOKENS: usize = 512;
+
+/// How many recent tokens the repetition penalty looks at.
+const PENALTY_LAST_N: usize = 64;
+
+/// A Llama-family model and its tokenizer, read from a Hugging Face model directory.
+struct LoadedModel {
+    path: String,
+    model: Llama,
+    config: Config,
+    tokenizer: Arc<Tokenizer>,
+    /// Tokens that end a generation.
+    eos: Vec<u32>,
+    chat_template: Option<String>,
+}
+
+/// Engine running safetensors models with candle, in pure Rust.
+///
+/// [Engine::load_model] takes a directory holding `config.json`, `tokenizer.json` and the
+/// `*.safetensors` weights (or the path of one of the weight files). Llama-architecture models
+/// (Llama, Mistral, Qwen-style checkpoints in the Llama layout, TinyLlama, ...) are supported.
+pub struct CandleEngine {
+    model: Option<LoadedModel>,
+    device: Device,
+}
+
+impl CandleEngine {
+    /// An engine on the first CUDA device when candle was built with CUDA, else on the CPU.
+    pub fn new() -> Self {
+        Self::with_device(Device::cuda_if_available(0).unwrap_or(Device::Cpu))
+    }
+
+    pub fn with_device(device: Device) -> Self {
+        Self {
+            model: None,
+            device,
+        }
+    }
+
+    fn dtype(&self) -> DType {
+        if self.device.is_cpu() {
+            DType::F32
+        } else {
+            DType::BF16
+        }
+    }
+}
+
+impl Default for CandleEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Engine for CandleEngine {
+    async fn load_model(&mut self, model_path: &str) -> Result<()> {
+        let path = Path::new(model_path);
+        let dir = if path.is_dir() {
+            path
+        } else {
+            path.parent()
+                .ok_or_else(|| anyhow!("{} is not a model directory", model_path))?
+        };
+
+        let config: LlamaConfig = serde_json::from_slice(&std::fs::read(dir.join("config.json"))?)
+            .map_err(|e| anyhow!("Unsupported config.json in {}: {}", dir.display(), e))?;
+        let eos = match &config.eos_token_id {
+            Some(LlamaEosToks::Single(token)) => vec![*token],
+            Some(LlamaEosToks::Multiple(tokens)) => tokens.clone(),
+            None => Vec::new(),
+        };
+        let config = config.into_config(false);
+
+        let tokenizer = Tokenizer::from_file(dir.join("tokenizer.json"))
+            .map_err(|e| anyhow!("Failed to load tokenizer from {}: {}", dir.display(), e))?;
+
+        let weights = weight_files(dir)?;
+        // SAFETY: the weight files are memory-mapped and must not change while loaded
+        let vb =
+            unsafe { VarBuilder::from_mmaped_safetensors(&weights, self.dtype(), &self.device)? };
+        let model = Llama::load(vb, &config)
+            .map_err(|e| anyhow!("Failed to load model from {}: {}", dir.display(), e))?;
+
+        self.model = Some(LoadedModel {
+            path: model_path.to_string(),
+            model,
+            config,
+            tokenizer: Arc::new(tokenizer),
+            eos,
+            chat_template: read_chat_template(dir),
+        });
+        Ok(())
+    }
+
+    async fn unload_model(&mut self) -> Result<()> {
+        self.model = None;
+        Ok(())
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.model.is_some()
+    }
+
+    fn default_model(&self) -> String {
+        self.model
+            .as_ref()
+            .map(|loaded| loaded.path.clone())
+            .unwrap_or_else(|| "TinyLlama/TinyLlama-1.1B-Chat-v1.0".to_string())
+    }
+
+    fn chat_template(&self) -> Option<String> {
+        self.model.as_ref()?.chat_template.clone()
+    }
+
+    fn model_info(&self) -> Option<ModelInfo> {
+        let config = &self.model.as_ref()?.config;
+        Some(ModelInfo {
+            architecture: Some("llama".to_string()),
+            context_length: Some(config.max_position_embeddings as u64),
+            embedding_length: Some(config.hidden_size as u64),
+            vocab_size: Some(config.vocab_size as u64),
+            ..Default::default()
+        })
+    }
+
+    async fn infer(
+        &mut self,
+        prompt: &str,
+        config: InferenceConfig,
+        cancel: CancellationToken,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        let dtype = self.dtype();
+        let device = self.device.clone();
+        let loaded = self
+            .model
+            .as_ref()
+            .ok_or_else(|| anyhow!("No model loaded"))?;
+        let job = Job {
+            model: loaded.model.clone(),
+            config: loaded.config.clone(),
+            tokenizer: loaded.tokenizer.clone(),
+            eos: loaded
+                .eos
+                .iter()
+                .copied()
+                .chain(
+                    config
+                        .end_tokens
+                        .iter()
+                        .filter_map(|token| loaded.tokenizer.token_to_id(token)),
+                )
+                .collect(),
+            dtype,
+            device,
+        };
+
+        let prompt = prompt.to_string();
+        let (tx, rx) = mpsc::channel(100);
+        runtime::spawn_blocking(move || {
+            let mut events = EventSender::new(tx);
+            if let Err(e) = job.generate(&prompt, &config, &cancel, &mut events) {
+                events.send(Err(e));
+            }
+        });
+
+        Ok(rx)
+    }
+
+    async fn embed(
+        &mut self,
+        _input: &str,
+        _config: InferenceConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        Err(anyhow!("The candle engine does not support embeddings"))
+    }
+}
+
+/// Everything one generation needs, moved onto its worker thread.
+struct Job {
+    model: Llama,
+    config: Config,
+    tokenizer: Arc<Tokenizer>,
+    eos: Vec<u32>,
+    dtype: DType,
+    device: Device,
+}
+
+impl Job {
+    fn generate(
+        &self,
+        prompt: &str,
+        inference: &InferenceConfig,
+        cancel: &CancellationToken,
+        events: &mut EventSender,
+    ) -> Result<()> {
+        events.send(Ok(InferenceEvent::ProcessStart));
+
+        let mut tokens = self
+            .tokenizer
+            .encode(prompt, true)
+            .map_err(|e| anyhow!("Tokenize failed: {}", e))?
+            .get_ids()
+            .to_vec();
+
+        let n_ctx = inference
+            .context_size
+            .map_or(self.config.max_position_embeddings, |n| n as usize)
+            .min(self.config.max_position_embeddings);
+        let reserve = inference.max_tokens.unwrap_or(MAX_GENERATED_TOKENS);
+        let dropped = inference
+            .context_overflow
+            .tokens_to_drop(tokens.len(), n_ctx, reserve)
+            .map_err(|e| anyhow!(e))?;
+        if dropped > 0 {
+            tokens.drain(1..1 + dropped);
+            events.send(Ok(InferenceEvent::ContextTruncated { dropped }));
+        }
+
+        let mut usage = TokenUsage {
+            prompt_tokens: tokens.len(),
+            ..Default::default()
+        };
+        let max_new = reserve.min(n_ctx.saturating_sub(tokens.len()));
+        let mut cache = Cache::new(true, self.dtype, &self.config, &self.device)?;
+        let mut sampler = LogitsProcessor::from_sampling(random_seed(), sampling(inference));
+        let mut text = TextStream::new(!inference.render_special_tokens);
+
+        let prompt_start = Instant::now();
+        let mut started = prompt_start;
+        let mut pos = 0;
+        for step in 0..max_new {
+            if cancel.is_cancelled() || events.is_closed() {
+                break;
+            }
+
+            // The first step evaluates the whole prompt, later ones the last sampled token
+            let input = if step == 0 {
+                &tokens[..]
+            } else {
+                &tokens[tokens.len() - 1..]
+            };
+            let input = Tensor::new(input, &self.device)?.unsqueeze(0)?;
+            let mut logits = self.model.forward(&input, pos, &mut cache)?.squeeze(0)?;
+            pos = tokens.len();
+            if step == 0 {
+                usage.prompt_ms = prompt_start.elapsed().as_millis() as u64;
+                started = Instant::now();
+            }
+
+            if let Some(penalty) = inference.repetition_penalty.filter(|p| *p != 1.0) {
+                let recent = &tokens[tokens.len().saturating_sub(PENALTY_LAST_N)..];
+                logits = apply_repeat_penalty(&logits, penalty, recent)?;
+            }
+            let next = sampler.sample(&logits)?;
+            if self.eos.contains(&next) {
+                break;
+            }
+            tokens.push(next);
+            usage.completion_tokens += 1;
+
+            if let Some(piece) = text.push(&self.tokenizer, next)? {
+                events.send(Ok(InferenceEvent::Content(piece)));
+            }
+        }
+
+        if let Some(rest) = text.finish(&self.tokenizer)? {
+            events.send(Ok(InferenceEvent::Content(rest)));
+        }
+        usage.completion_ms = started.elapsed().as_millis() as u64;
+        events.send(Ok(InferenceEvent::Usage(usage)));
+        events.send(Ok(InferenceEvent::Complete));
+        Ok(())
+    }
+}
+
+/// Turns generated tokens into text, holding back pieces that end partway through a character.
+struct TextStream {
+    tokens: Vec<u32>,
+    /// Start of the tokens not yet emitted.
+    emitted: usize,
+    /// Leave special tokens out of the text.
+    skip_special: bool,
+}
+
+impl TextStream {
+    fn new(skip_special: bool) -> Self {
+        Self {
+            tokens: Vec::new(),
+            emitted: 0,
+            skip_special,
+        }
+    }
+
+    /// Add `token` and return the text it completes, if any.
+    fn push(&mut self, tokenizer: &Tokenizer, token: u32) -> Result<Option<String>> {
+        let before = self.decode(tokenizer)?;
+        self.tokens.push(token);
+        let after = self.decode(tokenizer)?;
+        if after.len() > before.len() && !after.ends_with(char::REPLACEMENT_CHARACTER) {
+            self.emitted = self.tokens.len();
+            return Ok(Some(after));
+        }
+        Ok(None)
+    }
+
+    /// Return whatever is held back, once generation is over.
+    fn finish(&mut self, tokenizer: &Tokenizer) -> Result<Option<String>> {
+        let rest = self.decode(tokenizer)?;
+        self.emitted = self.tokens.len();
+        Ok((!rest.is_empty()).then_some(rest))
+    }
+
+    fn decode(&self, tokenizer: &Tokenizer) -> Result<String> {
+        tokenizer
+            .decode(&self.tokens[self.emitted..], self.skip_special)
+            .map_err(|e| anyhow!("Detokenize failed: {}", e))
+    }
+}
+
+/// The candle sampling mode closest to `config`. Min-p and Mirostat are not available.
+fn sampling(config: &InferenceConfig) -> Sampling {
+    let temperature = config.temperature as f64;
+    if temperature <= 0.0 {
+        return Sampling::ArgMax;
+    }
+    match (config.top_k, config.top_p) {
+        (Some(k), Some(p)) => Sampling::TopKThenTopP {
+            k: k as usize,
+            p: p as f64,
+            temperature,
+        },
+        (Some(k), None) => Sampling::TopK {
+            k: k as usize,
+            temperature,
+        },
+        (None, Some(p)) => Sampling::TopP {
+            p: p as f64,
+            temperature,
+        },
+        (None, None) => Sampling::All { temperature },
+    }
+}
+
+fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+/// The model's safetensors files: those listed in `model.safetensors.index.json` for a
+/// sharded model, else every `*.safetensors` file in `dir`.
+fn weight_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let index_path = dir.join("model.safetensors.index.json");
+    let mut files: Vec<PathBuf> = if index_path.exists() {
+        let index: serde_json::Value = serde_json::from_slice(&std::fs::read(&index_path)?)?;
+        let weight_map = index["weight_map"]
+            .as_object()
+            .ok_or_else(|| anyhow!("{} has no weight_map", index_path.display()))?;
+        weight_map
+            .values()
+            .filter_map(|file| file.as_str())
+            .map(|file| dir.join(file))
+            .collect()
+    } else {
+        std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "safetensors"))
+            .collect()
+    };
+    files.sort();
+    files.dedup();
+    if files.is_empty() {
+        return Err(anyhow!("No .safetensors weights in {}", dir.display()));
+    }
+    Ok(files)
+}
+
+/// The `chat_template` of the model's `tokenizer_config.json`, if it has one.
+fn read_chat_template(dir: &Path) -> Option<String> {
+    let config: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(dir.join("tokenizer_config.json")).ok()?).ok()?;
+    config["chat_template"].as_str().map(str::to_string)
+}
@@ -4,9 +4,11 @@ use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use futures::channel::mpsc;
 use futures::sink::SinkExt;
-use rusty_genius_core::engine::Engine;
+use rusty_genius_core::cosine::l2_normalize;
+use rusty_genius_core::engine::{CancellationToken, Engine};
 use rusty_genius_core::manifest::InferenceConfig;
 use rusty_genius_core::protocol::{InferenceEvent, ThoughtEvent};
+use rusty_genius_core::runtime;
 use serde::{Deserialize, Serialize};
 
 // ── API Configuration ──
@@ -247,6 +249,10 @@ impl Engine for GeminiEngine {
         self.loaded
     }
 
+    fn is_remote(&self) -> bool {
+        true
+    }
+
     fn default_model(&self) -> String {
         "gemini-2.0-flash".to_string()
     }
@@ -255,6 +261,7 @@ impl Engine for GeminiEngine {
         &mut self,
         prompt: &str,
         config: InferenceConfig,
+        cancel: CancellationToken,
     ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
         if !self.loaded {
             return Err(anyhow!("GeminiEngine: no model loaded"));
@@ -290,11 +297,19 @@ impl Engine for GeminiEngine {
 
         let (mut tx, rx) = mpsc::channel(100);
 
-        smol::spawn(async move {
+        runtime::spawn(async move {
             let _ = tx.send(Ok(InferenceEvent::ProcessStart)).await;
             let mut in_thought = false;
 
             for line in raw.lines() {
+                if cancel.is_cancelled() || tx.is_closed() {
+                    if in_thought {
+                        let _ = tx
+                            .send(Ok(InferenceEvent::Thought(ThoughtEvent::Stop)))
+                            .await;
+                    }
+                    break;
+                }
                 let line = line.trim();
                 if line.is_empty() || line.starts_with(':') {
                     continue;
@@ -336,8 +351,7 @@ impl Engine for GeminiEngine {
             }
 
             let _ = tx.send(Ok(InferenceEvent::Complete)).await;
-        })
-        .detach();
+        });
 
         Ok(rx)
     }
@@ -345,7 +359,7 @@ impl Engine for GeminiEngine {
     async fn embed(
         &mut self,
         input: &str,
-        _config: InferenceConfig,
+        config: InferenceConfig,
     ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
         if !self.loaded {
             return Err(anyhow!("GeminiEngine: no model loaded"));
@@ -379,19 +393,21 @@ impl Engine for GeminiEngine {
             .await
             .map_err(|e| anyhow!("Failed to read embed response body: {}", e))?;
 
-        let embed_resp: EmbedResponse =
-            serde_json::from_str(&raw).map_err(|e| anyhow!("Failed to parse embed response: {}", e))?;
+        let embed_resp: EmbedResponse = serde_json::from_str(&raw)
+            .map_err(|e| anyhow!("Failed to parse embed response: {}", e))?;
 
-        let values = embed_resp.embedding.values;
+        let mut values = embed_resp.embedding.values;
+        if config.normalize {
+            l2_normalize(&mut values);
+        }
 
         let (mut tx, rx) = mpsc::channel(100);
 
-        smol::spawn(async move {
+        runtime::spawn(async move {
             let _ = tx.send(Ok(InferenceEvent::ProcessStart)).await;
             let _ = tx.send(Ok(InferenceEvent::Embedding(values))).await;
             let _ = tx.send(Ok(InferenceEvent::Complete)).await;
-        })
-        .detach();
+        });
 
         Ok(rx)
     }
@@ -0,0 +1,477 @@
+#![cfg(feature = "openai")]
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use futures::io::AsyncBufReadExt;
+use futures::sink::SinkExt;
+use futures::StreamExt;
+use rusty_genius_core::cosine::l2_normalize;
+use rusty_genius_core::engine::{CancellationToken, Engine};
+use rusty_genius_core::manifest::InferenceConfig;
+use rusty_genius_core::protocol::{
+    InferenceEvent, ThoughtEvent, TokenLogprob, TokenUsage, TopLogprob,
+};
+use rusty_genius_core::runtime;
+use serde::Deserialize;
+use serde_json::json;
+
+// ── API Configuration ──
+
+/// Endpoint and credentials of an OpenAI-compatible server (OpenAI, llama-server, vLLM,
+/// Ollama, ...).
+#[derive(Debug, Clone)]
+pub struct OpenAiApiConfig {
+    /// API root the `/chat/completions` and `/embeddings` paths are appended to, e.g.
+    /// `https://api.openai.com/v1`.
+    pub base_url: String,
+    /// Sent as a Bearer token; local servers usually need none.
+    pub api_key: Option<String>,
+    /// Model used when a request names none.
+    pub default_model: String,
+}
+
+impl OpenAiApiConfig {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            api_key: None,
+            default_model: "gpt-4o-mini".to_string(),
+        }
+    }
+
+    pub fn with_api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    pub fn with_default_model(mut self, model: impl Into<String>) -> Self {
+        self.default_model = model.into();
+        self
+    }
+
+    /// Read `OPENAI_BASE_URL` (default `https://api.openai.com/v1`), `OPENAI_API_KEY` and
+    /// `OPENAI_MODEL`.
+    pub fn from_env() -> Self {
+        let base_url = std::env::var("OPENAI_BASE_URL")
+            .unwrap_or_else(|_| "https://api.openai.com/v1".to_string());
+        let mut config = Self::new(base_url);
+        config.api_key = std::env::var("OPENAI_API_KEY").ok();
+        if let Ok(model) = std::env::var("OPENAI_MODEL") {
+            config.default_model = model;
+        }
+        config
+    }
+}
+
+// ── Response serde types ──
+
+#[derive(Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    usage: Option<ChunkUsage>,
+    error: Option<ApiError>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    delta: Option<ChunkDelta>,
+    logprobs: Option<ChunkLogprobs>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChunkDelta {
+    content: Option<String>,
+    reasoning_content: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ChunkLogprobs {
+    #[serde(default)]
+    content: Vec<ChunkLogprob>,
+}
+
+#[derive(Deserialize)]
+struct ChunkLogprob {
+    token: String,
+    logprob: f32,
+    #[serde(default)]
+    top_logprobs: Vec<ChunkTopLogprob>,
+}
+
+#[derive(Deserialize)]
+struct ChunkTopLogprob {
+    token: String,
+    logprob: f32,
+}
+
+#[derive(Deserialize)]
+struct ChunkUsage {
+    prompt_tokens: usize,
+    completion_tokens: usize,
+    prompt_tokens_details: Option<PromptTokensDetails>,
+}
+
+#[derive(Deserialize)]
+struct PromptTokensDetails {
+    #[serde(default)]
+    cached_tokens: usize,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// One parsed line of a streamed chat completion.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpenAiSseLine {
+    Chunk(OpenAiChunk),
+    /// The `data: [DONE]` terminator.
+    Done,
+    /// An error the server reported mid-stream.
+    Error(String),
+}
+
+/// The parts of a streamed chat completion chunk the engine forwards.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OpenAiChunk {
+    pub content: Option<String>,
+    /// Reasoning text, as llama-server, vLLM and DeepSeek send it (`reasoning_content`).
+    pub reasoning: Option<String>,
+    pub logprobs: Vec<TokenLogprob>,
+    pub finish_reason: Option<String>,
+    pub usage: Option<TokenUsage>,
+}
+
+// ── URL / body construction (public for testing) ──
+
+/// Build the chat completions URL for the given config.
+pub fn chat_completions_url(config: &OpenAiApiConfig) -> String {
+    format!("{}/chat/completions", config.base_url.trim_end_matches('/'))
+}
+
+/// Build the embeddings URL for the given config.
+pub fn embeddings_url(config: &OpenAiApiConfig) -> String {
+    format!("{}/embeddings", config.base_url.trim_end_matches('/'))
+}
+
+/// Build the JSON body for a streaming chat completion request.
+pub fn build_chat_body(prompt: &str, model: &str, config: &InferenceConfig) -> serde_json::Value {
+    let mut body = json!({
+        "model": model,
+        "messages": [{ "role": "user", "content": prompt }],
+        "stream": true,
+        "stream_options": { "include_usage": true },
+        "temperature": config.temperature,
+    });
+    if let Some(top_p) = config.top_p {
+        body["top_p"] = json!(top_p);
+    }
+    if let Some(max_tokens) = config.max_tokens {
+        body["max_tokens"] = json!(max_tokens);
+    }
+    if let Some(schema) = &config.json_schema {
+        body["response_format"] = json!({
+            "type": "json_schema",
+            "json_schema": { "name": "response", "schema": schema, "strict": true },
+        });
+    }
+    if let Some(top_logprobs) = config.logprobs {
+        body["logprobs"] = json!(true);
+        body["top_logprobs"] = json!(top_logprobs);
+    }
+    body
+}
+
+/// Build the JSON body for an embeddings request.
+pub fn build_embeddings_body(inputs: &[String], model: &str) -> serde_json::Value {
+    json!({ "model": model, "input": inputs })
+}
+
+/// Parse a single SSE `data: ...` line of a streamed chat completion.
+pub fn parse_openai_sse_line(line: &str) -> Option<OpenAiSseLine> {
+    let data = line.strip_prefix("data:")?.trim_start();
+    if data == "[DONE]" {
+        return Some(OpenAiSseLine::Done);
+    }
+    let chunk: ChatChunk = serde_json::from_str(data).ok()?;
+    if let Some(error) = chunk.error {
+        return Some(OpenAiSseLine::Error(error.message));
+    }
+
+    let mut parsed = OpenAiChunk {
+        usage: chunk.usage.map(|usage| TokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            cached_tokens: usage
+                .prompt_tokens_details
+                .map_or(0, |details| details.cached_tokens),
+            completion_tokens: usage.completion_tokens,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    if let Some(choice) = chunk.choices.into_iter().next() {
+        if let Some(delta) = choice.delta {
+            parsed.content = delta.content.filter(|text| !text.is_empty());
+            parsed.reasoning = delta.reasoning_content.filter(|text| !text.is_empty());
+        }
+        if let Some(logprobs) = choice.logprobs {
+            parsed.logprobs = logprobs
+                .content
+                .into_iter()
+                .map(|entry| TokenLogprob {
+                    token: entry.token,
+                    logprob: entry.logprob,
+                    top_logprobs: entry
+                        .top_logprobs
+                        .into_iter()
+                        .map(|top| TopLogprob {
+                            token: top.token,
+                            logprob: top.logprob,
+                        })
+                        .collect(),
+                })
+                .collect();
+        }
+        parsed.finish_reason = choice.finish_reason;
+    }
+    Some(OpenAiSseLine::Chunk(parsed))
+}
+
+// ── OpenAiEngine ──
+
+/// Proxies inference and embeddings to a remote OpenAI-compatible server.
+pub struct OpenAiEngine {
+    config: OpenAiApiConfig,
+    model: String,
+    loaded: bool,
+}
+
+impl OpenAiEngine {
+    pub fn new(config: OpenAiApiConfig) -> Self {
+        Self {
+            model: config.default_model.clone(),
+            config,
+            loaded: false,
+        }
+    }
+
+    /// POST `body` to `url` with auth, failing on a non-success status.
+    async fn post(&self, url: &str, body: &serde_json::Value) -> Result<surf::Response> {
+        let mut req = surf::post(url)
+            .header("Content-Type", "application/json")
+            .body_json(body)
+            .map_err(|e| anyhow!("Failed to build request body: {}", e))?;
+        if let Some(api_key) = &self.config.api_key {
+            req = req.header("Authorization", format!("Bearer {}", api_key));
+        }
+
+        let mut response = req
+            .await
+            .map_err(|e| anyhow!("OpenAI API request failed: {}", e))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let err_body = response
+                .body_string()
+                .await
+                .unwrap_or_else(|_| "unknown".to_string());
+            return Err(anyhow!("OpenAI API error {}: {}", status, err_body));
+        }
+        Ok(response)
+    }
+
+    /// Embed `inputs` in one request, returning each embedding with its input index.
+    async fn embeddings(
+        &self,
+        inputs: &[String],
+        config: &InferenceConfig,
+    ) -> Result<Vec<(usize, Vec<f32>)>> {
+        if !self.loaded {
+            return Err(anyhow!("OpenAiEngine: no model loaded"));
+        }
+
+        let body = build_embeddings_body(inputs, &self.model);
+        let mut response = self.post(&embeddings_url(&self.config), &body).await?;
+        let raw = response
+            .body_string()
+            .await
+            .map_err(|e| anyhow!("Failed to read embed response body: {}", e))?;
+        let parsed: EmbeddingsResponse = serde_json::from_str(&raw)
+            .map_err(|e| anyhow!("Failed to parse embed response: {}", e))?;
+
+        Ok(parsed
+            .data
+            .into_iter()
+            .map(|mut data| {
+                if config.normalize {
+                    l2_normalize(&mut data.embedding);
+                }
+                (data.index, data.embedding)
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl Engine for OpenAiEngine {
+    async fn load_model(&mut self, model_name: &str) -> Result<()> {
+        self.model = model_name.to_string();
+        self.loaded = true;
+        Ok(())
+    }
+
+    async fn unload_model(&mut self) -> Result<()> {
+        self.loaded = false;
+        Ok(())
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+
+    fn is_remote(&self) -> bool {
+        true
+    }
+
+    fn default_model(&self) -> String {
+        self.config.default_model.clone()
+    }
+
+    async fn infer(
+        &mut self,
+        prompt: &str,
+        config: InferenceConfig,
+        cancel: CancellationToken,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        if !self.loaded {
+            return Err(anyhow!("OpenAiEngine: no model loaded"));
+        }
+
+        let body = build_chat_body(prompt, &self.model, &config);
+        let mut response = self
+            .post(&chat_completions_url(&self.config), &body)
+            .await?;
+        let mut lines = response.take_body().lines();
+
+        let (mut tx, rx) = mpsc::channel(100);
+
+        runtime::spawn(async move {
+            let _ = tx.send(Ok(InferenceEvent::ProcessStart)).await;
+            let mut in_thought = false;
+            let mut usage = None;
+
+            while let Some(line) = lines.next().await {
+                if cancel.is_cancelled() || tx.is_closed() {
+                    break;
+                }
+                let line = match line {
+                    Ok(line) => line,
+                    Err(e) => {
+                        let _ = tx
+                            .send(Err(anyhow!("Failed to read response stream: {}", e)))
+                            .await;
+                        break;
+                    }
+                };
+
+                let chunk = match parse_openai_sse_line(line.trim()) {
+                    Some(OpenAiSseLine::Chunk(chunk)) => chunk,
+                    Some(OpenAiSseLine::Done) => break,
+                    Some(OpenAiSseLine::Error(message)) => {
+                        let _ = tx.send(Err(anyhow!("OpenAI API error: {}", message))).await;
+                        break;
+                    }
+                    None => continue,
+                };
+
+                if let Some(text) = chunk.reasoning {
+                    if !in_thought {
+                        let _ = tx
+                            .send(Ok(InferenceEvent::Thought(ThoughtEvent::Start)))
+                            .await;
+                        in_thought = true;
+                    }
+                    let _ = tx
+                        .send(Ok(InferenceEvent::Thought(ThoughtEvent::Delta(text))))
+                        .await;
+                }
+                if let Some(text) = chunk.content {
+                    if in_thought {
+                        let _ = tx
+                            .send(Ok(InferenceEvent::Thought(ThoughtEvent::Stop)))
+                            .await;
+                        in_thought = false;
+                    }
+                    for logprob in chunk.logprobs {
+                        let _ = tx.send(Ok(InferenceEvent::Logprob(logprob))).await;
+                    }
+                    let _ = tx.send(Ok(InferenceEvent::Content(text))).await;
+                }
+                if chunk.usage.is_some() {
+                    usage = chunk.usage;
+                }
+            }
+
+            if in_thought {
+                let _ = tx
+                    .send(Ok(InferenceEvent::Thought(ThoughtEvent::Stop)))
+                    .await;
+            }
+            if let Some(usage) = usage {
+                let _ = tx.send(Ok(InferenceEvent::Usage(usage))).await;
+            }
+            let _ = tx.send(Ok(InferenceEvent::Complete)).await;
+        });
+
+        Ok(rx)
+    }
+
+    async fn embed(
+        &mut self,
+        input: &str,
+        config: InferenceConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        let (_, embedding) = self
+            .embeddings(&[input.to_string()], &config)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("OpenAI API returned no embedding"))?;
+
+        let (mut tx, rx) = mpsc::channel(3);
+        let _ = tx.try_send(Ok(InferenceEvent::ProcessStart));
+        let _ = tx.try_send(Ok(InferenceEvent::Embedding(embedding)));
+        let _ = tx.try_send(Ok(InferenceEvent::Complete));
+        Ok(rx)
+    }
+
+    async fn embed_batch(
+        &mut self,
+        inputs: &[String],
+        config: InferenceConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        let embeddings = self.embeddings(inputs, &config).await?;
+
+        let (mut tx, rx) = mpsc::channel(embeddings.len() + 2);
+        let _ = tx.try_send(Ok(InferenceEvent::ProcessStart));
+        for (index, embedding) in embeddings {
+            let _ = tx.try_send(Ok(InferenceEvent::IndexedEmbedding { index, embedding }));
+        }
+        let _ = tx.try_send(Ok(InferenceEvent::Complete));
+        Ok(rx)
+    }
+}
@@ -1,20 +1,43 @@
 #![cfg(feature = "real-engine")]
 
-use rusty_genius_core::engine::Engine;
+use crate::bridge::EventSender;
+use crate::offload::fit_gpu_layers;
+use crate::pool::ModelPool;
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use futures::channel::mpsc;
-use futures::sink::SinkExt;
-use llama_cpp_2::context::params::LlamaContextParams;
+use futures::channel::{mpsc, oneshot};
+use llama_cpp_2::context::params::{
+    KvCacheType as LlamaKvCacheType, LlamaContextParams, LlamaPoolingType, RopeScalingType,
+};
+use llama_cpp_2::context::LlamaContext;
 use llama_cpp_2::llama_backend::LlamaBackend;
 use llama_cpp_2::llama_batch::LlamaBatch;
 use llama_cpp_2::model::params::LlamaModelParams;
-use llama_cpp_2::model::{AddBos, LlamaModel, Special};
+use llama_cpp_2::model::{AddBos, LlamaLoraAdapter, LlamaModel};
+use llama_cpp_2::mtmd::{MtmdBitmap, MtmdContext, MtmdContextParams, MtmdInputText};
 use llama_cpp_2::sampling::LlamaSampler;
-use rusty_genius_core::manifest::InferenceConfig;
-use rusty_genius_core::protocol::{InferenceEvent, ThoughtEvent};
+use llama_cpp_2::token::LlamaToken;
+use llama_cpp_2::token_type::LlamaTokenAttr;
+use llama_cpp_2::{LlamaBackendDevice, LlamaBackendDeviceType, TokenToStringError};
+use rusty_genius_core::cosine::l2_normalize;
+use rusty_genius_core::engine::{smoke_test, CancellationToken, Engine};
+use rusty_genius_core::gguf::{file_type_name, split_parts};
+use rusty_genius_core::json_schema;
+use rusty_genius_core::manifest::{
+    BenchConfig, ContextOverflow, GpuLayers, ImageInput, InferenceConfig, KvCacheType, LoadConfig,
+    MirostatVersion, RopeScaling, IMAGE_MARKER,
+};
+use rusty_genius_core::protocol::{
+    BenchReport, DeviceInfo, EngineHealth, InferenceEvent, InferenceStats, ModelInfo, ThoughtEvent,
+    TokenLogprob, TokenUsage, TopLogprob,
+};
+use rusty_genius_core::runtime;
+use rusty_genius_core::utf8::Utf8Buffer;
+use std::collections::{HashMap, VecDeque};
 use std::num::NonZeroU32;
-use std::sync::{Arc, OnceLock};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
 
 static LLAMA_BACKEND: OnceLock<Arc<LlamaBackend>> = OnceLock::new();
 
@@ -24,24 +47,380 @@ fn get_llama_backend() -> Arc<LlamaBackend> {
         .clone()
 }
 
+/// Most sequences [Brain::embed_batch] and [Brain::rerank] evaluate in one decode.
+const MAX_BATCH_SEQUENCES: usize = 64;
+
+/// How many session contexts a [Brain] keeps by default; see [Brain::with_max_sessions].
+pub const DEFAULT_MAX_SESSIONS: usize = 4;
+
+/// How many requests a [Brain] generates for at once by default; see [Brain::with_max_parallel].
+pub const DEFAULT_MAX_PARALLEL: usize = 4;
+
+/// Tokens of context a sequence gets in a shared batch when its request doesn't ask for a size.
+const DEFAULT_SEQUENCE_CONTEXT: usize = 2048;
+
+/// Most tokens generated for a request that doesn't set `max_tokens`.
+const MAX_GENERATED_TOKENS: usize = 512;
+
+/// How many recent tokens the repetition penalty looks at.
+const PENALTY_LAST_N: i32 = 64;
+
+/// llama.cpp's `LLAMA_DEFAULT_SEED`, which makes a sampler seed itself randomly.
+const RANDOM_SEED: u32 = u32::MAX;
+
+/// A LoRA adapter loaded for the current model.
+struct Adapter(LlamaLoraAdapter);
+
+// SAFETY: the adapter is a handle to weights owned by the model, which outlives it; llama.cpp
+// only reads it, and every use goes through the mutex in [SharedAdapter].
+unsafe impl Send for Adapter {}
+
+type SharedAdapter = Arc<Mutex<Adapter>>;
+
+/// A multimodal projector, which turns images into embeddings its model takes.
+struct Projector {
+    ctx: MtmdContext,
+    /// The projector refers to the model, so it keeps it alive.
+    _model: Arc<LlamaModel>,
+}
+
+// SAFETY: the projector isn't tied to a thread, and every use goes through the mutex in
+// [SharedProjector].
+unsafe impl Send for Projector {}
+
+type SharedProjector = Arc<Mutex<Projector>>;
+
+/// What a job evaluates before generating.
+enum Prompt {
+    Text(String),
+    /// Tokens evaluated as given, without a BOS added.
+    Tokens(Vec<LlamaToken>),
+}
+
+impl Prompt {
+    fn tokenize(&self, model: &LlamaModel) -> Result<Vec<LlamaToken>> {
+        match self {
+            Prompt::Text(text) => model
+                .str_to_token(text, AddBos::Always)
+                .map_err(|e| anyhow!("Tokenize failed: {}", e)),
+            Prompt::Tokens(tokens) => Ok(tokens.clone()),
+        }
+    }
+}
+
+struct InferJob {
+    prompt: Prompt,
+    config: InferenceConfig,
+    /// The adapter and scale to generate with; `None` runs the base model.
+    adapter: Option<(SharedAdapter, f32)>,
+    /// The projector to evaluate the request's images with; `None` when it has none.
+    projector: Option<SharedProjector>,
+    cancel: CancellationToken,
+    events: EventSender,
+    /// When the request reached the engine.
+    received: Instant,
+}
+
+/// An inference context and what has been evaluated into it.
+#[derive(Default)]
+struct ContextState<'m> {
+    ctx: Option<LlamaContext<'m>>,
+    /// Tokens in the KV cache, in order.
+    history: Vec<LlamaToken>,
+    /// The adapter set on `ctx`.
+    adapter: Option<(SharedAdapter, f32)>,
+    /// Saved session to load into the context before the next request.
+    restore: Option<PathBuf>,
+}
+
+/// Work for a session's thread.
+enum SessionJob {
+    Infer(InferJob),
+    /// Write the session to a file.
+    Save(PathBuf, oneshot::Sender<Result<()>>),
+    /// Pick the session up from a saved file at the next request.
+    Restore(PathBuf),
+}
+
+/// A conversation's context. It lives on a worker thread that runs the session's requests in
+/// order; dropping `jobs` lets the worker finish its current request and free the context.
+struct Session {
+    jobs: std::sync::mpsc::Sender<SessionJob>,
+    last_used: Instant,
+}
+
+/// A model in the pool, with the sessions and adapters that belong to it.
+struct LoadedModel {
+    model: Arc<LlamaModel>,
+    /// Settings the model was loaded with, applied to every context created for it.
+    load: Arc<LoadConfig>,
+    sessions: HashMap<String, Session>,
+    /// Queue of the thread that batches requests without a session, started on first use.
+    batcher: Option<std::sync::mpsc::Sender<InferJob>>,
+    /// Adapters loaded for this model, by path.
+    adapters: HashMap<String, SharedAdapter>,
+    /// The adapter set with [Engine::apply_adapter], used by requests that don't pick one.
+    adapter: Option<(SharedAdapter, f32)>,
+    /// Projector loaded from [LoadConfig::mmproj], for requests with images.
+    projector: Option<SharedProjector>,
+    /// Layers offloaded to the GPU.
+    gpu_layers: u32,
+}
+
+impl LoadedModel {
+    fn new(
+        model: Arc<LlamaModel>,
+        load: LoadConfig,
+        projector: Option<SharedProjector>,
+        gpu_layers: u32,
+    ) -> Self {
+        Self {
+            model,
+            load: Arc::new(load),
+            sessions: HashMap::new(),
+            batcher: None,
+            adapters: HashMap::new(),
+            adapter: None,
+            projector,
+            gpu_layers,
+        }
+    }
+
+    fn session(
+        &mut self,
+        id: &str,
+        backend: &Arc<LlamaBackend>,
+        max_sessions: usize,
+    ) -> Result<&mut Session> {
+        if !self.sessions.contains_key(id) {
+            if self.sessions.len() >= max_sessions {
+                if let Some(oldest) = self
+                    .sessions
+                    .iter()
+                    .min_by_key(|(_, session)| session.last_used)
+                    .map(|(oldest, _)| oldest.clone())
+                {
+                    self.sessions.remove(&oldest);
+                }
+            }
+
+            let (jobs, queue) = std::sync::mpsc::channel::<SessionJob>();
+            let model = self.model.clone();
+            let load = self.load.clone();
+            let backend = backend.clone();
+            std::thread::Builder::new()
+                .name(format!("brain-session-{}", id))
+                .spawn(move || {
+                    let mut state = ContextState::default();
+                    for job in queue {
+                        match job {
+                            SessionJob::Infer(job) => {
+                                run_job(&model, &backend, &load, &mut state, job)
+                            }
+                            SessionJob::Save(path, reply) => {
+                                let _ = reply.send(save_state(&state, &path));
+                            }
+                            SessionJob::Restore(path) => state.restore = Some(path),
+                        }
+                    }
+                })
+                .map_err(|e| anyhow!("Failed to start session '{}': {}", id, e))?;
+
+            self.sessions.insert(
+                id.to_string(),
+                Session {
+                    jobs,
+                    last_used: Instant::now(),
+                },
+            );
+        }
+
+        let session = self
+            .sessions
+            .get_mut(id)
+            .expect("session was just inserted");
+        session.last_used = Instant::now();
+        Ok(session)
+    }
+
+    /// Hand `job` to the batching thread, starting it if needed.
+    fn batch(
+        &mut self,
+        job: InferJob,
+        backend: &Arc<LlamaBackend>,
+        max_parallel: usize,
+    ) -> Result<()> {
+        let job = match &self.batcher {
+            Some(batcher) => match batcher.send(job) {
+                Ok(()) => return Ok(()),
+                // The thread has stopped; start another
+                Err(std::sync::mpsc::SendError(job)) => job,
+            },
+            None => job,
+        };
+        self.start_batcher(job, backend, max_parallel)
+    }
+
+    fn start_batcher(
+        &mut self,
+        job: InferJob,
+        backend: &Arc<LlamaBackend>,
+        max_parallel: usize,
+    ) -> Result<()> {
+        let (jobs, queue) = std::sync::mpsc::channel::<InferJob>();
+        let model = self.model.clone();
+        let load = self.load.clone();
+        let backend = backend.clone();
+        std::thread::Builder::new()
+            .name("brain-batch".to_string())
+            .spawn(move || run_batcher(&model, &backend, &load, queue, max_parallel))
+            .map_err(|e| anyhow!("Failed to start batching: {}", e))?;
+        jobs.send(job)
+            .map_err(|_| anyhow!("Batching has stopped"))?;
+        self.batcher = Some(jobs);
+        Ok(())
+    }
+
+    /// The adapter at `path`, loading it into this model on first use.
+    fn load_adapter(&mut self, path: &str) -> Result<SharedAdapter> {
+        if let Some(adapter) = self.adapters.get(path) {
+            return Ok(adapter.clone());
+        }
+        if !Path::new(path).exists() {
+            return Err(anyhow!("Adapter {} does not exist", path));
+        }
+        let adapter = self
+            .model
+            .lora_adapter_init(path)
+            .map_err(|e| anyhow!("Failed to load adapter from {}: {}", path, e))?;
+        let adapter = Arc::new(Mutex::new(Adapter(adapter)));
+        self.adapters.insert(path.to_string(), adapter.clone());
+        Ok(adapter)
+    }
+}
+
 pub struct Brain {
-    model: Option<Arc<LlamaModel>>,
+    /// Every loaded model, by path; requests run on `current`.
+    models: ModelPool<LoadedModel>,
+    current: Option<String>,
     backend: Arc<LlamaBackend>,
-    model_loaded: bool,
+    max_sessions: usize,
+    max_parallel: usize,
 }
 
 impl Brain {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Keep at most `max` session contexts per model; opening another closes the least
+    /// recently used.
+    pub fn with_max_sessions(mut self, max: usize) -> Self {
+        self.max_sessions = max.max(1);
+        self
+    }
+
+    /// Generate for up to `max` requests without a session at once, interleaving their tokens in
+    /// one batch per decode step. Further requests wait for a free slot.
+    pub fn with_max_parallel(mut self, max: usize) -> Self {
+        self.max_parallel = max.max(1);
+        self
+    }
+
+    /// Keep at most `max` models loaded; see [ModelPool::with_max_models].
+    pub fn with_max_models(mut self, max: usize) -> Self {
+        self.models = self.models.with_max_models(max);
+        self
+    }
+
+    /// Keep the loaded models' combined size under `bytes`; see
+    /// [ModelPool::with_memory_budget].
+    pub fn with_memory_budget(mut self, bytes: u64) -> Self {
+        self.models = self.models.with_memory_budget(bytes);
+        self
+    }
+
+    /// The model requests currently run on.
+    fn loaded(&mut self) -> Result<&mut LoadedModel> {
+        self.current
+            .as_deref()
+            .and_then(|path| self.models.get(path))
+            .ok_or_else(|| anyhow!("No model loaded"))
+    }
+
+    fn model(&mut self) -> Result<(Arc<LlamaModel>, Arc<LoadConfig>)> {
+        let loaded = self.loaded()?;
+        Ok((loaded.model.clone(), loaded.load.clone()))
+    }
+
+    /// Queue a generation on the current model: on its session's thread, on a context of its
+    /// own, or in the shared batch.
+    fn submit(
+        &mut self,
+        prompt: Prompt,
+        config: InferenceConfig,
+        cancel: CancellationToken,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        let backend = self.backend.clone();
+        let max_sessions = self.max_sessions;
+        let max_parallel = self.max_parallel;
+        let loaded = self.loaded()?;
+        let model = loaded.model.clone();
+        let load = loaded.load.clone();
+
+        let adapter = match &config.adapter {
+            Some(requested) => Some((loaded.load_adapter(&requested.name)?, requested.scale)),
+            None => loaded.adapter.clone(),
+        };
+        let projector = match (config.images.is_empty(), &loaded.projector) {
+            (true, _) => None,
+            (false, Some(projector)) => Some(projector.clone()),
+            (false, None) => {
+                return Err(anyhow!(
+                    "The model has no projector for images; load it with an mmproj"
+                ))
+            }
+        };
+
+        let (tx, rx) = mpsc::channel(100);
+        let job = InferJob {
+            prompt,
+            config,
+            adapter,
+            projector,
+            cancel,
+            events: EventSender::new(tx),
+            received: Instant::now(),
+        };
+
+        match job.config.session_id.clone() {
+            Some(id) => loaded
+                .session(&id, &backend, max_sessions)?
+                .jobs
+                .send(SessionJob::Infer(job))
+                .map_err(|_| anyhow!("Session '{}' has stopped", id))?,
+            // An adapter applies to a whole context, and images can't join the shared batch, so
+            // those requests get a context of their own
+            None if job.adapter.is_some() || job.projector.is_some() => {
+                runtime::spawn_blocking(move || {
+                    run_job(&model, &backend, &load, &mut ContextState::default(), job)
+                });
+            }
+            None => loaded.batch(job, &backend, max_parallel)?,
+        }
+
+        Ok(rx)
+    }
 }
 
 impl Default for Brain {
     fn default() -> Self {
         Self {
-            model: None,
+            models: ModelPool::new(),
+            current: None,
             backend: get_llama_backend(),
-            model_loaded: false,
+            max_sessions: DEFAULT_MAX_SESSIONS,
+            max_parallel: DEFAULT_MAX_PARALLEL,
         }
     }
 }
@@ -49,282 +428,1780 @@ impl Default for Brain {
 #[async_trait]
 impl Engine for Brain {
     async fn load_model(&mut self, model_path: &str) -> Result<()> {
-        // Load model
-        let params = LlamaModelParams::default();
-        let model = LlamaModel::load_from_file(&self.backend, model_path, &params)
-            .map_err(|e| anyhow!("Failed to load model from {}: {}", model_path, e))?;
-        self.model = Some(Arc::new(model));
-        self.model_loaded = true;
+        self.load_model_with_config(model_path, LoadConfig::default())
+            .await
+    }
+
+    // A model that is still in the pool with the same settings only becomes current again;
+    // its sessions and adapters are kept. Different settings load it afresh.
+    async fn load_model_with_config(&mut self, model_path: &str, config: LoadConfig) -> Result<()> {
+        let model_path = &first_split_part(model_path)?;
+        let resident = self
+            .models
+            .get(model_path)
+            .is_some_and(|loaded| *loaded.load == config);
+        if !resident {
+            self.models.remove(model_path);
+            let gpu_layers = match config.gpu_layers {
+                Some(GpuLayers::Auto) => Some(auto_gpu_layers(&self.backend, model_path)?),
+                Some(GpuLayers::Count(n)) => Some(n),
+                Some(GpuLayers::All) | None => None,
+            };
+            let params = model_params(&config, gpu_layers);
+            let model = LlamaModel::load_from_file(&self.backend, model_path, &params)
+                .map_err(|e| anyhow!("Failed to load model from {}: {}", model_path, e))?;
+            let model = Arc::new(model);
+            // llama.cpp offloads nothing without a GPU, and never more than the model has
+            let offloadable = model.n_layer() + 1;
+            let gpu_layers = if self.backend.supports_gpu_offload() && gpu_free_memory().is_some() {
+                gpu_layers.map_or(offloadable, |n| n.min(offloadable))
+            } else {
+                0
+            };
+            let projector = match &config.mmproj {
+                Some(path) => Some(load_projector(&model, path, &config)?),
+                None => None,
+            };
+            let bytes = model.size();
+            // Evicted models are dropped here, with their sessions
+            self.models.insert(
+                model_path,
+                LoadedModel::new(model, config, projector, gpu_layers),
+                bytes,
+            );
+        }
+        self.current = Some(model_path.to_string());
         Ok(())
     }
 
     async fn unload_model(&mut self) -> Result<()> {
-        self.model_loaded = false;
-        self.model = None;
+        self.current = None;
+        self.models.clear();
+        Ok(())
+    }
+
+    async fn close_session(&mut self, session_id: &str) -> Result<()> {
+        for loaded in self.models.models_mut() {
+            loaded.sessions.remove(session_id);
+        }
+        Ok(())
+    }
+
+    async fn save_session(&mut self, session_id: &str, path: &str) -> Result<()> {
+        let session = self
+            .loaded()?
+            .sessions
+            .get(session_id)
+            .ok_or_else(|| anyhow!("No session '{}'", session_id))?;
+        let (reply, saved) = oneshot::channel();
+        session
+            .jobs
+            .send(SessionJob::Save(PathBuf::from(path), reply))
+            .map_err(|_| anyhow!("Session '{}' has stopped", session_id))?;
+        saved
+            .await
+            .map_err(|_| anyhow!("Session '{}' has stopped", session_id))?
+    }
+
+    async fn load_session(&mut self, session_id: &str, path: &str) -> Result<()> {
+        if !Path::new(path).exists() {
+            return Err(anyhow!("Session file {} does not exist", path));
+        }
+        let backend = self.backend.clone();
+        let max_sessions = self.max_sessions;
+        self.loaded()?
+            .session(session_id, &backend, max_sessions)?
+            .jobs
+            .send(SessionJob::Restore(PathBuf::from(path)))
+            .map_err(|_| anyhow!("Session '{}' has stopped", session_id))
+    }
+
+    // Running contexts pick the new adapter up at their next request.
+    async fn apply_adapter(&mut self, path: &str, scale: f32) -> Result<()> {
+        let loaded = self.loaded()?;
+        let adapter = loaded.load_adapter(path)?;
+        loaded.adapter = Some((adapter, scale));
+        Ok(())
+    }
+
+    async fn remove_adapter(&mut self) -> Result<()> {
+        if let Ok(loaded) = self.loaded() {
+            loaded.adapter = None;
+        }
         Ok(())
     }
 
     fn is_loaded(&self) -> bool {
-        self.model.is_some()
+        self.current.is_some()
     }
 
     fn default_model(&self) -> String {
         "Qwen/Qwen2.5-1.5B-Instruct".to_string()
     }
 
+    fn chat_template(&self) -> Option<String> {
+        self.models
+            .peek(self.current.as_deref()?)?
+            .model
+            .meta_val_str("tokenizer.chat_template")
+            .ok()
+    }
+
+    fn model_info(&self) -> Option<ModelInfo> {
+        let loaded = self.models.peek(self.current.as_deref()?)?;
+        let model = &loaded.model;
+        let meta = |key: &str| model.meta_val_str(key).ok();
+        Some(ModelInfo {
+            architecture: meta("general.architecture"),
+            parameter_count: model.n_params(),
+            quantization: meta("general.file_type")
+                .and_then(|file_type| file_type.parse().ok())
+                .and_then(file_type_name)
+                .map(str::to_string),
+            context_length: Some(model.n_ctx_train() as u64),
+            embedding_length: Some(model.n_embd() as u64),
+            vocab_size: Some(model.n_vocab() as u64),
+            chat_template: meta("tokenizer.chat_template"),
+            gpu_layers: Some(loaded.gpu_layers),
+        })
+    }
+
     async fn infer(
         &mut self,
         prompt: &str,
         config: InferenceConfig,
+        cancel: CancellationToken,
     ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
-        let model = self
-            .model
-            .as_ref()
-            .ok_or_else(|| anyhow!("No model loaded"))?
-            .clone();
+        self.submit(Prompt::Text(prompt.to_string()), config, cancel)
+    }
 
-        // Share the backend reference
-        let backend = self.backend.clone();
+    async fn infer_tokens(
+        &mut self,
+        tokens: Vec<i32>,
+        config: InferenceConfig,
+        cancel: CancellationToken,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        if tokens.is_empty() {
+            return Err(anyhow!("The prompt has no tokens"));
+        }
+        if !config.images.is_empty() {
+            return Err(anyhow!("Images need a text prompt with image markers"));
+        }
+        let n_vocab = self.loaded()?.model.n_vocab();
+        if let Some(token) = tokens.iter().find(|&&token| token < 0 || token >= n_vocab) {
+            return Err(anyhow!(
+                "Token {} is outside the model's vocabulary of {}",
+                token,
+                n_vocab
+            ));
+        }
+        let tokens = tokens.into_iter().map(LlamaToken).collect();
+        self.submit(Prompt::Tokens(tokens), config, cancel)
+    }
+
+    async fn embed(
+        &mut self,
+        input: &str,
+        config: InferenceConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        let (model, load) = self.model()?;
 
-        let prompt_str = prompt.to_string();
-        let (mut tx, rx) = mpsc::channel(100);
+        let backend = self.backend.clone();
+        let input_str = input.to_string();
+        let (tx, rx) = mpsc::channel(100);
 
-        smol::spawn(smol::unblock(move || {
-            // Send ProcessStart
-            let _ = futures::executor::block_on(tx.send(Ok(InferenceEvent::ProcessStart)));
+        runtime::spawn_blocking(move || {
+            let mut events = EventSender::new(tx);
+            events.send(Ok(InferenceEvent::ProcessStart));
 
-            // Use the shared backend (no re-init)
             let backend_ref = &backend;
 
-            // Create context
-            let ctx_params = LlamaContextParams::default()
-                .with_n_ctx(config.context_size.and_then(|s| NonZeroU32::new(s)));
-
-            let mut ctx = match model.new_context(backend_ref, ctx_params) {
-                Ok(c) => c,
+            // Tokenize input
+            let tokens_list = match model.str_to_token(&input_str, AddBos::Always) {
+                Ok(t) => t,
                 Err(e) => {
-                    let _ = futures::executor::block_on(
-                        tx.send(Err(anyhow!("Context creation failed: {}", e))),
-                    );
+                    events.send(Err(anyhow!("Tokenize failed: {}", e)));
                     return;
                 }
             };
 
-            // Tokenize
-            let tokens_list = match model.str_to_token(&prompt_str, AddBos::Always) {
-                Ok(t) => t,
+            // Create context for embeddings; the input is pooled, so it is evaluated in one batch
+            let n_tokens = tokens_list.len().max(1);
+            let ctx_params = context_params(&load)
+                .with_n_ctx(config.context_size.and_then(|s| NonZeroU32::new(s)))
+                .with_n_batch(n_tokens as u32)
+                .with_n_ubatch(n_tokens as u32)
+                .with_embeddings(true); // Enable embedding mode
+
+            let mut ctx = match model.new_context(backend_ref, ctx_params) {
+                Ok(c) => c,
                 Err(e) => {
-                    let _ = futures::executor::block_on(
-                        tx.send(Err(anyhow!("Tokenize failed: {}", e))),
-                    );
+                    events.send(Err(anyhow!("Context creation failed: {}", e)));
                     return;
                 }
             };
 
-            // Prepare Batch for Prompt
-            let n_tokens = tokens_list.len();
-            let mut batch = LlamaBatch::new(2048, 1); // Ensure batch size can handle context
+            // Prepare batch
+            let mut batch = LlamaBatch::new(n_tokens, 1);
 
-            // Load prompt into batch
-            let last_index = n_tokens as i32 - 1;
+            // Add all tokens to batch (no need for logits in embedding mode)
             for (i, token) in tokens_list.iter().enumerate() {
-                // add(token, pos, &[seq_id], logits)
-                // We only need logits for the very last token to predict the next one
-                let _ = batch.add(*token, i as i32, &[0], i as i32 == last_index);
+                let _ = batch.add(*token, i as i32, &[0], false);
             }
 
-            // Decode Prompt
+            // Decode to get embeddings
             if let Err(e) = ctx.decode(&mut batch) {
-                let _ = futures::executor::block_on(
-                    tx.send(Err(anyhow!("Decode prompt failed: {}", e))),
-                );
+                events.send(Err(anyhow!("Decode failed: {}", e)));
                 return;
             }
 
-            // Generation Loop
-            let mut n_cur = n_tokens as i32;
-            let n_decode = 0; // generated tokens count
-            let max_tokens = 512; // Hard limit for safety
+            // Extract embeddings from the context
+            let mut embeddings = match ctx.embeddings_seq_ith(0) {
+                Ok(e) => e.to_vec(),
+                Err(e) => {
+                    events.send(Err(anyhow!("Failed to get embeddings from context: {}", e)));
+                    return;
+                }
+            };
 
-            let mut in_think_block = false;
-            let mut token_str_buffer = String::new();
+            if config.normalize {
+                l2_normalize(&mut embeddings);
+            }
 
-            loop {
-                // Sample next token
-                let mut sampler = LlamaSampler::greedy();
-                let next_token = sampler.sample(&ctx, batch.n_tokens() - 1);
+            events.send(Ok(InferenceEvent::Embedding(embeddings)));
+            events.send(Ok(InferenceEvent::Complete));
+        });
 
-                // Decode token to string
-                let token_str = match model.token_to_str(next_token, Special::Plaintext) {
-                    Ok(s) => s.to_string(),
-                    Err(_) => "??".to_string(),
-                };
+        Ok(rx)
+    }
 
-                // Check for EOS
-                if next_token == model.token_eos() || n_decode >= max_tokens {
-                    break;
-                }
+    async fn embed_batch(
+        &mut self,
+        inputs: &[String],
+        config: InferenceConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        let (model, load) = self.model()?;
 
-                // Parse Logic for <think> tags
-                // Simple stream parsing
-                token_str_buffer.push_str(&token_str);
-
-                // If we are NOT in a think block, check if one is starting
-                if !in_think_block && config.show_thinking {
-                    if token_str_buffer.contains("<think>") {
-                        in_think_block = true;
-                        // Emit Start Thought event
-                        let _ = futures::executor::block_on(
-                            tx.send(Ok(InferenceEvent::Thought(ThoughtEvent::Start))),
-                        );
-
-                        // Remove <think> from buffer to find remainder
-                        token_str_buffer = token_str_buffer.replace("<think>", "");
-                    }
-                }
+        let backend = self.backend.clone();
+        let inputs = inputs.to_vec();
+        let (tx, rx) = mpsc::channel(inputs.len() + 2);
 
-                // If we ARE in a think block
-                if in_think_block {
-                    if token_str_buffer.contains("</think>") {
-                        in_think_block = false;
-                        // Emit Stop Thought event
-                        let parts: Vec<&str> = token_str_buffer.split("</think>").collect();
-                        if let Some(think_content) = parts.first() {
-                            if !think_content.is_empty() {
-                                let _ = futures::executor::block_on(tx.send(Ok(
-                                    InferenceEvent::Thought(ThoughtEvent::Delta(
-                                        think_content.to_string(),
-                                    )),
-                                )));
-                            }
-                        }
+        runtime::spawn_blocking(move || {
+            let mut events = EventSender::new(tx);
+            events.send(Ok(InferenceEvent::ProcessStart));
 
-                        let _ = futures::executor::block_on(
-                            tx.send(Ok(InferenceEvent::Thought(ThoughtEvent::Stop))),
-                        );
+            let token_lists = match inputs
+                .iter()
+                .map(|input| model.str_to_token(input, AddBos::Always))
+                .collect::<std::result::Result<Vec<_>, _>>()
+            {
+                Ok(t) => t,
+                Err(e) => {
+                    events.send(Err(anyhow!("Tokenize failed: {}", e)));
+                    return;
+                }
+            };
 
-                        // Remainder after </think> should be content?
-                        if parts.len() > 1 {
-                            token_str_buffer = parts[1].to_string();
-                            // Fallthrough to emit content
-                        } else {
-                            token_str_buffer.clear();
-                        }
-                    } else {
-                        // Stream delta
-                        if !token_str_buffer.is_empty() {
-                            let _ =
-                                futures::executor::block_on(tx.send(Ok(InferenceEvent::Thought(
-                                    ThoughtEvent::Delta(token_str_buffer.clone()),
-                                ))));
-                            token_str_buffer.clear();
-                        }
+            let budget = config.context_size.unwrap_or(2048) as usize;
+            let result = pooled_outputs(
+                &model,
+                &backend,
+                &load,
+                &token_lists,
+                budget,
+                None,
+                |index, mut embedding| {
+                    if config.normalize {
+                        l2_normalize(&mut embedding);
                     }
-                }
+                    events.send(Ok(InferenceEvent::IndexedEmbedding { index, embedding }));
+                },
+            );
+            if let Err(e) = result {
+                events.send(Err(e));
+                return;
+            }
 
-                // If NOT in think block (anymore), emit as content
-                if !in_think_block && !token_str_buffer.is_empty() {
-                    let _ = futures::executor::block_on(
-                        tx.send(Ok(InferenceEvent::Content(token_str_buffer.clone()))),
-                    );
-                    token_str_buffer.clear();
-                }
+            events.send(Ok(InferenceEvent::Complete));
+        });
 
-                // Prepare next batch
-                batch.clear();
-                let _ = batch.add(next_token, n_cur, &[0], true);
-                n_cur += 1;
+        Ok(rx)
+    }
 
-                if let Err(e) = ctx.decode(&mut batch) {
-                    let _ =
-                        futures::executor::block_on(tx.send(Err(anyhow!("Decode failed: {}", e))));
-                    break;
-                }
-            }
+    fn devices(&self) -> Vec<DeviceInfo> {
+        llama_cpp_2::list_llama_ggml_backend_devices()
+            .into_iter()
+            .map(|device| DeviceInfo {
+                gpu: is_gpu(&device),
+                name: device.name,
+                description: device.description,
+                memory_total: device.memory_total as u64,
+                memory_free: device.memory_free as u64,
+            })
+            .collect()
+    }
 
-            let _ = futures::executor::block_on(tx.send(Ok(InferenceEvent::Complete)));
-        }))
-        .detach();
+    async fn health(&mut self) -> Result<EngineHealth> {
+        let smoke_test = if self.is_loaded() {
+            Some(smoke_test(self).await)
+        } else {
+            None
+        };
+        Ok(EngineHealth {
+            backend: "llama.cpp".to_string(),
+            gpu_offload: self.backend.supports_gpu_offload(),
+            devices: self.devices(),
+            model: self.model_info(),
+            smoke_test,
+            state: None,
+        })
+    }
 
-        Ok(rx)
+    async fn bench(&mut self, config: BenchConfig) -> Result<BenchReport> {
+        let (model, load) = self.model()?;
+        let backend = self.backend.clone();
+        runtime::spawn_blocking(move || run_bench(&model, &backend, &load, &config)).await
     }
 
-    async fn embed(
+    async fn rerank(
         &mut self,
-        input: &str,
+        query: &str,
+        documents: &[String],
         config: InferenceConfig,
     ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
-        let model = self
-            .model
-            .as_ref()
-            .ok_or_else(|| anyhow!("No model loaded"))?
-            .clone();
+        let (model, load) = self.model()?;
 
         let backend = self.backend.clone();
-        let input_str = input.to_string();
-        let (mut tx, rx) = mpsc::channel(100);
-
-        smol::spawn(smol::unblock(move || {
-            let _ = futures::executor::block_on(tx.send(Ok(InferenceEvent::ProcessStart)));
-
-            let backend_ref = &backend;
+        let query = query.to_string();
+        let documents = documents.to_vec();
+        let (tx, rx) = mpsc::channel(documents.len() + 2);
 
-            // Create context for embeddings
-            let ctx_params = LlamaContextParams::default()
-                .with_n_ctx(config.context_size.and_then(|s| NonZeroU32::new(s)))
-                .with_embeddings(true); // Enable embedding mode
+        runtime::spawn_blocking(move || {
+            let mut events = EventSender::new(tx);
+            events.send(Ok(InferenceEvent::ProcessStart));
 
-            let mut ctx = match model.new_context(backend_ref, ctx_params) {
-                Ok(c) => c,
-                Err(e) => {
-                    let _ = futures::executor::block_on(
-                        tx.send(Err(anyhow!("Context creation failed: {}", e))),
-                    );
-                    return;
-                }
+            // Cross-encoders score a pair laid out as `<bos>query<eos><sep>document<eos>`
+            let pair_tokens = |document: &String| -> Result<Vec<LlamaToken>> {
+                let mut tokens = model.str_to_token(&query, AddBos::Always)?;
+                tokens.push(model.token_eos());
+                tokens.push(model.token_sep());
+                tokens.extend(model.str_to_token(document, AddBos::Never)?);
+                tokens.push(model.token_eos());
+                Ok(tokens)
             };
-
-            // Tokenize input
-            let tokens_list = match model.str_to_token(&input_str, AddBos::Always) {
+            let token_lists = match documents
+                .iter()
+                .map(pair_tokens)
+                .collect::<Result<Vec<_>>>()
+            {
                 Ok(t) => t,
                 Err(e) => {
-                    let _ = futures::executor::block_on(
-                        tx.send(Err(anyhow!("Tokenize failed: {}", e))),
-                    );
+                    events.send(Err(anyhow!("Tokenize failed: {}", e)));
                     return;
                 }
             };
 
-            // Prepare batch
-            let mut batch = LlamaBatch::new(2048, 1);
+            let budget = config.context_size.unwrap_or(2048) as usize;
+            let result = pooled_outputs(
+                &model,
+                &backend,
+                &load,
+                &token_lists,
+                budget,
+                Some(LlamaPoolingType::Rank),
+                |index, output| {
+                    let score = output.first().copied().unwrap_or(f32::NEG_INFINITY);
+                    events.send(Ok(InferenceEvent::RerankScore { index, score }));
+                },
+            );
+            if let Err(e) = result {
+                events.send(Err(e));
+                return;
+            }
 
-            // Add all tokens to batch (no need for logits in embedding mode)
-            for (i, token) in tokens_list.iter().enumerate() {
-                let _ = batch.add(*token, i as i32, &[0], false);
+            events.send(Ok(InferenceEvent::Complete));
+        });
+
+        Ok(rx)
+    }
+}
+
+/// Time `config`'s tests on one context, after a warm-up decode, as llama-bench does: the
+/// prompt is evaluated at `load`'s batch size (all at once by default) and generation decodes
+/// one token at a time. Tokens are spread over the vocabulary in place of real text.
+fn run_bench(
+    model: &LlamaModel,
+    backend: &LlamaBackend,
+    load: &LoadConfig,
+    config: &BenchConfig,
+) -> Result<BenchReport> {
+    if config.repetitions == 0 {
+        return Err(anyhow!("A benchmark needs at least one repetition"));
+    }
+    let n_ctx = config.prompt_tokens.max(config.generated_tokens).max(1);
+    let n_batch = load
+        .batch_size
+        .map_or(config.prompt_tokens, |size| size as usize)
+        .max(1);
+    let ctx_params = context_params(load)
+        .with_n_ctx(NonZeroU32::new(n_ctx as u32))
+        .with_n_batch(n_batch as u32);
+    let mut ctx = model
+        .new_context(backend, ctx_params)
+        .map_err(|e| anyhow!("Context creation failed: {}", e))?;
+    let mut batch = LlamaBatch::new(n_batch, 1);
+
+    let n_vocab = model.n_vocab().max(1) as usize;
+    let tokens: Vec<LlamaToken> = std::iter::once(model.token_bos())
+        .chain((1..n_ctx).map(|i| LlamaToken((i * 7_919 % n_vocab) as i32)))
+        .collect();
+
+    // Decodes `tokens` from position 0 and returns how long that took in milliseconds. Reading
+    // the last logits waits for the backend to finish.
+    let mut time = |tokens: &[LlamaToken], step: usize| -> Result<f64> {
+        ctx.clear_kv_cache();
+        let start = Instant::now();
+        for (chunk_index, chunk) in tokens.chunks(step).enumerate() {
+            batch.clear();
+            for (i, token) in chunk.iter().enumerate() {
+                let _ = batch.add(
+                    *token,
+                    (chunk_index * step + i) as i32,
+                    &[0],
+                    i == chunk.len() - 1,
+                );
             }
+            ctx.decode(&mut batch)
+                .map_err(|e| anyhow!("Decode failed: {}", e))?;
+        }
+        let _ = ctx.get_logits_ith(batch.n_tokens() - 1);
+        Ok(start.elapsed().as_secs_f64() * 1000.0)
+    };
 
-            // Decode to get embeddings
-            if let Err(e) = ctx.decode(&mut batch) {
-                let _ = futures::executor::block_on(tx.send(Err(anyhow!("Decode failed: {}", e))));
-                return;
+    time(&tokens[..1], 1)?;
+    let mut prompt_ms = 0.0;
+    let mut generation_ms = 0.0;
+    for _ in 0..config.repetitions {
+        if config.prompt_tokens > 0 {
+            prompt_ms += time(&tokens[..config.prompt_tokens], n_batch)?;
+        }
+        if config.generated_tokens > 0 {
+            generation_ms += time(&tokens[..config.generated_tokens], 1)?;
+        }
+    }
+    let repetitions = config.repetitions as f64;
+    Ok(BenchReport::new(
+        config,
+        prompt_ms / repetitions,
+        generation_ms / repetitions,
+    ))
+}
+
+/// Evaluate each of `token_lists` as its own sequence, packing as many into one decode as fit
+/// in `budget` tokens, and hand every sequence's pooled output to `emit` with its index.
+fn pooled_outputs(
+    model: &LlamaModel,
+    backend: &LlamaBackend,
+    load: &LoadConfig,
+    token_lists: &[Vec<LlamaToken>],
+    budget: usize,
+    pooling: Option<LlamaPoolingType>,
+    mut emit: impl FnMut(usize, Vec<f32>),
+) -> Result<()> {
+    let mut chunks: Vec<Vec<usize>> = Vec::new();
+    let mut chunk_tokens = 0;
+    for (index, tokens) in token_lists.iter().enumerate() {
+        let full = match chunks.last() {
+            Some(chunk) => {
+                chunk.len() >= MAX_BATCH_SEQUENCES || chunk_tokens + tokens.len() > budget
             }
+            None => true,
+        };
+        if full {
+            chunks.push(Vec::new());
+            chunk_tokens = 0;
+        }
+        chunks.last_mut().unwrap().push(index);
+        chunk_tokens += tokens.len();
+    }
+    let max_tokens = chunks
+        .iter()
+        .map(|chunk| chunk.iter().map(|&i| token_lists[i].len()).sum::<usize>())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let max_seqs = chunks.iter().map(Vec::len).max().unwrap_or(0).max(1);
 
-            // Extract embeddings from the context
-            let embeddings = match ctx.embeddings_seq_ith(0) {
-                Ok(e) => e.to_vec(),
-                Err(e) => {
-                    let _ = futures::executor::block_on(
-                        tx.send(Err(anyhow!("Failed to get embeddings from context: {}", e))),
-                    );
-                    return;
-                }
-            };
+    let mut ctx_params = context_params(load)
+        .with_n_ctx(NonZeroU32::new(max_tokens as u32))
+        .with_n_batch(max_tokens as u32)
+        .with_n_ubatch(max_tokens as u32)
+        .with_n_seq_max(max_seqs as u32)
+        .with_embeddings(true);
+    if let Some(pooling) = pooling {
+        ctx_params = ctx_params.with_pooling_type(pooling);
+    }
 
-            let _ = futures::executor::block_on(tx.send(Ok(InferenceEvent::Embedding(embeddings))));
-            let _ = futures::executor::block_on(tx.send(Ok(InferenceEvent::Complete)));
-        }))
-        .detach();
+    let mut ctx = model
+        .new_context(backend, ctx_params)
+        .map_err(|e| anyhow!("Context creation failed: {}", e))?;
 
-        Ok(rx)
+    for chunk in chunks {
+        ctx.clear_kv_cache();
+        let mut batch = LlamaBatch::new(max_tokens, max_seqs as i32);
+        for (seq, &index) in chunk.iter().enumerate() {
+            for (pos, token) in token_lists[index].iter().enumerate() {
+                let _ = batch.add(*token, pos as i32, &[seq as i32], false);
+            }
+        }
+
+        ctx.decode(&mut batch)
+            .map_err(|e| anyhow!("Decode failed: {}", e))?;
+
+        for (seq, &index) in chunk.iter().enumerate() {
+            let output = ctx
+                .embeddings_seq_ith(seq as i32)
+                .map_err(|e| anyhow!("Failed to get embeddings from context: {}", e))?;
+            emit(index, output.to_vec());
+        }
+    }
+    Ok(())
+}
+
+/// Run one inference request on `state`, creating the context on first use.
+///
+/// The longest prefix the new prompt shares with the tokens already evaluated is kept in the
+/// KV cache, so a follow-up turn of a session only evaluates what is new; a session restored
+/// from a file is loaded into the context first and matched the same way. Switching adapters
+/// invalidates the cache, and so does a prompt with images, which is evaluated from the start.
+/// Prompts longer than the context's batch size are evaluated in several decodes.
+fn run_job<'m>(
+    model: &'m LlamaModel,
+    backend: &LlamaBackend,
+    load: &LoadConfig,
+    state: &mut ContextState<'m>,
+    job: InferJob,
+) {
+    let InferJob {
+        prompt,
+        config,
+        adapter,
+        projector,
+        cancel,
+        mut events,
+        received,
+    } = job;
+    let ContextState {
+        ctx,
+        history,
+        adapter: current_adapter,
+        restore,
+    } = state;
+
+    // Send ProcessStart
+    events.send(Ok(InferenceEvent::ProcessStart));
+
+    // Create context, evaluating a prompt that fits it in one decode unless the batch size is set
+    if ctx.is_none() {
+        let n_ctx = config
+            .context_size
+            .filter(|&size| size > 0)
+            .unwrap_or_else(|| model.n_ctx_train());
+        let ctx_params = context_params(load)
+            .with_n_ctx(NonZeroU32::new(n_ctx))
+            .with_n_batch(load.batch_size.unwrap_or(n_ctx));
+
+        match model.new_context(backend, ctx_params) {
+            Ok(c) => *ctx = Some(c),
+            Err(e) => {
+                events.send(Err(anyhow!("Context creation failed: {}", e)));
+                return;
+            }
+        }
+    }
+    let Some(ctx) = ctx.as_mut() else {
+        return;
+    };
+
+    // Tokens evaluated with other weights can't be reused
+    match switch_adapter(ctx, current_adapter, adapter) {
+        Ok(true) => history.clear(),
+        Ok(false) => {}
+        Err(e) => {
+            events.send(Err(e));
+            return;
+        }
+    }
+
+    let prompt_start = Instant::now();
+    let n_ctx = ctx.n_ctx() as usize;
+
+    // A restored session continues from its saved tokens; one that can't be read starts over
+    if let Some(path) = restore.take() {
+        match ctx.state_load_file(&path, n_ctx) {
+            Ok(tokens) => *history = tokens,
+            Err(e) => {
+                ctx.clear_kv_cache();
+                history.clear();
+                eprintln!(
+                    "NOTICE: Could not restore session from {}: {}",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+    let n_batch = (ctx.n_batch() as usize).max(1);
+    let mut batch = LlamaBatch::new(n_batch, 1);
+    let (n_tokens, n_past, reused) =
+        if let (Some(projector), Prompt::Text(prompt)) = (&projector, &prompt) {
+            // Image embeddings can't be matched against the cached tokens
+            ctx.clear_kv_cache();
+            history.clear();
+            match eval_images(ctx, &mut batch, projector, prompt, &config.images) {
+                Ok((n_tokens, n_past)) => (n_tokens, n_past, 0),
+                Err(e) => {
+                    ctx.clear_kv_cache();
+                    events.send(Err(e));
+                    return;
+                }
+            }
+        } else {
+            // Tokenize
+            let mut tokens_list = match prompt.tokenize(model) {
+                Ok(t) => t,
+                Err(e) => {
+                    events.send(Err(e));
+                    return;
+                }
+            };
+
+            match fit_prompt(&mut tokens_list, n_ctx, &config) {
+                Ok(0) => {}
+                Ok(dropped) => {
+                    events.send(Ok(InferenceEvent::ContextTruncated { dropped }));
+                }
+                Err(e) => {
+                    events.send(Err(e));
+                    return;
+                }
+            }
+
+            // Keep the shared prefix, but always decode the last prompt token again for fresh logits
+            let mut reused = history
+                .iter()
+                .zip(&tokens_list)
+                .take_while(|(cached, token)| cached == token)
+                .count()
+                .min(tokens_list.len().saturating_sub(1));
+            if !matches!(
+                ctx.clear_kv_cache_seq(Some(0), Some(reused as u32), None),
+                Ok(true)
+            ) {
+                ctx.clear_kv_cache();
+                reused = 0;
+            }
+            history.truncate(reused);
+
+            // Decode the prompt, at most a batch's worth of tokens at a time
+            let n_tokens = tokens_list.len();
+            let last_index = n_tokens as i32 - 1;
+            let mut decoded = reused;
+            while decoded < n_tokens {
+                let end = (decoded + n_batch).min(n_tokens);
+                batch.clear();
+                for (i, token) in tokens_list.iter().enumerate().take(end).skip(decoded) {
+                    // add(token, pos, &[seq_id], logits)
+                    // We only need logits for the very last token to predict the next one
+                    let _ = batch.add(*token, i as i32, &[0], i as i32 == last_index);
+                }
+
+                if let Err(e) = ctx.decode(&mut batch) {
+                    ctx.clear_kv_cache();
+                    history.clear();
+                    events.send(Err(anyhow!("Decode prompt failed: {}", e)));
+                    return;
+                }
+                decoded = end;
+            }
+            history.extend_from_slice(&tokens_list[reused..]);
+            (n_tokens, n_tokens as i32, reused)
+        };
+    let usage = TokenUsage {
+        prompt_tokens: n_tokens,
+        cached_tokens: reused,
+        prompt_ms: prompt_start.elapsed().as_millis() as u64,
+        ..Default::default()
+    };
+
+    let sampler = match build_sampler(model, &config) {
+        Ok(sampler) => sampler,
+        Err(e) => {
+            events.send(Err(e));
+            return;
+        }
+    };
+    let mut generation = Generation::new(config, cancel, events, sampler, usage, received);
+
+    // Generation Loop
+    let mut n_cur = n_past;
+    loop {
+        // Stop burning compute for a caller that gave up or went away
+        if generation.stopped() {
+            break;
+        }
+
+        let next_token = generation.sample(ctx, batch.n_tokens() - 1);
+        if !generation.accept(model, ctx, batch.n_tokens() - 1, next_token) {
+            break;
+        }
+
+        // A full context either ends the generation or makes room by forgetting the oldest half.
+        // Images aren't in `history`, so a context holding them can't be shifted
+        if n_cur as usize >= n_ctx {
+            if generation.config.context_overflow != ContextOverflow::SlidingWindow
+                || projector.is_some()
+            {
+                break;
+            }
+            match shift_context(ctx, 0, n_cur) {
+                Ok(discarded) => {
+                    n_cur -= discarded;
+                    history.drain(1..1 + discarded as usize);
+                    generation.send(InferenceEvent::ContextTruncated {
+                        dropped: discarded as usize,
+                    });
+                }
+                Err(e) => {
+                    ctx.clear_kv_cache();
+                    history.clear();
+                    generation.fail(e);
+                    return;
+                }
+            }
+        }
+
+        // Prepare next batch
+        batch.clear();
+        let _ = batch.add(next_token, n_cur, &[0], true);
+        n_cur += 1;
+
+        if let Err(e) = ctx.decode(&mut batch) {
+            ctx.clear_kv_cache();
+            history.clear();
+            generation.fail(anyhow!("Decode failed: {}", e));
+            return;
+        }
+        history.push(next_token);
+    }
+
+    // Only what followed the images is in `history`, which a later prompt mustn't match
+    if projector.is_some() {
+        history.clear();
+    }
+    generation.finish();
+}
+
+/// Write a session's KV cache and the tokens in it to `path`.
+fn save_state(state: &ContextState, path: &Path) -> Result<()> {
+    // Until it is restored, the saved file is still the session's state
+    if let Some(restore) = &state.restore {
+        if restore != path {
+            std::fs::copy(restore, path)?;
+        }
+        return Ok(());
+    }
+    match &state.ctx {
+        Some(ctx) if !state.history.is_empty() => ctx
+            .state_save_file(path, &state.history)
+            .map_err(|e| anyhow!("Failed to save session to {}: {}", path.display(), e)),
+        _ => Err(anyhow!("The session has nothing to save")),
+    }
+}
+
+/// Evaluate a prompt with images into the start of `ctx` through the model's projector,
+/// leaving the logits of its last token in `batch`. Returns the number of tokens evaluated,
+/// the images' included, and the position after the last.
+fn eval_images(
+    ctx: &mut LlamaContext,
+    batch: &mut LlamaBatch,
+    projector: &SharedProjector,
+    prompt: &str,
+    images: &[ImageInput],
+) -> Result<(usize, i32)> {
+    let projector = projector.lock().unwrap_or_else(|e| e.into_inner());
+    let bitmaps = images
+        .iter()
+        .map(|image| {
+            MtmdBitmap::from_buffer(&projector.ctx, &image.bytes()?)
+                .map_err(|e| anyhow!("Failed to decode image: {}", e))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Images the prompt has no marker for go in front of it
+    let missing = images
+        .len()
+        .saturating_sub(prompt.matches(IMAGE_MARKER).count());
+    let text = MtmdInputText {
+        text: format!("{}{}", IMAGE_MARKER.repeat(missing), prompt),
+        add_special: true,
+        parse_special: true,
+    };
+    let chunks = projector
+        .ctx
+        .tokenize(text, &bitmaps.iter().collect::<Vec<_>>())
+        .map_err(|e| anyhow!("Tokenize failed: {}", e))?;
+
+    // Images can't be cut, so the whole prompt has to fit
+    let n_tokens = chunks.total_tokens();
+    ContextOverflow::Error
+        .tokens_to_drop(n_tokens, ctx.n_ctx() as usize, 0)
+        .map_err(|e| anyhow!(e))?;
+    let last = chunks
+        .get(chunks.len().saturating_sub(1))
+        .and_then(|chunk| {
+            chunk
+                .text_tokens()
+                .and_then(|tokens| tokens.last().copied())
+        })
+        .ok_or_else(|| anyhow!("A prompt with images must end in text"))?;
+
+    let n_past = chunks
+        .eval_chunks(&projector.ctx, ctx, 0, 0, ctx.n_batch() as i32, false)
+        .map_err(|e| anyhow!("Decode prompt failed: {}", e))?;
+    drop(projector);
+
+    // The projector decodes past `ctx`, which then can't hand out the logits, so the last
+    // token is decoded again here
+    if !matches!(
+        ctx.clear_kv_cache_seq(Some(0), Some(n_past as u32 - 1), None),
+        Ok(true)
+    ) {
+        return Err(anyhow!("Failed to re-evaluate the prompt's last token"));
+    }
+    batch.clear();
+    let _ = batch.add(last, n_past - 1, &[0], true);
+    ctx.decode(batch)
+        .map_err(|e| anyhow!("Decode prompt failed: {}", e))?;
+    Ok((n_tokens, n_past))
+}
+
+/// Load the multimodal projector at `path` for `model`.
+fn load_projector(
+    model: &Arc<LlamaModel>,
+    path: &str,
+    load: &LoadConfig,
+) -> Result<SharedProjector> {
+    let mut params = MtmdContextParams {
+        print_timings: false,
+        ..Default::default()
+    };
+    if let Some(n_threads) = load.n_threads {
+        params.n_threads = n_threads as i32;
+    }
+    let ctx = MtmdContext::init_from_file(path, model, &params)
+        .map_err(|e| anyhow!("Failed to load projector from {}: {}", path, e))?;
+    if !ctx.support_vision() {
+        return Err(anyhow!("Projector {} does not take images", path));
+    }
+    Ok(Arc::new(Mutex::new(Projector {
+        ctx,
+        _model: model.clone(),
+    })))
+}
+
+/// A request generating in the shared context of [run_batcher], on its own sequence.
+struct Sequence {
+    generation: Generation,
+    /// Position of the next token in the sequence.
+    pos: i32,
+    /// Index in the last decoded batch of the logits to sample this sequence's next token from.
+    logits_at: i32,
+    /// Token sampled at the last step, to be evaluated at the next.
+    next: Option<LlamaToken>,
+    /// Whether the last step evaluated the prompt.
+    prompting: bool,
+}
+
+/// Generate for the requests arriving on `queue`, up to `max_parallel` at a time, in one shared
+/// context (continuous batching).
+///
+/// Every decode step evaluates one token of each generating sequence together with the prompts
+/// of newly admitted requests, so a request starts as soon as a slot is free instead of waiting
+/// for the ones ahead of it to finish. The context gives each sequence room for the largest
+/// `context_size` among the waiting requests (see [shared_context_size]); a later request
+/// asking for more waits for the running sequences to finish and the context is rebuilt for
+/// it. It takes `load`'s settings except its batch size, as each prompt is evaluated in a
+/// single step. The thread ends once `queue` is closed and the last sequence is done.
+fn run_batcher(
+    model: &LlamaModel,
+    backend: &LlamaBackend,
+    load: &LoadConfig,
+    queue: std::sync::mpsc::Receiver<InferJob>,
+    max_parallel: usize,
+) {
+    let mut pending: VecDeque<InferJob> = VecDeque::new();
+    let mut slots: Vec<Option<Sequence>> = (0..max_parallel).map(|_| None).collect();
+    let mut shared: Option<(LlamaContext, LlamaBatch, usize)> = None;
+    let mut open = true;
+
+    loop {
+        // Sleep until there is work
+        if slots.iter().all(Option::is_none) && pending.is_empty() {
+            if !open {
+                return;
+            }
+            match queue.recv() {
+                Ok(job) => pending.push_back(job),
+                Err(_) => return,
+            }
+        }
+        loop {
+            match queue.try_recv() {
+                Ok(job) => pending.push_back(job),
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    open = false;
+                    break;
+                }
+            }
+        }
+
+        // A request wanting more context than the sequences have gets a new context once the
+        // running ones are done
+        if let Some(seq_ctx) = shared.as_ref().map(|(_, _, seq_ctx)| *seq_ctx) {
+            let running = slots.iter().any(Option::is_some);
+            if needs_rebuild(seq_ctx, pending.front().map(|job| &job.config), running) {
+                shared = None;
+            }
+        }
+
+        if shared.is_none() {
+            if pending.is_empty() {
+                continue;
+            }
+            let seq_ctx = shared_context_size(pending.iter().map(|job| &job.config));
+            let n_ctx = (seq_ctx * max_parallel) as u32;
+            let ctx_params = context_params(load)
+                .with_n_ctx(NonZeroU32::new(n_ctx))
+                .with_n_batch(n_ctx)
+                .with_n_seq_max(max_parallel as u32);
+            match model.new_context(backend, ctx_params) {
+                Ok(ctx) => {
+                    // Room for one sequence's whole context plus a token for each other
+                    // sequence, reused by every step until the context is rebuilt
+                    let batch = LlamaBatch::new(seq_ctx + max_parallel, max_parallel as i32);
+                    shared = Some((ctx, batch, seq_ctx));
+                }
+                Err(e) => {
+                    for mut job in pending.drain(..) {
+                        job.events
+                            .send(Err(anyhow!("Context creation failed: {}", e)));
+                    }
+                    continue;
+                }
+            }
+        }
+        let Some((ctx, batch, seq_ctx)) = shared.as_mut() else {
+            continue;
+        };
+        let seq_ctx = *seq_ctx;
+
+        // Free the slots of requests that were cancelled or dropped
+        for (seq, slot) in slots.iter_mut().enumerate() {
+            if slot.as_ref().is_some_and(|s| s.generation.stopped()) {
+                if let Some(sequence) = slot.take() {
+                    sequence.generation.finish();
+                }
+                let _ = ctx.clear_kv_cache_seq(Some(seq as u32), None, None);
+            }
+        }
+
+        batch.clear();
+
+        // Generating sequences evaluate the token they sampled last
+        for (seq, slot) in slots.iter_mut().enumerate() {
+            let Some(sequence) = slot else {
+                continue;
+            };
+            if let Some(token) = sequence.next.take() {
+                sequence.logits_at = batch.n_tokens();
+                let _ = batch.add(token, sequence.pos, &[seq as i32], true);
+                sequence.pos += 1;
+            }
+        }
+
+        // Admit waiting requests into free slots while their prompts fit in the batch
+        'admit: for (seq, slot) in slots.iter_mut().enumerate() {
+            while slot.is_none() {
+                let Some(job) = pending.front() else {
+                    break 'admit;
+                };
+                if sequence_context(&job.config) > seq_ctx {
+                    break 'admit;
+                }
+                let mut tokens = match job.prompt.tokenize(model) {
+                    Ok(t) => t,
+                    Err(e) => {
+                        reject(pending.pop_front(), e);
+                        continue;
+                    }
+                };
+                let dropped = match fit_prompt(&mut tokens, seq_ctx, &job.config) {
+                    Ok(dropped) => dropped,
+                    Err(e) => {
+                        reject(pending.pop_front(), e);
+                        continue;
+                    }
+                };
+                if !batch_has_room(
+                    batch.n_tokens() as usize,
+                    tokens.len(),
+                    seq_ctx,
+                    max_parallel,
+                ) {
+                    break 'admit;
+                }
+                let Some(InferJob {
+                    config,
+                    cancel,
+                    mut events,
+                    received,
+                    ..
+                }) = pending.pop_front()
+                else {
+                    break 'admit;
+                };
+
+                events.send(Ok(InferenceEvent::ProcessStart));
+                if dropped > 0 {
+                    events.send(Ok(InferenceEvent::ContextTruncated { dropped }));
+                }
+                let sampler = match build_sampler(model, &config) {
+                    Ok(sampler) => sampler,
+                    Err(e) => {
+                        events.send(Err(e));
+                        continue;
+                    }
+                };
+                let usage = TokenUsage {
+                    prompt_tokens: tokens.len(),
+                    ..Default::default()
+                };
+                let generation = Generation::new(config, cancel, events, sampler, usage, received);
+
+                let last_index = tokens.len() - 1;
+                for (i, token) in tokens.iter().enumerate() {
+                    let _ = batch.add(*token, i as i32, &[seq as i32], i == last_index);
+                }
+                *slot = Some(Sequence {
+                    generation,
+                    pos: tokens.len() as i32,
+                    logits_at: batch.n_tokens() - 1,
+                    next: None,
+                    prompting: true,
+                });
+            }
+        }
+
+        if batch.n_tokens() == 0 {
+            continue;
+        }
+
+        let step_start = Instant::now();
+        if let Err(e) = ctx.decode(batch) {
+            ctx.clear_kv_cache();
+            for slot in slots.iter_mut() {
+                if let Some(sequence) = slot.take() {
+                    sequence.generation.fail(anyhow!("Decode failed: {}", e));
+                }
+            }
+            continue;
+        }
+
+        for (seq, slot) in slots.iter_mut().enumerate() {
+            let Some(sequence) = slot else {
+                continue;
+            };
+            if sequence.prompting {
+                sequence.prompting = false;
+                sequence.generation.usage.prompt_ms = step_start.elapsed().as_millis() as u64;
+                sequence.generation.started = Instant::now();
+            }
+
+            let token = sequence.generation.sample(ctx, sequence.logits_at);
+            let mut done = !sequence
+                .generation
+                .accept(model, ctx, sequence.logits_at, token);
+
+            if !done && sequence.pos as usize >= seq_ctx {
+                if sequence.generation.config.context_overflow == ContextOverflow::SlidingWindow {
+                    match shift_context(ctx, seq as i32, sequence.pos) {
+                        Ok(discarded) => {
+                            sequence.pos -= discarded;
+                            sequence.generation.send(InferenceEvent::ContextTruncated {
+                                dropped: discarded as usize,
+                            });
+                        }
+                        Err(e) => {
+                            sequence.generation.send_error(e);
+                            done = true;
+                        }
+                    }
+                } else {
+                    done = true;
+                }
+            }
+
+            if !done {
+                sequence.next = Some(token);
+            } else if let Some(sequence) = slot.take() {
+                sequence.generation.finish();
+                let _ = ctx.clear_kv_cache_seq(Some(seq as u32), None, None);
+            }
+        }
+    }
+}
+
+/// Tokens of context the sequence of a request with `config` gets in a shared batch.
+fn sequence_context(config: &InferenceConfig) -> usize {
+    config
+        .context_size
+        .filter(|&size| size > 0)
+        .map_or(DEFAULT_SEQUENCE_CONTEXT, |size| size as usize)
+}
+
+/// Tokens of context per sequence for a shared context built for the `waiting` requests: enough
+/// for the one asking for the most, so they needn't wait for it to be rebuilt.
+fn shared_context_size<'a>(waiting: impl Iterator<Item = &'a InferenceConfig>) -> usize {
+    waiting
+        .map(sequence_context)
+        .max()
+        .unwrap_or(DEFAULT_SEQUENCE_CONTEXT)
+}
+
+/// Whether a shared context with `seq_ctx` tokens per sequence is rebuilt for `next`, the
+/// request at the front of the queue: when it asks for more and no sequences are `running`.
+fn needs_rebuild(seq_ctx: usize, next: Option<&InferenceConfig>, running: bool) -> bool {
+    !running && next.is_some_and(|config| sequence_context(config) > seq_ctx)
+}
+
+/// Whether a prompt of `prompt` tokens fits in a decode step already holding `batched` tokens:
+/// the batch has room for one sequence's whole context plus a token for each other sequence.
+fn batch_has_room(batched: usize, prompt: usize, seq_ctx: usize, max_parallel: usize) -> bool {
+    batched + prompt <= seq_ctx + max_parallel
+}
+
+/// Most tokens generated for a request with `config`.
+fn token_limit(config: &InferenceConfig) -> usize {
+    config.max_tokens.unwrap_or(MAX_GENERATED_TOKENS)
+}
+
+/// Fail a queued request that can't be run.
+fn reject(job: Option<InferJob>, e: anyhow::Error) {
+    if let Some(mut job) = job {
+        job.events.send(Err(e));
+    }
+}
+
+/// One request's generation: samples tokens and streams them to the caller as events.
+struct Generation {
+    config: InferenceConfig,
+    cancel: CancellationToken,
+    events: EventSender,
+    /// The grammar keeps state across tokens, so one sampler serves the whole generation.
+    sampler: LlamaSampler,
+    usage: TokenUsage,
+    /// When the request reached the engine.
+    received: Instant,
+    /// When the prompt was done and generation began.
+    started: Instant,
+    /// Time from `received` to the first generated token.
+    first_token_ms: Option<u64>,
+    /// Bytes of a character split across tokens, held until it is complete.
+    utf8: Utf8Buffer,
+    in_think_block: bool,
+    token_str_buffer: String,
+    /// Content emitted so far, checked against the JSON schema at the end.
+    content: String,
+}
+
+impl Generation {
+    fn new(
+        config: InferenceConfig,
+        cancel: CancellationToken,
+        events: EventSender,
+        sampler: LlamaSampler,
+        usage: TokenUsage,
+        received: Instant,
+    ) -> Self {
+        Self {
+            config,
+            cancel,
+            events,
+            sampler,
+            usage,
+            received,
+            started: Instant::now(),
+            first_token_ms: None,
+            utf8: Utf8Buffer::new(),
+            in_think_block: false,
+            token_str_buffer: String::new(),
+            content: String::new(),
+        }
+    }
+
+    /// Whether the caller gave up or went away.
+    fn stopped(&self) -> bool {
+        self.cancel.is_cancelled() || self.events.is_closed()
+    }
+
+    fn sample(&mut self, ctx: &LlamaContext, index: i32) -> LlamaToken {
+        self.sampler.sample(ctx, index)
+    }
+
+    /// Emit `token`, sampled from the logits at `index`. Returns false instead when the
+    /// generation is over.
+    fn accept(
+        &mut self,
+        model: &LlamaModel,
+        ctx: &LlamaContext,
+        index: i32,
+        token: LlamaToken,
+    ) -> bool {
+        if self.is_end(model, token) || self.usage.completion_tokens >= token_limit(&self.config) {
+            return false;
+        }
+
+        // A token can end partway through a character; only complete characters go out
+        let render = self.config.render_special_tokens;
+        let token_str = match token_bytes(model, token, render) {
+            Ok(bytes) if !render && is_template_marker(model, token, &bytes) => String::new(),
+            Ok(bytes) => self.utf8.push(&bytes),
+            Err(_) => char::REPLACEMENT_CHARACTER.to_string(),
+        };
+
+        self.usage.completion_tokens += 1;
+        self.first_token_ms
+            .get_or_insert_with(|| self.received.elapsed().as_millis() as u64);
+
+        if let Some(top) = self.config.logprobs {
+            let logits = ctx.get_logits_ith(index);
+            let logprob = token_logprobs(model, logits, token, top as usize);
+            self.send(InferenceEvent::Logprob(logprob));
+        }
+
+        self.stream(&token_str);
+        true
+    }
+
+    /// Whether `token` ends the generation: one of the model's end-of-generation tokens
+    /// (EOS, `<|im_end|>`, `<|eot_id|>` and the like) or of [InferenceConfig::end_tokens].
+    fn is_end(&self, model: &LlamaModel, token: LlamaToken) -> bool {
+        if model.is_eog_token(token) {
+            return true;
+        }
+        !self.config.end_tokens.is_empty()
+            && token_bytes(model, token, true).is_ok_and(|bytes| {
+                self.config
+                    .end_tokens
+                    .iter()
+                    .any(|end| end.as_bytes() == bytes)
+            })
+    }
+
+    /// Emit generated text as thoughts or content.
+    fn stream(&mut self, text: &str) {
+        // Parse Logic for <think> tags, or the model's own
+        // Simple stream parsing
+        self.token_str_buffer.push_str(text);
+        let tags = self.config.thinking_tags.clone().unwrap_or_default();
+
+        // If we are NOT in a think block, check if one is starting
+        if !self.in_think_block
+            && self.config.show_thinking
+            && self.token_str_buffer.contains(&tags.start)
+        {
+            self.in_think_block = true;
+            // Emit Start Thought event
+            self.send(InferenceEvent::Thought(ThoughtEvent::Start));
+
+            // Remove <think> from buffer to find remainder
+            self.token_str_buffer = self.token_str_buffer.replace(&tags.start, "");
+        }
+
+        // If we ARE in a think block
+        if self.in_think_block {
+            if self.token_str_buffer.contains(&tags.end) {
+                self.in_think_block = false;
+                // Emit Stop Thought event
+                let buffer = std::mem::take(&mut self.token_str_buffer);
+                let parts: Vec<&str> = buffer.split(tags.end.as_str()).collect();
+                if let Some(think_content) = parts.first() {
+                    if !think_content.is_empty() {
+                        self.send(InferenceEvent::Thought(ThoughtEvent::Delta(
+                            think_content.to_string(),
+                        )));
+                    }
+                }
+
+                self.send(InferenceEvent::Thought(ThoughtEvent::Stop));
+
+                // Remainder after </think> should be content
+                if parts.len() > 1 {
+                    self.token_str_buffer = parts[1].to_string();
+                }
+            } else if !self.token_str_buffer.is_empty() {
+                // Stream delta
+                let delta = std::mem::take(&mut self.token_str_buffer);
+                self.send(InferenceEvent::Thought(ThoughtEvent::Delta(delta)));
+            }
+        }
+
+        // If NOT in think block (anymore), emit as content
+        if !self.in_think_block && !self.token_str_buffer.is_empty() {
+            let delta = std::mem::take(&mut self.token_str_buffer);
+            self.content.push_str(&delta);
+            self.send(InferenceEvent::Content(delta));
+        }
+    }
+
+    /// Report usage and `Complete`, after holding the output to the JSON schema if one was
+    /// given.
+    fn finish(mut self) {
+        let rest = self.utf8.finish();
+        if !rest.is_empty() {
+            self.stream(&rest);
+        }
+        self.usage.completion_ms = self.started.elapsed().as_millis() as u64;
+
+        // A cancelled generation is cut short, so only finished output is held to the schema
+        let checked = match (&self.config.json_schema, self.cancel.is_cancelled()) {
+            (Some(schema), false) => serde_json::from_str(&self.content)
+                .map_err(anyhow::Error::from)
+                .and_then(|value| json_schema::validate(schema, &value)),
+            _ => Ok(()),
+        };
+        if let Err(e) = checked {
+            self.fail(anyhow!("Output does not match the JSON schema: {}", e));
+            return;
+        }
+
+        let first_token_ms = self
+            .first_token_ms
+            .unwrap_or_else(|| self.received.elapsed().as_millis() as u64);
+        let stats = InferenceStats::new(&self.usage, first_token_ms);
+        let usage = std::mem::take(&mut self.usage);
+        self.send(InferenceEvent::Usage(usage));
+        self.send(InferenceEvent::Stats(stats));
+        self.send(InferenceEvent::Complete);
+    }
+
+    fn fail(mut self, e: anyhow::Error) {
+        self.send_error(e);
+    }
+
+    fn send_error(&mut self, e: anyhow::Error) {
+        self.events.send(Err(e));
+    }
+
+    fn send(&mut self, event: InferenceEvent) {
+        self.events.send(Ok(event));
+    }
+}
+
+/// The text of `token`, with special tokens such as `<|im_end|>` spelled out when `special` is
+/// set.
+fn token_bytes(
+    model: &LlamaModel,
+    token: LlamaToken,
+    special: bool,
+) -> Result<Vec<u8>, TokenToStringError> {
+    match model.token_to_piece_bytes(token, 8, special, None) {
+        // Too small a buffer gives the size it needs, negated
+        Err(TokenToStringError::InsufficientBufferSpace(needed)) => {
+            model.token_to_piece_bytes(token, needed.unsigned_abs() as usize, special, None)
+        }
+        bytes => bytes,
+    }
+}
+
+/// Whether `token`, whose text is `text`, is a special token other than the think tags, which
+/// mark thoughts; e.g. `<|im_start|>`.
+fn is_template_marker(model: &LlamaModel, token: LlamaToken, text: &[u8]) -> bool {
+    model
+        .token_attr(token)
+        .intersects(LlamaTokenAttr::Control | LlamaTokenAttr::UserDefined)
+        && !matches!(text, b"<think>" | b"</think>")
+}
+
+/// Model parameters with the memory settings `load` gives, offloading `gpu_layers` layers
+/// (all of them when `None`).
+fn model_params(load: &LoadConfig, gpu_layers: Option<u32>) -> LlamaModelParams {
+    let mut params = LlamaModelParams::default();
+    if let Some(use_mmap) = load.use_mmap {
+        params = params.with_use_mmap(use_mmap);
+    }
+    if let Some(use_mlock) = load.use_mlock {
+        params = params.with_use_mlock(use_mlock);
+    }
+    if let Some(gpu_layers) = gpu_layers {
+        params = params.with_n_gpu_layers(gpu_layers);
+    }
+    params
+}
+
+/// The first part of a split model (`*-00001-of-00003.gguf`), which llama.cpp loads the
+/// others from, once every part is in place; other models as they are.
+fn first_split_part(model_path: &str) -> Result<String> {
+    let Some(parts) = split_parts(&file_name(model_path)) else {
+        return Ok(model_path.to_string());
+    };
+    let path = Path::new(model_path);
+    let missing: Vec<&str> = parts
+        .iter()
+        .filter(|part| !path.with_file_name(part).exists())
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "Split model {} is missing {}",
+            model_path,
+            missing.join(", ")
+        ));
+    }
+    Ok(path.with_file_name(&parts[0]).display().to_string())
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Whether llama.cpp can offload layers to `device`.
+fn is_gpu(device: &LlamaBackendDevice) -> bool {
+    matches!(
+        device.device_type,
+        LlamaBackendDeviceType::Gpu | LlamaBackendDeviceType::IntegratedGpu
+    )
+}
+
+/// Free memory across the GPUs llama.cpp can offload to, or `None` when there are none.
+fn gpu_free_memory() -> Option<u64> {
+    let gpus: Vec<_> = llama_cpp_2::list_llama_ggml_backend_devices()
+        .into_iter()
+        .filter(is_gpu)
+        .collect();
+    (!gpus.is_empty()).then(|| gpus.iter().map(|gpu| gpu.memory_free as u64).sum())
+}
+
+/// Layers of the model at `model_path` that fit in the free VRAM, for [GpuLayers::Auto].
+/// The layer count comes from loading only the model's vocabulary and hyperparameters.
+fn auto_gpu_layers(backend: &LlamaBackend, model_path: &str) -> Result<u32> {
+    let free = match gpu_free_memory() {
+        Some(free) if backend.supports_gpu_offload() => free,
+        _ => return Ok(0),
+    };
+    let params = LlamaModelParams::default().with_vocab_only(true);
+    let n_layer = LlamaModel::load_from_file(backend, model_path, &params)
+        .map_err(|e| anyhow!("Failed to read model {}: {}", model_path, e))?
+        .n_layer();
+    let bytes = match split_parts(&file_name(model_path)) {
+        Some(parts) => parts
+            .iter()
+            .map(|part| std::fs::metadata(Path::new(model_path).with_file_name(part)))
+            .map(|metadata| metadata.map(|m| m.len()))
+            .sum::<std::io::Result<u64>>()?,
+        None => std::fs::metadata(model_path)?.len(),
+    };
+    let layers = fit_gpu_layers(bytes, n_layer, free);
+    eprintln!(
+        "NOTICE: Offloading {}/{} layers to the GPU ({} MiB free).",
+        layers,
+        n_layer + 1,
+        free / (1024 * 1024)
+    );
+    Ok(layers)
+}
+
+/// Context parameters with the RoPE, YaRN, batch, attention, KV cache and thread settings `load`
+/// gives, and llama.cpp's defaults for the rest.
+fn context_params(load: &LoadConfig) -> LlamaContextParams {
+    let mut params = LlamaContextParams::default();
+    if let Some(base) = load.rope_freq_base {
+        params = params.with_rope_freq_base(base);
+    }
+    if let Some(scale) = load.rope_freq_scale {
+        params = params.with_rope_freq_scale(scale);
+    }
+    if let Some(scaling) = load.rope_scaling {
+        params = params.with_rope_scaling_type(match scaling {
+            RopeScaling::None => RopeScalingType::None,
+            RopeScaling::Linear => RopeScalingType::Linear,
+            RopeScaling::Yarn => RopeScalingType::Yarn,
+        });
+    }
+
+    let yarn = &load.yarn;
+    if let Some(orig_ctx) = yarn.orig_ctx {
+        params = params.with_yarn_orig_ctx(orig_ctx);
+    }
+    if let Some(factor) = yarn.ext_factor {
+        params = params.with_yarn_ext_factor(factor);
+    }
+    if let Some(factor) = yarn.attn_factor {
+        params = params.with_yarn_attn_factor(factor);
+    }
+    if let Some(beta) = yarn.beta_fast {
+        params = params.with_yarn_beta_fast(beta);
+    }
+    if let Some(beta) = yarn.beta_slow {
+        params = params.with_yarn_beta_slow(beta);
+    }
+    if let Some(batch_size) = load.batch_size {
+        params = params.with_n_batch(batch_size);
+    }
+    if let Some(ubatch_size) = load.ubatch_size {
+        params = params.with_n_ubatch(ubatch_size);
+    }
+    if let Some(n_threads) = load.n_threads {
+        params = params.with_n_threads(n_threads as i32);
+    }
+    if let Some(n_threads) = load.n_threads_batch {
+        params = params.with_n_threads_batch(n_threads as i32);
+    }
+    if let Some(enabled) = load.flash_attention {
+        params = params.with_flash_attention_policy(if enabled {
+            llama_cpp_sys_2::LLAMA_FLASH_ATTN_TYPE_ENABLED
+        } else {
+            llama_cpp_sys_2::LLAMA_FLASH_ATTN_TYPE_DISABLED
+        });
+    }
+    if let Some(kv_cache_type) = load.kv_cache_type {
+        let kv_cache_type = match kv_cache_type {
+            KvCacheType::F16 => LlamaKvCacheType::F16,
+            KvCacheType::Q8_0 => LlamaKvCacheType::Q8_0,
+            KvCacheType::Q4_0 => LlamaKvCacheType::Q4_0,
+        };
+        params = params.with_type_k(kv_cache_type).with_type_v(kv_cache_type);
+    }
+    params
+}
+
+/// The sampler chain for `config`: its JSON schema or grammar if it has one, the repetition
+/// penalty, then either Mirostat or top-k, top-p and min-p, at the configured temperature. A
+/// temperature of zero always picks the most likely token.
+fn build_sampler(model: &LlamaModel, config: &InferenceConfig) -> Result<LlamaSampler> {
+    let grammar = match &config.json_schema {
+        Some(schema) => Some(
+            json_schema::to_grammar(schema)
+                .map_err(|e| anyhow!("Unsupported JSON schema: {}", e))?,
+        ),
+        None => config.grammar.clone(),
+    };
+
+    let mut samplers = Vec::new();
+    if let Some(grammar) = &grammar {
+        samplers.push(
+            LlamaSampler::grammar(model, grammar, "root")
+                .map_err(|e| anyhow!("Invalid grammar: {}", e))?,
+        );
+    }
+    if let Some(penalty) = config.repetition_penalty {
+        samplers.push(LlamaSampler::penalties(PENALTY_LAST_N, penalty, 0.0, 0.0));
+    }
+
+    if config.temperature <= 0.0 {
+        samplers.push(LlamaSampler::greedy());
+    } else if let Some(mirostat) = config.mirostat {
+        samplers.push(LlamaSampler::temp(config.temperature));
+        samplers.push(match mirostat.version {
+            MirostatVersion::V1 => LlamaSampler::mirostat(
+                model.n_vocab(),
+                RANDOM_SEED,
+                mirostat.tau,
+                mirostat.eta,
+                // Tokens used to estimate the distribution, as llama.cpp does
+                100,
+            ),
+            MirostatVersion::V2 => {
+                LlamaSampler::mirostat_v2(RANDOM_SEED, mirostat.tau, mirostat.eta)
+            }
+        });
+    } else {
+        if let Some(k) = config.top_k {
+            samplers.push(LlamaSampler::top_k(k as i32));
+        }
+        if let Some(p) = config.top_p {
+            samplers.push(LlamaSampler::top_p(p, 1));
+        }
+        if let Some(p) = config.min_p {
+            samplers.push(LlamaSampler::min_p(p, 1));
+        }
+        samplers.push(LlamaSampler::temp(config.temperature));
+        samplers.push(LlamaSampler::dist(RANDOM_SEED));
+    }
+
+    Ok(LlamaSampler::chain_simple(samplers))
+}
+
+/// Apply the request's [ContextOverflow] policy to a prompt for a context of `n_ctx` tokens,
+/// dropping the oldest tokens after the first if it allows. Returns how many were dropped.
+fn fit_prompt(
+    tokens: &mut Vec<LlamaToken>,
+    n_ctx: usize,
+    config: &InferenceConfig,
+) -> Result<usize> {
+    let reserve = token_limit(config);
+    let dropped = config
+        .context_overflow
+        .tokens_to_drop(tokens.len(), n_ctx, reserve)
+        .map_err(|e| anyhow!(e))?;
+    if dropped > 0 {
+        tokens.drain(1..1 + dropped);
+    }
+    Ok(dropped)
+}
+
+/// Discard the older half of the `n_past` tokens of sequence `seq`, after the first, and move
+/// the rest back to close the gap. Returns how many tokens were discarded.
+fn shift_context(ctx: &mut LlamaContext, seq: i32, n_past: i32) -> Result<i32> {
+    let keep = 1;
+    let discard = (n_past - keep) / 2;
+    ctx.clear_kv_cache_seq(
+        Some(seq as u32),
+        Some(keep as u32),
+        Some((keep + discard) as u32),
+    )
+    .map_err(|e| anyhow!("Context shift failed: {}", e))?;
+    ctx.kv_cache_seq_add(
+        seq,
+        Some((keep + discard) as u32),
+        Some(n_past as u32),
+        -discard,
+    )
+    .map_err(|e| anyhow!("Context shift failed: {}", e))?;
+    Ok(discard)
+}
+
+/// Make `wanted` the adapter set on `ctx`, returning whether it changed.
+fn switch_adapter(
+    ctx: &LlamaContext,
+    current: &mut Option<(SharedAdapter, f32)>,
+    wanted: Option<(SharedAdapter, f32)>,
+) -> Result<bool> {
+    let unchanged = match (&*current, &wanted) {
+        (None, None) => true,
+        (Some((a, a_scale)), Some((b, b_scale))) => Arc::ptr_eq(a, b) && a_scale == b_scale,
+        _ => false,
+    };
+    if unchanged {
+        return Ok(false);
+    }
+
+    if let Some((old, _)) = current.take() {
+        let mut old = old.lock().unwrap_or_else(|e| e.into_inner());
+        ctx.lora_adapter_remove(&mut old.0)
+            .map_err(|e| anyhow!("Failed to remove adapter: {}", e))?;
+    }
+    if let Some((new, scale)) = &wanted {
+        let mut adapter = new.lock().unwrap_or_else(|e| e.into_inner());
+        ctx.lora_adapter_set(&mut adapter.0, *scale)
+            .map_err(|e| anyhow!("Failed to apply adapter: {}", e))?;
+    }
+    *current = wanted;
+    Ok(true)
+}
+
+/// The log probability of `chosen` and of the `top` most likely tokens, from the raw logits
+/// before sampling.
+fn token_logprobs(
+    model: &LlamaModel,
+    logits: &[f32],
+    chosen: LlamaToken,
+    top: usize,
+) -> TokenLogprob {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let log_sum = max + logits.iter().map(|l| (l - max).exp()).sum::<f32>().ln();
+    let piece = |token: LlamaToken| {
+        token_bytes(model, token, false)
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+            .unwrap_or_default()
+    };
+
+    let mut ranked: Vec<(usize, f32)> = logits.iter().copied().enumerate().collect();
+    let top = top.min(ranked.len());
+    if top > 0 && top < ranked.len() {
+        ranked.select_nth_unstable_by(top - 1, |a, b| b.1.total_cmp(&a.1));
+    }
+    ranked.truncate(top);
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    TokenLogprob {
+        token: piece(chosen),
+        logprob: logits
+            .get(chosen.0 as usize)
+            .map_or(f32::NEG_INFINITY, |logit| logit - log_sum),
+        top_logprobs: ranked
+            .into_iter()
+            .map(|(id, logit)| TopLogprob {
+                token: piece(LlamaToken::new(id as i32)),
+                logprob: logit - log_sum,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(context_size: Option<u32>) -> InferenceConfig {
+        InferenceConfig {
+            context_size,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_token_limit_follows_max_tokens() {
+        let config = InferenceConfig {
+            max_tokens: Some(2000),
+            ..Default::default()
+        };
+        assert_eq!(token_limit(&config), 2000);
+        assert_eq!(
+            token_limit(&InferenceConfig::default()),
+            MAX_GENERATED_TOKENS
+        );
+    }
+
+    #[test]
+    fn test_shared_context_fits_the_largest_waiting_request() {
+        let waiting = [config(None), config(Some(8192)), config(Some(4096))];
+        assert_eq!(shared_context_size(waiting.iter()), 8192);
+        assert_eq!(shared_context_size([config(Some(0))].iter()), 2048);
+        assert_eq!(shared_context_size(std::iter::empty()), 2048);
+    }
+
+    #[test]
+    fn test_larger_requests_rebuild_once_sequences_finish() {
+        let larger = config(Some(8192));
+        assert!(needs_rebuild(2048, Some(&larger), false));
+        assert!(!needs_rebuild(2048, Some(&larger), true));
+        assert!(!needs_rebuild(8192, Some(&larger), false));
+        assert!(!needs_rebuild(8192, Some(&config(None)), false));
+        assert!(!needs_rebuild(2048, None, false));
+    }
+
+    #[test]
+    fn test_prompts_are_admitted_while_the_batch_has_room() {
+        // One sequence's whole context plus a token for each of the others
+        assert!(batch_has_room(0, 2048, 2048, 4));
+        assert!(batch_has_room(3, 2049, 2048, 4));
+        assert!(!batch_has_room(3, 2050, 2048, 4));
+        assert!(!batch_has_room(2000, 100, 2048, 4));
     }
 }
@@ -1,14 +1,19 @@
 #![cfg(not(feature = "real-engine"))]
 
-use rusty_genius_core::engine::Engine;
 use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use futures::channel::mpsc;
 use futures::sink::SinkExt;
+use rusty_genius_core::cosine::l2_normalize;
+use rusty_genius_core::engine::{CancellationToken, Engine};
 use rusty_genius_core::manifest::InferenceConfig;
-use rusty_genius_core::protocol::{InferenceEvent, ThoughtEvent};
+use rusty_genius_core::protocol::{InferenceEvent, ModelInfo, ThoughtEvent};
+use rusty_genius_core::runtime;
 use std::time::Duration;
 
+/// Size of Pinky's mock embeddings.
+const EMBEDDING_LENGTH: usize = 384;
+
 #[derive(Default)]
 pub struct Pinky {
     model_loaded: bool,
@@ -23,7 +28,7 @@ impl Pinky {
 #[async_trait]
 impl Engine for Pinky {
     async fn load_model(&mut self, _model_path: &str) -> Result<()> {
-        smol::Timer::after(Duration::from_millis(100)).await;
+        runtime::sleep(Duration::from_millis(100)).await;
         self.model_loaded = true;
         Ok(())
     }
@@ -41,10 +46,20 @@ impl Engine for Pinky {
         "tiny-model".to_string()
     }
 
+    fn model_info(&self) -> Option<ModelInfo> {
+        self.model_loaded.then(|| ModelInfo {
+            architecture: Some("pinky".to_string()),
+            context_length: Some(2048),
+            embedding_length: Some(EMBEDDING_LENGTH as u64),
+            ..Default::default()
+        })
+    }
+
     async fn infer(
         &mut self,
         prompt: &str,
         _config: InferenceConfig,
+        cancel: CancellationToken,
     ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
         if !self.model_loaded {
             return Err(anyhow!("Pinky Error: No model loaded!"));
@@ -53,9 +68,13 @@ impl Engine for Pinky {
         let (mut tx, rx) = mpsc::channel(100);
         let prompt_owned = prompt.to_string();
         eprintln!("DEBUG: Pinky::infer prompt: {}", prompt_owned);
-        smol::spawn(async move {
+        runtime::spawn(async move {
             let _ = tx.send(Ok(InferenceEvent::ProcessStart)).await;
-            smol::Timer::after(Duration::from_millis(50)).await;
+            runtime::sleep(Duration::from_millis(50)).await;
+            if cancel.is_cancelled() {
+                let _ = tx.send(Ok(InferenceEvent::Complete)).await;
+                return;
+            }
 
             // Emit a "thought"
             let _ = tx
@@ -66,10 +85,14 @@ impl Engine for Pinky {
                     "Narf!".to_string(),
                 ))))
                 .await;
-            smol::Timer::after(Duration::from_millis(50)).await;
+            runtime::sleep(Duration::from_millis(50)).await;
             let _ = tx
                 .send(Ok(InferenceEvent::Thought(ThoughtEvent::Stop)))
                 .await;
+            if cancel.is_cancelled() {
+                let _ = tx.send(Ok(InferenceEvent::Complete)).await;
+                return;
+            }
 
             // Emit content (echo prompt mostly)
             let _ = tx
@@ -80,8 +103,7 @@ impl Engine for Pinky {
                 .await;
 
             let _ = tx.send(Ok(InferenceEvent::Complete)).await;
-        })
-        .detach();
+        });
 
         Ok(rx)
     }
@@ -89,7 +111,7 @@ impl Engine for Pinky {
     async fn embed(
         &mut self,
         input: &str,
-        _config: InferenceConfig,
+        config: InferenceConfig,
     ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
         if !self.model_loaded {
             return Err(anyhow!("Pinky Error: No model loaded!"));
@@ -98,17 +120,59 @@ impl Engine for Pinky {
         let (mut tx, rx) = mpsc::channel(100);
         let input_owned = input.to_string();
         eprintln!("DEBUG: Pinky::embed input: {}", input_owned);
-        smol::spawn(async move {
+        runtime::spawn(async move {
             let _ = tx.send(Ok(InferenceEvent::ProcessStart)).await;
-            smol::Timer::after(Duration::from_millis(50)).await;
+            runtime::sleep(Duration::from_millis(50)).await;
 
-            // Generate a simple mock embedding (384 dimensions with random-ish values)
-            let mock_embedding: Vec<f32> = (0..384).map(|i| (i as f32 * 0.01).sin()).collect();
+            // Generate a simple mock embedding (random-ish values)
+            let mut mock_embedding: Vec<f32> = (0..EMBEDDING_LENGTH)
+                .map(|i| (i as f32 * 0.01).sin())
+                .collect();
+            if config.normalize {
+                l2_normalize(&mut mock_embedding);
+            }
 
             let _ = tx.send(Ok(InferenceEvent::Embedding(mock_embedding))).await;
             let _ = tx.send(Ok(InferenceEvent::Complete)).await;
-        })
-        .detach();
+        });
+
+        Ok(rx)
+    }
+
+    async fn rerank(
+        &mut self,
+        query: &str,
+        documents: &[String],
+        _config: InferenceConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        if !self.model_loaded {
+            return Err(anyhow!("Pinky Error: No model loaded!"));
+        }
+
+        // Mock relevance: the share of query words that appear in the document
+        let query_words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let scores: Vec<f32> = documents
+            .iter()
+            .map(|document| {
+                let document = document.to_lowercase();
+                let hits = query_words
+                    .iter()
+                    .filter(|word| document.contains(word.as_str()))
+                    .count();
+                hits as f32 / query_words.len().max(1) as f32
+            })
+            .collect();
+
+        let (mut tx, rx) = mpsc::channel(scores.len() + 2);
+        runtime::spawn(async move {
+            let _ = tx.send(Ok(InferenceEvent::ProcessStart)).await;
+            for (index, score) in scores.into_iter().enumerate() {
+                let _ = tx
+                    .send(Ok(InferenceEvent::RerankScore { index, score }))
+                    .await;
+            }
+            let _ = tx.send(Ok(InferenceEvent::Complete)).await;
+        });
 
         Ok(rx)
     }
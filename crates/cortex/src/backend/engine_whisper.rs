@@ -0,0 +1,163 @@
+#![cfg(feature = "whisper")]
+
+use crate::bridge::EventSender;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::channel::mpsc;
+use rusty_genius_core::engine::{CancellationToken, Engine};
+use rusty_genius_core::manifest::{InferenceConfig, TranscriptionConfig};
+use rusty_genius_core::protocol::{InferenceEvent, TranscriptSegment};
+use rusty_genius_core::runtime;
+use std::sync::{Arc, Mutex};
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+/// Speech-to-text engine running whisper.cpp (`ggml-*.bin`) models.
+///
+/// It only transcribes; [Engine::infer] and [Engine::embed] fail.
+pub struct WhisperEngine {
+    context: Option<Arc<WhisperContext>>,
+}
+
+impl WhisperEngine {
+    pub fn new() -> Self {
+        Self { context: None }
+    }
+}
+
+impl Default for WhisperEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Engine for WhisperEngine {
+    async fn load_model(&mut self, model_path: &str) -> Result<()> {
+        let path = model_path.to_string();
+        let context = runtime::spawn_blocking(move || {
+            WhisperContext::new_with_params(&path, WhisperContextParameters::default())
+        })
+        .await
+        .map_err(|e| anyhow!("Failed to load whisper model {}: {}", model_path, e))?;
+        self.context = Some(Arc::new(context));
+        Ok(())
+    }
+
+    async fn unload_model(&mut self) -> Result<()> {
+        self.context = None;
+        Ok(())
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.context.is_some()
+    }
+
+    fn default_model(&self) -> String {
+        "whisper-base.en".to_string()
+    }
+
+    async fn infer(
+        &mut self,
+        _prompt: &str,
+        _config: InferenceConfig,
+        _cancel: CancellationToken,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        Err(anyhow!("WhisperEngine only transcribes audio"))
+    }
+
+    async fn embed(
+        &mut self,
+        _input: &str,
+        _config: InferenceConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        Err(anyhow!("WhisperEngine only transcribes audio"))
+    }
+
+    async fn transcribe(
+        &mut self,
+        samples: &[f32],
+        config: TranscriptionConfig,
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+        let context = self
+            .context
+            .clone()
+            .ok_or_else(|| anyhow!("WhisperEngine: no model loaded"))?;
+        if samples.is_empty() {
+            return Err(anyhow!("No audio to transcribe"));
+        }
+        let samples = samples.to_vec();
+        let (tx, rx) = mpsc::channel(100);
+
+        runtime::spawn_blocking(move || {
+            let events = Arc::new(Mutex::new(EventSender::new(tx)));
+            send(&events, Ok(InferenceEvent::ProcessStart));
+            if let Err(e) = run_transcription(&context, &samples, &config, &events) {
+                send(&events, Err(e));
+            }
+            send(&events, Ok(InferenceEvent::Complete));
+        });
+
+        Ok(rx)
+    }
+}
+
+/// Run whisper over `samples`, sending each segment as soon as it is decoded.
+fn run_transcription(
+    context: &WhisperContext,
+    samples: &[f32],
+    config: &TranscriptionConfig,
+    events: &Arc<Mutex<EventSender>>,
+) -> Result<()> {
+    let mut state = context
+        .create_state()
+        .map_err(|e| anyhow!("Failed to create whisper state: {}", e))?;
+
+    // whisper-rs panics on strings with NUL bytes.
+    let language = config
+        .language
+        .as_deref()
+        .unwrap_or("auto")
+        .replace('\0', "");
+
+    let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+    params.set_language(Some(&language));
+    params.set_translate(config.translate);
+    params.set_temperature(config.temperature);
+    if let Some(prompt) = &config.prompt {
+        params.set_initial_prompt(&prompt.replace('\0', ""));
+    }
+    if let Some(n_threads) = config.n_threads {
+        params.set_n_threads(n_threads as i32);
+    }
+    params.set_print_special(false);
+    params.set_print_progress(false);
+    params.set_print_realtime(false);
+    params.set_print_timestamps(false);
+
+    let segments = events.clone();
+    params.set_segment_callback_safe(move |segment: whisper_rs::SegmentCallbackData| {
+        // Timestamps are in centiseconds.
+        let event = InferenceEvent::Transcript(TranscriptSegment {
+            start_ms: segment.start_timestamp.max(0) as u64 * 10,
+            end_ms: segment.end_timestamp.max(0) as u64 * 10,
+            text: segment.text,
+        });
+        send(&segments, Ok(event));
+    });
+    // Stop once nobody is listening.
+    let listener = events.clone();
+    params.set_abort_callback_safe(move || lock(&listener).is_closed());
+
+    state
+        .full(params, samples)
+        .map_err(|e| anyhow!("Transcription failed: {}", e))
+}
+
+/// Send `event` through the bridge that the segment callback shares.
+fn send(events: &Mutex<EventSender>, event: Result<InferenceEvent>) {
+    lock(events).send(event);
+}
+
+fn lock(events: &Mutex<EventSender>) -> std::sync::MutexGuard<'_, EventSender> {
+    events.lock().unwrap_or_else(|e| e.into_inner())
+}
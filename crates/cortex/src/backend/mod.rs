@@ -4,7 +4,16 @@ mod engine_stub;
 #[cfg(feature = "genai")]
 mod engine_genai;
 
-pub use rusty_genius_core::engine::Engine;
+#[cfg(feature = "candle-engine")]
+mod engine_candle;
+
+#[cfg(feature = "openai")]
+mod engine_openai;
+
+#[cfg(feature = "whisper")]
+mod engine_whisper;
+
+pub use rusty_genius_core::engine::{CancellationToken, Engine};
 
 #[cfg(feature = "real-engine")]
 pub use engine_real::Brain;
@@ -15,11 +24,39 @@ pub use engine_stub::Pinky;
 #[cfg(feature = "genai")]
 pub use engine_genai::{GeminiApiConfig, GeminiEngine};
 
+#[cfg(feature = "candle-engine")]
+pub use engine_candle::CandleEngine;
+
+#[cfg(feature = "openai")]
+pub use engine_openai::{OpenAiApiConfig, OpenAiChunk, OpenAiEngine, OpenAiSseLine};
+
+#[cfg(feature = "whisper")]
+pub use engine_whisper::WhisperEngine;
+
 // Re-export URL / body builders for testing and advanced use
 #[cfg(feature = "genai")]
 pub use engine_genai::{build_embed_body, build_infer_body, embed_url, infer_url, parse_sse_line};
+#[cfg(feature = "openai")]
+pub use engine_openai::{
+    build_chat_body, build_embeddings_body, chat_completions_url, embeddings_url,
+    parse_openai_sse_line,
+};
 
+/// The local engine this build provides: [Brain] with `real-engine`, [CandleEngine] with
+/// `candle-engine`, or the [Pinky] stub. With both real engines built in, `GENIUS_ENGINE=candle`
+/// picks candle. With `openai`, `GENIUS_ENGINE=openai` picks a remote [OpenAiEngine] configured
+/// by [OpenAiApiConfig::from_env].
 pub async fn create_engine() -> Box<dyn Engine> {
+    #[cfg(feature = "openai")]
+    if std::env::var("GENIUS_ENGINE").as_deref() == Ok("openai") {
+        return Box::new(OpenAiEngine::new(OpenAiApiConfig::from_env()));
+    }
+
+    #[cfg(feature = "candle-engine")]
+    if !cfg!(feature = "real-engine") || std::env::var("GENIUS_ENGINE").as_deref() == Ok("candle") {
+        return Box::new(CandleEngine::new());
+    }
+
     #[cfg(feature = "real-engine")]
     {
         Box::new(Brain::new())
@@ -30,3 +67,16 @@ pub async fn create_engine() -> Box<dyn Engine> {
         Box::new(Pinky::new())
     }
 }
+
+/// The speech-to-text engine this build provides: [WhisperEngine] with `whisper`, otherwise none.
+pub fn create_transcriber() -> Option<Box<dyn Engine>> {
+    #[cfg(feature = "whisper")]
+    {
+        Some(Box::new(WhisperEngine::new()))
+    }
+
+    #[cfg(not(feature = "whisper"))]
+    {
+        None
+    }
+}
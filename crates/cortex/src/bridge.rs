@@ -0,0 +1,77 @@
+//! Sending events from an engine's blocking threads to its async receiver.
+
+use anyhow::Result;
+use futures::channel::mpsc;
+use futures::sink::SinkExt;
+use rusty_genius_core::protocol::{InferenceEvent, ThoughtEvent};
+use std::collections::VecDeque;
+
+/// The sending half of an event channel, for threads that generate tokens.
+///
+/// [EventSender::send] never waits for the receiver. While the channel is full, events queue
+/// here and consecutive content or thought deltas are merged, so a slow reader gets fewer,
+/// larger deltas instead of holding up generation (and, in a shared batch, every other
+/// sequence). Queued events go out with later sends, [EventSender::flush], or on drop, which
+/// waits for the receiver to take them so that nothing is lost at the end of a generation.
+pub struct EventSender {
+    tx: mpsc::Sender<Result<InferenceEvent>>,
+    pending: VecDeque<Result<InferenceEvent>>,
+}
+
+impl EventSender {
+    pub fn new(tx: mpsc::Sender<Result<InferenceEvent>>) -> Self {
+        Self {
+            tx,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Whether the receiver is gone.
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    /// Send `event`, or queue it while the channel is full.
+    pub fn send(&mut self, event: Result<InferenceEvent>) {
+        match (self.pending.back_mut(), event) {
+            (Some(Ok(InferenceEvent::Content(queued))), Ok(InferenceEvent::Content(text))) => {
+                queued.push_str(&text)
+            }
+            (
+                Some(Ok(InferenceEvent::Thought(ThoughtEvent::Delta(queued)))),
+                Ok(InferenceEvent::Thought(ThoughtEvent::Delta(text))),
+            ) => queued.push_str(&text),
+            (_, event) => self.pending.push_back(event),
+        }
+        self.pump();
+    }
+
+    /// Wait until every queued event is sent, or the receiver is gone.
+    pub fn flush(&mut self) {
+        while let Some(event) = self.pending.pop_front() {
+            if futures::executor::block_on(self.tx.send(event)).is_err() {
+                self.pending.clear();
+            }
+        }
+    }
+
+    /// Send queued events while the channel has room.
+    fn pump(&mut self) {
+        while let Some(event) = self.pending.pop_front() {
+            match self.tx.try_send(event) {
+                Ok(()) => {}
+                Err(e) if e.is_full() => {
+                    self.pending.push_front(e.into_inner());
+                    return;
+                }
+                Err(_) => self.pending.clear(),
+            }
+        }
+    }
+}
+
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
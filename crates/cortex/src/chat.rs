@@ -0,0 +1,4 @@
+//! Chat prompt rendering; it lives in core so the orchestrator can render prompts for any
+//! engine.
+
+pub use rusty_genius_core::chat::*;
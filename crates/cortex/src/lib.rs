@@ -1,5 +1,12 @@
-pub use rusty_genius_core::engine::Engine;
+pub use rusty_genius_core::engine::{CancellationToken, Engine};
 
 pub mod backend;
+pub mod bridge;
+pub mod chat;
+pub mod offload;
+pub mod pool;
 
-pub use backend::create_engine;
+pub use backend::{create_engine, create_transcriber};
+pub use bridge::EventSender;
+pub use chat::ChatTemplate;
+pub use pool::ModelPool;
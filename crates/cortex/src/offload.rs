@@ -0,0 +1,23 @@
+//! Choosing how many layers of a model to offload to the GPU.
+//!
+//! A model with `n` repeating layers offloads `n + 1` layers, the last being the output layer.
+//! Layers are taken to be the same size, so each holds an equal share of the model file. The
+//! VRAM left free after the weights goes to the KV cache and compute buffers, whose size
+//! depends on contexts that don't exist yet; [fit_gpu_layers] keeps a share of the free memory
+//! back for them.
+
+/// VRAM always kept back for the KV cache and compute buffers.
+pub const MIN_VRAM_HEADROOM: u64 = 512 * 1024 * 1024;
+
+/// Share of the free VRAM kept back, when it is more than [MIN_VRAM_HEADROOM].
+pub const VRAM_HEADROOM_FRACTION: f64 = 0.2;
+
+/// Layers of a model `model_bytes` in size with `n_layer` repeating layers that fit in
+/// `free_vram` bytes, from `0` to `n_layer + 1`.
+pub fn fit_gpu_layers(model_bytes: u64, n_layer: u32, free_vram: u64) -> u32 {
+    let layers = n_layer + 1;
+    let headroom = ((free_vram as f64 * VRAM_HEADROOM_FRACTION) as u64).max(MIN_VRAM_HEADROOM);
+    let usable = free_vram.saturating_sub(headroom);
+    let layer_bytes = model_bytes.div_ceil(layers as u64).max(1);
+    (usable / layer_bytes).min(layers as u64) as u32
+}
@@ -0,0 +1,149 @@
+//! Several loaded models at once.
+//!
+//! A [ModelPool] keeps models keyed by the path they were loaded from, so going back to a
+//! model that is still resident (a chat model after an embedding request, say) costs nothing.
+//! Each entry records how much memory it holds; when a new model would exceed the pool's
+//! limits, the least recently used ones are evicted first.
+
+use std::collections::HashMap;
+
+/// How many models a pool holds by default; see [ModelPool::with_max_models].
+pub const DEFAULT_MAX_MODELS: usize = 2;
+
+struct PoolEntry<M> {
+    model: M,
+    bytes: u64,
+    /// Value of the pool's use counter when the entry was last used.
+    last_used: u64,
+}
+
+pub struct ModelPool<M> {
+    entries: HashMap<String, PoolEntry<M>>,
+    max_models: usize,
+    memory_budget: Option<u64>,
+    uses: u64,
+}
+
+impl<M> ModelPool<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep at most `max` models loaded.
+    pub fn with_max_models(mut self, max: usize) -> Self {
+        self.max_models = max.max(1);
+        self
+    }
+
+    /// Keep the models' combined size under `bytes`. A single model larger than the budget is
+    /// still loaded, on its own.
+    pub fn with_memory_budget(mut self, bytes: u64) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    /// The model loaded from `path`, marking it as just used.
+    pub fn get(&mut self, path: &str) -> Option<&mut M> {
+        let entry = self.entries.get_mut(path)?;
+        self.uses += 1;
+        entry.last_used = self.uses;
+        Some(&mut entry.model)
+    }
+
+    /// The model loaded from `path`, without counting it as a use.
+    pub fn peek(&self, path: &str) -> Option<&M> {
+        self.entries.get(path).map(|entry| &entry.model)
+    }
+
+    /// Every loaded model, without counting it as a use.
+    pub fn models_mut(&mut self) -> impl Iterator<Item = &mut M> {
+        self.entries.values_mut().map(|entry| &mut entry.model)
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    /// Add a model taking `bytes` of memory, replacing any loaded from the same path.
+    ///
+    /// Returns the models evicted to make room, least recently used first, so the caller can
+    /// release whatever else refers to them.
+    pub fn insert(&mut self, path: &str, model: M, bytes: u64) -> Vec<(String, M)> {
+        let mut evicted = Vec::new();
+        if let Some(old) = self.entries.remove(path) {
+            evicted.push((path.to_string(), old.model));
+        }
+
+        while !self.entries.is_empty() && !self.fits(bytes) {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(oldest, _)| oldest.clone())
+            else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                evicted.push((oldest, entry.model));
+            }
+        }
+
+        self.uses += 1;
+        self.entries.insert(
+            path.to_string(),
+            PoolEntry {
+                model,
+                bytes,
+                last_used: self.uses,
+            },
+        );
+        evicted
+    }
+
+    pub fn remove(&mut self, path: &str) -> Option<M> {
+        self.entries.remove(path).map(|entry| entry.model)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Combined size of the loaded models, in bytes.
+    pub fn used_bytes(&self) -> u64 {
+        self.entries.values().map(|entry| entry.bytes).sum()
+    }
+
+    /// Paths of the loaded models, most recently used first.
+    pub fn paths(&self) -> Vec<String> {
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        entries.sort_by_key(|(_, entry)| std::cmp::Reverse(entry.last_used));
+        entries.into_iter().map(|(path, _)| path.clone()).collect()
+    }
+
+    /// Whether another model of `bytes` can join the current entries.
+    fn fits(&self, bytes: u64) -> bool {
+        self.entries.len() < self.max_models
+            && self
+                .memory_budget
+                .is_none_or(|budget| self.used_bytes() + bytes <= budget)
+    }
+}
+
+impl<M> Default for ModelPool<M> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            max_models: DEFAULT_MAX_MODELS,
+            memory_budget: None,
+            uses: 0,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use futures::channel::mpsc;
+use futures::StreamExt;
+use rusty_genius_core::protocol::{InferenceEvent, ThoughtEvent};
+use rusty_genius_cortex::EventSender;
+
+fn content(text: &str) -> anyhow::Result<InferenceEvent> {
+    Ok(InferenceEvent::Content(text.to_string()))
+}
+
+/// Everything the receiver has been sent so far, as debug strings.
+fn received(rx: &mut mpsc::Receiver<anyhow::Result<InferenceEvent>>) -> Vec<String> {
+    let mut events = Vec::new();
+    while let Ok(Some(event)) = rx.try_next() {
+        events.push(format!("{:?}", event.unwrap()));
+    }
+    events
+}
+
+#[test]
+fn test_bridge_passes_events_in_order() {
+    let (tx, mut rx) = mpsc::channel(8);
+    let mut events = EventSender::new(tx);
+    events.send(Ok(InferenceEvent::ProcessStart));
+    events.send(content("Hel"));
+    events.send(content("lo"));
+    events.send(Ok(InferenceEvent::Complete));
+    assert_eq!(
+        received(&mut rx),
+        vec![
+            "ProcessStart",
+            r#"Content("Hel")"#,
+            r#"Content("lo")"#,
+            "Complete"
+        ]
+    );
+}
+
+#[test]
+fn test_bridge_merges_deltas_while_full() {
+    // One slot for the channel plus one for the sender
+    let (tx, mut rx) = mpsc::channel(0);
+    let mut events = EventSender::new(tx);
+    events.send(Ok(InferenceEvent::ProcessStart));
+    for piece in ["a", "b", "c"] {
+        events.send(content(piece));
+    }
+    events.send(Ok(InferenceEvent::Thought(ThoughtEvent::Delta("x".into()))));
+    events.send(Ok(InferenceEvent::Thought(ThoughtEvent::Delta("y".into()))));
+    events.send(content("d"));
+
+    // Reading makes room for the queue, one event per send
+    assert_eq!(received(&mut rx), vec!["ProcessStart"]);
+    events.send(Ok(InferenceEvent::Complete));
+    assert_eq!(received(&mut rx), vec![r#"Content("abc")"#]);
+
+    let reader = std::thread::spawn(move || {
+        futures::executor::block_on(rx.map(|event| format!("{:?}", event.unwrap())).collect())
+    });
+    drop(events);
+    let rest: Vec<String> = reader.join().unwrap();
+    assert_eq!(
+        rest,
+        vec![r#"Thought(Delta("xy"))"#, r#"Content("d")"#, "Complete"]
+    );
+}
+
+#[test]
+fn test_bridge_drops_events_without_receiver() {
+    let (tx, rx) = mpsc::channel(0);
+    let mut events = EventSender::new(tx);
+    drop(rx);
+    assert!(events.is_closed());
+    events.send(content("lost"));
+    events.flush();
+}
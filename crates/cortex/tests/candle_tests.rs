@@ -0,0 +1,29 @@
+#![cfg(feature = "candle-engine")]
+
+use rusty_genius_core::manifest::InferenceConfig;
+use rusty_genius_cortex::backend::{CancellationToken, CandleEngine, Engine};
+
+#[async_std::test]
+async fn test_candle_requires_model() {
+    let mut engine = CandleEngine::new();
+    assert!(!engine.is_loaded());
+    assert!(engine.model_info().is_none());
+
+    let result = engine
+        .infer(
+            "Hello",
+            InferenceConfig::default(),
+            CancellationToken::new(),
+        )
+        .await;
+    assert!(result.is_err());
+}
+
+#[async_std::test]
+async fn test_candle_load_missing_directory() {
+    let dir = std::env::temp_dir().join("rusty-genius-candle-missing");
+    let mut engine = CandleEngine::new();
+    let result = engine.load_model(dir.to_str().unwrap()).await;
+    assert!(result.is_err());
+    assert!(!engine.is_loaded());
+}
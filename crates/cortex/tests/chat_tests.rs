@@ -0,0 +1,76 @@
+#[cfg(not(feature = "real-engine"))]
+use rusty_genius_cortex::backend::Pinky;
+use rusty_genius_cortex::chat::{build_prompt, ChatMessage, ChatTemplate};
+
+fn conversation() -> Vec<ChatMessage> {
+    vec![
+        ChatMessage::system("Be brief."),
+        ChatMessage::user("Hi"),
+        ChatMessage::assistant("Hello!"),
+        ChatMessage::user("Bye"),
+    ]
+}
+
+#[test]
+fn test_detect_from_jinja_source() {
+    let qwen = "{% for message in messages %}{{'<|im_start|>' + message['role'] + '\\n' + message['content'] + '<|im_end|>' + '\\n'}}{% endfor %}";
+    assert_eq!(ChatTemplate::detect(qwen), Some(ChatTemplate::ChatMl));
+    let llama3 = "{{ '<|start_header_id|>' + message['role'] + '<|end_header_id|>\\n\\n' }}";
+    assert_eq!(ChatTemplate::detect(llama3), Some(ChatTemplate::Llama3));
+    let mistral = "{{ '[INST] ' + message['content'] + ' [/INST]' }}";
+    assert_eq!(ChatTemplate::detect(mistral), Some(ChatTemplate::Mistral));
+    assert_eq!(ChatTemplate::detect("{{ messages }}"), None);
+}
+
+#[test]
+fn test_override_wins_over_metadata() {
+    let metadata = Some("{{ '<start_of_turn>' }}");
+    assert_eq!(ChatTemplate::resolve(None, metadata), ChatTemplate::Gemma);
+    assert_eq!(
+        ChatTemplate::resolve(Some("llama3"), metadata),
+        ChatTemplate::Llama3
+    );
+    assert_eq!(ChatTemplate::resolve(None, None), ChatTemplate::ChatMl);
+}
+
+#[test]
+fn test_render_chatml() {
+    let prompt = ChatTemplate::ChatMl.render(&conversation(), true);
+    assert_eq!(
+        prompt,
+        "<|im_start|>system\nBe brief.<|im_end|>\n\
+         <|im_start|>user\nHi<|im_end|>\n\
+         <|im_start|>assistant\nHello!<|im_end|>\n\
+         <|im_start|>user\nBye<|im_end|>\n\
+         <|im_start|>assistant\n"
+    );
+}
+
+#[test]
+fn test_render_folds_system_into_first_turn() {
+    let prompt = ChatTemplate::Gemma.render(&conversation(), true);
+    assert_eq!(
+        prompt,
+        "<start_of_turn>user\nBe brief.\n\nHi<end_of_turn>\n\
+         <start_of_turn>model\nHello!<end_of_turn>\n\
+         <start_of_turn>user\nBye<end_of_turn>\n\
+         <start_of_turn>model\n"
+    );
+
+    let prompt = ChatTemplate::Llama2.render(&conversation(), true);
+    assert_eq!(
+        prompt,
+        "[INST] <<SYS>>\nBe brief.\n<</SYS>>\n\nHi [/INST] Hello! </s><s>[INST] Bye [/INST]"
+    );
+}
+
+#[cfg(not(feature = "real-engine"))]
+#[test]
+fn test_build_prompt_defaults_to_chatml() {
+    let engine = Pinky::new();
+    let prompt = build_prompt(&engine, &[ChatMessage::user("Hi")]);
+    assert_eq!(
+        prompt,
+        "<|im_start|>user\nHi<|im_end|>\n<|im_start|>assistant\n"
+    );
+}
@@ -2,9 +2,9 @@ use anyhow::Result;
 use futures::StreamExt;
 use rusty_genius_core::manifest::InferenceConfig;
 use rusty_genius_core::protocol::InferenceEvent;
-use rusty_genius_cortex::backend::Engine;
 #[cfg(not(feature = "real-engine"))]
 use rusty_genius_cortex::backend::Pinky;
+use rusty_genius_cortex::backend::{CancellationToken, Engine};
 
 async fn get_engine() -> Box<dyn Engine> {
     #[cfg(feature = "real-engine")]
@@ -61,7 +61,13 @@ async fn test_engine_load_behavior() -> Result<()> {
 async fn test_stub_inference_protocol() -> Result<()> {
     let mut engine = get_engine_with_default_model().await?;
 
-    let mut rx = engine.infer("hello", InferenceConfig::default()).await?;
+    let mut rx = engine
+        .infer(
+            "hello",
+            InferenceConfig::default(),
+            CancellationToken::new(),
+        )
+        .await?;
     let mut has_content = false;
     let mut has_complete = false;
 
@@ -79,6 +85,32 @@ async fn test_stub_inference_protocol() -> Result<()> {
     Ok(())
 }
 
+#[cfg(not(feature = "real-engine"))]
+#[async_std::test]
+async fn test_stub_inference_cancelled() -> Result<()> {
+    let mut engine = get_engine_with_default_model().await?;
+
+    let cancel = CancellationToken::new();
+    let mut rx = engine
+        .infer("hello", InferenceConfig::default(), cancel.clone())
+        .await?;
+    cancel.cancel();
+
+    let mut events = vec![];
+    while let Some(res) = rx.next().await {
+        events.push(res?);
+    }
+
+    assert!(
+        !events
+            .iter()
+            .any(|e| matches!(e, InferenceEvent::Content(_))),
+        "Cancelled inference should not emit content"
+    );
+    assert!(matches!(events.last(), Some(InferenceEvent::Complete)));
+    Ok(())
+}
+
 #[cfg(not(feature = "real-engine"))]
 #[async_std::test]
 async fn test_stub_embedding_protocol() -> Result<()> {
@@ -112,3 +144,104 @@ async fn test_engine_unload() -> Result<()> {
     assert!(!engine.is_loaded());
     Ok(())
 }
+
+#[cfg(not(feature = "real-engine"))]
+#[async_std::test]
+async fn test_stub_batch_embedding() -> Result<()> {
+    let mut engine = get_engine_with_default_model().await?;
+
+    let inputs = vec!["one".to_string(), "two".to_string(), "three".to_string()];
+    let mut rx = engine
+        .embed_batch(&inputs, InferenceConfig::default())
+        .await?;
+    let mut indices = Vec::new();
+    let mut has_complete = false;
+
+    while let Some(res) = rx.next().await {
+        match res? {
+            InferenceEvent::IndexedEmbedding { index, embedding } => {
+                assert!(!embedding.is_empty());
+                indices.push(index);
+            }
+            InferenceEvent::Embedding(_) => panic!("Batch embeds should be indexed"),
+            InferenceEvent::Complete => has_complete = true,
+            _ => {}
+        }
+    }
+
+    assert_eq!(indices, vec![0, 1, 2]);
+    assert!(has_complete, "Engine should have emitted Complete");
+    Ok(())
+}
+
+#[cfg(not(feature = "real-engine"))]
+#[async_std::test]
+async fn test_stub_normalized_embedding() -> Result<()> {
+    let mut engine = get_engine_with_default_model().await?;
+
+    let config = InferenceConfig {
+        normalize: true,
+        ..InferenceConfig::default()
+    };
+    let mut rx = engine.embed("hello", config).await?;
+    let mut norm = None;
+
+    while let Some(res) = rx.next().await {
+        if let InferenceEvent::Embedding(emb) = res? {
+            norm = Some(emb.iter().map(|x| x * x).sum::<f32>().sqrt());
+        }
+    }
+
+    let norm = norm.expect("Engine should have emitted embedding");
+    assert!((norm - 1.0).abs() < 1e-4, "norm was {}", norm);
+    Ok(())
+}
+
+#[cfg(not(feature = "real-engine"))]
+#[async_std::test]
+async fn test_stub_rerank() -> Result<()> {
+    let mut engine = get_engine_with_default_model().await?;
+
+    let documents = vec![
+        "Pinky and the Brain".to_string(),
+        "Unrelated text".to_string(),
+    ];
+    let mut rx = engine
+        .rerank("the brain", &documents, InferenceConfig::default())
+        .await?;
+    let mut scores = Vec::new();
+
+    while let Some(res) = rx.next().await {
+        if let InferenceEvent::RerankScore { index, score } = res? {
+            scores.push((index, score));
+        }
+    }
+
+    assert_eq!(scores.len(), 2);
+    assert!(scores[0].1 > scores[1].1, "scores were {:?}", scores);
+    Ok(())
+}
+
+#[cfg(not(feature = "real-engine"))]
+#[async_std::test]
+async fn test_stub_has_no_adapters() -> Result<()> {
+    let mut engine = get_engine_with_default_model().await?;
+
+    let err = engine.apply_adapter("adapter.gguf", 1.0).await.unwrap_err();
+    assert!(err.to_string().contains("LoRA"));
+    engine.remove_adapter().await?;
+    Ok(())
+}
+
+#[cfg(not(feature = "real-engine"))]
+#[async_std::test]
+async fn test_stub_model_info() -> Result<()> {
+    let mut engine = get_engine().await;
+    assert!(engine.model_info().is_none());
+
+    engine.load_model(DEFAULT_MODEL).await?;
+    let info = engine.model_info().expect("info of the loaded model");
+    assert_eq!(info.embedding_length, Some(384));
+    assert_eq!(info.context_length, Some(2048));
+    Ok(())
+}
@@ -1,8 +1,8 @@
 #![cfg(feature = "genai")]
 
 use rusty_genius_cortex::backend::{
-    build_embed_body, build_infer_body, embed_url, infer_url, parse_sse_line, GeminiApiConfig,
-    GeminiEngine, Engine,
+    build_embed_body, build_infer_body, embed_url, infer_url, parse_sse_line, CancellationToken,
+    GeminiApiConfig, GeminiEngine, Engine,
 };
 use rusty_genius_core::manifest::InferenceConfig;
 
@@ -216,7 +216,9 @@ async fn test_infer_without_load_errors() {
     let mut engine = GeminiEngine::new(GeminiApiConfig::AiStudio {
         api_key: "k".to_string(),
     });
-    let result = engine.infer("test", InferenceConfig::default()).await;
+    let result = engine
+        .infer("test", InferenceConfig::default(), CancellationToken::new())
+        .await;
     assert!(result.is_err());
     assert!(
         result.unwrap_err().to_string().contains("no model loaded"),
@@ -0,0 +1,15 @@
+use rusty_genius_cortex::offload::fit_gpu_layers;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
+#[test]
+fn test_fit_gpu_layers() {
+    // A 4 GiB model with 31 repeating layers: 32 layers of 128 MiB
+    assert_eq!(fit_gpu_layers(4 * GIB, 31, 24 * GIB), 32);
+    // 8 GiB free keeps 1.6 GiB back, leaving room for 51 layers' worth
+    assert_eq!(fit_gpu_layers(4 * GIB, 31, 8 * GIB), 32);
+    // 2 GiB free keeps 512 MiB back: 12 layers
+    assert_eq!(fit_gpu_layers(4 * GIB, 31, 2 * GIB), 12);
+    assert_eq!(fit_gpu_layers(4 * GIB, 31, GIB / 4), 0);
+    assert_eq!(fit_gpu_layers(4 * GIB, 31, 0), 0);
+}
@@ -0,0 +1,268 @@
+#![cfg(feature = "openai")]
+
+use futures::StreamExt;
+use rusty_genius_core::manifest::InferenceConfig;
+use rusty_genius_core::protocol::{InferenceEvent, ThoughtEvent};
+use rusty_genius_cortex::backend::{
+    build_chat_body, build_embeddings_body, chat_completions_url, embeddings_url,
+    parse_openai_sse_line, CancellationToken, Engine, OpenAiApiConfig, OpenAiEngine, OpenAiSseLine,
+};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+
+/// Serve one HTTP request on a local port with `body`, returning the base URL.
+fn serve_once(content_type: &'static str, body: String) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = Vec::new();
+        let mut buf = [0u8; 4096];
+        // Read the headers, then the Content-Length bytes of body after them.
+        loop {
+            let n = stream.read(&mut buf).unwrap();
+            request.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text[..end]
+                    .lines()
+                    .find_map(|line| {
+                        line.to_ascii_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|value| value.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+                if request.len() >= end + 4 + length {
+                    break;
+                }
+            }
+        }
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+            content_type,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).unwrap();
+    });
+    format!("http://{}/v1", addr)
+}
+
+// ── URL / body construction tests ──
+
+#[test]
+fn test_urls_trim_trailing_slash() {
+    let config = OpenAiApiConfig::new("http://localhost:8080/v1/");
+    assert_eq!(
+        chat_completions_url(&config),
+        "http://localhost:8080/v1/chat/completions"
+    );
+    assert_eq!(
+        embeddings_url(&config),
+        "http://localhost:8080/v1/embeddings"
+    );
+}
+
+#[test]
+fn test_chat_body_structure() {
+    let config = InferenceConfig {
+        temperature: 0.5,
+        top_p: Some(0.9),
+        max_tokens: Some(64),
+        ..Default::default()
+    };
+    let body = build_chat_body("Hello", "gpt-4o-mini", &config);
+    assert_eq!(body["model"], "gpt-4o-mini");
+    assert_eq!(body["messages"][0]["role"], "user");
+    assert_eq!(body["messages"][0]["content"], "Hello");
+    assert_eq!(body["stream"], true);
+    assert_eq!(body["stream_options"]["include_usage"], true);
+    assert_eq!(body["temperature"], 0.5);
+    assert_eq!(body["max_tokens"], 64);
+    assert!(body.get("response_format").is_none());
+    assert!(body.get("logprobs").is_none());
+}
+
+#[test]
+fn test_chat_body_json_schema_and_logprobs() {
+    let config = InferenceConfig {
+        json_schema: Some(serde_json::json!({ "type": "object" })),
+        logprobs: Some(3),
+        ..Default::default()
+    };
+    let body = build_chat_body("Hi", "m", &config);
+    assert_eq!(body["response_format"]["type"], "json_schema");
+    assert_eq!(
+        body["response_format"]["json_schema"]["schema"]["type"],
+        "object"
+    );
+    assert_eq!(body["logprobs"], true);
+    assert_eq!(body["top_logprobs"], 3);
+}
+
+#[test]
+fn test_embeddings_body_structure() {
+    let body = build_embeddings_body(
+        &["a".to_string(), "b".to_string()],
+        "text-embedding-3-small",
+    );
+    assert_eq!(body["model"], "text-embedding-3-small");
+    assert_eq!(body["input"], serde_json::json!(["a", "b"]));
+}
+
+// ── SSE parsing tests ──
+
+#[test]
+fn test_parse_sse_content_chunk() {
+    let line = r#"data: {"choices":[{"delta":{"content":"Hi"},"finish_reason":null}]}"#;
+    match parse_openai_sse_line(line) {
+        Some(OpenAiSseLine::Chunk(chunk)) => {
+            assert_eq!(chunk.content.as_deref(), Some("Hi"));
+            assert!(chunk.reasoning.is_none());
+            assert!(chunk.finish_reason.is_none());
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_sse_reasoning_chunk() {
+    let line = r#"data:{"choices":[{"delta":{"reasoning_content":"hmm"}}]}"#;
+    match parse_openai_sse_line(line) {
+        Some(OpenAiSseLine::Chunk(chunk)) => {
+            assert_eq!(chunk.reasoning.as_deref(), Some("hmm"));
+            assert!(chunk.content.is_none());
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_sse_usage_chunk() {
+    let line = r#"data: {"choices":[],"usage":{"prompt_tokens":7,"completion_tokens":3,"prompt_tokens_details":{"cached_tokens":2}}}"#;
+    match parse_openai_sse_line(line) {
+        Some(OpenAiSseLine::Chunk(chunk)) => {
+            let usage = chunk.usage.unwrap();
+            assert_eq!(usage.prompt_tokens, 7);
+            assert_eq!(usage.completion_tokens, 3);
+            assert_eq!(usage.cached_tokens, 2);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_sse_logprobs() {
+    let line = r#"data: {"choices":[{"delta":{"content":"a"},"logprobs":{"content":[{"token":"a","logprob":-0.5,"top_logprobs":[{"token":"a","logprob":-0.5},{"token":"b","logprob":-1.5}]}]}}]}"#;
+    match parse_openai_sse_line(line) {
+        Some(OpenAiSseLine::Chunk(chunk)) => {
+            assert_eq!(chunk.logprobs.len(), 1);
+            assert_eq!(chunk.logprobs[0].top_logprobs[1].token, "b");
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_sse_done_and_error() {
+    assert_eq!(
+        parse_openai_sse_line("data: [DONE]"),
+        Some(OpenAiSseLine::Done)
+    );
+    assert_eq!(
+        parse_openai_sse_line(r#"data: {"error":{"message":"overloaded"}}"#),
+        Some(OpenAiSseLine::Error("overloaded".to_string()))
+    );
+}
+
+#[test]
+fn test_parse_sse_non_data_line() {
+    assert!(parse_openai_sse_line(": keep-alive").is_none());
+    assert!(parse_openai_sse_line("data: not json").is_none());
+}
+
+// ── Engine tests ──
+
+#[test]
+fn test_engine_defaults() {
+    let engine = OpenAiEngine::new(
+        OpenAiApiConfig::new("http://localhost:8080/v1").with_default_model("qwen"),
+    );
+    assert_eq!(engine.default_model(), "qwen");
+    assert!(engine.is_remote());
+    assert!(!engine.is_loaded());
+}
+
+#[smol_potat::test]
+async fn test_infer_without_load_errors() {
+    let mut engine = OpenAiEngine::new(OpenAiApiConfig::new("http://localhost:8080/v1"));
+    let result = engine
+        .infer("Hi", InferenceConfig::default(), CancellationToken::new())
+        .await;
+    assert!(result.is_err());
+}
+
+#[smol_potat::test]
+async fn test_infer_streams_events() {
+    let body = [
+        r#"data: {"choices":[{"delta":{"reasoning_content":"think"}}]}"#,
+        r#"data: {"choices":[{"delta":{"content":"Hello"}}]}"#,
+        r#"data: {"choices":[{"delta":{"content":" world"},"finish_reason":"stop"}]}"#,
+        r#"data: {"choices":[],"usage":{"prompt_tokens":4,"completion_tokens":2}}"#,
+        "data: [DONE]",
+    ]
+    .map(|line| format!("{}\n\n", line))
+    .concat();
+    let base_url = serve_once("text/event-stream", body);
+
+    let mut engine = OpenAiEngine::new(OpenAiApiConfig::new(base_url).with_api_key("key"));
+    engine.load_model("test-model").await.unwrap();
+    let mut rx = engine
+        .infer("Hi", InferenceConfig::default(), CancellationToken::new())
+        .await
+        .unwrap();
+
+    let mut content = String::new();
+    let mut thought = String::new();
+    let mut usage = None;
+    let mut completed = false;
+    while let Some(event) = rx.next().await {
+        match event.unwrap() {
+            InferenceEvent::Content(text) => content.push_str(&text),
+            InferenceEvent::Thought(ThoughtEvent::Delta(text)) => thought.push_str(&text),
+            InferenceEvent::Usage(u) => usage = Some(u),
+            InferenceEvent::Complete => completed = true,
+            _ => {}
+        }
+    }
+    assert_eq!(content, "Hello world");
+    assert_eq!(thought, "think");
+    assert_eq!(usage.unwrap().completion_tokens, 2);
+    assert!(completed);
+}
+
+#[smol_potat::test]
+async fn test_embed_batch() {
+    let body = r#"{"data":[{"index":1,"embedding":[0.0,2.0]},{"index":0,"embedding":[3.0,4.0]}]}"#;
+    let base_url = serve_once("application/json", body.to_string());
+
+    let mut engine = OpenAiEngine::new(OpenAiApiConfig::new(base_url));
+    engine.load_model("embedder").await.unwrap();
+    let config = InferenceConfig {
+        normalize: true,
+        ..Default::default()
+    };
+    let mut rx = engine
+        .embed_batch(&["a".to_string(), "b".to_string()], config)
+        .await
+        .unwrap();
+
+    let mut embeddings = Vec::new();
+    while let Some(event) = rx.next().await {
+        if let InferenceEvent::IndexedEmbedding { index, embedding } = event.unwrap() {
+            embeddings.push((index, embedding));
+        }
+    }
+    embeddings.sort_by_key(|(index, _)| *index);
+    assert_eq!(embeddings, vec![(0, vec![0.6, 0.8]), (1, vec![0.0, 1.0])]);
+}
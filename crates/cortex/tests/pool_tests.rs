@@ -0,0 +1,32 @@
+use rusty_genius_cortex::ModelPool;
+
+#[test]
+fn test_pool_evicts_least_recently_used() {
+    let mut pool = ModelPool::new().with_max_models(2);
+    assert!(pool.insert("chat.gguf", "chat", 100).is_empty());
+    assert!(pool.insert("embed.gguf", "embed", 10).is_empty());
+
+    // Using the chat model makes the embedding model the eviction candidate
+    assert_eq!(pool.get("chat.gguf"), Some(&mut "chat"));
+    let evicted = pool.insert("rerank.gguf", "rerank", 10);
+    assert_eq!(evicted, vec![("embed.gguf".to_string(), "embed")]);
+    assert_eq!(pool.paths(), vec!["rerank.gguf", "chat.gguf"]);
+    assert_eq!(pool.used_bytes(), 110);
+}
+
+#[test]
+fn test_pool_memory_budget() {
+    let mut pool = ModelPool::new().with_max_models(4).with_memory_budget(100);
+    pool.insert("a.gguf", 'a', 60);
+    pool.insert("b.gguf", 'b', 30);
+
+    let evicted = pool.insert("c.gguf", 'c', 50);
+    assert_eq!(evicted, vec![("a.gguf".to_string(), 'a')]);
+    assert_eq!(pool.used_bytes(), 80);
+
+    // A model over the whole budget still loads, alone
+    let evicted = pool.insert("huge.gguf", 'h', 500);
+    assert_eq!(evicted.len(), 2);
+    assert!(pool.contains("huge.gguf"));
+    assert_eq!(pool.len(), 1);
+}
@@ -33,9 +33,23 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 print!("\rDownload Progress: {:.1}% ({}/{})", pct, current, total);
                 let _ = std::io::Write::flush(&mut std::io::stdout());
             }
+            AssetEvent::Rate {
+                bytes_per_sec,
+                eta_secs,
+            } => {
+                print!(" [{} KB/s, ETA {:?}s]", bytes_per_sec / 1000, eta_secs);
+            }
+            AssetEvent::Queued { position } => println!("Queued at position {}", position),
+            AssetEvent::Resuming { offset, .. } => println!("Resuming from byte {}", offset),
+            AssetEvent::Verifying(path) => println!("\nVerifying: {}", path),
+            AssetEvent::Discarded(path) => println!("Discarded stale partial: {}", path),
+            AssetEvent::LicenseRequired { model, license } => {
+                println!("\n{} requires accepting its license: {}", model, license)
+            }
             AssetEvent::Complete(path) => {
                 println!("\nSuccessfully completed: {}", path);
             }
+            AssetEvent::Loading(_) | AssetEvent::Loaded { .. } => {}
             AssetEvent::Error(err) => {
                 eprintln!("\nAsset Error: {}", err);
             }
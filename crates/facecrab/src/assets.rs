@@ -1,24 +1,385 @@
+use crate::memory::quant_bits;
+use crate::queue::DownloadQueue;
 use crate::registry::ModelEntry;
 use crate::registry::ModelRegistry;
+use crate::sources::{
+    find_in_hub_cache, hub_cache_dir, select_gguf, AssetSource, HubCacheHit, HuggingFaceSource,
+    S3Source, SearchFilters, SearchResult, REPO_COMMIT_HEADER,
+};
 use anyhow::Result;
 use futures::channel::mpsc;
 use futures::sink::SinkExt;
 use futures::StreamExt;
-use rusty_genius_core::manifest::ModelSpec;
+use rusty_genius_core::gguf::split_parts;
+use rusty_genius_core::manifest::{ModelBundle, ModelSpec};
 use rusty_genius_core::protocol::AssetEvent;
+use rusty_genius_core::runtime;
 use rusty_genius_core::GeniusError;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant, SystemTime};
 
+/// Resolves, downloads and caches model files.
+///
+/// Cloning is cheap: clones share the registry, HTTP client and download queue, so entries
+/// recorded through one are visible to all.
+#[derive(Clone)]
 pub struct AssetAuthority {
-    registry: ModelRegistry,
+    state: Arc<AuthorityState>,
 }
 
+/// What every clone of an [AssetAuthority] and each of its download tasks share.
+struct AuthorityState {
+    registry: RwLock<ModelRegistry>,
+    sources: RwLock<Vec<Arc<dyn AssetSource>>>,
+    client: surf::Client,
+    http: HttpSettings,
+    offline: bool,
+    queue: Arc<DownloadQueue>,
+    hub_cache: Option<HubCache>,
+    partials: Vec<PartialDownload>,
+    progress: ProgressThrottle,
+}
+
+/// Where to look for files already downloaded by HuggingFace tooling.
+#[derive(Debug, Clone)]
+struct HubCache {
+    dir: PathBuf,
+    /// Symlink hits into facecrab's cache rather than serving them in place.
+    link: bool,
+}
+
+/// Default number of downloads an [AssetAuthority] runs at once.
+pub const DEFAULT_MAX_CONCURRENT_DOWNLOADS: usize = 2;
+
+/// How failed download requests are retried.
+///
+/// Only opening the stream is retried; the wait before retry `n` (starting at 0) is
+/// `backoff * 2^n`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Fail on the first error.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            backoff: Duration::ZERO,
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// How often [AssetEvent::Progress] is emitted while streaming.
+///
+/// An update is sent once `interval` has passed or `min_bytes` more bytes have arrived since
+/// the previous one, whichever comes first. The first and last updates are always sent.
+#[derive(Debug, Clone, Copy)]
+pub struct ProgressThrottle {
+    pub interval: Duration,
+    pub min_bytes: Option<u64>,
+}
+
+impl ProgressThrottle {
+    /// Report every read, however small.
+    pub fn none() -> Self {
+        Self {
+            interval: Duration::ZERO,
+            min_bytes: None,
+        }
+    }
+}
+
+impl Default for ProgressThrottle {
+    /// At most ten updates per second.
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(100),
+            min_bytes: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct HttpSettings {
+    timeout: Option<Duration>,
+    retry: RetryPolicy,
+    user_agent: String,
+    headers: Vec<(String, String)>,
+    max_redirects: u8,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            retry: RetryPolicy::default(),
+            user_agent: format!("facecrab/{}", env!("CARGO_PKG_VERSION")),
+            headers: Vec::new(),
+            max_redirects: 5,
+        }
+    }
+}
+
+/// Configures an [AssetAuthority]; see [AssetAuthority::builder].
+///
+/// ```no_run
+/// use facecrab::AssetAuthority;
+/// use std::time::Duration;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let authority = AssetAuthority::builder()
+///     .cache_dir("/mnt/models")
+///     .timeout(Duration::from_secs(30))
+///     .user_agent("my-app/1.0")
+///     .header("X-Mirror-Token", "secret")
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct AssetAuthorityBuilder {
+    config_dir: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    sources: Vec<Arc<dyn AssetSource>>,
+    http: HttpSettings,
+    offline: Option<bool>,
+    max_concurrent_downloads: Option<usize>,
+    hub_cache_dir: Option<PathBuf>,
+    no_hub_cache: bool,
+    hub_cache_in_place: bool,
+    progress: ProgressThrottle,
+}
+
+impl AssetAuthorityBuilder {
+    /// Directory holding `manifest.toml`. Defaults to `GENIUS_HOME` or the platform config dir.
+    pub fn config_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config_dir = Some(dir.into());
+        self
+    }
+
+    /// Directory downloads and `registry.toml` are stored in. Defaults to `GENIUS_CACHE`
+    /// or `cache/` under the config directory.
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// HTTP client timeout. Defaults to the surf client's own timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.http.timeout = Some(timeout);
+        self
+    }
+
+    pub fn retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.http.retry = retry;
+        self
+    }
+
+    /// `User-Agent` sent with every request. Defaults to `facecrab/<version>`.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.http.user_agent = user_agent.into();
+        self
+    }
+
+    /// Extra header sent with every request, including those that follow a redirect. May be
+    /// called repeatedly; setting `User-Agent` here overrides [Self::user_agent].
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.http.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Maximum number of redirects followed per request. Defaults to 5.
+    pub fn max_redirects(mut self, max_redirects: u8) -> Self {
+        self.http.max_redirects = max_redirects;
+        self
+    }
+
+    /// How often [AssetEvent::Progress] is emitted. Defaults to [ProgressThrottle::default].
+    pub fn progress_throttle(mut self, throttle: ProgressThrottle) -> Self {
+        self.progress = throttle;
+        self
+    }
+
+    /// Only serve models that are already cached and never touch the network.
+    /// Defaults to the `GENIUS_OFFLINE` environment variable (`1` or `true`).
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = Some(offline);
+        self
+    }
+
+    /// How many downloads may run at once; further downloads wait and report
+    /// [AssetEvent::Queued]. Defaults to [DEFAULT_MAX_CONCURRENT_DOWNLOADS].
+    pub fn max_concurrent_downloads(mut self, max: usize) -> Self {
+        self.max_concurrent_downloads = Some(max);
+        self
+    }
+
+    /// HuggingFace hub cache to reuse files from. Defaults to `HF_HUB_CACHE`, `HF_HOME/hub`
+    /// or `~/.cache/huggingface/hub`.
+    pub fn hub_cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.hub_cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Whether to look in the HuggingFace hub cache before downloading (default `true`).
+    pub fn use_hub_cache(mut self, enabled: bool) -> Self {
+        self.no_hub_cache = !enabled;
+        self
+    }
+
+    /// Whether files found in the hub cache are symlinked into the facecrab cache (default
+    /// `true`) or returned at their hub cache path.
+    pub fn link_hub_cache(mut self, link: bool) -> Self {
+        self.hub_cache_in_place = !link;
+        self
+    }
+
+    /// Register an additional [AssetSource], as with [AssetAuthority::add_source].
+    pub fn source(mut self, source: impl AssetSource + 'static) -> Self {
+        self.sources.insert(0, Arc::new(source));
+        self
+    }
+
+    pub fn build(self) -> Result<AssetAuthority> {
+        let config_dir = match self.config_dir {
+            Some(dir) => dir,
+            None => ModelRegistry::default_config_dir()?,
+        };
+        let cache_dir = self
+            .cache_dir
+            .unwrap_or_else(|| ModelRegistry::default_cache_dir(&config_dir));
+
+        let hub_cache = if self.no_hub_cache {
+            None
+        } else {
+            self.hub_cache_dir
+                .or_else(hub_cache_dir)
+                .map(|dir| HubCache {
+                    dir,
+                    link: !self.hub_cache_in_place,
+                })
+        };
+
+        let mut sources = self.sources;
+        sources.push(Arc::new(S3Source::from_env()));
+        sources.push(Arc::new(HuggingFaceSource));
+
+        let registry = ModelRegistry::with_dirs(config_dir, cache_dir)?;
+        let state = AuthorityState {
+            partials: scan_partials(&registry),
+            registry: RwLock::new(registry),
+            sources: RwLock::new(sources),
+            client: build_client(&self.http)?,
+            http: self.http,
+            offline: self.offline.unwrap_or_else(offline_from_env),
+            queue: DownloadQueue::new(
+                self.max_concurrent_downloads
+                    .unwrap_or(DEFAULT_MAX_CONCURRENT_DOWNLOADS),
+            ),
+            hub_cache,
+            progress: self.progress,
+        };
+        Ok(AssetAuthority {
+            state: Arc::new(state),
+        })
+    }
+}
+
+/// How often a [AssetEvent::Rate] update is emitted while streaming.
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+
 struct ProgressReader<R> {
     inner: R,
     current: u64,
+    /// Where this session started, so resumed bytes don't inflate the measured rate.
+    start_offset: u64,
     total: u64,
     sender: mpsc::Sender<AssetEvent>,
+    started: Instant,
+    last_rate: Instant,
+    throttle: ProgressThrottle,
+    /// When the last [AssetEvent::Progress] was sent and the position it reported.
+    last_progress: Option<(Instant, u64)>,
+}
+
+impl<R> ProgressReader<R> {
+    fn new(
+        inner: R,
+        offset: u64,
+        total: u64,
+        sender: mpsc::Sender<AssetEvent>,
+        throttle: ProgressThrottle,
+    ) -> Self {
+        let now = Instant::now();
+        Self {
+            inner,
+            current: offset,
+            start_offset: offset,
+            total,
+            sender,
+            started: now,
+            last_rate: now,
+            throttle,
+            last_progress: None,
+        }
+    }
+
+    /// Send a progress update if the throttle allows it; `finished` forces out the last one.
+    fn report_progress(&mut self, finished: bool) {
+        let due = match self.last_progress {
+            None => true,
+            Some((_, reported)) if reported == self.current => false,
+            Some((at, reported)) => {
+                finished
+                    || self.current == self.total
+                    || at.elapsed() >= self.throttle.interval
+                    || self
+                        .throttle
+                        .min_bytes
+                        .is_some_and(|min| self.current - reported >= min)
+            }
+        };
+        if !due {
+            return;
+        }
+        self.last_progress = Some((Instant::now(), self.current));
+        let _ = self
+            .sender
+            .try_send(AssetEvent::Progress(self.current, self.total));
+    }
+
+    fn report_rate(&mut self) {
+        let now = Instant::now();
+        if now.duration_since(self.last_rate) < RATE_INTERVAL {
+            return;
+        }
+        self.last_rate = now;
+
+        let elapsed = now.duration_since(self.started).as_secs_f64();
+        let bytes_per_sec = ((self.current - self.start_offset) as f64 / elapsed) as u64;
+        let eta_secs = if self.total > 0 && bytes_per_sec > 0 {
+            Some(self.total.saturating_sub(self.current) / bytes_per_sec)
+        } else {
+            None
+        };
+        let _ = self.sender.try_send(AssetEvent::Rate {
+            bytes_per_sec,
+            eta_secs,
+        });
+    }
 }
 
 impl<R: futures::io::AsyncRead + Unpin> futures::io::AsyncRead for ProgressReader<R> {
@@ -31,9 +392,10 @@ impl<R: futures::io::AsyncRead + Unpin> futures::io::AsyncRead for ProgressReade
             std::task::Poll::Ready(Ok(n)) => {
                 if n > 0 {
                     self.current += n as u64;
-                    let current = self.current;
-                    let total = self.total;
-                    let _ = self.sender.try_send(AssetEvent::Progress(current, total));
+                    self.report_progress(false);
+                    self.report_rate();
+                } else {
+                    self.report_progress(true);
                 }
                 std::task::Poll::Ready(Ok(n))
             }
@@ -42,51 +404,713 @@ impl<R: futures::io::AsyncRead + Unpin> futures::io::AsyncRead for ProgressReade
     }
 }
 
+/// How [AssetAuthority::import] places an existing file into the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImportMode {
+    /// Hardlink the file, or copy it if the cache is on another filesystem.
+    #[default]
+    Link,
+    /// Copy the file, leaving the original untouched.
+    Copy,
+    /// Move the file into the cache.
+    Move,
+}
+
+/// Hex-encoded SHA-256 of a file's contents.
+pub(crate) fn sha256_file(path: &Path) -> Result<String> {
+    use sha2::{Digest, Sha256};
+    use std::io::Read;
+
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Make `link` refer to `target`: a hardlink if possible, else a symlink, else a copy.
+fn link_or_copy(target: &Path, link: &Path) -> Result<()> {
+    // Link under a temporary name and rename over `link`, so it is replaced atomically.
+    let tmp = temp_path_for(link);
+    let _ = fs::remove_file(&tmp);
+    let mut linked = fs::hard_link(target, &tmp).is_ok();
+    #[cfg(unix)]
+    if !linked {
+        linked = std::os::unix::fs::symlink(target, &tmp).is_ok();
+    }
+    if !linked {
+        return copy_atomic(target, link);
+    }
+    persist(&tmp, link)
+}
+
+/// Temporary name next to `path` that it is written under before being renamed into place.
+fn temp_path_for(path: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".tmp");
+    path.with_file_name(name)
+}
+
+/// Flush the contents of the file at `path` to disk.
+fn sync_file(path: &Path) -> Result<()> {
+    // Windows refuses to flush handles opened read-only.
+    fs::OpenOptions::new().write(true).open(path)?.sync_all()?;
+    Ok(())
+}
+
+/// Flush the entries of `dir` to disk, so renames into it survive power loss. Windows can't
+/// open directories for this, and its renames are durable once they return.
+fn sync_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    fs::File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+/// Rename `from` to `to` and flush the directory entry. The contents of `from` must already
+/// be on disk (see [sync_file]), so `to` is never seen torn, even after a crash.
+fn persist(from: &Path, to: &Path) -> Result<()> {
+    fs::rename(from, to)?;
+    match to.parent() {
+        Some(dir) => sync_dir(dir),
+        None => Ok(()),
+    }
+}
+
+/// Copy `from` to `to` through a temporary file in the same directory, so `to` either keeps
+/// its previous contents or has all of the new ones.
+fn copy_atomic(from: &Path, to: &Path) -> Result<()> {
+    let tmp = temp_path_for(to);
+    let result = fs::copy(from, &tmp)
+        .map_err(anyhow::Error::from)
+        .and_then(|_| sync_file(&tmp))
+        .and_then(|_| persist(&tmp, to));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Whether `a` and `b` are the same file, following symlinks and recognising hardlinks.
+fn same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Where the in-progress download of `final_path` is written: `{filename}.partial`.
+fn partial_path_for(final_path: &Path) -> PathBuf {
+    let mut name = final_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    final_path.with_file_name(name)
+}
+
+/// A `.partial` file left in the cache by an interrupted download.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialDownload {
+    pub path: PathBuf,
+    /// Bytes already downloaded, i.e. where a resume would continue from.
+    pub offset: u64,
+    /// Registry entry the file belongs to, if one downloads to the same filename.
+    pub model: Option<String>,
+}
+
+/// A model file in the cache, as listed by [AssetAuthority::list_cached].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedModel {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Registry entry the file belongs to, if one downloads to the same filename.
+    pub model: Option<String>,
+    /// When `ensure_model` last resolved the file; `None` if it hasn't since usage tracking began.
+    pub last_used: Option<SystemTime>,
+}
+
+/// What [AssetAuthority::verify] does with corrupt files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CorruptAction {
+    /// Only report them.
+    #[default]
+    Report,
+    /// Delete them (and the blob they are linked to).
+    Delete,
+    /// Delete them and download the registry entry they belong to again.
+    Redownload,
+}
+
+/// Result of [AssetAuthority::verify].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VerifyReport {
+    /// Files whose content matches their recorded SHA-256.
+    pub intact: Vec<PathBuf>,
+    pub corrupt: Vec<CorruptFile>,
+    /// Files with no recorded hash whose GGUF header still parses.
+    pub unknown: Vec<PathBuf>,
+}
+
+/// A cached file that failed [AssetAuthority::verify].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorruptFile {
+    pub path: PathBuf,
+    /// Registry entry the file belongs to, if one downloads to the same filename.
+    pub model: Option<String>,
+    /// Why the file is considered corrupt.
+    pub reason: String,
+    pub outcome: RepairOutcome,
+}
+
+/// What happened to a [CorruptFile].
+#[derive(Debug, Clone, PartialEq)]
+pub enum RepairOutcome {
+    Kept,
+    Deleted,
+    Redownloaded,
+    /// Deleting or downloading again failed.
+    Failed(String),
+}
+
+/// What [AssetAuthority::recover] does with leftover partial downloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryAction {
+    /// Continue downloads that belong to a registry entry; others are left in place.
+    Resume,
+    /// Delete all partial files.
+    Clean,
+}
+
+fn scan_partials(registry: &ModelRegistry) -> Vec<PartialDownload> {
+    let Ok(dir) = fs::read_dir(registry.get_cache_dir()) else {
+        return vec![];
+    };
+    let entries = registry.list();
+    let mut found: Vec<PartialDownload> = dir
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+        .filter_map(|e| {
+            let file_name = e.file_name().into_string().ok()?;
+            let filename = file_name.strip_suffix(".partial")?;
+            Some(PartialDownload {
+                path: e.path(),
+                offset: e.metadata().map(|m| m.len()).unwrap_or(0),
+                model: entries
+                    .iter()
+                    .find(|m| m.filename == filename)
+                    .map(|m| m.name.clone()),
+            })
+        })
+        .collect();
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    found
+}
+
+/// The combined transfer rate of a profile's downloads, with an ETA over all remaining bytes.
+fn aggregate_rate(progress: &[(u64, u64)], rates: &[u64]) -> AssetEvent {
+    let bytes_per_sec: u64 = rates.iter().sum();
+    let remaining: u64 = progress
+        .iter()
+        .map(|(current, total)| total.saturating_sub(*current))
+        .sum();
+    AssetEvent::Rate {
+        bytes_per_sec,
+        eta_secs: remaining.checked_div(bytes_per_sec),
+    }
+}
+
+/// The spec of the auxiliary file `aux` of `parent`: a path in the same repo and revision,
+/// or `org/repo[@revision]:path`.
+fn aux_file_spec(parent: &ModelSpec, aux: &str) -> ModelSpec {
+    let (repo, revision, filename) = match aux.split_once(':') {
+        Some((repo, path)) => {
+            let (repo, revision) = split_revision(repo);
+            (repo.to_string(), revision, path.to_string())
+        }
+        None => (
+            parent.repo.clone(),
+            parent.revision.clone(),
+            aux.to_string(),
+        ),
+    };
+    ModelSpec {
+        repo,
+        filename,
+        quantization: parent.quantization.clone(),
+        revision,
+        aux_files: vec![],
+    }
+}
+
+/// Split an inline `org/repo@revision` into the repo and the pinned revision.
+fn split_revision(repo: &str) -> (&str, Option<String>) {
+    match repo.split_once('@') {
+        Some((repo, rev)) if !rev.is_empty() => (repo, Some(rev.to_string())),
+        _ => (repo, None),
+    }
+}
+
+/// The last segment of a path in a repo, e.g. `model.gguf` for `Q4_K_M/model.gguf`.
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// The HTTP client downloads and API requests share, with the configured headers, timeout
+/// and redirect handling.
+fn build_client(http: &HttpSettings) -> Result<surf::Client> {
+    let mut config = surf::Config::new()
+        .add_header("User-Agent", http.user_agent.as_str())
+        .map_err(|e| anyhow::anyhow!("Invalid user agent: {}", e))?;
+    for (name, value) in &http.headers {
+        config = config
+            .add_header(name.as_str(), value.as_str())
+            .map_err(|e| anyhow::anyhow!("Invalid header '{}': {}", name, e))?;
+    }
+    if let Some(timeout) = http.timeout {
+        config = config.set_timeout(Some(timeout));
+    }
+    let client: surf::Client = config.try_into()?;
+    Ok(client.with(RedirectMiddleware::new(http.max_redirects)))
+}
+
+fn offline_from_env() -> bool {
+    std::env::var("GENIUS_OFFLINE")
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
 impl AssetAuthority {
+    /// Create an authority using the default directories and HTTP settings.
     pub fn new() -> Result<Self> {
-        Ok(Self {
-            registry: ModelRegistry::new()?,
-        })
+        Self::builder().build()
+    }
+
+    pub fn builder() -> AssetAuthorityBuilder {
+        AssetAuthorityBuilder::default()
+    }
+
+    /// The shared registry. Don't hold the guard across an `.await`.
+    fn registry(&self) -> RwLockReadGuard<'_, ModelRegistry> {
+        self.state.registry.read().unwrap()
+    }
+
+    /// Add or replace an entry in `registry.toml`, updating the shared registry with it.
+    fn record_model(&self, entry: ModelEntry) -> Result<()> {
+        self.state.registry.write().unwrap().record_model(entry)
+    }
+
+    fn client(&self) -> &surf::Client {
+        &self.state.client
+    }
+
+    /// Register an additional [AssetSource]. Sources added later take precedence
+    /// over earlier ones and over the built-in S3 and HuggingFace sources. The source is
+    /// shared with every clone of this authority.
+    pub fn add_source(&mut self, source: impl AssetSource + 'static) {
+        self.state
+            .sources
+            .write()
+            .unwrap()
+            .insert(0, Arc::new(source));
+    }
+
+    fn source_for(&self, spec: &ModelSpec) -> Result<Arc<dyn AssetSource>> {
+        self.state
+            .sources
+            .read()
+            .unwrap()
+            .iter()
+            .find(|s| s.handles(spec))
+            .cloned()
+            .ok_or_else(|| {
+                GeniusError::AssetError(format!("No asset source handles '{}'", spec.repo)).into()
+            })
+    }
+
+    /// Partial downloads that were in the cache when this authority was created, typically
+    /// left behind by a crash. Use [AssetAuthority::recover] to resume or delete them.
+    pub fn partial_downloads(&self) -> &[PartialDownload] {
+        &self.state.partials
+    }
+
+    /// Resume or delete leftover partial downloads, reporting what happens as [AssetEvent]s.
+    ///
+    /// With [RecoveryAction::Resume], each partial that belongs to a registry entry is
+    /// continued through the download queue (emitting `Resuming`, progress and `Complete`);
+    /// a partial with no matching entry gets an `Error` event and is left alone. With
+    /// [RecoveryAction::Clean] each file is deleted and reported as `Discarded`.
+    pub fn recover(&self, action: RecoveryAction) -> mpsc::Receiver<AssetEvent> {
+        let (tx, rx) = mpsc::channel(100);
+        let auth = self.clone();
+
+        runtime::spawn(async move {
+            let mut tx = tx;
+            let partials = scan_partials(&auth.registry());
+            for partial in partials {
+                let path = partial.path.display().to_string();
+                match (action, partial.model) {
+                    (RecoveryAction::Clean, _) => match fs::remove_file(&partial.path) {
+                        Ok(()) => {
+                            let _ = tx.send(AssetEvent::Discarded(path)).await;
+                        }
+                        Err(e) => {
+                            let _ = tx
+                                .send(AssetEvent::Error(format!(
+                                    "Failed to delete {}: {}",
+                                    path, e
+                                )))
+                                .await;
+                        }
+                    },
+                    (RecoveryAction::Resume, Some(name)) => {
+                        if let Err(e) = auth.ensure_model_internal(&name, tx.clone(), true).await {
+                            let _ = tx.send(AssetEvent::Error(e.to_string())).await;
+                        }
+                    }
+                    (RecoveryAction::Resume, None) => {
+                        let _ = tx
+                            .send(AssetEvent::Error(format!(
+                                "Cannot resume {}: no registry entry downloads to this file",
+                                path
+                            )))
+                            .await;
+                    }
+                }
+            }
+        });
+
+        rx
+    }
+
+    /// Re-hash every model file in the cache and compare it with the SHA-256 recorded for its
+    /// registry entry (or pinned by a signed manifest).
+    ///
+    /// Files without a recorded hash are checked by parsing their GGUF header, which catches
+    /// files that were never finished but not every truncation. Corrupt files are handled as
+    /// `action` says.
+    pub async fn verify(&self, action: CorruptAction) -> Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+
+        for cached in self.list_cached() {
+            let expected = {
+                let registry = self.registry();
+                let entry = cached.model.as_deref().and_then(|name| registry.get(name));
+                entry.and_then(|entry| {
+                    registry
+                        .pinned_sha256(&entry.name)
+                        .map(str::to_string)
+                        .or_else(|| entry.sha256.clone())
+                })
+            };
+
+            let reason = match &expected {
+                Some(expected) => {
+                    let actual = sha256_file(&cached.path)?;
+                    if actual.eq_ignore_ascii_case(expected) {
+                        report.intact.push(cached.path);
+                        continue;
+                    }
+                    format!("sha256 is {}, expected {}", actual, expected)
+                }
+                None => match crate::gguf::inspect(&cached.path) {
+                    Ok(_) => {
+                        report.unknown.push(cached.path);
+                        continue;
+                    }
+                    Err(e) => format!("unreadable GGUF header: {}", e),
+                },
+            };
+
+            let outcome = match action {
+                CorruptAction::Report => RepairOutcome::Kept,
+                CorruptAction::Delete | CorruptAction::Redownload => {
+                    match self.remove_cached(&cached.path, expected.as_deref()) {
+                        Err(e) => RepairOutcome::Failed(e.to_string()),
+                        Ok(()) => match (action, &cached.model) {
+                            (CorruptAction::Redownload, Some(name)) => {
+                                match self.ensure_model(name).await {
+                                    Ok(_) => RepairOutcome::Redownloaded,
+                                    Err(e) => RepairOutcome::Failed(e.to_string()),
+                                }
+                            }
+                            (CorruptAction::Redownload, None) => RepairOutcome::Failed(
+                                "deleted, but no registry entry downloads to this file".to_string(),
+                            ),
+                            _ => RepairOutcome::Deleted,
+                        },
+                    }
+                }
+            };
+
+            report.corrupt.push(CorruptFile {
+                path: cached.path,
+                model: cached.model,
+                reason,
+                outcome,
+            });
+        }
+
+        Ok(report)
+    }
+
+    /// Delete a cached file, together with the blob named `sha256` if the file is linked to
+    /// it; the blob's content is just as damaged.
+    fn remove_cached(&self, path: &Path, sha256: Option<&str>) -> Result<()> {
+        if let Some(sha256) = sha256 {
+            let blob = self.blob_dir().join(sha256);
+            if same_file(path, &blob) {
+                fs::remove_file(&blob)?;
+            }
+        }
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    /// The license `name` requires before download, if it has one that hasn't been accepted,
+    /// so applications can ask for consent up front.
+    pub fn pending_license(&self, name: &str) -> Option<String> {
+        self.unaccepted_license(name).map(|(_, license)| license)
+    }
+
+    /// Record the user's acceptance of the license of `name`. Models without a license need
+    /// no acceptance; accepting again is harmless.
+    pub fn accept_license(&self, name: &str) -> Result<()> {
+        let registry = self.registry();
+        let entry = registry.get(name).ok_or_else(|| {
+            GeniusError::ManifestError(format!("Model '{}' not found in registry", name))
+        })?;
+        match &entry.license {
+            Some(license) => {
+                crate::licenses::accept(&registry.get_config_dir(), &entry.name, license)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// The entry name and license text of `name` if its license is not yet accepted.
+    fn unaccepted_license(&self, name: &str) -> Option<(String, String)> {
+        let registry = self.registry();
+        let entry = registry.get(name)?;
+        let license = entry.license.as_ref()?;
+        if crate::licenses::is_accepted(&registry.get_config_dir(), &entry.name, license) {
+            None
+        } else {
+            Some((entry.name.clone(), license.clone()))
+        }
+    }
+
+    /// Whether downloads are disabled and only cached models are served.
+    pub fn is_offline(&self) -> bool {
+        self.state.offline
     }
 
     /// List all models in the registry.
     pub fn list_models(&self) -> Vec<ModelEntry> {
-        self.registry.list_models()
+        self.registry().list_models()
+    }
+
+    /// Registry models of the repo `name` resolves to (or names, for an inline spec) that are
+    /// quantized to fewer than `bits` bits a weight, the most bits first.
+    pub fn smaller_quants(&self, name: &str, bits: u32) -> Vec<ModelEntry> {
+        let registry = self.registry();
+        let repo = match registry.resolve(name) {
+            Some(spec) => spec.repo,
+            None => split_revision(name.split(':').next().unwrap_or(name))
+                .0
+                .to_string(),
+        };
+        let mut smaller: Vec<(u32, ModelEntry)> = registry
+            .list_models()
+            .into_iter()
+            .filter(|entry| entry.repo == repo)
+            .filter_map(|entry| Some((quant_bits(&entry.quantization)?, entry)))
+            .filter(|(entry_bits, _)| *entry_bits < bits)
+            .collect();
+        smaller.sort_by_key(|(entry_bits, _)| std::cmp::Reverse(*entry_bits));
+        smaller.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    /// Model files present in the cache directory, least recently used first (files never
+    /// used since tracking began come before all others).
+    pub fn list_cached(&self) -> Vec<CachedModel> {
+        let cache_dir = self.registry().get_cache_dir();
+        let Ok(dir) = fs::read_dir(&cache_dir) else {
+            return vec![];
+        };
+        let usage = crate::usage::load(&cache_dir);
+        let entries = self.registry().list();
+
+        let mut cached: Vec<CachedModel> = dir
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let file_name = e.file_name().into_string().ok()?;
+                let model = entries
+                    .iter()
+                    .find(|m| m.filename == file_name)
+                    .map(|m| m.name.clone());
+                if model.is_none() && !file_name.to_lowercase().ends_with(".gguf") {
+                    return None;
+                }
+                // Follows links into the blob store.
+                let metadata = fs::metadata(e.path()).ok().filter(|m| m.is_file())?;
+                Some(CachedModel {
+                    path: e.path(),
+                    size: metadata.len(),
+                    model,
+                    last_used: usage.get(&file_name).copied(),
+                })
+            })
+            .collect();
+        cached.sort_by(|a, b| a.last_used.cmp(&b.last_used).then(a.path.cmp(&b.path)));
+        cached
+    }
+
+    /// Note that the cached `filename` was just used; failures only cost the timestamp.
+    fn mark_used(&self, filename: &str) {
+        if let Err(e) = crate::usage::touch(&self.registry().get_cache_dir(), filename) {
+            eprintln!("Warning: could not record use of {}: {}", filename, e);
+        }
     }
 
     /// Download a model and return its local path.
     pub async fn ensure_model(&self, name: &str) -> Result<PathBuf> {
+        self.ensure_bundle(name).await.map(|bundle| bundle.gguf)
+    }
+
+    /// Download a model and its auxiliary files (see [ModelSpec::aux_files]).
+    ///
+    /// Auxiliary files that can't be fetched are left out of the bundle with a warning, since
+    /// the GGUF file usually carries what an engine needs.
+    pub async fn ensure_bundle(&self, name: &str) -> Result<ModelBundle> {
         let (tx, mut rx) = mpsc::channel(1);
         let name = name.to_string();
+        let auth = self.clone();
 
-        let handle = async_std::task::spawn(async move {
-            if let Ok(auth) = AssetAuthority::new() {
-                auth.ensure_model_internal(&name, tx, true).await
-            } else {
-                Err(anyhow::anyhow!("Failed to create authority"))
-            }
-        });
+        let handle =
+            runtime::spawn(async move { auth.ensure_model_internal(&name, tx, true).await });
 
         while rx.next().await.is_some() {}
         handle.await
     }
 
+    /// Download several models through the download queue, returning their paths in order.
+    ///
+    /// At most the configured number of downloads run at once; the rest wait their turn.
+    pub async fn ensure_models(&self, names: &[&str]) -> Vec<Result<PathBuf>> {
+        futures::future::join_all(names.iter().map(|name| self.ensure_model(name))).await
+    }
+
     /// Download a model and return a stream of [AssetEvent]s.
     pub fn ensure_model_stream(&self, name: &str) -> mpsc::Receiver<AssetEvent> {
         let (tx, rx) = mpsc::channel(100);
         let name = name.to_string();
+        let auth = self.clone();
 
-        async_std::task::spawn(async move {
+        runtime::spawn(async move {
             let mut err_tx = tx.clone();
-            let result: Result<()> = async {
-                let auth = AssetAuthority::new()?;
-                auth.ensure_model_internal(&name, tx, false).await?;
-                Ok(())
+            if let Err(e) = auth.ensure_model_internal(&name, tx, false).await {
+                let _ = err_tx.send(AssetEvent::Error(e.to_string())).await;
             }
-            .await;
+        });
 
-            if let Err(e) = result {
-                let _ = err_tx.send(AssetEvent::Error(e.to_string())).await;
+        rx
+    }
+
+    /// Search HuggingFace for GGUF repositories; see [HuggingFaceSource::search].
+    pub async fn search(&self, query: &str, filters: &SearchFilters) -> Result<Vec<SearchResult>> {
+        if self.state.offline {
+            return Err(GeniusError::AssetError(format!(
+                "Cannot search for '{}' in offline mode",
+                query
+            ))
+            .into());
+        }
+        HuggingFaceSource
+            .search(query, filters, self.client())
+            .await
+    }
+
+    /// The members of the profile `name`, if one is defined (see [ModelRegistry::profile]).
+    pub fn profile_models(&self, name: &str) -> Option<Vec<String>> {
+        self.registry().profile(name).map(|p| p.models.clone())
+    }
+
+    /// Download every member of the profile `name` through the download queue and return one
+    /// merged stream of [AssetEvent]s.
+    ///
+    /// Per-model events (`Started`, `Queued`, `Verifying`, `Complete`, `Error`, ...) are passed
+    /// through unchanged. `Progress` and `Rate` are summed over all members so they describe the
+    /// profile as a whole; `Resuming` is folded into `Progress`. The stream ends once every
+    /// member has finished.
+    pub fn ensure_profile(&self, name: &str) -> mpsc::Receiver<AssetEvent> {
+        let (mut tx, rx) = mpsc::channel(100);
+        let members = match self.profile_models(name) {
+            Some(members) => members,
+            None => {
+                let _ = tx.try_send(AssetEvent::Error(format!(
+                    "Profile '{}' not found in registry",
+                    name
+                )));
+                return rx;
+            }
+        };
+
+        let streams: Vec<_> = members
+            .iter()
+            .map(|member| self.ensure_model_stream(member))
+            .collect();
+
+        runtime::spawn(async move {
+            let mut progress = vec![(0u64, 0u64); streams.len()];
+            let mut rates = vec![0u64; streams.len()];
+            let mut merged = futures::stream::select_all(
+                streams
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, stream)| stream.map(move |event| (i, event))),
+            );
+
+            while let Some((i, event)) = merged.next().await {
+                let event = match event {
+                    AssetEvent::Progress(current, total)
+                    | AssetEvent::Resuming {
+                        offset: current,
+                        total,
+                    } => {
+                        progress[i] = (current, total);
+                        let (current, total) = progress
+                            .iter()
+                            .fold((0, 0), |(c, t), (mc, mt)| (c + mc, t + mt));
+                        AssetEvent::Progress(current, total)
+                    }
+                    AssetEvent::Rate { bytes_per_sec, .. } => {
+                        rates[i] = bytes_per_sec;
+                        aggregate_rate(&progress, &rates)
+                    }
+                    event @ (AssetEvent::Complete(_) | AssetEvent::Error(_)) => {
+                        rates[i] = 0;
+                        event
+                    }
+                    event => event,
+                };
+                if tx.send(event).await.is_err() {
+                    break;
+                }
             }
         });
 
@@ -98,33 +1122,74 @@ impl AssetAuthority {
         name: &str,
         mut tx: mpsc::Sender<AssetEvent>,
         silent: bool,
-    ) -> Result<PathBuf> {
+    ) -> Result<ModelBundle> {
+        let (spec, gguf) = self.ensure_gguf(name, tx.clone(), silent).await?;
+        let gguf = self
+            .ensure_split_parts(&spec, gguf, tx.clone(), silent)
+            .await?;
+        let aux = self.ensure_aux_files(&spec).await;
+        let _ = tx
+            .send(AssetEvent::Complete(gguf.display().to_string()))
+            .await;
+        Ok(ModelBundle { gguf, aux })
+    }
+
+    /// Resolve `name` and make sure its GGUF file is available, returning the spec it
+    /// resolved to and the local path.
+    async fn ensure_gguf(
+        &self,
+        name: &str,
+        mut tx: mpsc::Sender<AssetEvent>,
+        silent: bool,
+    ) -> Result<(ModelSpec, PathBuf)> {
         let _ = tx.send(AssetEvent::Started(name.to_string())).await;
 
-        let spec = if let Some(s) = self.registry.resolve(name) {
+        let resolved = self.registry().resolve(name);
+        let spec = if let Some(s) = resolved {
             s
+        } else if let Some((dir, filename)) = name
+            .strip_prefix("s3://")
+            .and_then(|rest| rest.rsplit_once('/'))
+        {
+            // Direct object URL: s3://bucket/path/model.gguf
+            ModelSpec {
+                repo: format!("s3://{}", dir),
+                filename: filename.to_string(),
+                quantization: "Q4_K_M".to_string(),
+                revision: None,
+                aux_files: vec![],
+            }
         } else if name.contains('/') {
-            // Heuristic: If it contains '/', treat as Repo/Repo-GGUF:filename:quant
-            // and try to parse it.
-            // For now, let's assume Repo/Repo/filename pattern if possible or default.
-            // But let's check if the user wanted a specific format.
-            // Actually, let's just allow downloading by registry name mainly.
-            // If it's a Repo, we might need more info.
-
+            // Inline spec: "org/repo[@revision]:QUANT" or "org/repo[@revision]:filename.gguf[:QUANT]"
             let parts: Vec<&str> = name.split(':').collect();
-            if parts.len() >= 2 {
-                ModelSpec {
-                    repo: parts[0].to_string(),
-                    filename: parts[1].to_string(),
-                    quantization: parts.get(2).unwrap_or(&"Q4_K_M").to_string(),
+            match parts.as_slice() {
+                [repo, filename, rest @ ..]
+                    if filename.to_lowercase().ends_with(".gguf") && rest.len() <= 1 =>
+                {
+                    let (repo, revision) = split_revision(repo);
+                    ModelSpec {
+                        repo: repo.to_string(),
+                        filename: filename.to_string(),
+                        quantization: rest.first().unwrap_or(&"Q4_K_M").to_string(),
+                        revision,
+                        aux_files: vec![],
+                    }
+                }
+                [repo, quant] if !quant.is_empty() => match self.resolve_quant(repo, quant).await {
+                    Ok(spec) => spec,
+                    Err(e) => {
+                        let _ = tx.try_send(AssetEvent::Error(e.to_string()));
+                        return Err(e);
+                    }
+                },
+                _ => {
+                    let err = format!(
+                        "Model '{}' not found and not a valid org/repo:QUANT or org/repo:filename spec",
+                        name
+                    );
+                    let _ = tx.try_send(AssetEvent::Error(err.clone()));
+                    return Err(GeniusError::ManifestError(err).into());
                 }
-            } else {
-                let err = format!(
-                    "Model '{}' not found and invalid Repo/Repo:filename format",
-                    name
-                );
-                let _ = tx.try_send(AssetEvent::Error(err.clone()));
-                return Err(GeniusError::ManifestError(err).into());
             }
         } else {
             let err = format!("Model '{}' not found in registry", name);
@@ -132,91 +1197,469 @@ impl AssetAuthority {
             return Err(GeniusError::ManifestError(err).into());
         };
 
-        let cache_dir = self.registry.get_cache_dir();
+        let cache_dir = self.registry().get_cache_dir();
         fs::create_dir_all(&cache_dir)?;
 
+        let pinned = self.registry().pinned_sha256(name).map(str::to_string);
         let path = cache_dir.join(&spec.filename);
         if path.exists() {
-            let _ = tx
-                .send(AssetEvent::Complete(path.display().to_string()))
-                .await;
-            return Ok(path);
+            if let Some(expected) = &pinned {
+                if !self.matches_checksum(&path, expected)? {
+                    let err = format!(
+                        "{} does not match the sha256 pinned by the signed manifest for '{}'; remove it to download again",
+                        path.display(),
+                        name
+                    );
+                    let _ = tx.try_send(AssetEvent::Error(err.clone()));
+                    return Err(GeniusError::AssetError(err).into());
+                }
+            }
+            self.record_resolved(name, &spec, None, None)?;
+            self.mark_used(&spec.filename);
+            return Ok((spec, path));
+        }
+
+        if let Some(hit) = self
+            .state
+            .hub_cache
+            .as_ref()
+            .and_then(|hub| find_in_hub_cache(&hub.dir, &spec))
+            .filter(|hit| match &pinned {
+                Some(expected) => self.matches_checksum(&hit.path, expected).unwrap_or(false),
+                None => true,
+            })
+        {
+            let served = self.adopt_hub_file(&hit, &path);
+            self.record_resolved(name, &spec, Some(hit.commit), None)?;
+            self.mark_used(&spec.filename);
+            return Ok((spec, served));
+        }
+
+        if self.state.offline {
+            let err = format!(
+                "Model '{}' is not cached at {} and offline mode is enabled",
+                name,
+                path.display()
+            );
+            let _ = tx.try_send(AssetEvent::Error(err.clone()));
+            return Err(GeniusError::AssetError(err).into());
+        }
+
+        if let Some((model, license)) = self.unaccepted_license(name) {
+            let err = format!(
+                "Model '{}' requires accepting its license before download: {}",
+                model, license
+            );
+            let _ = tx.try_send(AssetEvent::LicenseRequired { model, license });
+            let _ = tx.try_send(AssetEvent::Error(err.clone()));
+            return Err(GeniusError::AssetError(err).into());
         }
 
+        let _slot = self.state.queue.acquire(tx.clone()).await;
         if !silent {
-            println!("Downloading {} from {}...", spec.filename, spec.repo);
+            eprintln!("Downloading {} from {}...", spec.filename, spec.repo);
         }
-        self.download_file_with_events(&spec, &path, tx.clone())
+        let (resolved, sha256) = self
+            .download_file_with_events(&spec, &path, tx.clone())
             .await?;
+        if let Some(expected) = &pinned {
+            if !sha256.eq_ignore_ascii_case(expected) {
+                let _ = fs::remove_file(&path);
+                let err = format!(
+                    "Downloaded {} has sha256 {}, but the signed manifest pins {}",
+                    spec.filename, sha256, expected
+                );
+                let _ = tx.try_send(AssetEvent::Error(err.clone()));
+                return Err(GeniusError::AssetError(err).into());
+            }
+        }
+
+        self.record_resolved(name, &spec, resolved, Some(sha256))?;
+        self.mark_used(&spec.filename);
+        Ok((spec, path))
+    }
+
+    /// Make sure every part of a split model (`*-00001-of-00003.gguf`) sits next to the part
+    /// `gguf` that `spec` resolved to, returning the path of the first part, which llama.cpp
+    /// loads the others from. Single-file models are returned as they are.
+    ///
+    /// Missing parts come from the hub cache or are downloaded like the model itself. Only the
+    /// part named by the spec is checked against a pinned checksum.
+    async fn ensure_split_parts(
+        &self,
+        spec: &ModelSpec,
+        gguf: PathBuf,
+        tx: mpsc::Sender<AssetEvent>,
+        silent: bool,
+    ) -> Result<PathBuf> {
+        let Some(parts) = split_parts(&spec.filename) else {
+            return Ok(gguf);
+        };
+        let cache_dir = self.registry().get_cache_dir();
+        for part in &parts {
+            let part_spec = ModelSpec {
+                filename: part.clone(),
+                aux_files: vec![],
+                ..spec.clone()
+            };
+            let path = gguf.with_file_name(file_name(part));
+            if !path.exists() {
+                if let Some(hit) = self
+                    .state
+                    .hub_cache
+                    .as_ref()
+                    .and_then(|hub| find_in_hub_cache(&hub.dir, &part_spec))
+                {
+                    self.adopt_hub_file(&hit, &cache_dir.join(part));
+                }
+            }
+            if !path.exists() {
+                if self.state.offline {
+                    let err = format!(
+                        "Part {} of '{}' is not cached at {} and offline mode is enabled",
+                        part,
+                        spec.filename,
+                        path.display()
+                    );
+                    let _ = tx.clone().try_send(AssetEvent::Error(err.clone()));
+                    return Err(GeniusError::AssetError(err).into());
+                }
+                let _slot = self.state.queue.acquire(tx.clone()).await;
+                if !silent {
+                    eprintln!("Downloading {} from {}...", part, spec.repo);
+                }
+                self.download_file_with_events(&part_spec, &path, tx.clone())
+                    .await?;
+            }
+            self.mark_used(part);
+        }
+        Ok(gguf.with_file_name(file_name(&parts[0])))
+    }
+
+    /// Fetch the auxiliary files of `spec` into `aux/<gguf filename>/` in the cache,
+    /// returning the ones that are available by their path in the repo.
+    async fn ensure_aux_files(
+        &self,
+        spec: &ModelSpec,
+    ) -> std::collections::BTreeMap<String, PathBuf> {
+        let dir = self
+            .registry()
+            .get_cache_dir()
+            .join("aux")
+            .join(&spec.filename);
+        let mut found = std::collections::BTreeMap::new();
+
+        for aux in &spec.aux_files {
+            let aux_spec = aux_file_spec(spec, aux);
+            let dest = dir.join(&aux_spec.filename);
+            if !dest.exists() {
+                if self.state.offline {
+                    eprintln!(
+                        "Warning: {} is not cached and offline mode is enabled",
+                        aux_spec.filename
+                    );
+                    continue;
+                }
+                // Progress of these small files would only confuse the model's own progress.
+                let (quiet, _) = mpsc::channel(1);
+                let fetched: Result<()> = async {
+                    if let Some(parent) = dest.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    self.download_file_with_events(&aux_spec, &dest, quiet)
+                        .await?;
+                    Ok(())
+                }
+                .await;
+                if let Err(e) = fetched {
+                    eprintln!(
+                        "Warning: could not fetch {} from {}: {}",
+                        aux_spec.filename, aux_spec.repo, e
+                    );
+                    continue;
+                }
+            }
+            found.insert(aux_spec.filename, dest);
+        }
+
+        found
+    }
+
+    /// Adopt a GGUF file that is already on disk into the cache and record it in the registry,
+    /// so it is never downloaded again.
+    ///
+    /// The file is validated with [inspect](crate::inspect) and hashed; with
+    /// [ImportMode::Link] it is hardlinked into the cache, falling back to a copy when the
+    /// cache is on another filesystem. The entry is named `name`, or after the file stem.
+    pub fn import(
+        &self,
+        path: impl AsRef<Path>,
+        name: Option<&str>,
+        mode: ImportMode,
+    ) -> Result<ModelEntry> {
+        let source = path.as_ref();
+        let info = crate::inspect(source)?;
+        let filename = source
+            .file_name()
+            .and_then(|f| f.to_str())
+            .ok_or_else(|| anyhow::anyhow!("Invalid model path: {:?}", source))?
+            .to_string();
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => source
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or(&filename)
+                .to_string(),
+        };
+        if let Some(existing) = self.registry().get(&name) {
+            return Err(GeniusError::ManifestError(format!(
+                "Model '{}' already exists in the registry ({})",
+                name, existing.repo
+            ))
+            .into());
+        }
 
-        // If it was a new model (resolved via heuristic), record it
-        if self.registry.resolve(name).is_none() {
-            let mut registry = ModelRegistry::new()?;
-            registry.record_model(crate::registry::ModelEntry {
+        let cache_dir = self.registry().get_cache_dir();
+        fs::create_dir_all(&cache_dir)?;
+        let target = cache_dir.join(&filename);
+
+        if target.exists() && sha256_file(&target)? != sha256_file(source)? {
+            return Err(GeniusError::AssetError(format!(
+                "A different file named {} is already cached",
+                filename
+            ))
+            .into());
+        }
+        let sha256 = match mode {
+            ImportMode::Link => self.store_blob(source, &target, false)?,
+            ImportMode::Move => self.store_blob(source, &target, true)?,
+            ImportMode::Copy => {
+                let staged = partial_path_for(&target);
+                fs::copy(source, &staged)?;
+                sync_file(&staged)?;
+                self.store_blob(&staged, &target, true)?
+            }
+        };
+
+        let architecture = info.architecture.unwrap_or_default();
+        let purpose = if architecture.contains("bert") || filename.to_lowercase().contains("embed")
+        {
+            crate::registry::ModelPurpose::Embedding
+        } else {
+            crate::registry::ModelPurpose::Inference
+        };
+        let origin = source
+            .parent()
+            .map(|dir| fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf()))
+            .unwrap_or_default();
+        let entry = ModelEntry {
+            name,
+            repo: format!("file://{}", origin.display()),
+            filename,
+            quantization: info.quantization.unwrap_or_else(|| "unknown".to_string()),
+            purpose,
+            aliases: vec![],
+            tags: vec!["imported".to_string()],
+            revision: None,
+            sha256: Some(sha256),
+            aux_files: vec![],
+            license: None,
+            chat_template: None,
+            defaults: None,
+        };
+
+        self.record_model(entry.clone())?;
+        Ok(entry)
+    }
+
+    /// Make a hub cache file available, symlinking it to `cache_path` when configured to.
+    /// Returns the path to serve, which is the hub path itself if no link could be made.
+    fn adopt_hub_file(&self, hit: &HubCacheHit, cache_path: &Path) -> PathBuf {
+        let link = self.state.hub_cache.as_ref().is_some_and(|hub| hub.link);
+        if !link {
+            return hit.path.clone();
+        }
+        // Replace a dangling link left behind by a cleared hub cache.
+        let _ = fs::remove_file(cache_path);
+        let target = fs::canonicalize(&hit.path).unwrap_or_else(|_| hit.path.clone());
+        #[cfg(unix)]
+        let linked = std::os::unix::fs::symlink(&target, cache_path);
+        #[cfg(windows)]
+        let linked = std::os::windows::fs::symlink_file(&target, cache_path);
+        #[cfg(not(any(unix, windows)))]
+        let linked: std::io::Result<()> = Err(std::io::ErrorKind::Unsupported.into());
+        match linked {
+            Ok(()) => cache_path.to_path_buf(),
+            Err(_) => hit.path.clone(),
+        }
+    }
+
+    /// Record a model in the dynamic registry after it was fetched or found in the cache.
+    ///
+    /// Models resolved from an inline spec get an entry so later lookups by `name` hit the
+    /// registry. Unpinned entries are pinned to the `resolved` revision the source reported,
+    /// so the exact files can be fetched again, and the content hash of the stored blob is
+    /// filled in when it was computed.
+    fn record_resolved(
+        &self,
+        name: &str,
+        spec: &ModelSpec,
+        resolved: Option<String>,
+        sha256: Option<String>,
+    ) -> Result<()> {
+        let entry = match self.registry().get(name) {
+            Some(existing) => {
+                let pin = existing.revision.is_none() && resolved.is_some();
+                let hash = sha256.is_some() && existing.sha256 != sha256;
+                if !pin && !hash {
+                    return Ok(());
+                }
+                ModelEntry {
+                    revision: existing.revision.clone().or(resolved),
+                    sha256: sha256.or_else(|| existing.sha256.clone()),
+                    ..existing.clone()
+                }
+            }
+            None => ModelEntry {
                 name: name.to_string(),
                 repo: spec.repo.clone(),
                 filename: spec.filename.clone(),
                 quantization: spec.quantization.clone(),
-                purpose: crate::registry::ModelPurpose::Inference,
-            })?;
+                purpose: if spec.repo.to_lowercase().contains("embed") {
+                    crate::registry::ModelPurpose::Embedding
+                } else if spec.repo.to_lowercase().contains("whisper") {
+                    crate::registry::ModelPurpose::Transcription
+                } else {
+                    crate::registry::ModelPurpose::Inference
+                },
+                aliases: vec![],
+                tags: vec![],
+                revision: spec.revision.clone().or(resolved),
+                sha256,
+                aux_files: spec.aux_files.clone(),
+                license: None,
+                chat_template: None,
+                defaults: None,
+            },
+        };
+        self.record_model(entry)
+    }
+
+    /// Resolve `repo:quant` to a concrete file using the HuggingFace file listing.
+    async fn resolve_quant(&self, repo: &str, quant: &str) -> Result<ModelSpec> {
+        if self.state.offline {
+            return Err(GeniusError::AssetError(format!(
+                "Cannot resolve '{}:{}' in offline mode; download it once while online",
+                repo, quant
+            ))
+            .into());
         }
+        let (repo, revision) = split_revision(repo);
+        let files = HuggingFaceSource
+            .list_files(repo, revision.as_deref(), self.client())
+            .await?;
+        let filename = select_gguf(&files, quant).ok_or_else(|| {
+            GeniusError::ManifestError(format!(
+                "No GGUF file for quantization '{}' in '{}'",
+                quant, repo
+            ))
+        })?;
+        Ok(ModelSpec {
+            repo: repo.to_string(),
+            filename,
+            quantization: quant.to_uppercase(),
+            revision,
+            aux_files: vec![],
+        })
+    }
 
-        let _ = tx
-            .send(AssetEvent::Complete(path.display().to_string()))
-            .await;
-        Ok(path)
+    async fn open_with_retry(
+        &self,
+        source: &dyn AssetSource,
+        spec: &ModelSpec,
+        client: &surf::Client,
+        offset: u64,
+    ) -> Result<crate::sources::AssetStream> {
+        let retry = &self.state.http.retry;
+        let mut attempt = 0;
+        loop {
+            match source.open(spec, client, offset).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) if attempt < retry.max_retries => {
+                    let wait = retry.backoff * 2u32.saturating_pow(attempt);
+                    eprintln!(
+                        "Warning: opening {} failed ({}), retrying in {:?}...",
+                        spec.filename, e, wait
+                    );
+                    runtime::sleep(wait).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
     }
 
     async fn download_file_with_events(
         &self,
         spec: &ModelSpec,
-        final_path: &PathBuf,
+        final_path: &Path,
         sender: mpsc::Sender<AssetEvent>,
-    ) -> Result<()> {
-        let url = format!(
-            "https://huggingface.co/{}/resolve/main/{}",
-            spec.repo, spec.filename
-        );
+    ) -> Result<(Option<String>, String)> {
+        let source = self.source_for(spec)?;
+        let url = source.url(spec);
         let _ = sender
             .clone()
             .try_send(AssetEvent::Started(format!("Downloading from: {}", url)));
-        if !final_path.exists() {
-            println!("DEBUG: Downloading from URL: {}", url);
-        }
-
-        let partial_path = final_path.with_extension("partial");
-        let client = surf::Client::new().with(RedirectMiddleware::new(5));
-        let response = client
-            .get(&url)
-            .await
-            .map_err(|e| anyhow::anyhow!("Surf request failed: {}", e))?;
-
-        let status = response.status();
-        if !status.is_success() {
-            return Err(anyhow::anyhow!("Download failed with status: {}", status));
+        if !final_path.exists() {
+            eprintln!("DEBUG: Downloading from URL: {}", url);
         }
 
-        let total_size = response
-            .header("Content-Length")
-            .and_then(|h| h.last().as_str().parse::<u64>().ok())
-            .unwrap_or(0);
+        let partial_path = partial_path_for(final_path);
+        let existing = fs::metadata(&partial_path).map(|m| m.len()).unwrap_or(0);
+        let stream = self
+            .open_with_retry(source.as_ref(), spec, self.client(), existing)
+            .await?;
 
-        let mut reader = ProgressReader {
-            inner: response,
-            current: 0,
-            total: total_size,
-            sender,
-        };
+        let total = stream.total.unwrap_or(0);
+        let offset = stream.offset;
+        let revision = stream.revision;
+        if offset > 0 {
+            let _ = sender
+                .clone()
+                .try_send(AssetEvent::Resuming { offset, total });
+        }
+        let mut reader = ProgressReader::new(
+            stream.reader,
+            offset,
+            total,
+            sender.clone(),
+            self.state.progress,
+        );
 
         {
-            let std_file = std::fs::File::create(&partial_path)
-                .map_err(|e| anyhow::anyhow!("Failed to create partial file: {}", e))?;
-            let mut file: async_std::fs::File = std_file.into();
+            use futures::io::AsyncWriteExt;
 
-            if let Err(e) = futures::io::copy(&mut reader, &mut file).await {
-                let _ = std::fs::remove_file(&partial_path);
+            let std_file = if offset > 0 {
+                std::fs::OpenOptions::new().append(true).open(&partial_path)
+            } else {
+                std::fs::File::create(&partial_path)
+            }
+            .map_err(|e| anyhow::anyhow!("Failed to create partial file: {}", e))?;
+            // Written on blocking's threads, so downloads don't tie the caller to a runtime
+            let mut file = blocking::Unblock::new(std_file);
+
+            // The partial file is kept so the next attempt can resume from it, so whatever
+            // arrived is flushed to it even when the stream breaks off.
+            let copied = futures::io::copy(&mut reader, &mut file).await;
+            let flushed = file.flush().await;
+            if let Err(e) = copied {
                 return Err(anyhow::anyhow!("Streaming failed: {}", e));
             }
+            flushed.map_err(|e| anyhow::anyhow!("Failed to write partial file: {}", e))?;
+            file.with_mut(|file| file.sync_all())
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to sync partial file: {}", e))?;
         }
 
         if !partial_path.exists() {
@@ -226,17 +1669,86 @@ impl AssetAuthority {
             ));
         }
 
-        if let Err(e) = std::fs::rename(&partial_path, final_path) {
-            eprintln!(
-                "Warning: rename {:?} -> {:?} failed ({}), falling back to copy...",
-                partial_path, final_path, e
-            );
-            std::fs::copy(&partial_path, final_path).map_err(|e| {
-                anyhow::anyhow!("Failed to finalize model file (copy fallback): {}", e)
-            })?;
-            let _ = std::fs::remove_file(&partial_path);
+        let _ = sender
+            .clone()
+            .try_send(AssetEvent::Verifying(final_path.display().to_string()));
+        let written = std::fs::metadata(&partial_path)?.len();
+        if total > 0 && written != total {
+            if written > total {
+                let _ = std::fs::remove_file(&partial_path);
+            }
+            return Err(anyhow::anyhow!(
+                "Incomplete download of {}: got {} of {} bytes",
+                spec.filename,
+                written,
+                total
+            ));
         }
-        Ok(())
+
+        let sha256 = self.store_blob(&partial_path, final_path, true)?;
+        Ok((revision, sha256))
+    }
+
+    /// Whether the file at `path` has the SHA-256 `expected`. Files that are already the blob
+    /// of that name are accepted without hashing them again.
+    fn matches_checksum(&self, path: &Path, expected: &str) -> Result<bool> {
+        if same_file(path, &self.blob_dir().join(expected)) {
+            return Ok(true);
+        }
+        Ok(sha256_file(path)?.eq_ignore_ascii_case(expected))
+    }
+
+    /// Directory holding content-addressed model files, named by their SHA-256.
+    pub fn blob_dir(&self) -> PathBuf {
+        self.registry().get_cache_dir().join("blobs")
+    }
+
+    /// Directory holding `manifest.toml` and the other settings.
+    pub fn config_dir(&self) -> PathBuf {
+        self.registry().get_config_dir()
+    }
+
+    /// Directory the orchestrator saves conversations' KV caches in.
+    pub fn session_dir(&self) -> PathBuf {
+        self.registry().get_cache_dir().join("sessions")
+    }
+
+    /// Store `file` as a blob and link `final_path` to it, returning the hex SHA-256.
+    ///
+    /// If a blob with the same content already exists, `file` is not stored again. With
+    /// `take` the file is moved into the blob store; otherwise it is hardlinked (or copied).
+    /// Blobs and links only ever appear complete, as each is renamed into place once on disk.
+    fn store_blob(&self, file: &Path, final_path: &Path, take: bool) -> Result<String> {
+        let sha256 = sha256_file(file)?;
+        let blob_dir = self.blob_dir();
+        fs::create_dir_all(&blob_dir)?;
+        let blob = blob_dir.join(&sha256);
+
+        if blob.exists() {
+            if take {
+                let _ = fs::remove_file(file);
+            }
+        } else if take {
+            if let Err(e) = persist(file, &blob) {
+                eprintln!(
+                    "Warning: rename {:?} -> {:?} failed ({}), falling back to copy...",
+                    file, blob, e
+                );
+                copy_atomic(file, &blob).map_err(|e| {
+                    anyhow::anyhow!("Failed to finalize model file (copy fallback): {}", e)
+                })?;
+                let _ = fs::remove_file(file);
+            }
+        } else if fs::hard_link(file, &blob).is_ok() {
+            sync_dir(&blob_dir)?;
+        } else {
+            copy_atomic(file, &blob)?;
+        }
+
+        if !same_file(&blob, final_path) {
+            link_or_copy(&blob, final_path)?;
+        }
+        Ok(sha256)
     }
 }
 
@@ -260,6 +1772,15 @@ impl surf::middleware::Middleware for RedirectMiddleware {
     ) -> surf::Result<surf::Response> {
         let mut attempts = 0;
         let mut current_req = req;
+        // Only requests made through `Client::get` and friends get the client's default
+        // headers; sources that build a `Request` themselves rely on them being added here.
+        for (name, values) in client.config().headers.iter() {
+            if current_req.header(name).is_none() {
+                current_req.insert_header(name, values);
+            }
+        }
+        // HuggingFace reports the resolved commit on the redirect, not on the CDN response.
+        let mut repo_commit = None;
 
         loop {
             // Check attempts
@@ -276,7 +1797,11 @@ impl surf::middleware::Middleware for RedirectMiddleware {
             // But `current_req.clone()` works in surf.
             let req_clone = current_req.clone();
 
-            let response = next.run(req_clone, client.clone()).await?;
+            let mut response = next.run(req_clone, client.clone()).await?;
+
+            if let Some(commit) = response.header(REPO_COMMIT_HEADER) {
+                repo_commit = Some(commit.last().as_str().to_string());
+            }
 
             if response.status().is_redirection() {
                 if let Some(location) = response.header("Location") {
@@ -303,16 +1828,31 @@ impl surf::middleware::Middleware for RedirectMiddleware {
                         }
                     };
 
-                    current_req = surf::Request::new(current_req.method(), new_url);
-                    // Copy headers? usually yes.
-                    // For now, new request is clean. simple GET.
-                    // HF auth headers not needed for public models, but if they were, we'd copy.
+                    // Carry the headers over, so resumed downloads keep their `Range` and
+                    // mirrors see the configured ones. Credentials aren't sent to another
+                    // origin, such as the CDN HuggingFace redirects to.
+                    let same_origin = new_url.origin() == current_req.url().origin();
+                    let mut redirected = surf::Request::new(current_req.method(), new_url);
+                    for (name, values) in current_req.iter() {
+                        if *name == surf::http::headers::HOST
+                            || (!same_origin && *name == surf::http::headers::AUTHORIZATION)
+                        {
+                            continue;
+                        }
+                        redirected.insert_header(name, values);
+                    }
+                    current_req = redirected;
 
                     attempts += 1;
                     continue;
                 }
             }
 
+            if let Some(commit) = repo_commit {
+                if response.header(REPO_COMMIT_HEADER).is_none() {
+                    response.insert_header(REPO_COMMIT_HEADER, commit.as_str());
+                }
+            }
             return Ok(response);
         }
     }
@@ -322,6 +1862,977 @@ mod tests {
     use super::*;
     use futures::StreamExt;
 
+    #[async_std::test]
+    async fn test_progress_reader_reports_rate() {
+        let (tx, mut rx) = mpsc::channel(16);
+        let data = vec![0u8; 1000];
+        let mut reader = ProgressReader::new(
+            futures::io::Cursor::new(data),
+            0,
+            4000,
+            tx,
+            ProgressThrottle::default(),
+        );
+        // Pretend the transfer started two seconds ago.
+        reader.started -= Duration::from_secs(2);
+        reader.last_rate = reader.started;
+
+        let mut sink = Vec::new();
+        futures::io::copy(&mut reader, &mut sink).await.unwrap();
+        drop(reader);
+
+        let mut rates = Vec::new();
+        while let Some(event) = rx.next().await {
+            if let AssetEvent::Rate {
+                bytes_per_sec,
+                eta_secs,
+            } = event
+            {
+                rates.push((bytes_per_sec, eta_secs));
+            }
+        }
+        // One update per interval, however many chunks were read.
+        assert_eq!(rates.len(), 1);
+        let (bytes_per_sec, eta_secs) = rates[0];
+        assert!((400..=500).contains(&bytes_per_sec), "{}", bytes_per_sec);
+        assert!(eta_secs.unwrap() >= 6);
+    }
+
+    /// Count the progress updates for reading 1000 bytes in 10-byte chunks.
+    async fn progress_updates(throttle: ProgressThrottle) -> Vec<u64> {
+        use futures::io::AsyncReadExt;
+
+        let (tx, mut rx) = mpsc::channel(256);
+        let data = vec![0u8; 1000];
+        let mut reader = ProgressReader::new(futures::io::Cursor::new(data), 0, 1000, tx, throttle);
+        let mut chunk = [0u8; 10];
+        while reader.read(&mut chunk).await.unwrap() > 0 {}
+        drop(reader);
+
+        let mut updates = Vec::new();
+        while let Some(event) = rx.next().await {
+            if let AssetEvent::Progress(current, _) = event {
+                updates.push(current);
+            }
+        }
+        updates
+    }
+
+    #[async_std::test]
+    async fn test_progress_is_throttled() {
+        assert_eq!(progress_updates(ProgressThrottle::none()).await.len(), 100);
+
+        // Only the first and the final update make it through a long interval.
+        let slow = ProgressThrottle {
+            interval: Duration::from_secs(60),
+            min_bytes: None,
+        };
+        assert_eq!(progress_updates(slow).await, vec![10, 1000]);
+
+        let by_bytes = ProgressThrottle {
+            interval: Duration::from_secs(60),
+            min_bytes: Some(250),
+        };
+        assert_eq!(
+            progress_updates(by_bytes).await,
+            vec![10, 260, 510, 760, 1000]
+        );
+    }
+
+    #[async_std::test]
+    async fn test_builder_uses_custom_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("models");
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path().join("config"))
+            .cache_dir(&cache)
+            .retry_policy(RetryPolicy::none())
+            .user_agent("facecrab-test")
+            .build()
+            .unwrap();
+
+        // Already cached, so no network access is needed.
+        std::fs::write(cache.join("local.gguf"), b"GGUF").unwrap();
+        let path = authority.ensure_model("org/repo:local.gguf").await.unwrap();
+        assert_eq!(path, cache.join("local.gguf"));
+
+        let registry = std::fs::read_to_string(cache.join("registry.toml")).unwrap();
+        assert!(registry.contains("org/repo:local.gguf"));
+    }
+
+    #[async_std::test]
+    async fn test_offline_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .offline(true)
+            .build()
+            .unwrap();
+        assert!(authority.is_offline());
+
+        let err = authority.ensure_model("tiny-model").await.unwrap_err();
+        assert!(err.to_string().contains("offline"), "{}", err);
+        let err = authority
+            .ensure_model("Qwen/Qwen2.5-0.5B-Instruct-GGUF:Q4_K_M")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("offline"), "{}", err);
+
+        std::fs::write(cache.join("cached.gguf"), b"GGUF").unwrap();
+        let path = authority
+            .ensure_model("org/repo:cached.gguf")
+            .await
+            .unwrap();
+        assert_eq!(path, cache.join("cached.gguf"));
+    }
+
+    #[async_std::test]
+    async fn test_revisions_are_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .offline(true)
+            .build()
+            .unwrap();
+
+        // An inline pin is kept in the recorded entry.
+        std::fs::write(cache.join("local.gguf"), b"GGUF").unwrap();
+        authority
+            .ensure_model("org/repo@v1.0:local.gguf")
+            .await
+            .unwrap();
+
+        // An unpinned entry is pinned to the revision the source served.
+        let spec = authority.registry().resolve("tiny-model").unwrap();
+        assert_eq!(spec.revision, None);
+        authority
+            .record_resolved("tiny-model", &spec, Some("abc123".to_string()), None)
+            .unwrap();
+
+        let registry = ModelRegistry::with_dirs(dir.path(), &cache).unwrap();
+        let inline = registry.get("org/repo@v1.0:local.gguf").unwrap();
+        assert_eq!(inline.repo, "org/repo");
+        assert_eq!(inline.revision.as_deref(), Some("v1.0"));
+        assert_eq!(
+            registry.resolve("tiny-model").unwrap().revision.as_deref(),
+            Some("abc123")
+        );
+    }
+
+    /// A GGUF header with no tensors and `general.file_type = Q4_K_M`.
+    fn tiny_gguf() -> Vec<u8> {
+        let key = "general.file_type";
+        let mut buf = b"GGUF".to_vec();
+        buf.extend_from_slice(&3u32.to_le_bytes());
+        buf.extend_from_slice(&0u64.to_le_bytes());
+        buf.extend_from_slice(&1u64.to_le_bytes());
+        buf.extend_from_slice(&(key.len() as u64).to_le_bytes());
+        buf.extend_from_slice(key.as_bytes());
+        buf.extend_from_slice(&4u32.to_le_bytes());
+        buf.extend_from_slice(&15u32.to_le_bytes());
+        buf
+    }
+
+    #[async_std::test]
+    async fn test_import_existing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .offline(true)
+            .build()
+            .unwrap();
+
+        let downloads = dir.path().join("downloads");
+        std::fs::create_dir_all(&downloads).unwrap();
+        let original = downloads.join("my-model.Q4_K_M.gguf");
+        std::fs::write(&original, tiny_gguf()).unwrap();
+
+        let entry = authority.import(&original, None, ImportMode::Link).unwrap();
+        assert_eq!(entry.name, "my-model.Q4_K_M");
+        assert_eq!(entry.quantization, "Q4_K_M");
+        assert_eq!(entry.sha256.as_deref().unwrap().len(), 64);
+        assert!(original.exists());
+        assert!(cache.join("my-model.Q4_K_M.gguf").exists());
+        assert!(authority
+            .blob_dir()
+            .join(entry.sha256.as_deref().unwrap())
+            .exists());
+
+        // Recorded, so ensure_model serves it without a download.
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .offline(true)
+            .build()
+            .unwrap();
+        let path = authority.ensure_model("my-model.Q4_K_M").await.unwrap();
+        assert_eq!(path, cache.join("my-model.Q4_K_M.gguf"));
+
+        // Re-importing under a taken name is refused.
+        assert!(authority.import(&original, None, ImportMode::Link).is_err());
+
+        // Moving removes the original.
+        let other = downloads.join("other.gguf");
+        std::fs::write(&other, tiny_gguf()).unwrap();
+        authority
+            .import(&other, Some("other"), ImportMode::Move)
+            .unwrap();
+        assert!(!other.exists());
+        assert!(cache.join("other.gguf").exists());
+
+        // Files that are not GGUF are rejected.
+        let bogus = downloads.join("bogus.gguf");
+        std::fs::write(&bogus, b"nope").unwrap();
+        assert!(authority.import(&bogus, None, ImportMode::Copy).is_err());
+    }
+
+    #[async_std::test]
+    async fn test_hub_cache_is_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let hub = dir.path().join("hub");
+        let snapshot = hub
+            .join("models--org--repo")
+            .join("snapshots")
+            .join("c0ffee");
+        std::fs::create_dir_all(&snapshot).unwrap();
+        std::fs::write(snapshot.join("shared.gguf"), tiny_gguf()).unwrap();
+
+        let cache = dir.path().join("cache");
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .hub_cache_dir(&hub)
+            .offline(true)
+            .build()
+            .unwrap();
+        let path = authority
+            .ensure_model("org/repo:shared.gguf")
+            .await
+            .unwrap();
+        assert_eq!(path, cache.join("shared.gguf"));
+        #[cfg(unix)]
+        assert!(std::fs::symlink_metadata(&path)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+
+        let registry = ModelRegistry::with_dirs(dir.path(), &cache).unwrap();
+        let entry = registry.get("org/repo:shared.gguf").unwrap();
+        assert_eq!(entry.revision.as_deref(), Some("c0ffee"));
+
+        let in_place = AssetAuthority::builder()
+            .config_dir(dir.path().join("other"))
+            .cache_dir(dir.path().join("other-cache"))
+            .hub_cache_dir(&hub)
+            .link_hub_cache(false)
+            .offline(true)
+            .build()
+            .unwrap();
+        let path = in_place.ensure_model("org/repo:shared.gguf").await.unwrap();
+        assert_eq!(path, snapshot.join("shared.gguf"));
+    }
+
+    /// Serves fixed bytes for `mem/...` repos and honours resume offsets.
+    struct MemorySource(Vec<u8>);
+
+    #[async_trait::async_trait]
+    impl AssetSource for MemorySource {
+        fn handles(&self, spec: &ModelSpec) -> bool {
+            spec.repo.starts_with("mem/")
+        }
+
+        fn url(&self, spec: &ModelSpec) -> String {
+            format!("{}/{}", spec.repo, spec.filename)
+        }
+
+        async fn open(
+            &self,
+            _spec: &ModelSpec,
+            _client: &surf::Client,
+            offset: u64,
+        ) -> Result<crate::sources::AssetStream> {
+            Ok(crate::sources::AssetStream {
+                reader: Box::new(futures::io::Cursor::new(self.0[offset as usize..].to_vec())),
+                total: Some(self.0.len() as u64),
+                offset,
+                revision: None,
+            })
+        }
+    }
+
+    #[async_std::test]
+    async fn test_partial_download_resumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        std::fs::create_dir_all(&cache).unwrap();
+        let data: Vec<u8> = (0..=255u8).cycle().take(10_000).collect();
+        // Pretend a previous run crashed after 4000 bytes.
+        std::fs::write(cache.join("mem.gguf.partial"), &data[..4000]).unwrap();
+
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .use_hub_cache(false)
+            .source(MemorySource(data.clone()))
+            .build()
+            .unwrap();
+
+        let mut events = authority.ensure_model_stream("mem/store:mem.gguf");
+        let mut resumed_at = None;
+        while let Some(event) = events.next().await {
+            match event {
+                AssetEvent::Resuming { offset, total } => {
+                    assert_eq!(total, 10_000);
+                    resumed_at = Some(offset);
+                }
+                AssetEvent::Error(e) => panic!("Download error: {}", e),
+                _ => {}
+            }
+        }
+        assert_eq!(resumed_at, Some(4000));
+        assert_eq!(std::fs::read(cache.join("mem.gguf")).unwrap(), data);
+        assert!(!cache.join("mem.gguf.partial").exists());
+    }
+
+    #[async_std::test]
+    async fn test_clones_share_registry() {
+        let dir = tempfile::tempdir().unwrap();
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(dir.path().join("cache"))
+            .use_hub_cache(false)
+            .source(MemorySource(b"GGUF shared".to_vec()))
+            .build()
+            .unwrap();
+        let clone = authority.clone();
+
+        clone.ensure_model("mem/shared:shared.gguf").await.unwrap();
+        // Recorded by the download task, visible without re-reading registry.toml.
+        let entry = authority
+            .list_models()
+            .into_iter()
+            .find(|m| m.name == "mem/shared:shared.gguf")
+            .unwrap();
+        assert!(entry.sha256.is_some());
+    }
+
+    #[async_std::test]
+    async fn test_signed_manifest_checksums_enforced() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let secret = [3u8; 32];
+        let data = b"GGUF signed weights".to_vec();
+        let good = {
+            use sha2::Digest;
+            hex::encode(sha2::Sha256::digest(&data))
+        };
+
+        std::fs::write(
+            dir.path().join(crate::trust::TRUST_FILE),
+            format!(
+                "public_keys = [\"{}\"]\nstrict = true\n",
+                crate::public_key_hex(&secret)
+            ),
+        )
+        .unwrap();
+        let manifest = dir.path().join("manifest.toml");
+        let entry = |name: &str, file: &str, sha: &str| {
+            format!(
+                "[[models]]\nname = \"{}\"\nrepo = \"mem/signed\"\nfilename = \"{}\"\nquantization = \"Q4_K_M\"\nsha256 = \"{}\"\n",
+                name, file, sha
+            )
+        };
+        std::fs::write(
+            &manifest,
+            entry("good", "good.gguf", &good) + &entry("bad", "bad.gguf", &"0".repeat(64)),
+        )
+        .unwrap();
+
+        // Unsigned manifests are refused in strict mode.
+        assert!(AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .build()
+            .is_err());
+
+        crate::sign_manifest(&manifest, &secret).unwrap();
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .use_hub_cache(false)
+            .source(MemorySource(data.clone()))
+            .build()
+            .unwrap();
+
+        let path = authority.ensure_model("good").await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        assert!(authority.ensure_model("bad").await.is_err());
+        assert!(!cache.join("bad.gguf").exists());
+
+        // A cached file that was tampered with is rejected too.
+        std::fs::remove_file(&path).unwrap();
+        std::fs::write(&path, b"GGUF tampered").unwrap();
+        assert!(authority.ensure_model("good").await.is_err());
+    }
+
+    #[async_std::test]
+    async fn test_ensure_profile_aggregates_progress() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("manifest.toml"),
+            "models = []\n\n[profiles.dev]\nmodels = [\"mem/a:one.gguf\", \"mem/b:two.gguf\"]\n",
+        )
+        .unwrap();
+        let data = vec![7u8; 5000];
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(dir.path().join("cache"))
+            .use_hub_cache(false)
+            .source(MemorySource(data))
+            .build()
+            .unwrap();
+
+        let mut events = authority.ensure_profile("dev");
+        let mut completed = 0;
+        let mut last_progress = None;
+        while let Some(event) = events.next().await {
+            match event {
+                AssetEvent::Progress(current, total) => last_progress = Some((current, total)),
+                AssetEvent::Complete(_) => completed += 1,
+                AssetEvent::Error(e) => panic!("Download error: {}", e),
+                _ => {}
+            }
+        }
+        assert_eq!(completed, 2);
+        assert_eq!(last_progress, Some((10_000, 10_000)));
+
+        let mut missing = authority.ensure_profile("nope");
+        assert!(matches!(missing.next().await, Some(AssetEvent::Error(_))));
+        assert!(missing.next().await.is_none());
+    }
+
+    #[async_std::test]
+    async fn test_list_cached_tracks_last_use() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        std::fs::create_dir_all(&cache).unwrap();
+        // Cached before usage tracking existed.
+        std::fs::write(cache.join("old.gguf"), b"GGUF old").unwrap();
+        std::fs::write(cache.join("notes.txt"), b"not a model").unwrap();
+
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .use_hub_cache(false)
+            .source(MemorySource(b"GGUF new".to_vec()))
+            .build()
+            .unwrap();
+        authority.ensure_model("mem/store:new.gguf").await.unwrap();
+
+        let cached = authority.list_cached();
+        assert_eq!(cached.len(), 2);
+        assert_eq!(cached[0].path, cache.join("old.gguf"));
+        assert_eq!(cached[0].last_used, None);
+        assert_eq!(cached[1].path, cache.join("new.gguf"));
+        assert_eq!(cached[1].model.as_deref(), Some("mem/store:new.gguf"));
+        assert_eq!(cached[1].size, 8);
+        assert!(cached[1].last_used.is_some());
+    }
+
+    #[async_std::test]
+    async fn test_verify_finds_and_repairs_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let data = b"GGUF good weights".to_vec();
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .use_hub_cache(false)
+            .source(MemorySource(data.clone()))
+            .build()
+            .unwrap();
+
+        let good = authority.ensure_model("mem/a:good.gguf").await.unwrap();
+        let damaged = authority.ensure_model("mem/b:damaged.gguf").await.unwrap();
+        // Overwrite in place, as an interrupted write would; the shared blob is damaged too.
+        std::fs::write(&damaged, b"GGUF good wei").unwrap();
+        std::fs::write(cache.join("imported.gguf"), tiny_gguf()).unwrap();
+        std::fs::write(cache.join("junk.gguf"), b"oops").unwrap();
+
+        let report = authority.verify(CorruptAction::Report).await.unwrap();
+        assert!(report.intact.is_empty());
+        assert_eq!(report.unknown, vec![cache.join("imported.gguf")]);
+        assert_eq!(report.corrupt.len(), 3);
+        assert!(report
+            .corrupt
+            .iter()
+            .all(|c| c.outcome == RepairOutcome::Kept));
+
+        let report = authority.verify(CorruptAction::Redownload).await.unwrap();
+        for corrupt in &report.corrupt {
+            match corrupt.model.as_deref() {
+                Some(_) => assert_eq!(corrupt.outcome, RepairOutcome::Redownloaded),
+                None => assert!(matches!(corrupt.outcome, RepairOutcome::Failed(_))),
+            }
+        }
+        assert_eq!(std::fs::read(&good).unwrap(), data);
+        assert_eq!(std::fs::read(&damaged).unwrap(), data);
+        assert!(!cache.join("junk.gguf").exists());
+
+        let report = authority.verify(CorruptAction::Report).await.unwrap();
+        assert_eq!(report.intact.len(), 2);
+        assert!(report.corrupt.is_empty());
+    }
+
+    /// Fails every request for `fail/...` repos.
+    struct FailingSource;
+
+    #[async_trait::async_trait]
+    impl AssetSource for FailingSource {
+        fn handles(&self, spec: &ModelSpec) -> bool {
+            spec.repo.starts_with("fail/")
+        }
+
+        fn url(&self, spec: &ModelSpec) -> String {
+            format!("{}/{}", spec.repo, spec.filename)
+        }
+
+        async fn open(
+            &self,
+            spec: &ModelSpec,
+            _client: &surf::Client,
+            _offset: u64,
+        ) -> Result<crate::sources::AssetStream> {
+            Err(anyhow::anyhow!("{} is unavailable", spec.filename))
+        }
+    }
+
+    #[async_std::test]
+    async fn test_bundle_includes_aux_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        std::fs::write(
+            dir.path().join("manifest.toml"),
+            "[[models]]\nname = \"bundled\"\nrepo = \"mem/gguf\"\nfilename = \"bundled.gguf\"\nquantization = \"Q4_K_M\"\naux_files = [\"tokenizer_config.json\", \"mem/original:nested/template.jinja\", \"fail/repo:missing.json\"]\n",
+        )
+        .unwrap();
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .use_hub_cache(false)
+            .retry_policy(RetryPolicy::none())
+            .offline(false)
+            .source(MemorySource(b"{}".to_vec()))
+            .source(FailingSource)
+            .build()
+            .unwrap();
+
+        let bundle = authority.ensure_bundle("bundled").await.unwrap();
+        assert_eq!(bundle.gguf, cache.join("bundled.gguf"));
+        let aux_dir = cache.join("aux").join("bundled.gguf");
+        assert_eq!(
+            bundle.aux_file("tokenizer_config.json"),
+            Some(aux_dir.join("tokenizer_config.json").as_path())
+        );
+        assert_eq!(
+            bundle.aux_file("template.jinja"),
+            Some(aux_dir.join("nested/template.jinja").as_path())
+        );
+        // Files that can't be fetched are left out rather than failing the model.
+        assert_eq!(bundle.aux.len(), 2);
+    }
+
+    #[async_std::test]
+    async fn test_split_model_fetches_every_part() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .use_hub_cache(false)
+            .source(MemorySource(b"part".to_vec()))
+            .build()
+            .unwrap();
+
+        // Naming any part resolves to the first, with the others beside it
+        let path = authority
+            .ensure_model("mem/split:big-q4_k_m-00002-of-00003.gguf")
+            .await
+            .unwrap();
+        assert_eq!(path, cache.join("big-q4_k_m-00001-of-00003.gguf"));
+        for part in 1..=3 {
+            assert!(cache
+                .join(format!("big-q4_k_m-0000{}-of-00003.gguf", part))
+                .exists());
+        }
+
+        std::fs::remove_file(cache.join("big-q4_k_m-00003-of-00003.gguf")).unwrap();
+        let offline = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .use_hub_cache(false)
+            .offline(true)
+            .build()
+            .unwrap();
+        let err = offline
+            .ensure_model("mem/split:big-q4_k_m-00001-of-00003.gguf")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("00003-of-00003"));
+    }
+
+    /// Serves its data but breaks off halfway through the first download.
+    struct InterruptedSource {
+        data: Vec<u8>,
+        interrupted: std::sync::atomic::AtomicBool,
+    }
+
+    struct BrokenPipe;
+
+    impl futures::io::AsyncRead for BrokenPipe {
+        fn poll_read(
+            self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            _buf: &mut [u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            std::task::Poll::Ready(Err(std::io::ErrorKind::ConnectionReset.into()))
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl AssetSource for InterruptedSource {
+        fn handles(&self, spec: &ModelSpec) -> bool {
+            spec.repo.starts_with("mem/")
+        }
+
+        fn url(&self, spec: &ModelSpec) -> String {
+            format!("{}/{}", spec.repo, spec.filename)
+        }
+
+        async fn open(
+            &self,
+            _spec: &ModelSpec,
+            _client: &surf::Client,
+            offset: u64,
+        ) -> Result<crate::sources::AssetStream> {
+            use futures::io::AsyncReadExt;
+            use std::sync::atomic::Ordering;
+
+            let rest = self.data[offset as usize..].to_vec();
+            let reader: Box<dyn futures::io::AsyncRead + Send + Unpin> =
+                if self.interrupted.swap(true, Ordering::SeqCst) {
+                    Box::new(futures::io::Cursor::new(rest))
+                } else {
+                    let half = rest[..rest.len() / 2].to_vec();
+                    Box::new(futures::io::Cursor::new(half).chain(BrokenPipe))
+                };
+            Ok(crate::sources::AssetStream {
+                reader,
+                total: Some(self.data.len() as u64),
+                offset,
+                revision: None,
+            })
+        }
+    }
+
+    #[async_std::test]
+    async fn test_interrupted_download_never_exposes_torn_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let data: Vec<u8> = (0..=255u8).cycle().take(10_000).collect();
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .use_hub_cache(false)
+            .source(InterruptedSource {
+                data: data.clone(),
+                interrupted: Default::default(),
+            })
+            .build()
+            .unwrap();
+        let name = "mem/interrupted:interrupted.gguf";
+
+        assert!(authority.ensure_model(name).await.is_err());
+        // Everything received before the break was flushed to the partial file, and nothing
+        // reached the final name or the blob store.
+        assert_eq!(
+            std::fs::read(cache.join("interrupted.gguf.partial")).unwrap(),
+            &data[..5000]
+        );
+        assert!(!cache.join("interrupted.gguf").exists());
+        assert!(!authority.blob_dir().exists());
+
+        let path = authority.ensure_model(name).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        let leftovers: Vec<_> = std::fs::read_dir(&cache)
+            .unwrap()
+            .chain(std::fs::read_dir(authority.blob_dir()).unwrap())
+            .filter_map(|e| e.ok())
+            .filter(|e| {
+                let name = e.file_name().to_string_lossy().into_owned();
+                name.ends_with(".tmp") || name.ends_with(".partial")
+            })
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[test]
+    fn test_failed_copy_leaves_destination_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("model.gguf");
+        std::fs::write(&dest, b"old").unwrap();
+
+        // The source vanishing mid-copy must not leave a torn destination behind.
+        assert!(copy_atomic(&dir.path().join("missing.gguf"), &dest).is_err());
+        assert_eq!(std::fs::read(&dest).unwrap(), b"old");
+        assert!(!temp_path_for(&dest).exists());
+
+        let source = dir.path().join("new.gguf");
+        std::fs::write(&source, b"new").unwrap();
+        copy_atomic(&source, &dest).unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"new");
+        assert!(!temp_path_for(&dest).exists());
+    }
+
+    /// Answer one HTTP request on `listener` with `respond(request head)`.
+    async fn serve_once(
+        listener: &async_std::net::TcpListener,
+        respond: impl FnOnce(&str) -> String,
+    ) -> String {
+        use futures::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut head = Vec::new();
+        let mut byte = [0u8; 1];
+        while !head.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).await.unwrap();
+            head.push(byte[0]);
+        }
+        // Normalised to `name:value`, whatever spacing the client uses.
+        let head = String::from_utf8(head)
+            .unwrap()
+            .to_lowercase()
+            .replace(": ", ":");
+        stream.write_all(respond(&head).as_bytes()).await.unwrap();
+        head
+    }
+
+    #[async_std::test]
+    async fn test_headers_follow_redirects() {
+        let origin = async_std::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let mirror = async_std::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let start_url = format!("http://{}/start", origin.local_addr().unwrap());
+        let mirror_url = format!("http://{}/file.gguf", mirror.local_addr().unwrap());
+        let server = async_std::task::spawn(async move {
+            let first = serve_once(&origin, |_| {
+                format!(
+                    "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    mirror_url
+                )
+            })
+            .await;
+            let second = serve_once(&mirror, |_| {
+                "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok".to_string()
+            })
+            .await;
+            (first, second)
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .user_agent("my-app/1.0")
+            .header("X-Mirror-Token", "secret")
+            .build()
+            .unwrap();
+        let client = authority.client();
+        let mut request = surf::Request::new(
+            surf::http::Method::Get,
+            surf::Url::parse(&start_url).unwrap(),
+        );
+        request.insert_header("Authorization", "Bearer hf_token");
+        request.insert_header("Range", "bytes=5-");
+        let mut response = client.send(request).await.unwrap();
+        assert_eq!(response.body_string().await.unwrap(), "ok");
+
+        let (first, second) = server.await;
+        for head in [&first, &second] {
+            assert!(head.contains("user-agent:my-app/1.0"), "{}", head);
+            assert!(head.contains("x-mirror-token:secret"), "{}", head);
+            assert!(head.contains("range:bytes=5-"), "{}", head);
+        }
+        assert!(first.contains("authorization:bearer hf_token"));
+        assert!(!second.contains("authorization"));
+    }
+
+    #[test]
+    fn test_smaller_quants_of_the_same_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        let entry = |name: &str, repo: &str, quant: &str| {
+            format!(
+                "[[models]]\nname = \"{}\"\nrepo = \"{}\"\nfilename = \"{}.gguf\"\nquantization = \"{}\"\n",
+                name, repo, name, quant
+            )
+        };
+        let manifest = [
+            entry("big-q8", "org/big", "Q8_0"),
+            entry("big-q2", "org/big", "Q2_K"),
+            entry("big-q4", "org/big", "Q4_K_M"),
+            entry("other-q2", "org/other", "Q2_K"),
+        ]
+        .concat();
+        std::fs::write(dir.path().join("manifest.toml"), manifest).unwrap();
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(dir.path().join("cache"))
+            .use_hub_cache(false)
+            .build()
+            .unwrap();
+
+        let names = |name: &str, bits: u32| -> Vec<String> {
+            authority
+                .smaller_quants(name, bits)
+                .into_iter()
+                .map(|entry| entry.name)
+                .collect()
+        };
+        assert_eq!(names("big-q8", 8), vec!["big-q4", "big-q2"]);
+        assert_eq!(names("org/big:Q4_K_M", 4), vec!["big-q2"]);
+        assert!(names("big-q2", 2).is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_license_must_be_accepted() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("manifest.toml"),
+            "[[models]]\nname = \"gated\"\nrepo = \"mem/gated\"\nfilename = \"gated.gguf\"\nquantization = \"Q4_K_M\"\nlicense = \"https://example.com/eula\"\n",
+        )
+        .unwrap();
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(dir.path().join("cache"))
+            .use_hub_cache(false)
+            .source(MemorySource(b"GGUF gated".to_vec()))
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            authority.pending_license("gated").as_deref(),
+            Some("https://example.com/eula")
+        );
+        let mut events = authority.ensure_model_stream("gated");
+        let mut required = None;
+        while let Some(event) = events.next().await {
+            if let AssetEvent::LicenseRequired { model, license } = event {
+                required = Some((model, license));
+            }
+        }
+        assert_eq!(
+            required,
+            Some(("gated".to_string(), "https://example.com/eula".to_string()))
+        );
+        assert!(!dir.path().join("cache").join("gated.gguf").exists());
+
+        authority.accept_license("gated").unwrap();
+        assert_eq!(authority.pending_license("gated"), None);
+        authority.ensure_model("gated").await.unwrap();
+    }
+
+    #[async_std::test]
+    async fn test_identical_files_share_a_blob() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let data = b"GGUF same weights".to_vec();
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .use_hub_cache(false)
+            .source(MemorySource(data.clone()))
+            .build()
+            .unwrap();
+
+        let a = authority.ensure_model("mem/a:one.gguf").await.unwrap();
+        let b = authority.ensure_model("mem/b:two.gguf").await.unwrap();
+        assert_eq!(std::fs::read(&a).unwrap(), data);
+        assert_eq!(std::fs::read(&b).unwrap(), data);
+
+        let blobs: Vec<_> = std::fs::read_dir(authority.blob_dir())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(blobs.len(), 1);
+
+        let registry = ModelRegistry::with_dirs(dir.path(), &cache).unwrap();
+        assert_eq!(
+            registry.get("mem/a:one.gguf").unwrap().sha256.as_deref(),
+            Some(blobs[0].as_str())
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inode = |p: &Path| std::fs::metadata(p).unwrap().ino();
+            assert_eq!(inode(&a), inode(&b));
+        }
+    }
+
+    #[async_std::test]
+    async fn test_recover_partials() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::write(cache.join("orphan.gguf.partial"), b"1234").unwrap();
+        std::fs::write(
+            cache.join("qwen2.5-0.5b-instruct-q4_k_m.gguf.partial"),
+            b"12",
+        )
+        .unwrap();
+
+        let authority = AssetAuthority::builder()
+            .config_dir(dir.path())
+            .cache_dir(&cache)
+            .offline(true)
+            .build()
+            .unwrap();
+        let partials = authority.partial_downloads();
+        assert_eq!(partials.len(), 2);
+        assert_eq!(partials[0].offset, 4);
+        assert_eq!(partials[0].model, None);
+        assert_eq!(partials[1].model.as_deref(), Some("tiny-model"));
+
+        // Offline, so neither can be resumed: both are reported as errors and kept.
+        let events: Vec<AssetEvent> = authority.recover(RecoveryAction::Resume).collect().await;
+        let errors: Vec<&String> = events
+            .iter()
+            .filter_map(|e| match e {
+                AssetEvent::Error(e) => Some(e),
+                _ => None,
+            })
+            .collect();
+        assert!(errors.iter().any(|e| e.contains("orphan.gguf.partial")));
+        assert!(errors.iter().any(|e| e.contains("offline")));
+        assert!(cache.join("orphan.gguf.partial").exists());
+
+        let events: Vec<AssetEvent> = authority.recover(RecoveryAction::Clean).collect().await;
+        let discarded = events
+            .iter()
+            .filter(|e| matches!(e, AssetEvent::Discarded(_)))
+            .count();
+        assert_eq!(discarded, 2);
+        assert!(!cache.join("orphan.gguf.partial").exists());
+    }
+
     #[async_std::test]
     async fn test_ensure_model_tiny() {
         let authority = AssetAuthority::new().unwrap();
@@ -0,0 +1,373 @@
+use anyhow::{anyhow, Result};
+use rusty_genius_core::gguf::file_type_name;
+use rusty_genius_core::protocol::ModelInfo;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+
+/// GGUF value type of arrays.
+const ARRAY: u32 = 9;
+
+/// Suffixes of the `{architecture}.*` keys read into [GgufInfo].
+const ARCH_SUFFIXES: &[&str] = &[
+    ".context_length",
+    ".embedding_length",
+    ".block_count",
+    ".attention.head_count",
+    ".attention.head_count_kv",
+    ".attention.key_length",
+    ".attention.value_length",
+];
+
+/// Model details read from the header of a GGUF file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GgufInfo {
+    /// GGUF format version (1–3).
+    pub version: u32,
+    /// `general.name`, if the file sets one.
+    pub name: Option<String>,
+    /// `general.architecture`, e.g. `llama` or `qwen2`.
+    pub architecture: Option<String>,
+    /// Total number of weights across all tensors.
+    pub parameter_count: u64,
+    /// Quantization derived from `general.file_type`, e.g. `Q4_K_M`.
+    pub quantization: Option<String>,
+    /// Training context length (`{architecture}.context_length`).
+    pub context_length: Option<u64>,
+    /// Embedding size (`{architecture}.embedding_length`).
+    pub embedding_length: Option<u64>,
+    /// Number of repeating layers (`{architecture}.block_count`).
+    pub block_count: Option<u64>,
+    /// Attention heads (`{architecture}.attention.head_count`).
+    pub head_count: Option<u64>,
+    /// Key/value heads, fewer than `head_count` with grouped-query attention.
+    pub head_count_kv: Option<u64>,
+    /// Size of each key head, when it isn't `embedding_length / head_count`.
+    pub key_length: Option<u64>,
+    /// Size of each value head, when it isn't `embedding_length / head_count`.
+    pub value_length: Option<u64>,
+    /// Number of tokens in `tokenizer.ggml.tokens`.
+    pub vocab_size: Option<u64>,
+    /// Jinja chat template (`tokenizer.chat_template`).
+    pub chat_template: Option<String>,
+}
+
+impl From<GgufInfo> for ModelInfo {
+    fn from(info: GgufInfo) -> Self {
+        Self {
+            architecture: info.architecture,
+            parameter_count: info.parameter_count,
+            quantization: info.quantization,
+            context_length: info.context_length,
+            embedding_length: info.embedding_length,
+            vocab_size: info.vocab_size,
+            chat_template: info.chat_template,
+            gpu_layers: None,
+        }
+    }
+}
+
+/// Parse the GGUF header of the file at `path`.
+///
+/// Only the metadata and tensor descriptors are read; tensor data is never touched, so this is
+/// cheap even for multi-gigabyte models.
+pub fn inspect(path: impl AsRef<Path>) -> Result<GgufInfo> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| anyhow!("Failed to open {:?}: {}", path, e))?;
+    let mut reader = GgufReader {
+        inner: BufReader::new(file),
+        version: 0,
+    };
+
+    let mut magic = [0u8; 4];
+    reader.inner.read_exact(&mut magic)?;
+    if &magic != GGUF_MAGIC {
+        return Err(anyhow!("{:?} is not a GGUF file", path));
+    }
+    reader.version = reader.u32()?;
+    if !(1..=3).contains(&reader.version) {
+        return Err(anyhow!("Unsupported GGUF version {}", reader.version));
+    }
+
+    let tensor_count = reader.count()?;
+    let kv_count = reader.count()?;
+
+    let mut info = GgufInfo {
+        version: reader.version,
+        ..Default::default()
+    };
+    let mut file_type = None;
+    // `{architecture}.*` values, picked once the architecture is known
+    let mut arch_values = Vec::new();
+
+    for _ in 0..kv_count {
+        let key = reader.string()?;
+        let value_type = reader.u32()?;
+        match key.as_str() {
+            "general.name" => info.name = reader.value(value_type)?.into_string(),
+            "general.architecture" => info.architecture = reader.value(value_type)?.into_string(),
+            "general.file_type" => file_type = reader.value(value_type)?.as_u64(),
+            "tokenizer.chat_template" => {
+                info.chat_template = reader.value(value_type)?.into_string()
+            }
+            "tokenizer.ggml.tokens" if value_type == ARRAY => {
+                info.vocab_size = Some(reader.skip_array()?)
+            }
+            k if ARCH_SUFFIXES.iter().any(|suffix| k.ends_with(suffix)) => {
+                if let Some(n) = reader.value(value_type)?.as_u64() {
+                    arch_values.push((key, n));
+                }
+            }
+            _ => reader.skip_value(value_type)?,
+        }
+    }
+
+    info.quantization = file_type.and_then(file_type_name).map(str::to_string);
+    let arch_value = |suffix: &str| {
+        let mut matching = arch_values.iter().filter(|(k, _)| k.ends_with(suffix));
+        match &info.architecture {
+            Some(arch) => {
+                let key = format!("{}{}", arch, suffix);
+                matching.find(|(k, _)| *k == key).map(|(_, n)| *n)
+            }
+            None => matching.next().map(|(_, n)| *n),
+        }
+    };
+    info.context_length = arch_value(".context_length");
+    info.embedding_length = arch_value(".embedding_length");
+    info.block_count = arch_value(".block_count");
+    info.head_count = arch_value(".attention.head_count");
+    info.head_count_kv = arch_value(".attention.head_count_kv");
+    info.key_length = arch_value(".attention.key_length");
+    info.value_length = arch_value(".attention.value_length");
+
+    for _ in 0..tensor_count {
+        reader.skip_string()?;
+        let n_dims = reader.u32()?;
+        let mut elements: u64 = 1;
+        for _ in 0..n_dims {
+            elements = elements.saturating_mul(reader.count()?);
+        }
+        reader.u32()?; // tensor type
+        reader.u64()?; // data offset
+        info.parameter_count = info.parameter_count.saturating_add(elements);
+    }
+
+    Ok(info)
+}
+
+enum Value {
+    Int(u64),
+    Str(String),
+    Other,
+}
+
+impl Value {
+    fn into_string(self) -> Option<String> {
+        match self {
+            Value::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Int(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+struct GgufReader<R> {
+    inner: R,
+    version: u32,
+}
+
+impl<R: Read + Seek> GgufReader<R> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        self.inner.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    /// Lengths and counts are 32-bit in GGUF v1 and 64-bit afterwards.
+    fn count(&mut self) -> Result<u64> {
+        if self.version == 1 {
+            Ok(self.u32()? as u64)
+        } else {
+            self.u64()
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.count()?;
+        let mut buf = Vec::new();
+        (&mut self.inner).take(len).read_to_end(&mut buf)?;
+        if buf.len() as u64 != len {
+            return Err(anyhow!("Truncated GGUF string"));
+        }
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    fn skip_string(&mut self) -> Result<()> {
+        let len = self.count()?;
+        self.skip(len)
+    }
+
+    fn skip(&mut self, len: u64) -> Result<()> {
+        let offset = i64::try_from(len).map_err(|_| anyhow!("Corrupt GGUF length"))?;
+        self.inner.seek(SeekFrom::Current(offset))?;
+        Ok(())
+    }
+
+    fn value(&mut self, value_type: u32) -> Result<Value> {
+        Ok(match value_type {
+            0 => Value::Int(self.bytes::<1>()?[0] as u64),
+            1 => Value::Int(i8::from_le_bytes(self.bytes()?).max(0) as u64),
+            2 => Value::Int(u16::from_le_bytes(self.bytes()?) as u64),
+            3 => Value::Int(i16::from_le_bytes(self.bytes()?).max(0) as u64),
+            4 => Value::Int(self.u32()? as u64),
+            5 => Value::Int(i32::from_le_bytes(self.bytes()?).max(0) as u64),
+            10 => Value::Int(self.u64()?),
+            11 => Value::Int(i64::from_le_bytes(self.bytes()?).max(0) as u64),
+            8 => Value::Str(self.string()?),
+            _ => {
+                self.skip_value(value_type)?;
+                Value::Other
+            }
+        })
+    }
+
+    fn skip_value(&mut self, value_type: u32) -> Result<()> {
+        match value_type {
+            0 | 1 | 7 => self.skip(1),
+            2 | 3 => self.skip(2),
+            4..=6 => self.skip(4),
+            10..=12 => self.skip(8),
+            8 => self.skip_string(),
+            ARRAY => self.skip_array().map(|_| ()),
+            other => Err(anyhow!("Unknown GGUF value type {}", other)),
+        }
+    }
+
+    /// Skip an array value, returning how many items it held.
+    fn skip_array(&mut self) -> Result<u64> {
+        let item_type = self.u32()?;
+        let len = self.count()?;
+        match item_type {
+            0 | 1 | 7 => self.skip(len)?,
+            2 | 3 => self.skip(len.saturating_mul(2))?,
+            4..=6 => self.skip(len.saturating_mul(4))?,
+            10..=12 => self.skip(len.saturating_mul(8))?,
+            _ => {
+                for _ in 0..len {
+                    self.skip_value(item_type)?;
+                }
+            }
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn string(buf: &mut Vec<u8>, s: &str) {
+        buf.extend_from_slice(&(s.len() as u64).to_le_bytes());
+        buf.extend_from_slice(s.as_bytes());
+    }
+
+    fn kv_str(buf: &mut Vec<u8>, key: &str, value: &str) {
+        string(buf, key);
+        buf.extend_from_slice(&8u32.to_le_bytes());
+        string(buf, value);
+    }
+
+    fn kv_u32(buf: &mut Vec<u8>, key: &str, value: u32) {
+        string(buf, key);
+        buf.extend_from_slice(&4u32.to_le_bytes());
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn tensor(buf: &mut Vec<u8>, name: &str, dims: &[u64]) {
+        string(buf, name);
+        buf.extend_from_slice(&(dims.len() as u32).to_le_bytes());
+        for d in dims {
+            buf.extend_from_slice(&d.to_le_bytes());
+        }
+        buf.extend_from_slice(&12u32.to_le_bytes());
+        buf.extend_from_slice(&0u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_inspect_header() {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(b"GGUF");
+        buf.extend_from_slice(&3u32.to_le_bytes());
+        buf.extend_from_slice(&2u64.to_le_bytes()); // tensors
+        buf.extend_from_slice(&10u64.to_le_bytes()); // kvs
+
+        kv_str(&mut buf, "general.architecture", "qwen2");
+        kv_str(&mut buf, "general.name", "Tiny Qwen");
+        kv_u32(&mut buf, "general.file_type", 15);
+        kv_u32(&mut buf, "qwen2.context_length", 32768);
+        kv_u32(&mut buf, "qwen2.embedding_length", 896);
+        kv_u32(&mut buf, "qwen2.block_count", 24);
+        kv_u32(&mut buf, "qwen2.attention.head_count", 14);
+        kv_u32(&mut buf, "qwen2.attention.head_count_kv", 2);
+        // A string array, as used for tokenizer vocabularies, is counted and skipped.
+        string(&mut buf, "tokenizer.ggml.tokens");
+        buf.extend_from_slice(&9u32.to_le_bytes());
+        buf.extend_from_slice(&8u32.to_le_bytes());
+        buf.extend_from_slice(&2u64.to_le_bytes());
+        string(&mut buf, "<s>");
+        string(&mut buf, "</s>");
+        kv_str(&mut buf, "tokenizer.chat_template", "{{ messages }}");
+
+        tensor(&mut buf, "token_embd.weight", &[896, 1000]);
+        tensor(&mut buf, "output_norm.weight", &[896]);
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&buf).unwrap();
+
+        let info = inspect(file.path()).unwrap();
+        assert_eq!(info.version, 3);
+        assert_eq!(info.architecture.as_deref(), Some("qwen2"));
+        assert_eq!(info.name.as_deref(), Some("Tiny Qwen"));
+        assert_eq!(info.quantization.as_deref(), Some("Q4_K_M"));
+        assert_eq!(info.context_length, Some(32768));
+        assert_eq!(info.embedding_length, Some(896));
+        assert_eq!(info.block_count, Some(24));
+        assert_eq!(info.head_count, Some(14));
+        assert_eq!(info.head_count_kv, Some(2));
+        assert_eq!(info.key_length, None);
+        assert_eq!(info.vocab_size, Some(2));
+        assert_eq!(info.chat_template.as_deref(), Some("{{ messages }}"));
+        assert_eq!(info.parameter_count, 896 * 1000 + 896);
+
+        // 24 layers x 1024 positions x 2 heads x (64 + 64) x 2 bytes
+        let estimate = crate::memory::estimate_memory(file.path(), Some(1024), None).unwrap();
+        assert_eq!(estimate.kv_cache, 24 * 1024 * 2 * 128 * 2);
+        assert_eq!(estimate.weights, buf.len() as u64);
+        assert_eq!(estimate.layers, 25);
+    }
+
+    #[test]
+    fn test_inspect_rejects_non_gguf() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"not a model").unwrap();
+        assert!(inspect(file.path()).is_err());
+    }
+}
@@ -9,9 +9,22 @@
 //! ## Core Features
 //!
 //! - **Registry Management**: Uses `registry.toml` to map friendly names to HuggingFace repositories.
-//! - **HuggingFace Integration**: Automatically resolves and downloads GGUF assets.
-//! - **Streaming Downloads**: Provides an event-based API for tracking download progress (bytes/total).
-//! - **Local Caching**: Deduplicates downloads and manages assets in `~/.config/rusty-genius/`.
+//! - **HuggingFace Integration**: Automatically resolves and downloads GGUF assets, and searches the hub for GGUF repos.
+//! - **Pluggable Sources**: Registry entries can point at `s3://bucket/path` (S3, MinIO) or any custom [`AssetSource`].
+//! - **Streaming Downloads**: Provides an event-based API for tracking download progress (bytes/total), throttled to ten updates per second by default.
+//! - **Content-Addressed Storage**: Files are stored once under `blobs/<sha256>` and linked to their friendly filenames.
+//! - **License Gating**: Entries with a `license` emit `LicenseRequired` and are not downloaded until [`AssetAuthority::accept_license`] is called.
+//! - **Verification**: [`AssetAuthority::verify`] re-hashes cached files against their recorded checksums and can delete or re-download corrupt ones.
+//! - **Hub Cache Reuse**: Files already in the HuggingFace hub cache (`~/.cache/huggingface/hub`) are symlinked instead of downloaded again.
+//! - **Sidecar Files**: Entries can list auxiliary files (e.g. `tokenizer_config.json`); [`AssetAuthority::ensure_bundle`] returns them with the GGUF path.
+//! - **Usage Tracking**: `ensure_model` records when each cached file was last used; [`AssetAuthority::list_cached`] reports it.
+//! - **Download Queue**: At most a few downloads run at once (configurable); the rest report `Queued` and wait.
+//! - **Signed Manifests**: `manifest.toml` can carry a detached ed25519 signature checked against keys in `trust.toml`; pinned checksums are verified on every load.
+//! - **GGUF Inspection**: [`inspect`] reads architecture, parameter count, quantization, context length, embedding size, vocabulary size and chat template from a model file.
+//! - **Memory Estimates**: [`estimate_memory`] sizes a model's weights and KV cache at a given context length, to compare with [`system_memory`] before loading it.
+//! - **Local Caching**: Deduplicates downloads and manages assets in `~/.config/rusty-genius/`,
+//!   or any directory chosen with [`AssetAuthority::builder`] (which also sets timeouts, retries,
+//!   user agent and redirect limit).
 //!
 //! ## Usage
 //!
@@ -56,8 +69,12 @@
 //!                 let pct = (current as f64 / total as f64) * 100.0;
 //!                 print!("\rProgress: {:.2}% ({}/{})", pct, current, total);
 //!             }
+//!             AssetEvent::Rate { bytes_per_sec, eta_secs } => {
+//!                 print!(" {} B/s, ETA {:?}s", bytes_per_sec, eta_secs);
+//!             }
 //!             AssetEvent::Complete(path) => println!("\nModel ready at: {}", path),
 //!             AssetEvent::Error(err) => eprintln!("Error: {}", err),
+//!             _ => {}
 //!         }
 //!     }
 //!
@@ -68,8 +85,40 @@
 /// Logic for downloading and caching assets from remote sources.
 pub mod assets;
 
+/// Bounded-concurrency scheduling of downloads.
+mod queue;
+
+/// Last-used timestamps of cached files.
+mod usage;
+
+/// Record of accepted model licenses.
+mod licenses;
+
+/// Local inspection of GGUF model headers.
+pub mod gguf;
+
+/// Estimates of the memory a model needs, and of what the machine has.
+pub mod memory;
+
 /// Management of the local model registry and configuration.
 pub mod registry;
 
-pub use assets::AssetAuthority;
+/// Remote locations (HuggingFace, S3) that assets are streamed from.
+pub mod sources;
+
+/// Optional ed25519 signing of `manifest.toml`.
+pub mod trust;
+
+pub use assets::{
+    AssetAuthority, AssetAuthorityBuilder, CachedModel, CorruptAction, CorruptFile, ImportMode,
+    PartialDownload, ProgressThrottle, RecoveryAction, RepairOutcome, RetryPolicy, VerifyReport,
+};
+pub use gguf::{inspect, GgufInfo};
+pub use memory::{
+    estimate_memory, system_memory, InsufficientMemory, MemoryEstimate, SystemMemory,
+};
 pub use registry::ModelRegistry;
+pub use sources::{
+    AssetSource, HuggingFaceSource, S3Source, SearchFilters, SearchResult, SearchSort,
+};
+pub use trust::{public_key_hex, sign_manifest};
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Record in the config directory of the licenses the user has accepted.
+const LICENSES_FILE: &str = "accepted_licenses.toml";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LicensesFile {
+    /// SHA-256 of the accepted license text, by model name. A changed license needs
+    /// accepting again.
+    #[serde(default)]
+    accepted: BTreeMap<String, String>,
+}
+
+fn fingerprint(license: &str) -> String {
+    hex::encode(Sha256::digest(license.as_bytes()))
+}
+
+fn read(config_dir: &Path) -> LicensesFile {
+    fs::read_to_string(config_dir.join(LICENSES_FILE))
+        .ok()
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Whether `license` has been accepted for `model`.
+pub(crate) fn is_accepted(config_dir: &Path, model: &str, license: &str) -> bool {
+    read(config_dir).accepted.get(model) == Some(&fingerprint(license))
+}
+
+/// Record that `license` was accepted for `model`.
+pub(crate) fn accept(config_dir: &Path, model: &str, license: &str) -> Result<()> {
+    fs::create_dir_all(config_dir)?;
+    let mut file = read(config_dir);
+    file.accepted
+        .insert(model.to_string(), fingerprint(license));
+
+    let path = config_dir.join(LICENSES_FILE);
+    let tmp_path = path.with_extension("toml.tmp");
+    fs::write(&tmp_path, toml::to_string(&file)?)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_license_needs_accepting_again() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(!is_accepted(dir.path(), "llama", "v1"));
+
+        accept(dir.path(), "llama", "v1").unwrap();
+        assert!(is_accepted(dir.path(), "llama", "v1"));
+        assert!(!is_accepted(dir.path(), "llama", "v2"));
+        assert!(!is_accepted(dir.path(), "other", "v1"));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::gguf::inspect;
+use anyhow::Result;
+use rusty_genius_core::gguf::split_parts;
+use rusty_genius_core::manifest::KvCacheType;
+use std::fmt;
+use std::path::Path;
+
+/// Memory a model needs once loaded: its weights and the KV cache of one context.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryEstimate {
+    pub weights: u64,
+    pub kv_cache: u64,
+    /// Layers the memory is spread over, counting the output layer; what offloading all of
+    /// them to the GPU takes.
+    pub layers: u32,
+}
+
+impl MemoryEstimate {
+    pub fn total(&self) -> u64 {
+        self.weights + self.kv_cache
+    }
+
+    /// The RAM and VRAM needed with `gpu_layers` layers offloaded, taking layers to be the
+    /// same size.
+    pub fn split(&self, gpu_layers: u32) -> (u64, u64) {
+        let layers = self.layers.max(1) as u64;
+        let vram =
+            (self.total() as u128 * gpu_layers.min(self.layers) as u128 / layers as u128) as u64;
+        (self.total() - vram, vram)
+    }
+
+    /// The estimate for the same model quantized to `to_bits` bits a weight rather than
+    /// `from_bits`, taking its weights to shrink in proportion.
+    pub fn requantized(&self, from_bits: u32, to_bits: u32) -> MemoryEstimate {
+        MemoryEstimate {
+            weights: self.weights * to_bits as u64 / from_bits.max(1) as u64,
+            ..*self
+        }
+    }
+}
+
+/// A model refused because it needs more memory than is available to load it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InsufficientMemory {
+    pub model: String,
+    pub required: MemoryEstimate,
+    /// RAM and VRAM available to the model.
+    pub available: u64,
+    /// A smaller quantization of the model, from the registry, that would fit.
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for InsufficientMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Not enough memory for {}: it needs about {}, and {} is available",
+            self.model,
+            self.required,
+            format_bytes(self.available)
+        )?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "; try the smaller quantization {}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for InsufficientMemory {}
+
+/// Bits a weight takes at `quantization`, as its name says: 4 for `Q4_K_M` or `IQ4_XS`, 16
+/// for `F16`.
+pub fn quant_bits(quantization: &str) -> Option<u32> {
+    let quantization = quantization.to_uppercase();
+    match quantization.as_str() {
+        "F32" => Some(32),
+        "F16" | "BF16" => Some(16),
+        _ => quantization
+            .strip_prefix("IQ")
+            .or_else(|| quantization.strip_prefix('Q'))?
+            .chars()
+            .next()?
+            .to_digit(10),
+    }
+}
+
+impl fmt::Display for MemoryEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} weights + {} KV cache)",
+            format_bytes(self.total()),
+            format_bytes(self.weights),
+            format_bytes(self.kv_cache)
+        )
+    }
+}
+
+/// Estimate the memory the GGUF at `path` needs with a context of `context_size` tokens (the
+/// model's training context when `None`) and a KV cache of `kv_cache_type` (`f16` when
+/// `None`).
+///
+/// The weights are taken to be the size of the file, or of all its parts for a split model.
+/// The KV cache is sized from the layer and head counts in the header; it is left at `0` when
+/// they are missing.
+pub fn estimate_memory(
+    path: impl AsRef<Path>,
+    context_size: Option<u32>,
+    kv_cache_type: Option<KvCacheType>,
+) -> Result<MemoryEstimate> {
+    let path = path.as_ref();
+    let info = inspect(path)?;
+    let weights = match path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(split_parts)
+    {
+        Some(parts) => parts
+            .iter()
+            .map(|part| std::fs::metadata(path.with_file_name(part)).map(|m| m.len()))
+            .sum::<std::io::Result<u64>>()?,
+        None => std::fs::metadata(path)?.len(),
+    };
+    let block_count = info.block_count.unwrap_or(0);
+
+    let context = context_size
+        .map(u64::from)
+        .or(info.context_length)
+        .unwrap_or(0);
+    let head_size = match (info.embedding_length, info.head_count) {
+        (Some(embedding), Some(heads)) if heads > 0 => Some(embedding / heads),
+        _ => None,
+    };
+    let kv_heads = info.head_count_kv.or(info.head_count).unwrap_or(0);
+    let kv_width = kv_heads
+        * (info.key_length.or(head_size).unwrap_or(0)
+            + info.value_length.or(head_size).unwrap_or(0));
+    // Bytes per 32 elements
+    let block_bytes = match kv_cache_type.unwrap_or(KvCacheType::F16) {
+        KvCacheType::F16 => 64,
+        KvCacheType::Q8_0 => 34,
+        KvCacheType::Q4_0 => 18,
+    };
+    let kv_cache = block_count * context * kv_width * block_bytes / 32;
+
+    Ok(MemoryEstimate {
+        weights,
+        kv_cache,
+        layers: block_count as u32 + 1,
+    })
+}
+
+/// Physical memory of this machine, and how much of it is available without swapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SystemMemory {
+    pub total: u64,
+    pub available: u64,
+}
+
+/// Read the machine's memory from `/proc/meminfo`; `None` where there is no such file.
+pub fn system_memory() -> Option<SystemMemory> {
+    parse_meminfo(&std::fs::read_to_string("/proc/meminfo").ok()?)
+}
+
+fn parse_meminfo(meminfo: &str) -> Option<SystemMemory> {
+    let field = |name: &str| {
+        meminfo.lines().find_map(|line| {
+            let kb = line.strip_prefix(name)?.strip_prefix(':')?;
+            kb.trim().trim_end_matches("kB").trim().parse::<u64>().ok()
+        })
+    };
+    Some(SystemMemory {
+        total: field("MemTotal")? * 1024,
+        available: field("MemAvailable")? * 1024,
+    })
+}
+
+/// Format a byte count in GiB (or MiB below one GiB), e.g. `4.2 GiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const MIB: f64 = 1024.0 * 1024.0;
+    let mib = bytes as f64 / MIB;
+    if mib < 1024.0 {
+        format!("{:.0} MiB", mib)
+    } else {
+        format!("{:.1} GiB", mib / 1024.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_by_layers() {
+        let estimate = MemoryEstimate {
+            weights: 900,
+            kv_cache: 100,
+            layers: 10,
+        };
+        assert_eq!(estimate.split(0), (1000, 0));
+        assert_eq!(estimate.split(4), (600, 400));
+        assert_eq!(estimate.split(99), (0, 1000));
+
+        let estimate = MemoryEstimate {
+            weights: 4 << 30,
+            kv_cache: 512 << 20,
+            layers: 29,
+        };
+        assert_eq!(
+            estimate.to_string(),
+            "4.5 GiB (4.0 GiB weights + 512 MiB KV cache)"
+        );
+    }
+
+    #[test]
+    fn test_parse_meminfo() {
+        let meminfo =
+            "MemTotal:       16384000 kB\nMemFree:         1000 kB\nMemAvailable:    8192000 kB\n";
+        assert_eq!(
+            parse_meminfo(meminfo),
+            Some(SystemMemory {
+                total: 16384000 * 1024,
+                available: 8192000 * 1024,
+            })
+        );
+        assert_eq!(parse_meminfo("MemTotal: 1 kB\n"), None);
+    }
+
+    #[test]
+    fn test_quant_bits_and_requantized() {
+        assert_eq!(quant_bits("Q4_K_M"), Some(4));
+        assert_eq!(quant_bits("iq3_xs"), Some(3));
+        assert_eq!(quant_bits("Q8_0"), Some(8));
+        assert_eq!(quant_bits("BF16"), Some(16));
+        assert_eq!(quant_bits("mystery"), None);
+
+        let estimate = MemoryEstimate {
+            weights: 800,
+            kv_cache: 100,
+            layers: 10,
+        };
+        assert_eq!(estimate.requantized(8, 4).total(), 500);
+    }
+
+    #[test]
+    fn test_insufficient_memory_message() {
+        let error = InsufficientMemory {
+            model: "big".to_string(),
+            required: MemoryEstimate {
+                weights: 8 << 30,
+                kv_cache: 0,
+                layers: 1,
+            },
+            available: 6 << 30,
+            suggestion: Some("big-q4".to_string()),
+        };
+        assert_eq!(
+            error.to_string(),
+            "Not enough memory for big: it needs about 8.0 GiB (8.0 GiB weights + 0 MiB KV cache), \
+             and 6.0 GiB is available; try the smaller quantization big-q4"
+        );
+    }
+}
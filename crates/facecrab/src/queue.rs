@@ -0,0 +1,132 @@
+use futures::channel::{mpsc, oneshot};
+use rusty_genius_core::protocol::AssetEvent;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Limits how many downloads run at once. Downloads beyond the limit wait in FIFO order and
+/// are told their position with [AssetEvent::Queued] whenever it changes.
+pub(crate) struct DownloadQueue {
+    max_concurrent: usize,
+    state: Mutex<QueueState>,
+}
+
+struct QueueState {
+    active: usize,
+    waiting: VecDeque<Waiter>,
+}
+
+struct Waiter {
+    wake: oneshot::Sender<()>,
+    events: mpsc::Sender<AssetEvent>,
+}
+
+/// A running download's slot in the queue; dropping it starts the next waiting download.
+pub(crate) struct DownloadSlot {
+    queue: Arc<DownloadQueue>,
+}
+
+impl DownloadQueue {
+    pub(crate) fn new(max_concurrent: usize) -> Arc<Self> {
+        Arc::new(Self {
+            max_concurrent: max_concurrent.max(1),
+            state: Mutex::new(QueueState {
+                active: 0,
+                waiting: VecDeque::new(),
+            }),
+        })
+    }
+
+    /// Wait for a free slot, reporting the queue position on `events` while waiting.
+    pub(crate) async fn acquire(
+        self: &Arc<Self>,
+        mut events: mpsc::Sender<AssetEvent>,
+    ) -> DownloadSlot {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            if state.active < self.max_concurrent {
+                state.active += 1;
+                None
+            } else {
+                let (wake, woken) = oneshot::channel();
+                let position = state.waiting.len();
+                let _ = events.try_send(AssetEvent::Queued { position });
+                state.waiting.push_back(Waiter { wake, events });
+                Some(woken)
+            }
+        };
+
+        if let Some(woken) = wait {
+            // The slot is handed over by `release`, which never drops the sender unsent.
+            let _ = woken.await;
+        }
+
+        DownloadSlot {
+            queue: Arc::clone(self),
+        }
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        let mut handed_over = false;
+        while let Some(waiter) = state.waiting.pop_front() {
+            if waiter.wake.send(()).is_ok() {
+                handed_over = true;
+                break;
+            }
+        }
+        if !handed_over {
+            state.active -= 1;
+        }
+        for (position, waiter) in state.waiting.iter_mut().enumerate() {
+            let _ = waiter.events.try_send(AssetEvent::Queued { position });
+        }
+    }
+}
+
+impl Drop for DownloadSlot {
+    fn drop(&mut self) {
+        self.queue.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[async_std::test]
+    async fn test_queue_limits_concurrency() {
+        let queue = DownloadQueue::new(1);
+        let (tx_a, _rx_a) = mpsc::channel(8);
+        let (tx_b, mut rx_b) = mpsc::channel(8);
+        let (tx_c, mut rx_c) = mpsc::channel(8);
+
+        let first = queue.acquire(tx_a).await;
+
+        let q = Arc::clone(&queue);
+        let second = async_std::task::spawn(async move { q.acquire(tx_b).await });
+        assert!(matches!(
+            rx_b.next().await,
+            Some(AssetEvent::Queued { position: 0 })
+        ));
+
+        let q = Arc::clone(&queue);
+        let third = async_std::task::spawn(async move { q.acquire(tx_c).await });
+        assert!(matches!(
+            rx_c.next().await,
+            Some(AssetEvent::Queued { position: 1 })
+        ));
+
+        drop(first);
+        let second = second.await;
+        // The third download moved up the queue.
+        assert!(matches!(
+            rx_c.next().await,
+            Some(AssetEvent::Queued { position: 0 })
+        ));
+
+        drop(second);
+        let _third = third.await;
+        assert_eq!(queue.state.lock().unwrap().active, 1);
+    }
+}
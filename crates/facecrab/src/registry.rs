@@ -1,21 +1,141 @@
+use crate::trust::{signature_path, ManifestTrust};
 use anyhow::{Context, Result};
-use rusty_genius_core::manifest::ModelSpec;
+use rusty_genius_core::manifest::{ModelDefaults, ModelSpec};
+use rusty_genius_core::GeniusError;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const DEFAULT_MODELS: &str = include_str!("models.toml");
 
+/// Version of the `manifest.toml` / `registry.toml` format written by this facecrab.
+/// Files without a `schema_version` predate versioning and count as version 0.
+pub const SCHEMA_VERSION: u32 = 1;
+
 #[derive(Debug, Serialize, Deserialize)]
 struct RegistryFile {
+    #[serde(default)]
+    schema_version: u32,
     models: Vec<ModelEntry>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl RegistryFile {
+    fn new(models: Vec<ModelEntry>, profiles: BTreeMap<String, Profile>) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            models,
+            profiles,
+        }
+    }
+
+    /// Parse the contents of `path`, upgrading older schema versions in memory.
+    fn parse(content: &str, path: &Path) -> Result<Self> {
+        let mut value: toml::Value = toml::from_str(content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        let version = schema_version(&value, path)?;
+        migrate(&mut value, version);
+        value
+            .try_into()
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+}
+
+/// The `schema_version` of a parsed file, rejecting versions newer than [SCHEMA_VERSION].
+fn schema_version(value: &toml::Value, path: &Path) -> Result<u32> {
+    let version = match value.get("schema_version") {
+        None => 0,
+        Some(v) => v
+            .as_integer()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| {
+                GeniusError::ManifestError(format!(
+                    "{}: schema_version must be a non-negative integer",
+                    path.display()
+                ))
+            })?,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(GeniusError::ManifestError(format!(
+            "{} uses schema version {}, but this version of facecrab only understands up to {}; upgrade facecrab to use it",
+            path.display(),
+            version,
+            SCHEMA_VERSION
+        ))
+        .into());
+    }
+    Ok(version)
+}
+
+/// Upgrade a parsed file from schema version `from` to [SCHEMA_VERSION], one step at a time.
+fn migrate(value: &mut toml::Value, from: u32) {
+    for version in from..SCHEMA_VERSION {
+        match version {
+            // 0 -> 1 only introduced `schema_version` itself.
+            0 => {}
+            _ => unreachable!("no migration from schema version {}", version),
+        }
+    }
+    if let Some(table) = value.as_table_mut() {
+        table.insert(
+            "schema_version".to_string(),
+            toml::Value::Integer(SCHEMA_VERSION as i64),
+        );
+    }
+}
+
+/// Rewrite the file at `path` in the current schema if it uses an older one.
+///
+/// When the upgrade changes nothing but the version, the version line is prepended so
+/// comments and formatting in hand-edited files survive. Otherwise the original is kept
+/// next to it as `<name>.v<old>.bak`.
+fn upgrade_file(path: &Path) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+    let content = fs::read_to_string(path)?;
+    let original: toml::Value =
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))?;
+    let version = schema_version(&original, path)?;
+    if version == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    let mut upgraded = original.clone();
+    migrate(&mut upgraded, version);
+
+    let mut unversioned = upgraded.clone();
+    if let Some(table) = unversioned.as_table_mut() {
+        table.remove("schema_version");
+    }
+    let mut original_unversioned = original;
+    if let Some(table) = original_unversioned.as_table_mut() {
+        table.remove("schema_version");
+    }
+
+    let new_content = if unversioned == original_unversioned && version == 0 {
+        format!("schema_version = {}\n\n{}", SCHEMA_VERSION, content)
+    } else {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(format!(".v{}.bak", version));
+        fs::copy(path, PathBuf::from(backup))?;
+        toml::to_string(&upgraded)?
+    };
+
+    let tmp_path = path.with_extension("toml.tmp");
+    fs::write(&tmp_path, new_content)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum ModelPurpose {
     Inference,
     Embedding,
+    /// Speech-to-text (whisper.cpp) models.
+    Transcription,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,69 +146,210 @@ pub struct ModelEntry {
     pub quantization: String,
     #[serde(default = "default_purpose")]
     pub purpose: ModelPurpose,
+    /// Alternate names `resolve()` accepts for this entry (e.g. "qwen").
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Free-form labels for querying models (e.g. "embedding", "coder").
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Pinned commit hash or tag. Downloads of unpinned entries record the commit they
+    /// resolved to here.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+    /// SHA-256 of the model file, hex encoded, when known (e.g. for imported files).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Auxiliary files downloaded with the model; see [ModelSpec::aux_files].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aux_files: Vec<String>,
+    /// License text or URL that must be accepted with
+    /// [AssetAuthority::accept_license](crate::AssetAuthority::accept_license) before download.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// Chat template to use instead of the one in the GGUF metadata: a template name such as
+    /// `chatml` or `llama3`, or Jinja source.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_template: Option<String>,
+    /// Generation settings for the model, merged under what each request sets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub defaults: Option<ModelDefaults>,
+}
+
+/// A named group of models that are fetched together, e.g. with
+/// [AssetAuthority::ensure_profile](crate::AssetAuthority::ensure_profile).
+///
+/// ```toml
+/// [profiles.dev]
+/// models = ["tiny-model", "qwen-2.5-1.5b-instruct"]
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Profile {
+    /// Model names, aliases or inline specs, as accepted by `ensure_model`.
+    #[serde(default)]
+    pub models: Vec<String>,
 }
 
 fn default_purpose() -> ModelPurpose {
     ModelPurpose::Inference
 }
 
+impl ModelEntry {
+    /// The entry's tags plus its purpose as a lowercase tag ("inference", "embedding" or
+    /// "transcription"), so entries written before tags existed can still be queried.
+    pub fn all_tags(&self) -> Vec<String> {
+        let purpose = format!("{:?}", self.purpose).to_lowercase();
+        let mut tags = self.tags.clone();
+        if !tags.contains(&purpose) {
+            tags.push(purpose);
+        }
+        tags
+    }
+
+    /// Whether the entry carries `tag`, compared case-insensitively.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.all_tags().iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
 pub struct ModelRegistry {
     config_dir: PathBuf,
     cache_dir: PathBuf,
     models: HashMap<String, ModelEntry>,
+    profiles: HashMap<String, Profile>,
+    trust: ManifestTrust,
+    /// Checksums pinned by a signed `manifest.toml`, by entry name.
+    pinned: HashMap<String, String>,
 }
 
 impl ModelRegistry {
+    /// Open the registry in the default locations (`GENIUS_HOME` / `GENIUS_CACHE`).
     pub fn new() -> Result<Self> {
-        let config_dir = if let Ok(home) = std::env::var("GENIUS_HOME") {
-            PathBuf::from(home)
+        let config_dir = Self::default_config_dir()?;
+        let cache_dir = Self::default_cache_dir(&config_dir);
+        Self::with_dirs(config_dir, cache_dir)
+    }
+
+    /// The config directory used by [ModelRegistry::new].
+    pub(crate) fn default_config_dir() -> Result<PathBuf> {
+        if let Ok(home) = std::env::var("GENIUS_HOME") {
+            Ok(PathBuf::from(home))
         } else if let Ok(custom_path) = std::env::var("RUSTY_GENIUS_CONFIG_DIR") {
-            PathBuf::from(custom_path)
+            Ok(PathBuf::from(custom_path))
         } else {
-            dirs::config_dir()
+            Ok(dirs::config_dir()
                 .context("Could not find config directory")?
-                .join("rusty-genius")
-        };
+                .join("rusty-genius"))
+        }
+    }
 
-        // Resolve Cache Directory
-        let cache_dir = if let Ok(cache) = std::env::var("GENIUS_CACHE") {
+    /// The cache directory used by [ModelRegistry::new] for a given config directory.
+    pub(crate) fn default_cache_dir(config_dir: &Path) -> PathBuf {
+        if let Ok(cache) = std::env::var("GENIUS_CACHE") {
             PathBuf::from(cache)
         } else {
             config_dir.join("cache")
-        };
+        }
+    }
 
+    /// Open the registry with an explicit config directory (holding `manifest.toml`)
+    /// and cache directory (holding downloads and `registry.toml`).
+    pub fn with_dirs(
+        config_dir: impl Into<PathBuf>,
+        cache_dir: impl Into<PathBuf>,
+    ) -> Result<Self> {
+        let config_dir = config_dir.into();
+        let cache_dir = cache_dir.into();
         fs::create_dir_all(&config_dir)?;
         fs::create_dir_all(&cache_dir)?;
 
         let mut registry = Self {
+            trust: ManifestTrust::load(&config_dir)?,
             config_dir,
             cache_dir,
             models: HashMap::new(),
+            profiles: HashMap::new(),
+            pinned: HashMap::new(),
         };
-
-        registry.load_defaults()?;
-        registry.load_manifest()?;
-        registry.load_dynamic()?;
+        registry.migrate()?;
+        registry.reload()?;
 
         Ok(registry)
     }
 
+    /// Rebuild the in-memory map from the built-in list, `manifest.toml` and `registry.toml`,
+    /// picking up changes made by other processes.
+    pub fn reload(&mut self) -> Result<()> {
+        self.models.clear();
+        self.profiles.clear();
+        self.pinned.clear();
+        self.load_defaults()?;
+        self.load_manifest()?;
+        self.load_dynamic()?;
+        Ok(())
+    }
+
+    /// Upgrade `manifest.toml` and `registry.toml` in place to [SCHEMA_VERSION].
+    ///
+    /// Files from a newer facecrab are left untouched and reported as an error. A manifest
+    /// that can't be rewritten (e.g. a read-only mount) or is signed is only upgraded in
+    /// memory on load.
+    fn migrate(&self) -> Result<()> {
+        let manifest_path = self.config_dir.join("manifest.toml");
+        if signature_path(&manifest_path).exists() {
+            // Rewriting would invalidate the signature.
+        } else if let Err(e) = upgrade_file(&manifest_path) {
+            if e.downcast_ref::<GeniusError>().is_some() {
+                return Err(e);
+            }
+            eprintln!(
+                "Warning: could not upgrade {}: {}",
+                manifest_path.display(),
+                e
+            );
+        }
+
+        let lock = self.lock_dynamic()?;
+        let result = upgrade_file(&self.cache_dir.join("registry.toml"));
+        drop(lock);
+        result
+    }
+
     fn load_defaults(&mut self) -> Result<()> {
-        let parsed: RegistryFile = toml::from_str(DEFAULT_MODELS)?;
+        let parsed = RegistryFile::parse(DEFAULT_MODELS, Path::new("models.toml"))?;
         for model in parsed.models {
             self.models.insert(model.name.clone(), model);
         }
+        self.profiles.extend(parsed.profiles);
         Ok(())
     }
 
     fn load_manifest(&mut self) -> Result<()> {
         let manifest_path = self.config_dir.join("manifest.toml");
         if manifest_path.exists() {
-            let content = fs::read_to_string(manifest_path)?;
-            let parsed: RegistryFile = toml::from_str(&content)?;
+            let content = fs::read_to_string(&manifest_path)?;
+            let signed = self.trust.verify(&manifest_path, content.as_bytes())?;
+            let parsed = RegistryFile::parse(&content, &manifest_path)?;
             for model in parsed.models {
+                if signed {
+                    match &model.sha256 {
+                        Some(sha256) => {
+                            self.pinned
+                                .insert(model.name.clone(), sha256.to_lowercase());
+                        }
+                        None if self.trust.strict => {
+                            return Err(GeniusError::ManifestError(format!(
+                                "Model '{}' in signed {} has no sha256 and strict mode is enabled",
+                                model.name,
+                                manifest_path.display()
+                            ))
+                            .into())
+                        }
+                        None => {}
+                    }
+                }
                 self.models.insert(model.name.clone(), model);
             }
+            self.profiles.extend(parsed.profiles);
         }
         Ok(())
     }
@@ -96,54 +357,186 @@ impl ModelRegistry {
     fn load_dynamic(&mut self) -> Result<()> {
         let registry_path = self.cache_dir.join("registry.toml");
         if registry_path.exists() {
-            let content = fs::read_to_string(registry_path)?;
-            let parsed: RegistryFile = toml::from_str(&content)?;
+            let content = fs::read_to_string(&registry_path)?;
+            let parsed = RegistryFile::parse(&content, &registry_path)?;
             for model in parsed.models {
                 self.models.insert(model.name.clone(), model);
             }
+            self.profiles.extend(parsed.profiles);
         }
         Ok(())
     }
 
+    /// Add or replace an entry in the dynamic registry.
     pub fn record_model(&mut self, entry: ModelEntry) -> Result<()> {
-        // Add to in-memory map
-        self.models.insert(entry.name.clone(), entry.clone());
+        self.modify_dynamic(|entries| {
+            if let Some(pos) = entries.iter().position(|e| e.name == entry.name) {
+                entries[pos] = entry;
+            } else {
+                entries.push(entry);
+            }
+            Ok(())
+        })
+    }
+
+    /// Replace the entry called `name` with `entry`, which may carry a new name.
+    ///
+    /// Entries from `manifest.toml` or the built-in list are overridden by writing the
+    /// replacement to the dynamic registry, which takes precedence when loading.
+    pub fn update(&mut self, name: &str, entry: ModelEntry) -> Result<()> {
+        let name = match self.get(name) {
+            Some(existing) => existing.name.clone(),
+            None => {
+                return Err(GeniusError::ManifestError(format!(
+                    "Model '{}' not found in registry",
+                    name
+                ))
+                .into())
+            }
+        };
+        let name = name.as_str();
+
+        self.modify_dynamic(|entries| {
+            entries.retain(|e| e.name != name && e.name != entry.name);
+            entries.push(entry);
+            Ok(())
+        })
+    }
+
+    /// Remove an entry from the dynamic registry.
+    ///
+    /// Returns `Ok(false)` if no such model exists. Entries that only exist in
+    /// `manifest.toml` or the built-in list cannot be removed here.
+    pub fn remove(&mut self, name: &str) -> Result<bool> {
+        let name = match self.get(name) {
+            Some(existing) => existing.name.clone(),
+            None => return Ok(false),
+        };
+        let name = name.as_str();
+
+        self.modify_dynamic(|entries| {
+            let before = entries.len();
+            entries.retain(|e| e.name != name);
+            if entries.len() == before {
+                return Err(GeniusError::ManifestError(format!(
+                    "Model '{}' is defined in manifest.toml or the built-in list and cannot be removed",
+                    name
+                ))
+                .into());
+            }
+            Ok(())
+        })?;
+
+        Ok(true)
+    }
+
+    /// Look up an entry by name or alias. Exact names win over aliases.
+    pub fn get(&self, name: &str) -> Option<&ModelEntry> {
+        self.models.get(name).or_else(|| {
+            self.models
+                .values()
+                .find(|e| e.aliases.iter().any(|a| a == name))
+        })
+    }
+
+    /// Whether `name` resolves to a registry entry, by name or alias.
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// The SHA-256 a signed `manifest.toml` pins for the entry `name` resolves to.
+    ///
+    /// The pin outlives later changes to the entry in `registry.toml`, so downloaded files are
+    /// always checked against the signed value.
+    pub fn pinned_sha256(&self, name: &str) -> Option<&str> {
+        let entry = self.get(name)?;
+        self.pinned.get(&entry.name).map(String::as_str)
+    }
+
+    /// Look up a profile by name. Profiles in `registry.toml` replace those of the same name
+    /// in `manifest.toml`, which replace built-in ones.
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+
+    /// Names of all known profiles, sorted.
+    pub fn list_profiles(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.profiles.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// All entries carrying `tag`, sorted by name.
+    pub fn with_tag(&self, tag: &str) -> Vec<ModelEntry> {
+        self.list().into_iter().filter(|e| e.has_tag(tag)).collect()
+    }
+
+    /// All known entries, sorted by name.
+    pub fn list(&self) -> Vec<ModelEntry> {
+        let mut entries: Vec<ModelEntry> = self.models.values().cloned().collect();
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        entries
+    }
+
+    /// Read-modify-write `registry.toml` under an exclusive lock so concurrent
+    /// processes don't clobber each other, then refresh the in-memory map.
+    fn modify_dynamic(&mut self, f: impl FnOnce(&mut Vec<ModelEntry>) -> Result<()>) -> Result<()> {
+        let lock = self.lock_dynamic()?;
 
-        // Save to cache_dir/registry.toml
         let registry_path = self.cache_dir.join("registry.toml");
         let mut entries = Vec::new();
-
-        // If it exists, read existing ones to preserve them
+        let mut profiles = BTreeMap::new();
         if registry_path.exists() {
             let content = fs::read_to_string(&registry_path)?;
-            if let Ok(parsed) = toml::from_str::<RegistryFile>(&content) {
-                entries = parsed.models;
+            match RegistryFile::parse(&content, &registry_path) {
+                Ok(parsed) => {
+                    entries = parsed.models;
+                    profiles = parsed.profiles;
+                }
+                // Never overwrite a registry written by a newer facecrab.
+                Err(e) if e.downcast_ref::<GeniusError>().is_some() => return Err(e),
+                Err(_) => {}
             }
         }
 
-        // Add or update entry
-        if let Some(pos) = entries.iter().position(|e| e.name == entry.name) {
-            entries[pos] = entry;
-        } else {
-            entries.push(entry);
-        }
+        f(&mut entries)?;
 
-        let new_content = toml::to_string(&RegistryFile { models: entries })?;
-        fs::write(registry_path, new_content)?;
+        // Write to a sibling file and rename so readers never see a torn registry.
+        let tmp_path = registry_path.with_extension("toml.tmp");
+        fs::write(
+            &tmp_path,
+            toml::to_string(&RegistryFile::new(entries, profiles))?,
+        )?;
+        fs::rename(&tmp_path, &registry_path)?;
 
-        Ok(())
+        let result = self.reload();
+        drop(lock);
+        result
+    }
+
+    /// Take the exclusive lock guarding `registry.toml`; it is released when the file drops.
+    fn lock_dynamic(&self) -> Result<fs::File> {
+        let lock = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.cache_dir.join("registry.lock"))?;
+        lock.lock()?;
+        Ok(lock)
     }
 
     pub fn list_models(&self) -> Vec<ModelEntry> {
-        self.models.values().cloned().collect()
+        self.list()
     }
 
     pub fn resolve(&self, name_or_spec: &str) -> Option<ModelSpec> {
-        if let Some(entry) = self.models.get(name_or_spec) {
+        if let Some(entry) = self.get(name_or_spec) {
             return Some(ModelSpec {
                 repo: entry.repo.clone(),
                 filename: entry.filename.clone(),
                 quantization: entry.quantization.clone(),
+                revision: entry.revision.clone(),
+                aux_files: entry.aux_files.clone(),
             });
         }
         None
@@ -152,4 +545,231 @@ impl ModelRegistry {
     pub fn get_cache_dir(&self) -> PathBuf {
         self.cache_dir.clone()
     }
+
+    pub fn get_config_dir(&self) -> PathBuf {
+        self.config_dir.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, filename: &str) -> ModelEntry {
+        ModelEntry {
+            name: name.to_string(),
+            repo: "Qwen/Qwen2.5-0.5B-Instruct-GGUF".to_string(),
+            filename: filename.to_string(),
+            quantization: "Q4_K_M".to_string(),
+            purpose: ModelPurpose::Inference,
+            aliases: vec![],
+            tags: vec![],
+            revision: None,
+            sha256: None,
+            aux_files: vec![],
+            license: None,
+            chat_template: None,
+            defaults: None,
+        }
+    }
+
+    fn temp_registry(dir: &tempfile::TempDir) -> ModelRegistry {
+        ModelRegistry::with_dirs(dir.path().join("config"), dir.path().join("cache")).unwrap()
+    }
+
+    #[test]
+    fn test_record_and_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut registry = temp_registry(&dir);
+        assert!(registry.contains("tiny-model"));
+        assert!(!registry.contains("custom"));
+
+        registry
+            .record_model(entry("custom", "custom.gguf"))
+            .unwrap();
+        assert!(registry.contains("custom"));
+
+        let names: Vec<String> = registry.list().into_iter().map(|e| e.name).collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+        assert!(names.contains(&"custom".to_string()));
+
+        // Persisted for the next process
+        assert!(temp_registry(&dir).contains("custom"));
+    }
+
+    #[test]
+    fn test_update_renames_and_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut registry = temp_registry(&dir);
+        registry.record_model(entry("custom", "a.gguf")).unwrap();
+
+        registry
+            .update("custom", entry("renamed", "b.gguf"))
+            .unwrap();
+        assert!(!registry.contains("custom"));
+        assert_eq!(registry.resolve("renamed").unwrap().filename, "b.gguf");
+
+        // Built-in entries can be overridden
+        registry
+            .update("tiny-model", entry("tiny-model", "override.gguf"))
+            .unwrap();
+        assert_eq!(
+            temp_registry(&dir).resolve("tiny-model").unwrap().filename,
+            "override.gguf"
+        );
+
+        assert!(registry
+            .update("missing", entry("missing", "x.gguf"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut registry = temp_registry(&dir);
+        registry
+            .record_model(entry("custom", "custom.gguf"))
+            .unwrap();
+
+        assert!(registry.remove("custom").unwrap());
+        assert!(!registry.contains("custom"));
+        assert!(!temp_registry(&dir).contains("custom"));
+
+        assert!(!registry.remove("custom").unwrap());
+        assert!(registry.remove("tiny-model").is_err());
+        assert!(registry.contains("tiny-model"));
+    }
+
+    #[test]
+    fn test_aliases_and_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut registry = temp_registry(&dir);
+
+        // Built-in alias
+        assert_eq!(
+            registry.resolve("qwen").unwrap().filename,
+            registry.resolve("qwen-2.5-3b-instruct").unwrap().filename
+        );
+
+        let mut coder = entry("my-coder", "coder.gguf");
+        coder.aliases = vec!["coder".to_string()];
+        coder.tags = vec!["Coder".to_string()];
+        registry.record_model(coder).unwrap();
+
+        assert_eq!(registry.resolve("coder").unwrap().filename, "coder.gguf");
+        assert_eq!(registry.get("coder").unwrap().name, "my-coder");
+
+        let coders: Vec<String> = registry
+            .with_tag("coder")
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(coders, vec!["my-coder".to_string()]);
+
+        // Purpose doubles as a tag
+        assert!(registry
+            .with_tag("embedding")
+            .iter()
+            .any(|e| e.name == "nomic-embed-text"));
+        assert!(registry
+            .with_tag("embedding")
+            .iter()
+            .all(|e| e.purpose == ModelPurpose::Embedding));
+
+        // Removing by alias removes the entry
+        assert!(registry.remove("coder").unwrap());
+        assert!(!registry.contains("my-coder"));
+    }
+
+    #[test]
+    fn test_concurrent_writers_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut first = temp_registry(&dir);
+        let mut second = temp_registry(&dir);
+
+        first.record_model(entry("one", "one.gguf")).unwrap();
+        second.record_model(entry("two", "two.gguf")).unwrap();
+
+        // The second writer must not drop the first writer's entry
+        assert!(second.contains("one"));
+        assert!(temp_registry(&dir).contains("one"));
+        assert!(temp_registry(&dir).contains("two"));
+    }
+
+    #[test]
+    fn test_legacy_files_upgraded_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config");
+        let cache = dir.path().join("cache");
+        fs::create_dir_all(&config).unwrap();
+        fs::create_dir_all(&cache).unwrap();
+        let legacy = "# my models\n[[models]]\nname = \"legacy\"\nrepo = \"a/b\"\nfilename = \"l.gguf\"\nquantization = \"Q4_K_M\"\n";
+        fs::write(config.join("manifest.toml"), legacy).unwrap();
+        fs::write(
+            cache.join("registry.toml"),
+            legacy.replace("legacy", "cached"),
+        )
+        .unwrap();
+
+        let registry = ModelRegistry::with_dirs(&config, &cache).unwrap();
+        assert!(registry.contains("legacy"));
+        assert!(registry.contains("cached"));
+
+        let manifest = fs::read_to_string(config.join("manifest.toml")).unwrap();
+        assert!(manifest.starts_with("schema_version = 1\n"));
+        assert!(manifest.contains("# my models"));
+        let dynamic: RegistryFile =
+            toml::from_str(&fs::read_to_string(cache.join("registry.toml")).unwrap()).unwrap();
+        assert_eq!(dynamic.schema_version, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn test_newer_schema_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut registry = temp_registry(&dir);
+        registry.record_model(entry("one", "one.gguf")).unwrap();
+
+        let registry_path = dir.path().join("cache").join("registry.toml");
+        let future = format!("schema_version = {}\nmodels = []\n", SCHEMA_VERSION + 1);
+        fs::write(&registry_path, &future).unwrap();
+
+        let err = ModelRegistry::with_dirs(dir.path().join("config"), dir.path().join("cache"))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("upgrade facecrab"));
+        assert!(registry.record_model(entry("two", "two.gguf")).is_err());
+        assert_eq!(fs::read_to_string(&registry_path).unwrap(), future);
+    }
+
+    #[test]
+    fn test_profiles_survive_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config");
+        fs::create_dir_all(&config).unwrap();
+        fs::write(
+            config.join("manifest.toml"),
+            "schema_version = 1\nmodels = []\n\n[profiles.dev]\nmodels = [\"tiny-model\", \"qwen\"]\n",
+        )
+        .unwrap();
+
+        let mut registry = ModelRegistry::with_dirs(&config, dir.path().join("cache")).unwrap();
+        assert_eq!(registry.list_profiles(), vec!["dev".to_string()]);
+
+        // Profiles hand-written into registry.toml are kept when the registry is rewritten.
+        let registry_path = dir.path().join("cache").join("registry.toml");
+        fs::write(
+            &registry_path,
+            "schema_version = 1\nmodels = []\n\n[profiles.ci]\nmodels = [\"tiny-model\"]\n",
+        )
+        .unwrap();
+        registry.record_model(entry("one", "one.gguf")).unwrap();
+        assert_eq!(
+            registry.profile("ci").unwrap().models,
+            vec!["tiny-model".to_string()]
+        );
+        assert_eq!(registry.profile("dev").unwrap().models.len(), 2);
+        assert!(registry.profile("missing").is_none());
+    }
 }
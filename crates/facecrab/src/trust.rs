@@ -0,0 +1,194 @@
+use anyhow::{anyhow, Context, Result};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rusty_genius_core::GeniusError;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File in the config directory listing the keys `manifest.toml` may be signed with.
+///
+/// ```toml
+/// public_keys = ["<hex-encoded ed25519 public key>"]
+/// strict = true   # refuse manifests without a valid signature
+/// ```
+pub const TRUST_FILE: &str = "trust.toml";
+
+#[derive(Debug, Default, Deserialize)]
+struct TrustFile {
+    #[serde(default)]
+    public_keys: Vec<String>,
+    #[serde(default)]
+    strict: bool,
+}
+
+/// The signing policy for `manifest.toml`, read from [TRUST_FILE].
+#[derive(Debug, Default)]
+pub(crate) struct ManifestTrust {
+    keys: Vec<VerifyingKey>,
+    pub(crate) strict: bool,
+}
+
+impl ManifestTrust {
+    /// Load the policy from `config_dir`. Without a [TRUST_FILE] manifests are not checked.
+    pub(crate) fn load(config_dir: &Path) -> Result<Self> {
+        let path = config_dir.join(TRUST_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        let file: TrustFile = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        let keys = file
+            .public_keys
+            .iter()
+            .map(|key| parse_public_key(key).with_context(|| format!("In {}", path.display())))
+            .collect::<Result<Vec<_>>>()?;
+        if file.strict && keys.is_empty() {
+            return Err(GeniusError::ManifestError(format!(
+                "{} enables strict mode but lists no public_keys",
+                path.display()
+            ))
+            .into());
+        }
+
+        Ok(Self {
+            keys,
+            strict: file.strict,
+        })
+    }
+
+    /// Check `content` (the bytes of `manifest_path`) against its detached signature.
+    ///
+    /// Returns `Ok(true)` when a trusted key signed it and `Ok(false)` when it is unsigned or no
+    /// keys are configured. A signature that doesn't verify is always an error, as is an
+    /// unsigned manifest in strict mode.
+    pub(crate) fn verify(&self, manifest_path: &Path, content: &[u8]) -> Result<bool> {
+        let sig_path = signature_path(manifest_path);
+        if !sig_path.exists() {
+            if self.strict {
+                return Err(GeniusError::ManifestError(format!(
+                    "{} is not signed ({} is missing) and strict mode is enabled in {}",
+                    manifest_path.display(),
+                    sig_path.display(),
+                    TRUST_FILE
+                ))
+                .into());
+            }
+            return Ok(false);
+        }
+        if self.keys.is_empty() {
+            return Ok(false);
+        }
+
+        let encoded = fs::read_to_string(&sig_path)?;
+        let signature = hex::decode(encoded.trim())
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+            .ok_or_else(|| {
+                GeniusError::ManifestError(format!(
+                    "{} is not a hex-encoded ed25519 signature",
+                    sig_path.display()
+                ))
+            })?;
+
+        if self
+            .keys
+            .iter()
+            .any(|key| key.verify_strict(content, &signature).is_ok())
+        {
+            Ok(true)
+        } else {
+            Err(GeniusError::ManifestError(format!(
+                "Signature {} does not match {} for any key in {}",
+                sig_path.display(),
+                manifest_path.display(),
+                TRUST_FILE
+            ))
+            .into())
+        }
+    }
+}
+
+fn parse_public_key(key: &str) -> Result<VerifyingKey> {
+    let bytes: [u8; 32] = hex::decode(key.trim())
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("'{}' is not a hex-encoded ed25519 public key", key))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| anyhow!("Invalid public key '{}': {}", key, e))
+}
+
+/// Where the detached signature of `manifest_path` lives: `manifest.toml.sig`.
+pub fn signature_path(manifest_path: &Path) -> PathBuf {
+    let mut name = manifest_path.as_os_str().to_owned();
+    name.push(".sig");
+    PathBuf::from(name)
+}
+
+/// Sign the manifest at `manifest_path` with an ed25519 secret key, writing the hex-encoded
+/// signature next to it. Returns the signature path.
+///
+/// Sign the exact file you distribute: any later edit, including reformatting, invalidates it.
+pub fn sign_manifest(manifest_path: &Path, secret_key: &[u8; 32]) -> Result<PathBuf> {
+    let content = fs::read(manifest_path)?;
+    let signature = SigningKey::from_bytes(secret_key).sign(&content);
+    let sig_path = signature_path(manifest_path);
+    fs::write(&sig_path, hex::encode(signature.to_bytes()))?;
+    Ok(sig_path)
+}
+
+/// The hex-encoded public key for `secret_key`, as listed in [TRUST_FILE].
+pub fn public_key_hex(secret_key: &[u8; 32]) -> String {
+    hex::encode(
+        SigningKey::from_bytes(secret_key)
+            .verifying_key()
+            .to_bytes(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: [u8; 32] = [7; 32];
+
+    fn trusted_dir(strict: bool) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(TRUST_FILE),
+            format!(
+                "public_keys = [\"{}\"]\nstrict = {}\n",
+                public_key_hex(&SECRET),
+                strict
+            ),
+        )
+        .unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_signed_manifest_verifies() {
+        let dir = trusted_dir(false);
+        let manifest = dir.path().join("manifest.toml");
+        fs::write(&manifest, "models = []\n").unwrap();
+        sign_manifest(&manifest, &SECRET).unwrap();
+
+        let trust = ManifestTrust::load(dir.path()).unwrap();
+        assert!(trust.verify(&manifest, b"models = []\n").unwrap());
+        // Any change to the signed bytes is rejected.
+        assert!(trust.verify(&manifest, b"models = [ ]\n").is_err());
+    }
+
+    #[test]
+    fn test_strict_mode_refuses_unsigned() {
+        let dir = trusted_dir(true);
+        let manifest = dir.path().join("manifest.toml");
+        fs::write(&manifest, "models = []\n").unwrap();
+
+        let trust = ManifestTrust::load(dir.path()).unwrap();
+        assert!(trust.verify(&manifest, b"models = []\n").is_err());
+        assert!(!ManifestTrust::default()
+            .verify(&manifest, b"models = []\n")
+            .unwrap());
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Sidecar index in the cache directory recording when each cached file was last used.
+const USAGE_FILE: &str = "usage.toml";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageFile {
+    /// Seconds since the Unix epoch, by cached file name.
+    #[serde(default)]
+    last_used: BTreeMap<String, u64>,
+}
+
+/// When each file in `cache_dir` was last used. Unreadable indexes count as empty.
+pub(crate) fn load(cache_dir: &Path) -> BTreeMap<String, SystemTime> {
+    read(cache_dir)
+        .last_used
+        .into_iter()
+        .map(|(file, secs)| (file, UNIX_EPOCH + Duration::from_secs(secs)))
+        .collect()
+}
+
+/// Record that `filename` in `cache_dir` was used now.
+pub(crate) fn touch(cache_dir: &Path, filename: &str) -> Result<()> {
+    let lock = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(cache_dir.join("usage.lock"))?;
+    lock.lock()?;
+
+    let mut usage = read(cache_dir);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    usage.last_used.insert(filename.to_string(), now);
+
+    let path = cache_dir.join(USAGE_FILE);
+    let tmp_path = path.with_extension("toml.tmp");
+    fs::write(&tmp_path, toml::to_string(&usage)?)?;
+    fs::rename(&tmp_path, &path)?;
+    drop(lock);
+    Ok(())
+}
+
+fn read(cache_dir: &Path) -> UsageFile {
+    fs::read_to_string(cache_dir.join(USAGE_FILE))
+        .ok()
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_touch_records_time() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(load(dir.path()).is_empty());
+
+        let before = SystemTime::now() - Duration::from_secs(1);
+        touch(dir.path(), "a.gguf").unwrap();
+        touch(dir.path(), "b.gguf").unwrap();
+
+        let usage = load(dir.path());
+        assert_eq!(usage.len(), 2);
+        assert!(usage["a.gguf"] >= before);
+    }
+}
@@ -53,14 +53,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     print!("\r[Asset] Downloading: {:.1}%", pct);
                     let _ = std::io::Write::flush(&mut std::io::stdout());
                 }
+                AssetEvent::Verifying(s) => println!("\n[Asset] Verifying: {}", s),
                 AssetEvent::Complete(s) => println!("\n[Asset] Ready: {}", s),
                 AssetEvent::Error(e) => eprintln!("\n[Asset] Error: {}", e),
+                _ => {}
             },
             BrainstemBody::Event(e) => match e {
                 InferenceEvent::Content(c) => {
                     print!("{}", c);
                     std::io::Write::flush(&mut std::io::stdout())?;
                 }
+                InferenceEvent::Stats(stats) => {
+                    println!("\n[{}]", stats);
+                }
                 InferenceEvent::Complete => {
                     println!("\n--- Inference Complete ---");
                     break;
@@ -77,7 +82,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 eprintln!("\nBrainstem Error: {}", err);
                 break;
             }
-            BrainstemBody::ModelList(_) => {
+            BrainstemBody::Cancelled => {
+                println!("\n--- Inference Cancelled ---");
+                break;
+            }
+            BrainstemBody::Waking => println!("[Loading model...]"),
+            BrainstemBody::Retrying { delay, error, .. } => {
+                println!("[Load failed ({}); retrying in {:?}]", error, delay)
+            }
+            BrainstemBody::Accepted
+            | BrainstemBody::Busy { .. }
+            | BrainstemBody::Batch { .. }
+            | BrainstemBody::AwaitingTools
+            | BrainstemBody::ModelList(_)
+            | BrainstemBody::Health(_)
+            | BrainstemBody::Bench(_)
+            | BrainstemBody::Hibernated
+            | BrainstemBody::Ready
+            | BrainstemBody::Restarted
+            | BrainstemBody::State { .. }
+            | BrainstemBody::Metrics(_) => {
                 // Ignored in this example
             }
         }
@@ -0,0 +1,118 @@
+//! Configuring a [Genius] before it starts.
+
+use crate::Genius;
+use anyhow::Result;
+use rusty_genius_core::engine::Engine;
+use rusty_genius_core::manifest::ModelDefaults;
+use rusty_genius_stem::{CortexStrategy, Orchestrator, DEFAULT_CHANNEL_CAPACITY};
+
+#[cfg(feature = "cortex-engine")]
+use std::path::PathBuf;
+
+/// Builds a [Genius]; every setting left out is as [Genius::new] has it.
+///
+/// ```no_run
+/// # use rusty_genius::Genius;
+/// # use rusty_genius_core::manifest::ModelDefaults;
+/// # use rusty_genius_stem::CortexStrategy;
+/// # async fn build() -> anyhow::Result<()> {
+/// let mut genius = Genius::builder()
+///     .default_model("qwen-2.5-3b-instruct")
+///     .strategy(CortexStrategy::KeepAlive)
+///     .inference_defaults(ModelDefaults {
+///         temperature: Some(0.2),
+///         ..Default::default()
+///     })
+///     .cache_dir("/mnt/models")
+///     .build()
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct GeniusBuilder {
+    engine: Option<Box<dyn Engine>>,
+    strategy: Option<CortexStrategy>,
+    default_model: Option<String>,
+    inference_defaults: ModelDefaults,
+    #[cfg(feature = "cortex-engine")]
+    cache_dir: Option<PathBuf>,
+    channel_capacity: (usize, usize),
+}
+
+impl Default for GeniusBuilder {
+    fn default() -> Self {
+        Self {
+            engine: None,
+            strategy: None,
+            default_model: None,
+            inference_defaults: ModelDefaults::default(),
+            #[cfg(feature = "cortex-engine")]
+            cache_dir: None,
+            channel_capacity: (DEFAULT_CHANNEL_CAPACITY, DEFAULT_CHANNEL_CAPACITY),
+        }
+    }
+}
+
+impl GeniusBuilder {
+    /// Run on `engine` instead of the one the enabled features pick; see
+    /// [OrchestratorBuilder::engine](rusty_genius_stem::OrchestratorBuilder::engine).
+    pub fn engine(mut self, engine: Box<dyn Engine>) -> Self {
+        self.engine = Some(engine);
+        self
+    }
+
+    /// When to unload the idle engine; defaults to after 5 minutes.
+    pub fn strategy(mut self, strategy: CortexStrategy) -> Self {
+        self.strategy = Some(strategy);
+        self
+    }
+
+    /// Run requests that name no model on `model`, rather than the last one loaded or the
+    /// engine's default.
+    pub fn default_model(mut self, model: impl Into<String>) -> Self {
+        self.default_model = Some(model.into());
+        self
+    }
+
+    /// Take the settings requests leave at their defaults from `defaults`, ahead of those a
+    /// model's registry entry gives.
+    pub fn inference_defaults(mut self, defaults: ModelDefaults) -> Self {
+        self.inference_defaults = defaults;
+        self
+    }
+
+    /// Keep downloaded models in `dir` instead of `GENIUS_CACHE` or the default cache.
+    #[cfg(feature = "cortex-engine")]
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Capacities of the channels requests and their outputs are sent on.
+    pub fn channel_capacity(mut self, input: usize, output: usize) -> Self {
+        self.channel_capacity = (input, output);
+        self
+    }
+
+    pub async fn build(self) -> Result<Genius> {
+        let mut builder = Orchestrator::builder()
+            .channel_capacity(self.channel_capacity.0, self.channel_capacity.1);
+        if let Some(engine) = self.engine {
+            builder = builder.engine(engine);
+        }
+        if let Some(strategy) = self.strategy {
+            builder = builder.strategy(strategy);
+        }
+        #[cfg(feature = "cortex-engine")]
+        if let Some(dir) = self.cache_dir {
+            builder = builder
+                .asset_authority(facecrab::AssetAuthority::builder().cache_dir(dir).build()?);
+        }
+        Genius::start(
+            builder.build().await?,
+            self.default_model,
+            self.inference_defaults,
+        )
+        .await
+    }
+}
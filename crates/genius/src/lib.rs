@@ -1,38 +1,107 @@
-use anyhow::Result;
-use async_std::sync::Mutex;
+pub mod builder;
+pub mod stream;
+
+pub use builder::GeniusBuilder;
+pub use stream::{ChatDelta, ChatStream};
+
+use anyhow::{anyhow, Result};
 use futures::channel::mpsc;
+use futures::lock::Mutex;
 use futures::sink::SinkExt;
 use futures::StreamExt;
-use rusty_genius_core::manifest::InferenceConfig;
+use rusty_genius_core::manifest::{InferenceConfig, ModelDefaults};
 use rusty_genius_core::protocol::{
-    BrainstemBody, BrainstemCommand, BrainstemInput, BrainstemOutput, ContextInput, ContextOutput,
-    InferenceEvent,
+    AssetEvent, BrainstemBody, BrainstemCommand, BrainstemInput, BrainstemOutput, ContextInput,
+    ContextOutput, InferenceEvent, InferenceStats, ModelDescriptor, ModelInfo, TokenUsage,
 };
-use rusty_genius_core::InMemoryContextStore;
+pub use rusty_genius_core::GeniusError;
+use rusty_genius_core::{l2_normalize, runtime, InMemoryContextStore};
 use rusty_genius_stem::{ContextWorker, Orchestrator};
+use std::collections::HashMap;
 use std::sync::Arc;
 
+/// Where the outputs of each request in flight go, by request id.
+type Routes = Arc<std::sync::Mutex<HashMap<String, mpsc::UnboundedSender<BrainstemOutput>>>>;
+
+/// A finished generation; see [Genius::generate].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Generation {
+    /// The generated text, without the model's thoughts.
+    pub text: String,
+    /// Token counts and timings, from engines that measure them.
+    pub usage: Option<TokenUsage>,
+    pub stats: Option<InferenceStats>,
+}
+
+/// A model the registry offers, or the one loaded; see [Genius::list_models].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvailableModel {
+    pub name: String,
+    /// e.g. `Inference` or `Embedding`.
+    pub purpose: String,
+    pub tags: Vec<String>,
+    /// Downloaded, so it loads without waiting for the network.
+    pub cached: bool,
+    pub loaded: bool,
+    /// Details of the model, for models that are loaded or downloaded.
+    pub info: Option<ModelInfo>,
+}
+
+impl From<ModelDescriptor> for AvailableModel {
+    fn from(model: ModelDescriptor) -> Self {
+        let has = |tag: &str| model.tags.iter().any(|t| t == tag);
+        Self {
+            cached: has("cached"),
+            loaded: has("loaded"),
+            name: model.id,
+            purpose: model.purpose,
+            tags: model.tags,
+            info: model.info,
+        }
+    }
+}
+
 pub struct Genius {
     input_tx: mpsc::Sender<BrainstemInput>,
-    output_rx: Arc<Mutex<mpsc::Receiver<BrainstemOutput>>>,
+    routes: Routes,
+    /// How many requests were sent, numbering their ids.
+    next_request: u64,
+    /// The model requests that name none run on; `None` leaves it to the orchestrator.
+    default_model: Option<String>,
+    /// Settings requests leave at their defaults take.
+    inference_defaults: ModelDefaults,
     context_tx: mpsc::Sender<ContextInput>,
     context_rx: Arc<Mutex<mpsc::Receiver<ContextOutput>>>,
 }
 
 impl Genius {
     pub async fn new() -> Result<Self> {
-        let (input_tx, input_rx) = mpsc::channel(100);
-        let (output_tx, output_rx) = mpsc::channel(100);
+        Self::builder().build().await
+    }
 
-        let mut orchestrator = Orchestrator::new().await?;
+    /// Configure a [Genius] before it starts.
+    pub fn builder() -> GeniusBuilder {
+        GeniusBuilder::default()
+    }
+
+    /// Run `orchestrator` and a context worker, and send them requests.
+    async fn start(
+        orchestrator: Orchestrator,
+        default_model: Option<String>,
+        inference_defaults: ModelDefaults,
+    ) -> Result<Self> {
+        let (input_tx, output_rx, run) = orchestrator.connect();
 
         // Spawn the brainstem orchestrator
-        async_std::task::spawn(async move {
-            if let Err(e) = orchestrator.run(input_rx, output_tx).await {
+        runtime::spawn(async move {
+            if let Err(e) = run.await {
                 eprintln!("Orchestrator error: {}", e);
             }
         });
 
+        let routes = Routes::default();
+        runtime::spawn(route_outputs(output_rx, routes.clone()));
+
         // Set up context worker
         let (context_tx, context_input_rx) = mpsc::channel(100);
         let (context_output_tx, context_rx) = mpsc::channel(100);
@@ -40,13 +109,16 @@ impl Genius {
         let store: Box<dyn rusty_genius_core::context::ContextStore> = Self::create_store().await?;
         let worker = ContextWorker::new(store);
 
-        async_std::task::spawn(async move {
+        runtime::spawn(async move {
             worker.run(context_input_rx, context_output_tx).await;
         });
 
         Ok(Self {
             input_tx,
-            output_rx: Arc::new(Mutex::new(output_rx)),
+            routes,
+            next_request: 0,
+            default_model,
+            inference_defaults,
             context_tx,
             context_rx: Arc::new(Mutex::new(context_rx)),
         })
@@ -86,115 +158,646 @@ impl Genius {
         Ok(())
     }
 
+    /// Stream the answer to `prompt`: its events up to `Complete`, or an error once it fails.
     pub async fn infer(
         &mut self,
         model: Option<String>,
         prompt: String,
         config: InferenceConfig,
-    ) -> Result<mpsc::Receiver<InferenceEvent>> {
-        let request_id = format!(
-            "facade-chat-{}",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_micros()
-        );
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent, GeniusError>>> {
+        Ok(self.start_infer(model, prompt, config).await?.1)
+    }
 
-        self.input_tx
-            .send(BrainstemInput {
-                id: Some(request_id.clone()),
-                command: BrainstemCommand::Infer {
+    /// Stream the answer to `prompt` as [ChatDelta]s; see [Genius::infer].
+    pub async fn infer_stream(
+        &mut self,
+        model: Option<String>,
+        prompt: String,
+        config: InferenceConfig,
+    ) -> Result<ChatStream> {
+        let (request_id, events) = self.start_infer(model, prompt, config).await?;
+        Ok(ChatStream::for_request(request_id, events))
+    }
+
+    /// Send an `Infer`, returning its id and its events.
+    async fn start_infer(
+        &mut self,
+        model: Option<String>,
+        prompt: String,
+        config: InferenceConfig,
+    ) -> Result<(String, mpsc::Receiver<Result<InferenceEvent, GeniusError>>)> {
+        let (model, config) = self.with_defaults(model, config);
+        let (request_id, outputs) = self
+            .request(
+                "facade-chat",
+                BrainstemCommand::Infer {
                     model,
                     prompt,
                     config,
                 },
-            })
+            )
             .await?;
+        Ok((request_id, Self::events(outputs)))
+    }
 
-        let (mut tx, rx) = mpsc::channel(100);
-        let output_rx = self.output_rx.clone();
-
-        async_std::task::spawn(async move {
-            let mut output_rx = output_rx.lock().await;
-            while let Some(output) = output_rx.next().await {
-                // Ignore events for other request IDs
-                if output.id != Some(request_id.clone()) {
-                    continue;
-                }
+    /// Give `content` as the result of the tool call `call_id` the request `request_id` made;
+    /// it carries on once each of its calls has a result, streaming on as before. See
+    /// [ChatStream::request_id].
+    pub async fn tool_result(
+        &mut self,
+        request_id: impl Into<String>,
+        call_id: impl Into<String>,
+        content: impl Into<String>,
+    ) -> Result<()> {
+        self.input_tx
+            .send(BrainstemInput {
+                id: Some(request_id.into()),
+                command: BrainstemCommand::ToolResult {
+                    call_id: call_id.into(),
+                    content: content.into(),
+                },
+            })
+            .await?;
+        Ok(())
+    }
 
-                match output.body {
-                    BrainstemBody::Event(event) => {
-                        if let InferenceEvent::Complete = event {
-                            let _ = tx.send(event).await;
-                            break;
-                        }
-                        let _ = tx.send(event).await;
-                    }
-                    BrainstemBody::Error(e) => {
-                        eprintln!("Error: {}", e);
-                        break;
-                    }
-                    _ => {}
-                }
+    /// Generate the answer to `prompt` in one go, with its usage, instead of streaming it.
+    pub async fn generate(
+        &mut self,
+        model: Option<String>,
+        prompt: String,
+        config: InferenceConfig,
+    ) -> Result<Generation> {
+        let mut events = self.infer(model, prompt, config).await?;
+        let mut generation = Generation::default();
+        while let Some(event) = events.next().await {
+            match event? {
+                InferenceEvent::Content(text) => generation.text.push_str(&text),
+                InferenceEvent::Usage(usage) => generation.usage = Some(usage),
+                InferenceEvent::Stats(stats) => generation.stats = Some(stats),
+                _ => {}
             }
-        });
+        }
+        Ok(generation)
+    }
 
-        Ok(rx)
+    /// Generate the answer to `prompt` and return its text; see [Genius::generate].
+    pub async fn generate_text(
+        &mut self,
+        model: Option<String>,
+        prompt: String,
+        config: InferenceConfig,
+    ) -> Result<String> {
+        Ok(self.generate(model, prompt, config).await?.text)
     }
 
+    /// Embed `input`: its `Embedding` and `Complete`, or an error once it fails.
     pub async fn embed(
         &mut self,
         model: Option<String>,
         input: String,
         config: InferenceConfig,
-    ) -> Result<mpsc::Receiver<InferenceEvent>> {
-        let request_id = format!(
-            "facade-embed-{}",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_micros()
-        );
-
-        self.input_tx
-            .send(BrainstemInput {
-                id: Some(request_id.clone()),
-                command: BrainstemCommand::Embed {
+    ) -> Result<mpsc::Receiver<Result<InferenceEvent, GeniusError>>> {
+        let (model, config) = self.with_defaults(model, config);
+        let (_, outputs) = self
+            .request(
+                "facade-embed",
+                BrainstemCommand::Embed {
                     model,
                     input,
                     config,
                 },
-            })
+            )
             .await?;
+        Ok(Self::events(outputs))
+    }
 
-        let (mut tx, rx) = mpsc::channel(100);
-        let output_rx = self.output_rx.clone();
-
-        async_std::task::spawn(async move {
-            let mut output_rx = output_rx.lock().await;
-            while let Some(output) = output_rx.next().await {
-                // Ignore events for other request IDs
-                if output.id != Some(request_id.clone()) {
-                    continue;
+    /// Embed `input` and return its vector, scaled to unit length if `normalize`.
+    pub async fn embed_text(
+        &mut self,
+        model: Option<String>,
+        input: impl Into<String>,
+        normalize: bool,
+    ) -> Result<Vec<f32>> {
+        let events = self
+            .embed(model, input.into(), InferenceConfig::default())
+            .await?;
+        collect_embeddings(events, normalize)
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow!("The engine sent no embedding"))
+    }
+
+    /// Embed each of `inputs` in one request, returning their vectors in the same order; see
+    /// [Genius::embed_text].
+    pub async fn embed_texts(
+        &mut self,
+        model: Option<String>,
+        inputs: Vec<String>,
+        normalize: bool,
+    ) -> Result<Vec<Vec<f32>>> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let count = inputs.len();
+        let (model, config) = self.with_defaults(model, InferenceConfig::default());
+        let (_, outputs) = self
+            .request(
+                "facade-embed",
+                BrainstemCommand::EmbedBatch {
+                    model,
+                    inputs,
+                    config,
+                },
+            )
+            .await?;
+        let embeddings = collect_embeddings(Self::events(outputs), normalize).await?;
+        if embeddings.len() != count || embeddings.iter().any(Vec::is_empty) {
+            return Err(anyhow!(
+                "The engine sent {} embeddings for {} inputs",
+                embeddings.iter().filter(|e| !e.is_empty()).count(),
+                count
+            ));
+        }
+        Ok(embeddings)
+    }
+
+    /// Load `model` ahead of the requests for it, so the first doesn't wait for it to download
+    /// and load; resolves once it is loaded. `progress` is told of each of its asset events,
+    /// such as the download's `Progress` and the final `Loaded`.
+    pub async fn load_model(
+        &mut self,
+        model: impl Into<String>,
+        mut progress: Option<&mut (dyn FnMut(&AssetEvent) + Send)>,
+    ) -> Result<()> {
+        let (request_id, mut outputs) = self
+            .request("facade-load", BrainstemCommand::LoadModel(model.into()))
+            .await?;
+        let loaded = loop {
+            let Some(output) = outputs.next().await else {
+                break Err(GeniusError::EngineError(
+                    "The orchestrator stopped before the model loaded".to_string(),
+                ));
+            };
+            match output.body {
+                BrainstemBody::Asset(event) => {
+                    if let Some(progress) = progress.as_mut() {
+                        progress(&event);
+                    }
+                    if let AssetEvent::Loaded { .. } = event {
+                        break Ok(());
+                    }
+                }
+                BrainstemBody::Error(e) => break Err(GeniusError::EngineError(e)),
+                _ => {}
+            }
+        };
+        // Loading ends in `Loaded`, which doesn't end a route, as it may come mid-request
+        self.routes.lock().unwrap().remove(&request_id);
+        Ok(loaded?)
+    }
+
+    /// Unload the model, freeing its memory; the next request loads the default model again.
+    pub async fn unload_model(&mut self) -> Result<()> {
+        let (_, outputs) = self
+            .request("facade-unload", BrainstemCommand::Reset)
+            .await?;
+        let mut events = Self::events(outputs);
+        while let Some(event) = events.next().await {
+            event?;
+        }
+        Ok(())
+    }
+
+    /// The models the registry offers, and the one loaded, with whether each is downloaded or
+    /// loaded.
+    pub async fn list_models(&mut self) -> Result<Vec<AvailableModel>> {
+        let (_, mut outputs) = self
+            .request("facade-models", BrainstemCommand::ListModels)
+            .await?;
+        while let Some(output) = outputs.next().await {
+            match output.body {
+                BrainstemBody::ModelList(models) => {
+                    return Ok(models.into_iter().map(Into::into).collect())
                 }
+                BrainstemBody::Error(e) => return Err(GeniusError::EngineError(e).into()),
+                _ => {}
+            }
+        }
+        Err(anyhow!(
+            "The orchestrator stopped before listing the models"
+        ))
+    }
+
+    /// `model` and `config`, with the defaults the [GeniusBuilder] was given in place of what
+    /// they leave unset.
+    fn with_defaults(
+        &self,
+        model: Option<String>,
+        config: InferenceConfig,
+    ) -> (Option<String>, InferenceConfig) {
+        (
+            model.or_else(|| self.default_model.clone()),
+            self.inference_defaults.apply(config),
+        )
+    }
+
+    /// Send `command` under a new id starting with `prefix`, returning the id and the outputs
+    /// routed to it.
+    async fn request(
+        &mut self,
+        prefix: &str,
+        command: BrainstemCommand,
+    ) -> Result<(String, mpsc::UnboundedReceiver<BrainstemOutput>)> {
+        self.next_request += 1;
+        let request_id = format!("{}-{}", prefix, self.next_request);
+        // Routed before it is sent, so none of its outputs can arrive first
+        let (route, outputs) = mpsc::unbounded();
+        self.routes
+            .lock()
+            .unwrap()
+            .insert(request_id.clone(), route);
+
+        let sent = self
+            .input_tx
+            .send(BrainstemInput {
+                id: Some(request_id.clone()),
+                command,
+            })
+            .await;
+        if let Err(e) = sent {
+            self.routes.lock().unwrap().remove(&request_id);
+            return Err(e.into());
+        }
+        Ok((request_id, outputs))
+    }
+
+    /// The inference events among a request's `outputs`, up to its `Complete` or an error. A
+    /// request that ends any other way ends with an error too, so a stream that ends without
+    /// `Complete` has always failed.
+    fn events(
+        mut outputs: mpsc::UnboundedReceiver<BrainstemOutput>,
+    ) -> mpsc::Receiver<Result<InferenceEvent, GeniusError>> {
+        let (mut tx, rx) = mpsc::channel(100);
 
+        runtime::spawn(async move {
+            let error = loop {
+                let Some(output) = outputs.next().await else {
+                    break GeniusError::EngineError(
+                        "The orchestrator stopped before the request completed".to_string(),
+                    );
+                };
                 match output.body {
                     BrainstemBody::Event(event) => {
                         if let InferenceEvent::Complete = event {
-                            let _ = tx.send(event).await;
-                            break;
+                            let _ = tx.send(Ok(event)).await;
+                            return;
                         }
-                        let _ = tx.send(event).await;
+                        let _ = tx.send(Ok(event)).await;
                     }
-                    BrainstemBody::Error(e) => {
-                        eprintln!("Error: {}", e);
-                        break;
+                    BrainstemBody::Error(e) => break GeniusError::EngineError(e),
+                    BrainstemBody::Cancelled => {
+                        break GeniusError::EngineError("The request was cancelled".to_string())
                     }
                     _ => {}
                 }
+            };
+            let _ = tx.send(Err(error)).await;
+        });
+
+        rx
+    }
+}
+
+/// Wait for the embeddings in `events`, in input order, scaling each to unit length if
+/// `normalize`.
+async fn collect_embeddings(
+    mut events: mpsc::Receiver<Result<InferenceEvent, GeniusError>>,
+    normalize: bool,
+) -> Result<Vec<Vec<f32>>> {
+    let mut embeddings = Vec::new();
+    while let Some(event) = events.next().await {
+        match event? {
+            InferenceEvent::Embedding(embedding) => embeddings.push(embedding),
+            InferenceEvent::IndexedEmbedding { index, embedding } => {
+                if embeddings.len() <= index {
+                    embeddings.resize(index + 1, Vec::new());
+                }
+                embeddings[index] = embedding;
             }
+            _ => {}
+        }
+    }
+    if normalize {
+        embeddings.iter_mut().for_each(|v| l2_normalize(v));
+    }
+    Ok(embeddings)
+}
+
+/// Hand each output to the request it answers, until the orchestrator stops. A request's route
+/// goes once it ends or its receiver is dropped; outputs no request is waiting for, such as
+/// lifecycle broadcasts, are dropped.
+async fn route_outputs(mut output_rx: mpsc::Receiver<BrainstemOutput>, routes: Routes) {
+    while let Some(output) = output_rx.next().await {
+        let Some(id) = output.id.clone() else {
+            continue;
+        };
+        let ends = matches!(
+            output.body,
+            BrainstemBody::Event(InferenceEvent::Complete)
+                | BrainstemBody::ModelList(_)
+                | BrainstemBody::Error(_)
+                | BrainstemBody::Cancelled
+        );
+        let mut routes = routes.lock().unwrap();
+        let delivered = routes
+            .get(&id)
+            .is_some_and(|route| route.unbounded_send(output).is_ok());
+        if ends || !delivered {
+            routes.remove(&id);
+        }
+    }
+    // Ends the requests still waiting
+    routes.lock().unwrap().clear();
+}
+
+// The tests run on async-std, or on tokio when the `tokio` feature is enabled
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(not(feature = "tokio"))]
+    use async_std::test as async_test;
+    #[cfg(feature = "tokio")]
+    use tokio::test as async_test;
+    use async_trait::async_trait;
+    use rusty_genius_core::engine::{CancellationToken, Engine};
+
+    const CALL: &str = "<tool_call>\n{\"name\": \"get_weather\", \"arguments\": {}}\n</tool_call>";
+
+    /// A remote engine answering a prompt with its words, one `Content` each, and failing the
+    /// prompt `fail`. Given tools, it calls `get_weather` until it has the result. Clones share
+    /// what is loaded, so a test can look on.
+    #[derive(Clone, Default)]
+    struct StubEngine {
+        loaded: Arc<std::sync::Mutex<Option<String>>>,
+        loads: Arc<std::sync::Mutex<usize>>,
+    }
+
+    #[async_trait]
+    impl Engine for StubEngine {
+        async fn load_model(&mut self, model_path: &str) -> Result<()> {
+            *self.loaded.lock().unwrap() = Some(model_path.to_string());
+            *self.loads.lock().unwrap() += 1;
+            Ok(())
+        }
+
+        async fn unload_model(&mut self) -> Result<()> {
+            *self.loaded.lock().unwrap() = None;
+            Ok(())
+        }
+
+        fn is_loaded(&self) -> bool {
+            self.loaded.lock().unwrap().is_some()
+        }
+
+        fn is_remote(&self) -> bool {
+            true
+        }
+
+        fn default_model(&self) -> String {
+            "stub".to_string()
+        }
+
+        async fn infer(
+            &mut self,
+            prompt: &str,
+            config: InferenceConfig,
+            _cancel: CancellationToken,
+        ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+            if prompt == "fail" {
+                return Err(anyhow!("The stub failed"));
+            }
+            let answer = match config.tools.is_empty() {
+                true => prompt,
+                false if prompt.contains("</tool_response>") => "It is sunny.",
+                false => CALL,
+            };
+            let words: Vec<&str> = match answer {
+                CALL => vec![CALL],
+                answer => answer.split_whitespace().collect(),
+            };
+            let (mut tx, rx) = mpsc::channel(words.len() + 1);
+            for word in words {
+                tx.send(Ok(InferenceEvent::Content(word.to_string())))
+                    .await?;
+            }
+            tx.send(Ok(InferenceEvent::Complete)).await?;
+            Ok(rx)
+        }
+
+        async fn embed(
+            &mut self,
+            _input: &str,
+            _config: InferenceConfig,
+        ) -> Result<mpsc::Receiver<Result<InferenceEvent>>> {
+            Err(anyhow!("no embeddings"))
+        }
+    }
+
+    async fn start(engine: &StubEngine) -> Genius {
+        Genius::builder()
+            .engine(Box::new(engine.clone()))
+            .build()
+            .await
+            .unwrap()
+    }
+
+    /// The content of `events`, and whether they ended with `Complete`.
+    async fn answer(
+        mut events: mpsc::Receiver<Result<InferenceEvent, GeniusError>>,
+    ) -> (Vec<String>, bool) {
+        let (mut words, mut complete) = (Vec::new(), false);
+        while let Some(event) = events.next().await {
+            complete = false;
+            match event.unwrap() {
+                InferenceEvent::Content(word) => words.push(word),
+                InferenceEvent::Complete => complete = true,
+                _ => {}
+            }
+        }
+        (words, complete)
+    }
+
+    #[async_test]
+    async fn test_requests_get_only_their_own_events() {
+        let engine = StubEngine::default();
+        let mut genius = start(&engine).await;
+        let first = genius
+            .infer(None, "one two".to_string(), InferenceConfig::default())
+            .await
+            .unwrap();
+        let second = genius
+            .infer(
+                None,
+                "three four five".to_string(),
+                InferenceConfig::default(),
+            )
+            .await
+            .unwrap();
+
+        let (first, second) = futures::join!(answer(first), answer(second));
+        assert_eq!(first, (vec!["one".into(), "two".into()], true));
+        assert_eq!(
+            second,
+            (vec!["three".into(), "four".into(), "five".into()], true)
+        );
+        assert!(genius.routes.lock().unwrap().is_empty());
+    }
+
+    #[async_test]
+    async fn test_errors_end_the_stream() {
+        let engine = StubEngine::default();
+        let mut genius = start(&engine).await;
+        let mut events = genius
+            .infer(None, "fail".to_string(), InferenceConfig::default())
+            .await
+            .unwrap();
+
+        match events.next().await {
+            Some(Err(GeniusError::EngineError(e))) => {
+                assert!(e.contains("The stub failed"), "{}", e)
+            }
+            other => panic!("expected an engine error, got {:?}", other),
+        }
+        assert!(events.next().await.is_none());
+
+        // The next request is served as usual
+        let events = genius
+            .infer(None, "fine".to_string(), InferenceConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(answer(events).await, (vec!["fine".into()], true));
+    }
+
+    #[async_test]
+    async fn test_load_model_loads_ahead_of_requests() {
+        let engine = StubEngine::default();
+        let mut genius = start(&engine).await;
+        let mut events = Vec::new();
+        genius
+            .load_model(
+                "warm-model",
+                Some(&mut |event: &AssetEvent| events.push(format!("{:?}", event))),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![r#"Loaded { path: "warm-model", gpu_layers: None }"#]
+        );
+        assert_eq!(engine.loaded.lock().unwrap().as_deref(), Some("warm-model"));
+        assert!(genius.routes.lock().unwrap().is_empty());
+
+        // Served without loading again
+        let events = genius
+            .infer(None, "hello".to_string(), InferenceConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(answer(events).await, (vec!["hello".into()], true));
+        assert_eq!(*engine.loads.lock().unwrap(), 1);
+    }
+
+    #[async_test]
+    async fn test_unload_model_leaves_none_loaded() {
+        let engine = StubEngine::default();
+        let mut genius = start(&engine).await;
+        genius.load_model("warm-model", None).await.unwrap();
+        genius.unload_model().await.unwrap();
+        assert_eq!(*engine.loaded.lock().unwrap(), None);
+
+        // The next request loads the default model again
+        let events = genius
+            .infer(None, "hello".to_string(), InferenceConfig::default())
+            .await
+            .unwrap();
+        assert_eq!(answer(events).await, (vec!["hello".into()], true));
+        assert_eq!(engine.loaded.lock().unwrap().as_deref(), Some("stub"));
+        assert_eq!(*engine.loads.lock().unwrap(), 2);
+    }
+
+    #[async_test]
+    async fn test_list_models_marks_the_loaded_model() {
+        let engine = StubEngine::default();
+        let mut genius = start(&engine).await;
+        assert!(genius
+            .list_models()
+            .await
+            .unwrap()
+            .iter()
+            .all(|m| !m.loaded));
+
+        genius.load_model("warm-model", None).await.unwrap();
+        let models = genius.list_models().await.unwrap();
+        let loaded: Vec<&str> = models
+            .iter()
+            .filter(|m| m.loaded)
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(loaded, vec!["warm-model"]);
+        assert!(genius.routes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_status_tags_set_the_flags() {
+        let model = AvailableModel::from(ModelDescriptor {
+            id: "qwen".to_string(),
+            purpose: "Inference".to_string(),
+            tags: vec!["chat".to_string(), "cached".to_string()],
+            info: None,
         });
+        assert!(model.cached);
+        assert!(!model.loaded);
+        assert_eq!(model.name, "qwen");
+    }
+
+    #[async_test]
+    async fn test_tool_results_carry_the_stream_on() {
+        let engine = StubEngine::default();
+        let mut genius = start(&engine).await;
+        let config = InferenceConfig {
+            tools: vec![rusty_genius_core::manifest::ToolDefinition {
+                name: "get_weather".to_string(),
+                description: None,
+                parameters: Default::default(),
+            }],
+            ..Default::default()
+        };
+        let prompt = "<|im_start|>user\nWeather?<|im_end|>\n<|im_start|>assistant\n";
+        let mut stream = genius
+            .infer_stream(None, prompt.to_string(), config)
+            .await
+            .unwrap();
 
-        Ok(rx)
+        let call = match stream.next().await {
+            Some(ChatDelta::ToolCall(call)) => call,
+            other => panic!("expected a tool call, got {:?}", other),
+        };
+        assert_eq!(call.name, "get_weather");
+        let request_id = stream.request_id().unwrap().to_string();
+        genius
+            .tool_result(request_id, call.id, "Sunny, 21C")
+            .await
+            .unwrap();
+
+        let rest: Vec<String> = stream.map(|delta| format!("{:?}", delta)).collect().await;
+        assert_eq!(
+            rest,
+            vec![
+                r#"Content("It")"#,
+                r#"Content("is")"#,
+                r#"Content("sunny.")"#,
+                "Done { usage: None, stats: None }"
+            ]
+        );
     }
 }
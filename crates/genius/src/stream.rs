@@ -0,0 +1,191 @@
+//! A typed view of an answer as it streams, for callers that would rather not match the
+//! protocol's events.
+
+use futures::channel::mpsc;
+use futures::stream::{Stream, StreamExt};
+use futures::task::{Context, Poll};
+use rusty_genius_core::protocol::{
+    InferenceEvent, InferenceStats, ThoughtEvent, TokenUsage, ToolCall,
+};
+use rusty_genius_core::GeniusError;
+use std::pin::Pin;
+
+/// A piece of an answer; see [ChatStream].
+#[derive(Debug)]
+pub enum ChatDelta {
+    /// More of the answer's text.
+    Content(String),
+    /// More of the model's thinking, before its answer.
+    Thought(String),
+    /// The model called a tool; the request waits for its result, given with
+    /// [Genius::tool_result](crate::Genius::tool_result), before carrying on.
+    ToolCall(ToolCall),
+    /// The answer is finished, with its token counts and timings from engines that measure
+    /// them.
+    Done {
+        usage: Option<TokenUsage>,
+        stats: Option<InferenceStats>,
+    },
+    /// The request failed.
+    Error(GeniusError),
+}
+
+/// The answer to a request as [ChatDelta]s, ending after `Done` or an `Error`. Events with no
+/// delta of their own, such as log probabilities, are left out.
+pub struct ChatStream {
+    events: mpsc::Receiver<Result<InferenceEvent, GeniusError>>,
+    request_id: Option<String>,
+    /// Held for `Done`, as they come before `Complete`.
+    usage: Option<TokenUsage>,
+    stats: Option<InferenceStats>,
+    finished: bool,
+}
+
+impl ChatStream {
+    /// Wrap the events of [Genius::infer](crate::Genius::infer).
+    pub fn new(events: mpsc::Receiver<Result<InferenceEvent, GeniusError>>) -> Self {
+        Self {
+            events,
+            request_id: None,
+            usage: None,
+            stats: None,
+            finished: false,
+        }
+    }
+
+    pub(crate) fn for_request(
+        request_id: String,
+        events: mpsc::Receiver<Result<InferenceEvent, GeniusError>>,
+    ) -> Self {
+        Self {
+            request_id: Some(request_id),
+            ..Self::new(events)
+        }
+    }
+
+    /// The id of the request, to answer its tool calls with; streams from
+    /// [Genius::infer_stream](crate::Genius::infer_stream) have one.
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_id.as_deref()
+    }
+}
+
+impl Stream for ChatStream {
+    type Item = ChatDelta;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ChatDelta>> {
+        let this = &mut *self;
+        while !this.finished {
+            let event = match this.events.poll_next_unpin(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Ok(event))) => event,
+                Poll::Ready(Some(Err(e))) => {
+                    this.finished = true;
+                    return Poll::Ready(Some(ChatDelta::Error(e)));
+                }
+                Poll::Ready(None) => break,
+            };
+            let delta = match event {
+                InferenceEvent::Content(text) => ChatDelta::Content(text),
+                InferenceEvent::Thought(ThoughtEvent::Delta(text)) => ChatDelta::Thought(text),
+                InferenceEvent::ToolCall(call) => ChatDelta::ToolCall(call),
+                InferenceEvent::Usage(usage) => {
+                    this.usage = Some(usage);
+                    continue;
+                }
+                InferenceEvent::Stats(stats) => {
+                    this.stats = Some(stats);
+                    continue;
+                }
+                InferenceEvent::Complete => {
+                    this.finished = true;
+                    ChatDelta::Done {
+                        usage: this.usage.take(),
+                        stats: this.stats.take(),
+                    }
+                }
+                _ => continue,
+            };
+            return Poll::Ready(Some(delta));
+        }
+        this.finished = true;
+        Poll::Ready(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::sink::SinkExt;
+
+    /// The deltas a stream of `events` gives.
+    fn deltas(events: Vec<Result<InferenceEvent, GeniusError>>) -> Vec<String> {
+        block_on(async {
+            let (mut tx, rx) = mpsc::channel(events.len());
+            for event in events {
+                tx.send(event).await.unwrap();
+            }
+            drop(tx);
+            ChatStream::new(rx)
+                .map(|delta| format!("{:?}", delta))
+                .collect()
+                .await
+        })
+    }
+
+    #[test]
+    fn test_events_map_to_deltas() {
+        let usage = TokenUsage {
+            prompt_tokens: 3,
+            completion_tokens: 2,
+            ..Default::default()
+        };
+        let call = ToolCall {
+            id: "call_0".to_string(),
+            name: "get_weather".to_string(),
+            arguments: Default::default(),
+        };
+        let events = vec![
+            InferenceEvent::ProcessStart,
+            InferenceEvent::Thought(ThoughtEvent::Start),
+            InferenceEvent::Thought(ThoughtEvent::Delta("hmm".to_string())),
+            InferenceEvent::Thought(ThoughtEvent::Stop),
+            InferenceEvent::Content("Hi".to_string()),
+            InferenceEvent::ToolCall(call.clone()),
+            InferenceEvent::Usage(usage.clone()),
+            InferenceEvent::Complete,
+        ];
+        assert_eq!(
+            deltas(events.into_iter().map(Ok).collect()),
+            vec![
+                format!("{:?}", ChatDelta::Thought("hmm".to_string())),
+                format!("{:?}", ChatDelta::Content("Hi".to_string())),
+                format!("{:?}", ChatDelta::ToolCall(call)),
+                format!(
+                    "{:?}",
+                    ChatDelta::Done {
+                        usage: Some(usage),
+                        stats: None
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stream_ends_at_an_error() {
+        let events = vec![
+            Ok(InferenceEvent::Content("Hi".to_string())),
+            Err(GeniusError::EngineError("The engine crashed".to_string())),
+            Ok(InferenceEvent::Content("never".to_string())),
+        ];
+        assert_eq!(
+            deltas(events),
+            vec![
+                r#"Content("Hi")"#.to_string(),
+                r#"Error(EngineError("The engine crashed"))"#.to_string(),
+            ]
+        );
+    }
+}
@@ -1,11 +1,14 @@
 use futures::channel::mpsc;
 use futures::sink::SinkExt;
 use futures::StreamExt;
+use rusty_genius_core::manifest::ToolDefinition;
 use rusty_genius_core::protocol::{
     BrainstemBody, BrainstemCommand, BrainstemInput, BrainstemOutput, ContextBody, ContextCommand,
-    ContextInput, ContextOutput, InferenceConfig, InferenceEvent,
+    ContextInput, ContextOutput, CortexStrategy, EngineHealth, ImageInput, InferenceConfig,
+    InferenceEvent, ModelDescriptor, ModelInfo, TokenLogprob, TokenUsage, ToolCall,
+    TranscriptionConfig, IMAGE_MARKER,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::sync::Arc;
 use tide::{Body, Request, Response, StatusCode};
 
@@ -14,6 +17,27 @@ pub struct ModelResponse {
     pub id: String,
     pub object: String,
     pub purpose: String,
+    pub tags: Vec<String>,
+    /// Architecture, size and so on, for models that are loaded or downloaded.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub info: Option<ModelInfo>,
+}
+
+impl From<ModelDescriptor> for ModelResponse {
+    fn from(desc: ModelDescriptor) -> Self {
+        Self {
+            id: desc.id,
+            object: "model".to_string(),
+            purpose: desc.purpose,
+            tags: desc.tags,
+            info: desc.info,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct ModelQuery {
+    pub tag: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -24,30 +48,197 @@ pub struct ModelList {
 
 #[derive(Deserialize)]
 pub struct ChatMessage {
-    #[allow(dead_code)]
     pub role: String,
-    pub content: String,
+    /// Empty for an assistant message that only made tool calls.
+    #[serde(default, deserialize_with = "null_as_empty")]
+    pub content: MessageContent,
+    /// For a `tool` message, the call it gives the result of.
+    #[serde(default)]
+    pub tool_call_id: Option<String>,
+}
+
+fn null_as_empty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<MessageContent, D::Error> {
+    Ok(Option::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// A message's `content`: plain text, or a list of text and image parts.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: ImageUrl },
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ImageUrl {
+    pub url: String,
+}
+
+impl Default for MessageContent {
+    fn default() -> Self {
+        MessageContent::Text(String::new())
+    }
+}
+
+impl MessageContent {
+    /// The text of every text part.
+    pub fn text(&self) -> String {
+        match self {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    ContentPart::ImageUrl { .. } => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// The prompt, with an [IMAGE_MARKER] where each image was, and the images in order. Images
+    /// have to be `data:` URLs; the server doesn't fetch any.
+    pub fn to_prompt(&self) -> Result<(String, Vec<ImageInput>), String> {
+        let MessageContent::Parts(parts) = self else {
+            return Ok((self.text(), Vec::new()));
+        };
+        let mut prompt = String::new();
+        let mut images = Vec::new();
+        for part in parts {
+            match part {
+                ContentPart::Text { text } => prompt.push_str(text),
+                ContentPart::ImageUrl { image_url } => {
+                    let image = ImageInput::from_data_url(&image_url.url).ok_or_else(|| {
+                        "Only base64 data: URLs are supported for images".to_string()
+                    })?;
+                    prompt.push_str(IMAGE_MARKER);
+                    images.push(image);
+                }
+            }
+        }
+        Ok((prompt, images))
+    }
 }
 
 #[derive(Deserialize)]
 pub struct ChatCompletionRequest {
     pub model: String,
     pub messages: Vec<ChatMessage>,
+    #[serde(default)]
+    pub response_format: Option<ResponseFormat>,
+    /// Return the log probability of each generated token.
+    #[serde(default)]
+    pub logprobs: bool,
+    /// With `logprobs`, how many most likely alternatives to list per token.
+    #[serde(default)]
+    pub top_logprobs: Option<u32>,
+    /// Functions the model may call instead of answering.
+    #[serde(default)]
+    pub tools: Vec<ChatTool>,
+}
+
+/// An entry of OpenAI's `tools`; only functions are offered.
+#[derive(Deserialize)]
+pub struct ChatTool {
+    pub function: ToolDefinition,
+}
+
+/// OpenAI's `response_format`: plain text, any JSON object, or JSON matching a schema.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: JsonSchemaFormat },
+}
+
+#[derive(Deserialize)]
+pub struct JsonSchemaFormat {
+    #[allow(dead_code)]
+    pub name: Option<String>,
+    pub schema: serde_json::Value,
+}
+
+impl ResponseFormat {
+    /// The schema to constrain the output with, if any.
+    fn into_schema(self) -> Option<serde_json::Value> {
+        match self {
+            ResponseFormat::Text => None,
+            ResponseFormat::JsonObject => Some(serde_json::json!({ "type": "object" })),
+            ResponseFormat::JsonSchema { json_schema } => Some(json_schema.schema),
+        }
+    }
 }
 
 #[derive(Serialize)]
 pub struct ChatMessageOut {
     pub role: String,
-    pub content: String,
+    /// `None` when the model only called tools.
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCallOut>,
+}
+
+/// A call the model made, the way OpenAI's `tool_calls` list it.
+#[derive(Serialize)]
+pub struct ToolCallOut {
+    /// Names the request waiting on the call as well as the call, as its result comes back in
+    /// a request of its own; see [tool_call_id].
+    pub id: String,
+    #[serde(rename = "type")]
+    pub call_type: String,
+    pub function: FunctionCallOut,
+}
+
+#[derive(Serialize)]
+pub struct FunctionCallOut {
+    pub name: String,
+    /// The arguments as a JSON string, as OpenAI sends them.
+    pub arguments: String,
+}
+
+impl ToolCallOut {
+    pub fn new(request_id: &str, call: ToolCall) -> Self {
+        Self {
+            id: tool_call_id(request_id, &call.id),
+            call_type: "function".to_string(),
+            function: FunctionCallOut {
+                name: call.name,
+                arguments: call.arguments.to_string(),
+            },
+        }
+    }
+}
+
+/// The id a call `call_id` of the request `request_id` is given over HTTP.
+pub fn tool_call_id(request_id: &str, call_id: &str) -> String {
+    format!("{}/{}", request_id, call_id)
+}
+
+/// The request and call a `tool` message's `tool_call_id` answers, as [tool_call_id] made it.
+pub fn split_tool_call_id(id: &str) -> Option<(&str, &str)> {
+    id.split_once('/')
 }
 
 #[derive(Serialize)]
 pub struct ChatChoice {
     pub index: usize,
     pub message: ChatMessageOut,
+    pub logprobs: Option<ChoiceLogprobs>,
     pub finish_reason: String,
 }
 
+#[derive(Serialize)]
+pub struct ChoiceLogprobs {
+    pub content: Vec<TokenLogprob>,
+}
+
 #[derive(Serialize)]
 pub struct ChatCompletionResponse {
     pub id: String,
@@ -55,12 +246,40 @@ pub struct ChatCompletionResponse {
     pub created: u64,
     pub model: String,
     pub choices: Vec<ChatChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<CompletionUsage>,
+}
+
+/// OpenAI's `usage` object.
+#[derive(Serialize)]
+pub struct CompletionUsage {
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub total_tokens: usize,
+}
+
+impl From<TokenUsage> for CompletionUsage {
+    fn from(usage: TokenUsage) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.prompt_tokens + usage.completion_tokens,
+        }
+    }
 }
 
 #[derive(Deserialize)]
 pub struct EmbeddingRequest {
     pub model: String,
-    pub input: String,
+    pub input: EmbeddingInput,
+}
+
+/// `input` of an embeddings request: one string or an array of them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingInput {
+    Single(String),
+    Batch(Vec<String>),
 }
 
 #[derive(Serialize)]
@@ -77,6 +296,60 @@ pub struct EmbeddingResponse {
     pub model: String,
 }
 
+#[derive(Deserialize)]
+pub struct RerankRequest {
+    pub model: String,
+    pub query: String,
+    pub documents: Vec<String>,
+    /// Return only the most relevant `top_n` documents.
+    pub top_n: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct RerankResult {
+    pub index: usize,
+    pub relevance_score: f32,
+}
+
+#[derive(Serialize)]
+pub struct RerankResponse {
+    pub model: String,
+    /// Most relevant first.
+    pub results: Vec<RerankResult>,
+}
+
+/// One field of a `multipart/form-data` body.
+#[derive(Debug, PartialEq)]
+pub struct FormPart {
+    pub name: String,
+    pub filename: Option<String>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize)]
+pub struct TranscriptionResponse {
+    pub text: String,
+}
+
+#[derive(Serialize)]
+pub struct TranscriptionSegment {
+    pub id: usize,
+    /// Seconds into the audio.
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+#[derive(Serialize)]
+pub struct VerboseTranscriptionResponse {
+    pub task: String,
+    pub language: Option<String>,
+    /// Seconds of audio.
+    pub duration: f64,
+    pub text: String,
+    pub segments: Vec<TranscriptionSegment>,
+}
+
 #[derive(Serialize)]
 pub struct ApiConfig {
     pub ws_addr: String,
@@ -116,10 +389,8 @@ pub struct ApiState {
     pub ws_addr: String,
 }
 
-pub async fn list_models(req: Request<ApiState>) -> tide::Result {
-    eprintln!("DEBUG: list_models entry");
-    let state = req.state();
-
+/// Ask the orchestrator for the models it knows.
+async fn fetch_models(state: &ApiState) -> tide::Result<Vec<ModelDescriptor>> {
     let request_id = format!(
         "api-list-{}",
         std::time::SystemTime::now()
@@ -166,13 +437,21 @@ pub async fn list_models(req: Request<ApiState>) -> tide::Result {
         }
     }
 
-    let models = models_vec
+    Ok(models_vec)
+}
+
+pub async fn list_models(req: Request<ApiState>) -> tide::Result {
+    eprintln!("DEBUG: list_models entry");
+    let query: ModelQuery = req.query().unwrap_or_default();
+
+    let models = fetch_models(req.state())
+        .await?
         .into_iter()
-        .map(|desc| ModelResponse {
-            id: desc.id,
-            object: "model".to_string(),
-            purpose: desc.purpose,
+        .filter(|desc| match &query.tag {
+            Some(tag) => desc.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+            None => true,
         })
+        .map(ModelResponse::from)
         .collect();
 
     let resp = ModelList {
@@ -184,29 +463,96 @@ pub async fn list_models(req: Request<ApiState>) -> tide::Result {
         .build())
 }
 
+/// `GET /v1/models/{id}`: one model, with its metadata when it is loaded or downloaded.
+pub async fn get_model(req: Request<ApiState>) -> tide::Result {
+    let id = req.param("id")?.to_string();
+
+    let model = fetch_models(req.state())
+        .await?
+        .into_iter()
+        .find(|desc| desc.id == id)
+        .ok_or_else(|| tide::Error::from_str(404, format!("Model {} not found", id)))?;
+
+    Ok(Response::builder(StatusCode::Ok)
+        .body(Body::from_json(&ModelResponse::from(model))?)
+        .build())
+}
+
 pub async fn chat_completions(mut req: Request<ApiState>) -> tide::Result {
     eprintln!("DEBUG: chat_completions entry");
     let body: ChatCompletionRequest = req.body_json().await?;
     eprintln!("DEBUG: chat_completions body parsed");
     let state = req.state();
 
-    let prompt = body
+    // `tool` messages closing the conversation answer the calls of a request that waits on
+    // them; it carries on rather than a new one starting
+    let results: Vec<&ChatMessage> = body
         .messages
-        .last()
-        .map(|m| m.content.clone())
-        .unwrap_or_default();
-
-    let request_id = format!(
-        "api-chat-{}",
-        std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_micros()
-    );
-    eprintln!(
-        "DEBUG: chat_completions [{}] prompt: {}",
-        request_id, prompt
-    );
+        .iter()
+        .rev()
+        .take_while(|m| m.role == "tool")
+        .collect();
+    let mut inputs = Vec::new();
+    for message in results.into_iter().rev() {
+        let (request_id, call_id) = message
+            .tool_call_id
+            .as_deref()
+            .and_then(split_tool_call_id)
+            .ok_or_else(|| tide::Error::from_str(400, "Unknown tool_call_id"))?;
+        inputs.push(BrainstemInput {
+            id: Some(request_id.to_string()),
+            command: BrainstemCommand::ToolResult {
+                call_id: call_id.to_string(),
+                content: message.content.text(),
+            },
+        });
+    }
+    let request_id = match inputs.first() {
+        Some(input) => input.id.clone().unwrap_or_default(),
+        None => format!(
+            "api-chat-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_micros()
+        ),
+    };
+    if inputs
+        .iter()
+        .any(|input| input.id.as_ref() != Some(&request_id))
+    {
+        return Err(tide::Error::from_str(
+            400,
+            "Tool results have to answer the calls of one request",
+        ));
+    }
+    if inputs.is_empty() {
+        let (prompt, images) = body
+            .messages
+            .last()
+            .map(|m| m.content.to_prompt())
+            .transpose()
+            .map_err(|e| tide::Error::from_str(400, e))?
+            .unwrap_or_default();
+        eprintln!(
+            "DEBUG: chat_completions [{}] prompt: {}",
+            request_id, prompt
+        );
+        inputs.push(BrainstemInput {
+            id: Some(request_id.clone()),
+            command: BrainstemCommand::Infer {
+                model: Some(body.model.clone()),
+                prompt,
+                config: InferenceConfig {
+                    json_schema: body.response_format.and_then(ResponseFormat::into_schema),
+                    logprobs: body.logprobs.then(|| body.top_logprobs.unwrap_or(0)),
+                    images,
+                    tools: body.tools.into_iter().map(|tool| tool.function).collect(),
+                    ..Default::default()
+                },
+            },
+        });
+    }
 
     let mut input_tx = state.input_tx.clone();
     let (tx, mut rx) = mpsc::channel(100);
@@ -216,19 +562,17 @@ pub async fn chat_completions(mut req: Request<ApiState>) -> tide::Result {
         senders.push(tx);
     }
 
-    input_tx
-        .send(BrainstemInput {
-            id: Some(request_id.clone()),
-            command: BrainstemCommand::Infer {
-                model: Some(body.model.clone()),
-                prompt,
-                config: InferenceConfig::default(),
-            },
-        })
-        .await
-        .map_err(|e| tide::Error::from_str(500, e))?;
+    for input in inputs {
+        input_tx
+            .send(input)
+            .await
+            .map_err(|e| tide::Error::from_str(500, e))?;
+    }
 
     let mut full_content = String::new();
+    let mut tool_calls = Vec::new();
+    let mut logprobs = Vec::new();
+    let mut usage = None;
     let timeout = std::time::Duration::from_secs(30);
 
     while let Ok(msg_opt) = async_std::future::timeout(timeout, rx.next()).await {
@@ -243,6 +587,20 @@ pub async fn chat_completions(mut req: Request<ApiState>) -> tide::Result {
                         eprintln!("DEBUG: [{}] received Content", request_id);
                         full_content.push_str(&c);
                     }
+                    BrainstemBody::Event(InferenceEvent::Logprob(logprob)) => {
+                        logprobs.push(logprob);
+                    }
+                    BrainstemBody::Event(InferenceEvent::Usage(u)) => {
+                        usage = Some(CompletionUsage::from(u));
+                    }
+                    BrainstemBody::Event(InferenceEvent::Stats(stats)) => {
+                        eprintln!("NOTICE: [{}] {}", request_id, stats);
+                    }
+                    BrainstemBody::Event(InferenceEvent::ToolCall(call)) => {
+                        tool_calls.push(ToolCallOut::new(&request_id, call));
+                    }
+                    // It waits for the results, which come in the client's next request
+                    BrainstemBody::AwaitingTools => break,
                     BrainstemBody::Event(InferenceEvent::Complete) => {
                         eprintln!("DEBUG: [{}] received Complete", request_id);
                         break;
@@ -258,6 +616,10 @@ pub async fn chat_completions(mut req: Request<ApiState>) -> tide::Result {
         }
     }
 
+    let finish_reason = match tool_calls.is_empty() {
+        true => "stop",
+        false => "tool_calls",
+    };
     let response = ChatCompletionResponse {
         id: format!("gen-{}", request_id),
         object: "chat.completion".to_string(),
@@ -270,10 +632,16 @@ pub async fn chat_completions(mut req: Request<ApiState>) -> tide::Result {
             index: 0,
             message: ChatMessageOut {
                 role: "assistant".to_string(),
-                content: full_content,
+                content: (tool_calls.is_empty() || !full_content.is_empty())
+                    .then_some(full_content),
+                tool_calls,
             },
-            finish_reason: "stop".to_string(),
+            logprobs: body
+                .logprobs
+                .then_some(ChoiceLogprobs { content: logprobs }),
+            finish_reason: finish_reason.to_string(),
         }],
+        usage,
     };
 
     Ok(Response::builder(StatusCode::Ok)
@@ -294,8 +662,28 @@ pub async fn embeddings(mut req: Request<ApiState>) -> tide::Result {
             .unwrap()
             .as_micros()
     );
-    let input = body.input.clone();
-    eprintln!("DEBUG: embeddings [{}] request for: {}", request_id, input);
+    let command = match body.input {
+        EmbeddingInput::Single(input) => {
+            eprintln!("DEBUG: embeddings [{}] request for: {}", request_id, input);
+            BrainstemCommand::Embed {
+                model: Some(body.model.clone()),
+                input,
+                config: InferenceConfig::default(),
+            }
+        }
+        EmbeddingInput::Batch(inputs) => {
+            eprintln!(
+                "DEBUG: embeddings [{}] request for {} inputs",
+                request_id,
+                inputs.len()
+            );
+            BrainstemCommand::EmbedBatch {
+                model: Some(body.model.clone()),
+                inputs,
+                config: InferenceConfig::default(),
+            }
+        }
+    };
 
     let mut input_tx = state.input_tx.clone();
     let (tx, mut rx) = mpsc::channel(100);
@@ -308,16 +696,12 @@ pub async fn embeddings(mut req: Request<ApiState>) -> tide::Result {
     input_tx
         .send(BrainstemInput {
             id: Some(request_id.clone()),
-            command: BrainstemCommand::Embed {
-                model: Some(body.model.clone()),
-                input,
-                config: InferenceConfig::default(),
-            },
+            command,
         })
         .await
         .map_err(|e| tide::Error::from_str(500, e))?;
 
-    let mut embedding_vec: Option<Vec<f32>> = None;
+    let mut data = Vec::new();
     let timeout = std::time::Duration::from_secs(60);
 
     while let Ok(msg_opt) = async_std::future::timeout(timeout, rx.next()).await {
@@ -330,7 +714,18 @@ pub async fn embeddings(mut req: Request<ApiState>) -> tide::Result {
                 match output.body {
                     BrainstemBody::Event(InferenceEvent::Embedding(emb)) => {
                         eprintln!("DEBUG: [{}] received Embedding", request_id);
-                        embedding_vec = Some(emb);
+                        data.push(EmbeddingData {
+                            object: "embedding".to_string(),
+                            embedding: emb,
+                            index: 0,
+                        });
+                    }
+                    BrainstemBody::Event(InferenceEvent::IndexedEmbedding { index, embedding }) => {
+                        data.push(EmbeddingData {
+                            object: "embedding".to_string(),
+                            embedding,
+                            index,
+                        });
                     }
                     BrainstemBody::Event(InferenceEvent::Complete) => {
                         eprintln!("DEBUG: [{}] received Complete", request_id);
@@ -347,14 +742,11 @@ pub async fn embeddings(mut req: Request<ApiState>) -> tide::Result {
         }
     }
 
-    if let Some(vec) = embedding_vec {
+    if !data.is_empty() {
+        data.sort_by_key(|d| d.index);
         let response = EmbeddingResponse {
             object: "list".to_string(),
-            data: vec![EmbeddingData {
-                object: "embedding".to_string(),
-                embedding: vec,
-                index: 0,
-            }],
+            data,
             model: body.model,
         };
         Ok(Response::builder(StatusCode::Ok)
@@ -367,6 +759,249 @@ pub async fn embeddings(mut req: Request<ApiState>) -> tide::Result {
     }
 }
 
+pub async fn rerank(mut req: Request<ApiState>) -> tide::Result {
+    let body: RerankRequest = req.body_json().await?;
+    let state = req.state();
+
+    let request_id = format!(
+        "api-rerank-{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_micros()
+    );
+
+    let mut input_tx = state.input_tx.clone();
+    let (tx, mut rx) = mpsc::channel(100);
+
+    {
+        let mut senders = state.output_senders.lock().await;
+        senders.push(tx);
+    }
+
+    input_tx
+        .send(BrainstemInput {
+            id: Some(request_id.clone()),
+            command: BrainstemCommand::Rerank {
+                model: Some(body.model.clone()),
+                query: body.query,
+                documents: body.documents,
+                config: InferenceConfig::default(),
+            },
+        })
+        .await
+        .map_err(|e| tide::Error::from_str(500, e))?;
+
+    let mut results = Vec::new();
+    let timeout = std::time::Duration::from_secs(60);
+
+    while let Ok(Some(output)) = async_std::future::timeout(timeout, rx.next()).await {
+        if output.id.as_ref() != Some(&request_id) {
+            continue;
+        }
+        match output.body {
+            BrainstemBody::Event(InferenceEvent::RerankScore { index, score }) => {
+                results.push(RerankResult {
+                    index,
+                    relevance_score: score,
+                });
+            }
+            BrainstemBody::Event(InferenceEvent::Complete) => break,
+            BrainstemBody::Error(e) => {
+                return Err(tide::Error::from_str(500, e));
+            }
+            _ => {}
+        }
+    }
+
+    results.sort_by(|a, b| b.relevance_score.total_cmp(&a.relevance_score));
+    if let Some(top_n) = body.top_n {
+        results.truncate(top_n);
+    }
+
+    let response = RerankResponse {
+        model: body.model,
+        results,
+    };
+    Ok(Response::builder(StatusCode::Ok)
+        .body(Body::from_json(&response)?)
+        .build())
+}
+
+/// Split a `multipart/form-data` body into its fields.
+pub fn parse_multipart(body: &[u8], boundary: &str) -> Vec<FormPart> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+    let mut rest = match find(body, &delimiter) {
+        Some(start) => &body[start + delimiter.len()..],
+        None => return parts,
+    };
+
+    // Each part runs from "\r\n" after a delimiter to "\r\n" before the next; "--" after a
+    // delimiter ends the body.
+    while let Some(part) = rest.strip_prefix(b"\r\n") {
+        let Some(end) = find(part, &delimiter) else {
+            break;
+        };
+        let content = part[..end].strip_suffix(b"\r\n").unwrap_or(&part[..end]);
+        rest = &part[end + delimiter.len()..];
+
+        let Some(header_end) = find(content, b"\r\n\r\n") else {
+            continue;
+        };
+        let headers = String::from_utf8_lossy(&content[..header_end]);
+        let disposition = headers
+            .lines()
+            .find(|line| {
+                line.to_ascii_lowercase()
+                    .starts_with("content-disposition:")
+            })
+            .unwrap_or_default();
+        let Some(name) = disposition_param(disposition, "name") else {
+            continue;
+        };
+        parts.push(FormPart {
+            name,
+            filename: disposition_param(disposition, "filename"),
+            data: content[header_end + 4..].to_vec(),
+        });
+    }
+    parts
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// The value of `param="..."` in a `Content-Disposition` header.
+fn disposition_param(header: &str, param: &str) -> Option<String> {
+    header.split(';').skip(1).find_map(|field| {
+        let (key, value) = field.trim().split_once('=')?;
+        (key == param).then(|| value.trim_matches('"').to_string())
+    })
+}
+
+/// OpenAI-compatible `/v1/audio/transcriptions`: a multipart form with the WAV `file` and
+/// optional `model`, `language`, `prompt`, `temperature` and `response_format` (`json`,
+/// `text` or `verbose_json`).
+pub async fn transcriptions(mut req: Request<ApiState>) -> tide::Result {
+    let boundary = req
+        .content_type()
+        .and_then(|mime| mime.param("boundary").map(|b| b.to_string()))
+        .ok_or_else(|| tide::Error::from_str(400, "Expected a multipart/form-data body"))?;
+    let body = req.body_bytes().await?;
+    let state = req.state();
+
+    let mut audio = None;
+    let mut model = None;
+    let mut response_format = "json".to_string();
+    let mut config = TranscriptionConfig::default();
+    for part in parse_multipart(&body, &boundary) {
+        let text = || String::from_utf8_lossy(&part.data).trim().to_string();
+        match part.name.as_str() {
+            "file" => audio = Some(part.data),
+            "model" => model = Some(text()),
+            "language" => config.language = Some(text()),
+            "prompt" => config.prompt = Some(text()),
+            "temperature" => config.temperature = text().parse().unwrap_or_default(),
+            "response_format" => response_format = text(),
+            _ => {}
+        }
+    }
+    let audio = audio.ok_or_else(|| tide::Error::from_str(400, "Missing `file` field"))?;
+    if !matches!(response_format.as_str(), "json" | "text" | "verbose_json") {
+        return Err(tide::Error::from_str(
+            400,
+            format!("Unsupported response_format: {}", response_format),
+        ));
+    }
+    let language = config.language.clone();
+
+    let request_id = format!(
+        "api-transcribe-{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_micros()
+    );
+
+    let mut input_tx = state.input_tx.clone();
+    let (tx, mut rx) = mpsc::channel(100);
+
+    {
+        let mut senders = state.output_senders.lock().await;
+        senders.push(tx);
+    }
+
+    input_tx
+        .send(BrainstemInput {
+            id: Some(request_id.clone()),
+            command: BrainstemCommand::Transcribe {
+                model,
+                audio,
+                config,
+            },
+        })
+        .await
+        .map_err(|e| tide::Error::from_str(500, e))?;
+
+    let mut segments = Vec::new();
+    let timeout = std::time::Duration::from_secs(600);
+
+    while let Ok(Some(output)) = async_std::future::timeout(timeout, rx.next()).await {
+        if output.id.as_ref() != Some(&request_id) {
+            continue;
+        }
+        match output.body {
+            BrainstemBody::Event(InferenceEvent::Transcript(segment)) => segments.push(segment),
+            BrainstemBody::Event(InferenceEvent::Complete) => break,
+            BrainstemBody::Error(e) => {
+                return Err(tide::Error::from_str(500, e));
+            }
+            _ => {}
+        }
+    }
+
+    let text = segments
+        .iter()
+        .map(|segment| segment.text.trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let response = match response_format.as_str() {
+        "text" => Response::builder(StatusCode::Ok)
+            .content_type(tide::http::mime::PLAIN)
+            .body(text)
+            .build(),
+        "verbose_json" => {
+            let response = VerboseTranscriptionResponse {
+                task: "transcribe".to_string(),
+                language,
+                duration: segments.last().map_or(0.0, |s| s.end_ms as f64 / 1000.0),
+                text,
+                segments: segments
+                    .into_iter()
+                    .enumerate()
+                    .map(|(id, segment)| TranscriptionSegment {
+                        id,
+                        start: segment.start_ms as f64 / 1000.0,
+                        end: segment.end_ms as f64 / 1000.0,
+                        text: segment.text,
+                    })
+                    .collect(),
+            };
+            Response::builder(StatusCode::Ok)
+                .body(Body::from_json(&response)?)
+                .build()
+        }
+        _ => Response::builder(StatusCode::Ok)
+            .body(Body::from_json(&TranscriptionResponse { text })?)
+            .build(),
+    };
+    Ok(response)
+}
+
 pub async fn get_config(req: Request<ApiState>) -> tide::Result {
     let state = req.state();
     let response = ApiConfig {
@@ -377,6 +1012,123 @@ pub async fn get_config(req: Request<ApiState>) -> tide::Result {
         .build())
 }
 
+#[derive(Serialize)]
+pub struct HealthResponse {
+    /// `ok`, or `degraded` when the loaded model failed its smoke test.
+    pub status: String,
+    #[serde(flatten)]
+    pub health: EngineHealth,
+}
+
+/// Engine health: 200 when it works, 503 when the loaded model failed to generate.
+pub async fn health(req: Request<ApiState>) -> tide::Result {
+    let state = req.state();
+
+    let request_id = format!(
+        "api-health-{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_micros()
+    );
+
+    let mut input_tx = state.input_tx.clone();
+    let (tx, mut rx) = mpsc::channel(100);
+
+    {
+        let mut senders = state.output_senders.lock().await;
+        senders.push(tx);
+    }
+
+    input_tx
+        .send(BrainstemInput {
+            id: Some(request_id.clone()),
+            command: BrainstemCommand::Health,
+        })
+        .await
+        .map_err(|e| tide::Error::from_str(500, e))?;
+
+    // The smoke test may wait for a running generation
+    let timeout = std::time::Duration::from_secs(60);
+    while let Ok(Some(output)) = async_std::future::timeout(timeout, rx.next()).await {
+        if output.id.as_ref() != Some(&request_id) {
+            continue;
+        }
+        match output.body {
+            BrainstemBody::Health(health) => {
+                let (status, code) = if health.is_healthy() {
+                    ("ok", StatusCode::Ok)
+                } else {
+                    ("degraded", StatusCode::ServiceUnavailable)
+                };
+                let response = HealthResponse {
+                    status: status.to_string(),
+                    health,
+                };
+                return Ok(Response::builder(code)
+                    .body(Body::from_json(&response)?)
+                    .build());
+            }
+            BrainstemBody::Error(e) => return Err(tide::Error::from_str(500, e)),
+            _ => {}
+        }
+    }
+    Err(tide::Error::from_str(
+        StatusCode::ServiceUnavailable,
+        "The engine did not answer",
+    ))
+}
+
+/// The orchestrator's metrics, in the Prometheus text format.
+pub async fn metrics(req: Request<ApiState>) -> tide::Result {
+    let state = req.state();
+
+    let request_id = format!(
+        "api-metrics-{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_micros()
+    );
+
+    let mut input_tx = state.input_tx.clone();
+    let (tx, mut rx) = mpsc::channel(100);
+
+    {
+        let mut senders = state.output_senders.lock().await;
+        senders.push(tx);
+    }
+
+    input_tx
+        .send(BrainstemInput {
+            id: Some(request_id.clone()),
+            command: BrainstemCommand::GetMetrics,
+        })
+        .await
+        .map_err(|e| tide::Error::from_str(500, e))?;
+
+    let timeout = std::time::Duration::from_secs(10);
+    while let Ok(Some(output)) = async_std::future::timeout(timeout, rx.next()).await {
+        if output.id.as_ref() != Some(&request_id) {
+            continue;
+        }
+        match output.body {
+            BrainstemBody::Metrics(metrics) => {
+                return Ok(Response::builder(StatusCode::Ok)
+                    .content_type("text/plain; version=0.0.4")
+                    .body(metrics.to_prometheus())
+                    .build());
+            }
+            BrainstemBody::Error(e) => return Err(tide::Error::from_str(500, e)),
+            _ => {}
+        }
+    }
+    Err(tide::Error::from_str(
+        StatusCode::ServiceUnavailable,
+        "The engine did not answer",
+    ))
+}
+
 pub async fn reset_engine(req: Request<ApiState>) -> tide::Result {
     eprintln!("DEBUG: reset_engine entry");
     let state = req.state();
@@ -430,6 +1182,78 @@ pub async fn reset_engine(req: Request<ApiState>) -> tide::Result {
         .build())
 }
 
+/// The body of `POST /v1/engine/strategy`, e.g.
+/// `{"strategy": "hibernate_after", "seconds": 60}`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum StrategyRequest {
+    Immediate,
+    HibernateAfter { seconds: u64 },
+    KeepAlive,
+}
+
+impl From<StrategyRequest> for CortexStrategy {
+    fn from(request: StrategyRequest) -> Self {
+        match request {
+            StrategyRequest::Immediate => CortexStrategy::Immediate,
+            StrategyRequest::HibernateAfter { seconds } => {
+                CortexStrategy::HibernateAfter(std::time::Duration::from_secs(seconds))
+            }
+            StrategyRequest::KeepAlive => CortexStrategy::KeepAlive,
+        }
+    }
+}
+
+/// Change when the engine unloads while idle, without restarting the server.
+pub async fn set_strategy(mut req: Request<ApiState>) -> tide::Result {
+    let body: StrategyRequest = req.body_json().await?;
+    let state = req.state();
+
+    let request_id = format!(
+        "api-strategy-{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_micros()
+    );
+
+    let mut input_tx = state.input_tx.clone();
+    let (tx, mut rx) = mpsc::channel(100);
+
+    {
+        let mut senders = state.output_senders.lock().await;
+        senders.push(tx);
+    }
+
+    input_tx
+        .send(BrainstemInput {
+            id: Some(request_id.clone()),
+            command: BrainstemCommand::SetStrategy(body.into()),
+        })
+        .await
+        .map_err(|e| tide::Error::from_str(500, e))?;
+
+    let timeout = std::time::Duration::from_secs(10);
+    while let Ok(Some(output)) = async_std::future::timeout(timeout, rx.next()).await {
+        if output.id.as_ref() != Some(&request_id) {
+            continue;
+        }
+        match output.body {
+            BrainstemBody::Event(InferenceEvent::Complete) => {
+                return Ok(Response::builder(StatusCode::Ok)
+                    .body("Strategy updated")
+                    .build());
+            }
+            BrainstemBody::Error(e) => return Err(tide::Error::from_str(500, e)),
+            _ => {}
+        }
+    }
+    Err(tide::Error::from_str(
+        StatusCode::ServiceUnavailable,
+        "The engine did not answer",
+    ))
+}
+
 pub async fn context_chat(mut req: Request<ApiState>) -> tide::Result {
     eprintln!("DEBUG: context_chat entry");
     let body: ChatCompletionRequest = req.body_json().await?;
@@ -438,7 +1262,7 @@ pub async fn context_chat(mut req: Request<ApiState>) -> tide::Result {
     let user_content = body
         .messages
         .last()
-        .map(|m| m.content.clone())
+        .map(|m| m.content.text())
         .unwrap_or_default();
 
     let request_id = format!(
@@ -470,10 +1294,13 @@ pub async fn context_chat(mut req: Request<ApiState>) -> tide::Result {
                     index: 0,
                     message: ChatMessageOut {
                         role: "assistant".to_string(),
-                        content: serde_json::to_string(&result).unwrap(),
+                        content: Some(serde_json::to_string(&result).unwrap()),
+                        tool_calls: Vec::new(),
                     },
+                    logprobs: None,
                     finish_reason: "stop".to_string(),
                 }],
+                usage: None,
             };
             return Ok(Response::builder(StatusCode::Ok)
                 .body(Body::from_json(&response)?)
@@ -519,10 +1346,13 @@ pub async fn context_chat(mut req: Request<ApiState>) -> tide::Result {
                     index: 0,
                     message: ChatMessageOut {
                         role: "assistant".to_string(),
-                        content: serde_json::to_string(&result).unwrap(),
+                        content: Some(serde_json::to_string(&result).unwrap()),
+                        tool_calls: Vec::new(),
                     },
+                    logprobs: None,
                     finish_reason: "stop".to_string(),
                 }],
+                usage: None,
             };
             return Ok(Response::builder(StatusCode::Ok)
                 .body(Body::from_json(&response)?)
@@ -608,10 +1438,13 @@ pub async fn context_chat(mut req: Request<ApiState>) -> tide::Result {
             index: 0,
             message: ChatMessageOut {
                 role: "assistant".to_string(),
-                content: serde_json::to_string(&result).unwrap(),
+                content: Some(serde_json::to_string(&result).unwrap()),
+                tool_calls: Vec::new(),
             },
+            logprobs: None,
             finish_reason: "stop".to_string(),
         }],
+        usage: None,
     };
 
     Ok(Response::builder(StatusCode::Ok)
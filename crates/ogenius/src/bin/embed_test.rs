@@ -13,7 +13,10 @@ struct ModelResponse {
     id: String,
     #[allow(dead_code)]
     object: String,
+    #[allow(dead_code)]
     purpose: String,
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -123,18 +126,11 @@ async fn main() -> Result<()> {
         .await
         .map_err(|e| anyhow!("Failed to parse model list: {}", e))?;
 
-    // 2. Filter for embedding model
-    // Note: The API returns { id: "name", object: "model" }
-    // It does not currently return the purpose.
-    // We need to update Ogenius API to return the purpose or detailed info.
-    // FOR NOW: We will rely on the name still, until we update the API.
-    // Wait, the task is to use the purpose.
-    // I need to update `crates/ogenius/src/api.rs` to include `purpose` in `ModelResponse`.
-
+    // 2. Filter for an embedding model by tag
     let model_id = list_body
         .data
         .iter()
-        .find(|m| m.purpose == "Embedding")
+        .find(|m| m.tags.iter().any(|t| t == "embedding"))
         .map(|m| m.id.clone())
         .ok_or_else(|| {
             anyhow!(
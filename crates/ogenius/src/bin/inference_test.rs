@@ -17,6 +17,8 @@ struct ModelResponse {
     // pub role: String,
     #[allow(dead_code)]
     purpose: String,
+    #[serde(default)]
+    tags: Vec<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -134,11 +136,11 @@ async fn main() -> Result<()> {
         .await
         .map_err(|e| anyhow!("Failed to parse model list: {}", e))?;
 
-    // 2. Filter for tiny-llama, ensuring purpose is Inference
+    // 2. Filter for tiny-llama, ensuring it is tagged for inference
     let candidates: Vec<&ModelResponse> = list_body
         .data
         .iter()
-        .filter(|m| m.purpose == "Inference")
+        .filter(|m| m.tags.iter().any(|t| t == "inference"))
         .collect();
 
     let model_id = candidates
@@ -7,24 +7,29 @@ mod api;
 use anyhow::Result;
 use api::{chat_completions, context_chat, list_models, ApiState};
 use async_std::sync::Mutex;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use colored::*;
 use futures::channel::mpsc;
 use futures::sink::SinkExt;
 use futures::StreamExt;
 #[cfg(feature = "cortex-engine")]
 use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use rusty_genius_core::manifest::{GpuLayers, KvCacheType, RopeScaling, YarnConfig};
 use rusty_genius_core::protocol::{
-    AssetEvent, BrainstemBody, BrainstemCommand, BrainstemInput, BrainstemOutput, ContextOutput,
-    InferenceConfig, InferenceEvent,
+    AssetEvent, BenchConfig, BrainstemBody, BrainstemCommand, BrainstemInput, BrainstemOutput,
+    ContextOutput, InferenceConfig, InferenceEvent, LoadConfig,
 };
 use rusty_genius_core::InMemoryContextStore;
-use rusty_genius_stem::{ContextWorker, Orchestrator};
+use rusty_genius_stem::{
+    AuditLog, ContextWorker, CortexStrategy, Orchestrator, QueueFull, DEFAULT_MAX_PARALLEL,
+    DEFAULT_MAX_QUEUE,
+};
 #[cfg(feature = "cortex-engine")]
 use std::io::IsTerminal;
 use std::io::{self, Write};
 use std::process;
 use std::sync::Arc;
+use std::time::Duration;
 use tide_websockets::{Message, WebSocket};
 
 #[derive(Parser)]
@@ -41,6 +46,45 @@ enum Commands {
         /// HuggingFace model repo (e.g., Qwen/Qwen2.5-1.5B-Instruct)
         repo: String,
     },
+    /// Adopt a GGUF file already on disk into the model cache
+    #[cfg(feature = "cortex-engine")]
+    Import {
+        /// Path to the .gguf file
+        path: std::path::PathBuf,
+        /// Registry name for the model (defaults to the file name)
+        #[arg(long)]
+        name: Option<String>,
+        /// Copy the file instead of hardlinking it
+        #[arg(long, conflicts_with = "move_file")]
+        copy: bool,
+        /// Move the file into the cache instead of hardlinking it
+        #[arg(long = "move")]
+        move_file: bool,
+    },
+    /// Accept the license a registry model requires before it can be downloaded
+    #[cfg(feature = "cortex-engine")]
+    AcceptLicense {
+        /// Registry model name
+        model: String,
+    },
+    /// Search HuggingFace for GGUF models
+    #[cfg(feature = "cortex-engine")]
+    Search {
+        /// Search terms, e.g. "qwen coder"
+        query: String,
+        /// Only show repos offering this quantization (e.g. Q4_K_M)
+        #[arg(long)]
+        quant: Option<String>,
+        /// Only show repos by this user or organisation
+        #[arg(long)]
+        author: Option<String>,
+        /// Sort by downloads, likes or updated
+        #[arg(long, default_value = "downloads")]
+        sort: String,
+        /// Maximum number of results
+        #[arg(long, default_value = "20")]
+        limit: usize,
+    },
     /// Start interactive chat in CLI
     Serve {
         /// HTTP server address
@@ -58,6 +102,22 @@ enum Commands {
         /// Unload model after inactivity (seconds)
         #[arg(long, default_value = "300")]
         unload_after: u64,
+        /// Models never to unload for inactivity, e.g. the main chat model; may be repeated
+        #[arg(long)]
+        keep_warm: Vec<String>,
+        /// Load the model used last, with its settings, when the server starts
+        #[arg(long)]
+        resume: bool,
+        /// Record every command, with its timing, model, tokens and outcome, in audit.jsonl in
+        /// the config dir
+        #[arg(long)]
+        audit_log: bool,
+        /// Leave prompts out of the audit log
+        #[arg(long)]
+        audit_redact_prompts: bool,
+        /// Rotate the audit log once it reaches this many megabytes
+        #[arg(long, default_value = "10")]
+        audit_max_mb: u64,
         /// Quantization level (e.g. Q4_K_M)
         #[arg(long, default_value = "Q4_K_M")]
         quant: String,
@@ -69,9 +129,45 @@ enum Commands {
         /// Show thinking tokens
         #[arg(long, default_value = "true")]
         show_thinking: bool,
-        /// Models to pre-load (download/verify) before starting
+        /// Models or registry profiles to pre-load (download/verify) before starting
         #[arg(long)]
         load_models: Vec<String>,
+        /// Requests to stream at once; more wait for one to finish
+        #[arg(long, default_value_t = DEFAULT_MAX_PARALLEL)]
+        parallel: usize,
+        /// Requests to queue behind those streaming; more are refused
+        #[arg(long, default_value_t = DEFAULT_MAX_QUEUE)]
+        max_queue: usize,
+        /// What to do with a request when the queue is full: reject or drop_oldest
+        #[arg(long, default_value = "reject", value_parser = parse_name::<QueueFull>)]
+        queue_full: QueueFull,
+        #[command(flatten)]
+        load: LoadArgs,
+    },
+    /// Run the orchestrator on stdin and stdout, for hosts driving it as a subprocess: a
+    /// BrainstemInput JSON object per line in, a BrainstemOutput per line out
+    Stdio {
+        /// Unload model after inactivity (seconds)
+        #[arg(long, default_value = "300")]
+        unload_after: u64,
+        /// Requests to stream at once; more wait for one to finish
+        #[arg(long, default_value_t = DEFAULT_MAX_PARALLEL)]
+        parallel: usize,
+        #[command(flatten)]
+        load: LoadArgs,
+    },
+    /// Keep one orchestrator running on a Unix socket, so chat and embed share its warm
+    /// model instead of each loading their own
+    #[cfg(unix)]
+    Daemon {
+        /// Unload model after inactivity (seconds)
+        #[arg(long, default_value = "300")]
+        unload_after: u64,
+        /// Requests to stream at once; more wait for one to finish
+        #[arg(long, default_value_t = DEFAULT_MAX_PARALLEL)]
+        parallel: usize,
+        #[command(flatten)]
+        load: LoadArgs,
     },
     /// Start interactive chat in CLI
     Chat {
@@ -87,9 +183,11 @@ enum Commands {
         /// Show thinking tokens
         #[arg(long, default_value = "true")]
         show_thinking: bool,
-        /// Models to pre-load (download/verify) before starting
+        /// Models or registry profiles to pre-load (download/verify) before starting
         #[arg(long)]
         load_models: Vec<String>,
+        #[command(flatten)]
+        load: LoadArgs,
     },
     /// Generate embeddings for input text
     Embed {
@@ -105,9 +203,182 @@ enum Commands {
         /// Context size
         #[arg(long, default_value = "2048")]
         context_size: u32,
+        #[command(flatten)]
+        load: LoadArgs,
+    },
+    /// Measure prompt-processing and generation speed of a model
+    Bench {
+        /// Model repository
+        #[arg(long, default_value = "Qwen/Qwen2.5-1.5B-Instruct")]
+        model: String,
+        /// Prompt length of the prompt-processing test (0 skips it)
+        #[arg(long, default_value = "512")]
+        prompt_tokens: usize,
+        /// Tokens generated in the generation test (0 skips it)
+        #[arg(long, default_value = "128")]
+        gen_tokens: usize,
+        /// Times each test runs
+        #[arg(long, default_value = "3")]
+        repetitions: usize,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        load: LoadArgs,
     },
 }
 
+/// Model loading options shared by the commands that run a model
+#[derive(Args)]
+struct LoadArgs {
+    /// RoPE base frequency (defaults to the model's)
+    #[arg(long)]
+    rope_freq_base: Option<f32>,
+    /// RoPE frequency scaling factor, e.g. 0.25 to run an 8k model at 32k
+    #[arg(long)]
+    rope_freq_scale: Option<f32>,
+    /// RoPE scaling method: none, linear or yarn
+    #[arg(long, value_parser = parse_name::<RopeScaling>)]
+    rope_scaling: Option<RopeScaling>,
+    /// YaRN: context length the model was trained with
+    #[arg(long)]
+    yarn_orig_ctx: Option<u32>,
+    /// YaRN: extrapolation mix factor
+    #[arg(long)]
+    yarn_ext_factor: Option<f32>,
+    /// YaRN: attention magnitude scaling
+    #[arg(long)]
+    yarn_attn_factor: Option<f32>,
+    /// YaRN: low correction dimension
+    #[arg(long)]
+    yarn_beta_fast: Option<f32>,
+    /// YaRN: high correction dimension
+    #[arg(long)]
+    yarn_beta_slow: Option<f32>,
+    /// Most tokens evaluated in one decode (defaults to the context size)
+    #[arg(long)]
+    batch_size: Option<u32>,
+    /// Most tokens computed at once within a decode; raise it on big GPUs
+    #[arg(long)]
+    ubatch_size: Option<u32>,
+    /// Use flash attention
+    #[arg(long)]
+    flash_attn: bool,
+    /// KV cache type: f16, q8_0 or q4_0 (quantized V cache needs --flash-attn)
+    #[arg(long, value_parser = parse_name::<KvCacheType>)]
+    kv_cache_type: Option<KvCacheType>,
+    /// Read the model into memory instead of mapping the file
+    #[arg(long)]
+    no_mmap: bool,
+    /// Lock the model in memory so it is never paged out
+    #[arg(long)]
+    mlock: bool,
+    /// Layers offloaded to the GPU: auto (as many as fit in free VRAM), all, or a number
+    #[arg(long, default_value = "auto")]
+    gpu_layers: GpuLayers,
+    /// Threads used while generating
+    #[arg(long)]
+    threads: Option<u32>,
+    /// Threads used while evaluating prompts
+    #[arg(long)]
+    threads_batch: Option<u32>,
+    /// Multimodal projector (mmproj GGUF) that lets a vision model take images
+    #[arg(long)]
+    mmproj: Option<String>,
+}
+
+impl From<LoadArgs> for LoadConfig {
+    fn from(args: LoadArgs) -> Self {
+        Self {
+            rope_freq_base: args.rope_freq_base,
+            rope_freq_scale: args.rope_freq_scale,
+            rope_scaling: args.rope_scaling,
+            yarn: YarnConfig {
+                orig_ctx: args.yarn_orig_ctx,
+                ext_factor: args.yarn_ext_factor,
+                attn_factor: args.yarn_attn_factor,
+                beta_fast: args.yarn_beta_fast,
+                beta_slow: args.yarn_beta_slow,
+            },
+            batch_size: args.batch_size,
+            ubatch_size: args.ubatch_size,
+            flash_attention: args.flash_attn.then_some(true),
+            kv_cache_type: args.kv_cache_type,
+            use_mmap: args.no_mmap.then_some(false),
+            use_mlock: args.mlock.then_some(true),
+            gpu_layers: Some(args.gpu_layers),
+            n_threads: args.threads,
+            n_threads_batch: args.threads_batch,
+            mmproj: args.mmproj,
+        }
+    }
+}
+
+/// Parse an option value by its config (serde) name, e.g. `yarn` or `q8_0`.
+fn parse_name<T: serde::de::DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string())).map_err(|e| e.to_string())
+}
+
+/// Describe how many layers a model has on the GPU, e.g. " (28 layers on the GPU)".
+fn format_gpu_layers(gpu_layers: Option<u32>) -> String {
+    match gpu_layers {
+        Some(layers) => format!(" ({} layers on the GPU)", layers),
+        None => String::new(),
+    }
+}
+
+/// Format a transfer rate as a suffix for progress lines, e.g. " @ 12.3 MB/s, ETA 42s".
+fn format_rate(bytes_per_sec: u64, eta_secs: Option<u64>) -> String {
+    let mb_per_sec = bytes_per_sec as f64 / 1_000_000.0;
+    match eta_secs {
+        Some(eta) => format!(" @ {:.1} MB/s, ETA {}s", mb_per_sec, eta),
+        None => format!(" @ {:.1} MB/s", mb_per_sec),
+    }
+}
+
+/// Where `ogenius daemon` listens: `$OGENIUS_SOCKET`, or `ogenius.sock` in
+/// `$XDG_RUNTIME_DIR` or else the temp dir.
+#[cfg(unix)]
+fn daemon_socket() -> std::path::PathBuf {
+    if let Some(socket) = std::env::var_os("OGENIUS_SOCKET") {
+        return socket.into();
+    }
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join("ogenius.sock")
+}
+
+/// Channels to the daemon when one is running, sharing its loaded model, or else to an
+/// orchestrator of our own that loads models with `load`.
+async fn brainstem(
+    load: LoadConfig,
+) -> Result<(
+    mpsc::Sender<BrainstemInput>,
+    mpsc::Receiver<BrainstemOutput>,
+)> {
+    #[cfg(unix)]
+    if let Ok(stream) = async_std::os::unix::net::UnixStream::connect(daemon_socket()).await {
+        println!("🔌 Using the running daemon");
+        let (input_tx, output_rx, carry) = rusty_genius_stem::ipc::connect(stream);
+        async_std::task::spawn(async move {
+            if let Err(e) = carry.await {
+                eprintln!("❌ Lost the daemon: {}", e);
+            }
+        });
+        return Ok((input_tx, output_rx));
+    }
+
+    let mut orchestrator = Orchestrator::new().await?;
+    orchestrator.set_load_config(load);
+    let (input_tx, input_rx) = mpsc::channel(100);
+    let (output_tx, output_rx) = mpsc::channel(100);
+    async_std::task::spawn(async move {
+        let _ = orchestrator.run(input_rx, output_tx).await;
+    });
+    Ok((input_tx, output_rx))
+}
+
 /// Pre-load and verify models in parallel with progress tracking
 #[cfg(feature = "cortex-engine")]
 async fn wait_for_models(load_models: Vec<String>) -> Result<()> {
@@ -115,8 +386,17 @@ async fn wait_for_models(load_models: Vec<String>) -> Result<()> {
         return Ok(());
     }
 
-    println!("📦 Pre-loading {} models...", load_models.len());
     let authority = facecrab::AssetAuthority::new()?;
+    // Profile names expand to their member models.
+    let load_models: Vec<String> = load_models
+        .into_iter()
+        .flat_map(|name| {
+            authority
+                .profile_models(&name)
+                .unwrap_or_else(|| vec![name])
+        })
+        .collect();
+    println!("📦 Pre-loading {} models...", load_models.len());
     let multi_progress = MultiProgress::new();
     let is_tty = io::stdout().is_terminal();
 
@@ -140,6 +420,7 @@ async fn wait_for_models(load_models: Vec<String>) -> Result<()> {
                 let mut stream = auth.ensure_model_stream(&name);
                 let mut last_path = None;
                 let mut last_pct = 0;
+                let mut rate = String::new();
                 while let Some(event) = stream.next().await {
                     match event {
                         AssetEvent::Started(_) => {
@@ -149,18 +430,58 @@ async fn wait_for_models(load_models: Vec<String>) -> Result<()> {
                                 println!("Downloading: {}", name);
                             }
                         }
+                        AssetEvent::Queued { position } => {
+                            if is_tty {
+                                pb.set_message(format!("Queued (#{}): {}", position + 1, name));
+                            } else {
+                                println!("Queued (#{}): {}", position + 1, name);
+                            }
+                        }
+                        AssetEvent::Resuming { offset, total } => {
+                            if is_tty {
+                                pb.set_length(total);
+                                pb.set_position(offset);
+                                pb.set_message(format!("Resuming: {}", name));
+                            } else {
+                                println!("Resuming: {} at {} bytes", name, offset);
+                            }
+                        }
                         AssetEvent::Progress(current, total) => {
                             if is_tty {
                                 pb.set_length(total);
                                 pb.set_position(current);
-                            } else if total > 0 {
-                                let current_pct = (current * 100) / total;
+                            } else if let Some(current_pct) = (current * 100).checked_div(total) {
                                 if current_pct >= last_pct + 10 {
-                                    println!("Downloading: {} {}%", name, current_pct);
+                                    println!("Downloading: {} {}%{}", name, current_pct, rate);
                                     last_pct = current_pct;
                                 }
                             }
                         }
+                        AssetEvent::Rate { bytes_per_sec, eta_secs } => {
+                            rate = format_rate(bytes_per_sec, eta_secs);
+                            if is_tty {
+                                pb.set_message(format!("Downloading: {}{}", name, rate));
+                            }
+                        }
+                        AssetEvent::Verifying(_) => {
+                            if is_tty {
+                                pb.set_message(format!("Verifying: {}", name));
+                            } else {
+                                println!("Verifying: {}", name);
+                            }
+                        }
+                        AssetEvent::Discarded(_) => {}
+                        AssetEvent::LicenseRequired { model, license } => {
+                            let msg = format!(
+                                "License required for {}: {} (accept with `ogenius accept-license {}`)",
+                                model, license, model
+                            );
+                            if is_tty {
+                                pb.set_message(msg);
+                            } else {
+                                println!("{}", msg);
+                            }
+                        }
                         AssetEvent::Complete(path) => {
                             if is_tty {
                                 pb.finish_with_message(format!("✅ Ready: {}", name));
@@ -169,6 +490,7 @@ async fn wait_for_models(load_models: Vec<String>) -> Result<()> {
                             }
                             last_path = Some(std::path::PathBuf::from(path));
                         }
+                        AssetEvent::Loading(_) | AssetEvent::Loaded { .. } => {}
                         AssetEvent::Error(e) => {
                             if is_tty {
                                 pb.abandon_with_message(format!("❌ Error: {}", e));
@@ -224,11 +546,10 @@ async fn wait_for_models(load_models: Vec<String>) -> Result<()> {
 
 #[async_std::main]
 async fn main() -> anyhow::Result<()> {
-    println!("DEBUG: ogenius main starting...");
-    let _ = io::stdout().flush();
+    eprintln!("DEBUG: ogenius main starting...");
     // Install Ctrl-C handler for graceful shutdown (especially during downloads)
     ctrlc::set_handler(move || {
-        println!("\n🛑 Received Ctrl-C, exiting...");
+        eprintln!("\n🛑 Received Ctrl-C, exiting...");
         process::exit(130);
     })?;
 
@@ -252,6 +573,7 @@ async fn main() -> anyhow::Result<()> {
                 })
                 .await?;
 
+            let mut rate = String::new();
             while let Some(output) = output_rx.next().await {
                 match output.body {
                     BrainstemBody::Asset(AssetEvent::Progress(curr, total)) => {
@@ -260,7 +582,17 @@ async fn main() -> anyhow::Result<()> {
                         } else {
                             0.0
                         };
-                        print!("\rProgress: {:.1}% ({}/{})", pct, curr, total);
+                        print!("\rProgress: {:.1}% ({}/{}){}", pct, curr, total, rate);
+                        io::stdout().flush()?;
+                    }
+                    BrainstemBody::Asset(AssetEvent::Rate {
+                        bytes_per_sec,
+                        eta_secs,
+                    }) => {
+                        rate = format_rate(bytes_per_sec, eta_secs);
+                    }
+                    BrainstemBody::Asset(AssetEvent::Verifying(_)) => {
+                        print!("\nVerifying...");
                         io::stdout().flush()?;
                     }
                     BrainstemBody::Asset(AssetEvent::Complete(path)) => {
@@ -279,24 +611,98 @@ async fn main() -> anyhow::Result<()> {
                 }
             }
         }
+        #[cfg(feature = "cortex-engine")]
+        Commands::Import {
+            path,
+            name,
+            copy,
+            move_file,
+        } => {
+            let mode = if move_file {
+                facecrab::ImportMode::Move
+            } else if copy {
+                facecrab::ImportMode::Copy
+            } else {
+                facecrab::ImportMode::Link
+            };
+            println!("📦 Importing {}", path.display().to_string().cyan());
+            let authority = facecrab::AssetAuthority::new()?;
+            let entry = authority.import(&path, name.as_deref(), mode)?;
+            println!(
+                "✅ Imported {} ({}, sha256 {})",
+                entry.name.green(),
+                entry.quantization,
+                entry.sha256.unwrap_or_default()
+            );
+        }
+        #[cfg(feature = "cortex-engine")]
+        Commands::AcceptLicense { model } => {
+            let authority = facecrab::AssetAuthority::new()?;
+            match authority.pending_license(&model) {
+                Some(license) => {
+                    println!("📜 {} is distributed under: {}", model.cyan(), license);
+                    authority.accept_license(&model)?;
+                    println!("✅ License accepted for {}", model.green());
+                }
+                None => println!("No license acceptance needed for {}", model),
+            }
+        }
+        #[cfg(feature = "cortex-engine")]
+        Commands::Search {
+            query,
+            quant,
+            author,
+            sort,
+            limit,
+        } => {
+            let sort = match sort.to_lowercase().as_str() {
+                "downloads" => facecrab::SearchSort::Downloads,
+                "likes" => facecrab::SearchSort::Likes,
+                "updated" => facecrab::SearchSort::Updated,
+                other => anyhow::bail!("Unknown sort '{}'; use downloads, likes or updated", other),
+            };
+            let filters = facecrab::SearchFilters {
+                author,
+                quantization: quant.clone(),
+                limit: Some(limit),
+                sort,
+            };
+            let authority = facecrab::AssetAuthority::new()?;
+            let results = authority.search(&query, &filters).await?;
+            if results.is_empty() {
+                println!("No GGUF models found for '{}'", query);
+                return Ok(());
+            }
+            for result in results {
+                println!(
+                    "{}  ⬇ {}  ♥ {}",
+                    result.id.green(),
+                    result.downloads,
+                    result.likes
+                );
+                if !result.quantizations.is_empty() {
+                    println!("    {}", result.quantizations.join(", ").dimmed());
+                }
+            }
+            let quant = quant.unwrap_or_else(|| "Q4_K_M".to_string());
+            println!(
+                "\nPull one with: ogenius download <repo>:{}",
+                quant.to_uppercase()
+            );
+        }
         Commands::Chat {
             model,
             quant: _,
             context_size,
             show_thinking,
             load_models,
+            load,
         } => {
             // Pre-load models if requested
             wait_for_models(load_models).await?;
 
             println!("💬 Starting chat with {}", model.cyan());
-            let mut orchestrator = Orchestrator::new().await?;
-            let (mut input_tx, input_rx) = mpsc::channel(100);
-            let (output_tx, mut output_rx) = mpsc::channel(100);
-
-            async_std::task::spawn(async move {
-                let _ = orchestrator.run(input_rx, output_tx).await;
-            });
+            let (mut input_tx, mut output_rx) = brainstem(load.into()).await?;
 
             let config = InferenceConfig {
                 context_size: Some(context_size),
@@ -313,9 +719,15 @@ async fn main() -> anyhow::Result<()> {
                 .await?;
             println!("⏳ Loading model...");
 
+            let mut gpu_layers = None;
             while let Some(output) = output_rx.next().await {
                 match output.body {
-                    BrainstemBody::Asset(AssetEvent::Complete(_)) => break,
+                    BrainstemBody::Asset(AssetEvent::Loaded {
+                        gpu_layers: layers, ..
+                    }) => {
+                        gpu_layers = layers;
+                        break;
+                    }
                     BrainstemBody::Error(e) => {
                         eprintln!("❌ Failed to load: {}", e.red());
                         return Ok(());
@@ -323,7 +735,7 @@ async fn main() -> anyhow::Result<()> {
                     _ => {}
                 }
             }
-            println!("✅ Model loaded!");
+            println!("✅ Model loaded!{}", format_gpu_layers(gpu_layers));
             println!("(Type 'exit' to quit)\n");
 
             let stdin = io::stdin();
@@ -358,14 +770,33 @@ async fn main() -> anyhow::Result<()> {
                 print!("{} ", "AI >".bright_green());
                 io::stdout().flush()?;
 
+                let mut stats = None;
+                // Set while the model reloads after hibernating
+                let mut loading = None;
                 while let Some(output) = output_rx.next().await {
                     match output.body {
+                        BrainstemBody::Asset(AssetEvent::Loading(_)) => {
+                            print!("{}", "(loading model…) ".dimmed());
+                            io::stdout().flush()?;
+                            loading = Some(std::time::Instant::now());
+                        }
+                        BrainstemBody::Asset(AssetEvent::Loaded { .. }) => {
+                            if let Some(start) = loading.take() {
+                                let took = format!("(loaded in {:.1?}) ", start.elapsed());
+                                print!("{}", took.dimmed());
+                                io::stdout().flush()?;
+                            }
+                        }
                         BrainstemBody::Event(InferenceEvent::Content(c)) => {
                             print!("{}", c);
                             io::stdout().flush()?;
                         }
+                        BrainstemBody::Event(InferenceEvent::Stats(s)) => stats = Some(s),
                         BrainstemBody::Event(InferenceEvent::Complete) => {
                             println!();
+                            if let Some(stats) = stats.take() {
+                                println!("{}", stats.to_string().dimmed());
+                            }
                             break;
                         }
                         BrainstemBody::Error(e) => {
@@ -382,15 +813,10 @@ async fn main() -> anyhow::Result<()> {
             quant: _,
             input,
             context_size,
+            load,
         } => {
             println!("🔢 Generating embeddings using {}", model.cyan());
-            let mut orchestrator = Orchestrator::new().await?;
-            let (mut input_tx, input_rx) = mpsc::channel(100);
-            let (output_tx, mut output_rx) = mpsc::channel(100);
-
-            async_std::task::spawn(async move {
-                let _ = orchestrator.run(input_rx, output_tx).await;
-            });
+            let (mut input_tx, mut output_rx) = brainstem(load.into()).await?;
 
             let config = InferenceConfig {
                 context_size: Some(context_size),
@@ -407,9 +833,15 @@ async fn main() -> anyhow::Result<()> {
                 .await?;
             println!("⏳ Loading model...");
 
+            let mut gpu_layers = None;
             while let Some(output) = output_rx.next().await {
                 match output.body {
-                    BrainstemBody::Asset(AssetEvent::Complete(_)) => break,
+                    BrainstemBody::Asset(AssetEvent::Loaded {
+                        gpu_layers: layers, ..
+                    }) => {
+                        gpu_layers = layers;
+                        break;
+                    }
                     BrainstemBody::Error(e) => {
                         eprintln!("❌ Failed to load: {}", e.red());
                         return Ok(());
@@ -417,7 +849,7 @@ async fn main() -> anyhow::Result<()> {
                     _ => {}
                 }
             }
-            println!("✅ Model loaded!");
+            println!("✅ Model loaded!{}", format_gpu_layers(gpu_layers));
 
             // Send embedding request
             input_tx
@@ -451,16 +883,179 @@ async fn main() -> anyhow::Result<()> {
                 }
             }
         }
+        Commands::Bench {
+            model,
+            prompt_tokens,
+            gen_tokens,
+            repetitions,
+            json,
+            load,
+        } => {
+            let mut orchestrator = Orchestrator::new().await?;
+            orchestrator.set_load_config(load.into());
+            let (mut input_tx, input_rx) = mpsc::channel(100);
+            let (output_tx, mut output_rx) = mpsc::channel(100);
+
+            async_std::task::spawn(async move {
+                let _ = orchestrator.run(input_rx, output_tx).await;
+            });
+
+            input_tx
+                .send(BrainstemInput {
+                    id: None,
+                    command: BrainstemCommand::LoadModel(model.clone()),
+                })
+                .await?;
+            if !json {
+                println!("⏳ Loading {}...", model.cyan());
+            }
+
+            let mut gpu_layers = None;
+            while let Some(output) = output_rx.next().await {
+                match output.body {
+                    BrainstemBody::Asset(AssetEvent::Loaded {
+                        gpu_layers: layers, ..
+                    }) => {
+                        gpu_layers = layers;
+                        break;
+                    }
+                    BrainstemBody::Error(e) => {
+                        eprintln!("❌ Failed to load: {}", e.red());
+                        process::exit(1);
+                    }
+                    _ => {}
+                }
+            }
+            if !json {
+                println!("✅ Loaded{}", format_gpu_layers(gpu_layers));
+            }
+
+            input_tx
+                .send(BrainstemInput {
+                    id: None,
+                    command: BrainstemCommand::Bench {
+                        model: Some(model),
+                        config: BenchConfig {
+                            prompt_tokens,
+                            generated_tokens: gen_tokens,
+                            repetitions,
+                        },
+                    },
+                })
+                .await?;
+            if !json {
+                println!(
+                    "⏱️  Running pp{} / tg{} x{}...",
+                    prompt_tokens, gen_tokens, repetitions
+                );
+            }
+
+            while let Some(output) = output_rx.next().await {
+                match output.body {
+                    BrainstemBody::Bench(report) if json => {
+                        println!("{}", serde_json::to_string_pretty(&report)?);
+                        break;
+                    }
+                    BrainstemBody::Bench(report) => {
+                        if report.prompt_tokens > 0 {
+                            println!(
+                                "pp{:<6} {:>10.2} tok/s {:>10.1} ms",
+                                report.prompt_tokens,
+                                report.prompt_tokens_per_sec,
+                                report.prompt_ms
+                            );
+                        }
+                        if report.generated_tokens > 0 {
+                            println!(
+                                "tg{:<6} {:>10.2} tok/s {:>10.1} ms",
+                                report.generated_tokens,
+                                report.tokens_per_sec,
+                                report.generation_ms
+                            );
+                        }
+                        break;
+                    }
+                    BrainstemBody::Error(e) => {
+                        eprintln!("❌ Benchmark failed: {}", e.red());
+                        process::exit(1);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Commands::Stdio {
+            unload_after,
+            parallel,
+            load,
+        } => {
+            let mut orchestrator = Orchestrator::new().await?;
+            orchestrator.set_load_config(load.into());
+            orchestrator.set_max_parallel(parallel);
+            orchestrator.set_strategy(CortexStrategy::HibernateAfter(Duration::from_secs(
+                unload_after,
+            )));
+            let stdin = futures::io::BufReader::new(async_std::io::stdin());
+            orchestrator
+                .run_json_lines(stdin, async_std::io::stdout())
+                .await?;
+        }
+        #[cfg(unix)]
+        Commands::Daemon {
+            unload_after,
+            parallel,
+            load,
+        } => {
+            use async_std::os::unix::net::{UnixListener, UnixStream};
+
+            let socket = daemon_socket();
+            if UnixStream::connect(&socket).await.is_ok() {
+                anyhow::bail!("A daemon is already listening on {}", socket.display());
+            }
+            // Left behind by a daemon that didn't exit cleanly
+            let _ = std::fs::remove_file(&socket);
+            let listener = UnixListener::bind(&socket).await?;
+
+            let mut orchestrator = Orchestrator::new().await?;
+            orchestrator.set_load_config(load.into());
+            orchestrator.set_max_parallel(parallel);
+            orchestrator.set_strategy(CortexStrategy::HibernateAfter(Duration::from_secs(
+                unload_after,
+            )));
+            let connections = futures::stream::unfold(listener, |listener| async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => return Some((stream, listener)),
+                        Err(e) => eprintln!("NOTICE: Failed to accept a client: {}", e),
+                    }
+                }
+            });
+            eprintln!(
+                "🔌 Daemon listening on {}",
+                socket.display().to_string().cyan()
+            );
+            let result = orchestrator.run_daemon(connections).await;
+            let _ = std::fs::remove_file(&socket);
+            result?;
+        }
         Commands::Serve {
             addr,
             ws_addr,
             model,
             no_open,
-            unload_after: _,
+            unload_after,
+            keep_warm,
+            resume,
+            audit_log,
+            audit_redact_prompts,
+            audit_max_mb,
             quant: _,
             context_size,
             show_thinking,
             load_models,
+            parallel,
+            max_queue,
+            queue_full,
+            load,
         } => {
             // Pre-load models if requested
             wait_for_models(load_models).await?;
@@ -468,6 +1063,22 @@ async fn main() -> anyhow::Result<()> {
             println!("DEBUG: Initializing Orchestrator...");
             let _ = io::stdout().flush();
             let mut orchestrator = Orchestrator::new().await?;
+            orchestrator.set_load_config(load.into());
+            orchestrator.set_max_parallel(parallel);
+            orchestrator.set_max_queue(max_queue, queue_full);
+            orchestrator.set_strategy(CortexStrategy::HibernateAfter(Duration::from_secs(
+                unload_after,
+            )));
+            orchestrator.set_keep_warm(keep_warm);
+            orchestrator.set_resume(resume);
+            if audit_log {
+                let config_dir = facecrab::AssetAuthority::new()?.config_dir();
+                let log = AuditLog::new(config_dir.join("audit.jsonl"))
+                    .redact_prompts(audit_redact_prompts)
+                    .max_bytes(audit_max_mb * 1024 * 1024);
+                println!("DEBUG: Auditing commands in {}", log.path().display());
+                orchestrator.set_audit_log(Some(log));
+            }
             println!("DEBUG: Orchestrator initialized.");
             let _ = io::stdout().flush();
             let (input_tx, input_rx) = mpsc::channel(500);
@@ -505,10 +1116,17 @@ async fn main() -> anyhow::Result<()> {
                     let mut senders = bridge_senders.lock().await;
                     let mut to_remove = Vec::new();
                     for (i, sender) in senders.iter_mut().enumerate() {
-                        // Use try_send to avoid blocking the whole bridge if one client is slow
+                        // Use try_send to avoid blocking the whole bridge if one client is slow;
+                        // it sees the gap in `seq`
                         if let Err(e) = sender.try_send(msg.clone()) {
                             if e.is_disconnected() {
                                 to_remove.push(i);
+                            } else {
+                                eprintln!(
+                                    "NOTICE: dropped output {} of [{}] for a slow client",
+                                    msg.seq,
+                                    msg.id.as_deref().unwrap_or("-")
+                                );
                             }
                         }
                     }
@@ -594,10 +1212,16 @@ async fn main() -> anyhow::Result<()> {
             });
 
             app.at("/v1/models").get(list_models);
+            app.at("/v1/models/*id").get(api::get_model);
             app.at("/v1/chat/completions").post(chat_completions);
             app.at("/v1/context").post(context_chat);
             app.at("/v1/embeddings").post(api::embeddings);
+            app.at("/v1/rerank").post(api::rerank);
+            app.at("/v1/audio/transcriptions").post(api::transcriptions);
             app.at("/v1/engine/reset").post(api::reset_engine);
+            app.at("/v1/engine/strategy").post(api::set_strategy);
+            app.at("/health").get(api::health);
+            app.at("/metrics").get(api::metrics);
             app.at("/v1/config").get(api::get_config);
 
             let input_tx_ws = input_tx.clone();
@@ -0,0 +1,39 @@
+use ogenius::api::{ChatMessage, MessageContent};
+use rusty_genius_core::protocol::{ImageInput, IMAGE_MARKER};
+
+#[test]
+fn test_plain_text_content() {
+    let message: ChatMessage =
+        serde_json::from_str(r#"{"role":"user","content":"Hello"}"#).unwrap();
+    assert_eq!(message.content, MessageContent::Text("Hello".to_string()));
+    assert_eq!(
+        message.content.to_prompt().unwrap(),
+        ("Hello".to_string(), Vec::new())
+    );
+}
+
+#[test]
+fn test_image_parts_become_markers() {
+    let message: ChatMessage = serde_json::from_str(
+        r#"{"role":"user","content":[
+            {"type":"text","text":"What is in "},
+            {"type":"image_url","image_url":{"url":"data:image/png;base64,aGk=","detail":"low"}},
+            {"type":"text","text":"?"}
+        ]}"#,
+    )
+    .unwrap();
+
+    let (prompt, images) = message.content.to_prompt().unwrap();
+    assert_eq!(prompt, format!("What is in {}?", IMAGE_MARKER));
+    assert_eq!(images, vec![ImageInput::Base64("aGk=".to_string())]);
+    assert_eq!(message.content.text(), "What is in ?");
+}
+
+#[test]
+fn test_remote_image_urls_are_rejected() {
+    let message: ChatMessage = serde_json::from_str(
+        r#"{"role":"user","content":[{"type":"image_url","image_url":{"url":"https://example.com/cat.png"}}]}"#,
+    )
+    .unwrap();
+    assert!(message.content.to_prompt().is_err());
+}
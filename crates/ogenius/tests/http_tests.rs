@@ -29,7 +29,7 @@ impl TestServer {
         };
 
         let child = Command::new(&binary_path)
-            .args(&["serve", "--addr", &addr, "--ws-addr", &ws_addr, "--no-open"])
+            .args(["serve", "--addr", &addr, "--ws-addr", &ws_addr, "--no-open"])
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit())
             .spawn()
@@ -39,7 +39,7 @@ impl TestServer {
         // Wait for server to start
         for _ in 0..50 {
             task::sleep(Duration::from_millis(200)).await;
-            if surf::get(&format!("{}/v1/models", base_url)).await.is_ok() {
+            if surf::get(format!("{}/v1/models", base_url)).await.is_ok() {
                 return Self { child, base_url };
             }
         }
@@ -0,0 +1,36 @@
+use ogenius::api::{parse_multipart, FormPart};
+
+#[test]
+fn test_parse_multipart_fields() {
+    let body = b"--XyZ\r\n\
+Content-Disposition: form-data; name=\"model\"\r\n\r\n\
+whisper-base.en\r\n\
+--XyZ\r\n\
+Content-Disposition: form-data; name=\"file\"; filename=\"clip.wav\"\r\n\
+Content-Type: audio/wav\r\n\r\n\
+RIFF\r\n\x00\x01\r\n\
+--XyZ--\r\n";
+
+    let parts = parse_multipart(body, "XyZ");
+    assert_eq!(
+        parts,
+        vec![
+            FormPart {
+                name: "model".to_string(),
+                filename: None,
+                data: b"whisper-base.en".to_vec(),
+            },
+            FormPart {
+                name: "file".to_string(),
+                filename: Some("clip.wav".to_string()),
+                data: b"RIFF\r\n\x00\x01".to_vec(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_parse_multipart_wrong_boundary() {
+    let body = b"--abc\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n--abc--\r\n";
+    assert!(parse_multipart(body, "other").is_empty());
+}
@@ -0,0 +1,56 @@
+use ogenius::api::{split_tool_call_id, ChatCompletionRequest, MessageContent, ToolCallOut};
+use rusty_genius_core::protocol::ToolCall;
+use serde_json::json;
+
+#[test]
+fn test_tools_and_tool_messages_parse() {
+    let request: ChatCompletionRequest = serde_json::from_value(json!({
+        "model": "qwen",
+        "tools": [{
+            "type": "function",
+            "function": {
+                "name": "get_weather",
+                "description": "Current weather in a city",
+                "parameters": {"type": "object", "properties": {"city": {"type": "string"}}}
+            }
+        }],
+        "messages": [
+            {"role": "user", "content": "Weather in Oslo?"},
+            {"role": "assistant", "content": null, "tool_calls": [{
+                "id": "api-chat-1/call_0",
+                "type": "function",
+                "function": {"name": "get_weather", "arguments": "{\"city\":\"Oslo\"}"}
+            }]},
+            {"role": "tool", "tool_call_id": "api-chat-1/call_0", "content": "Sunny"}
+        ]
+    }))
+    .unwrap();
+
+    assert_eq!(request.tools[0].function.name, "get_weather");
+    assert_eq!(request.messages[1].content, MessageContent::default());
+    let result = &request.messages[2];
+    assert_eq!(result.role, "tool");
+    assert_eq!(
+        result.tool_call_id.as_deref().and_then(split_tool_call_id),
+        Some(("api-chat-1", "call_0"))
+    );
+}
+
+#[test]
+fn test_tool_calls_are_listed_as_openai_does() {
+    let call = ToolCall {
+        id: "call_0".to_string(),
+        name: "get_weather".to_string(),
+        arguments: json!({"city": "Oslo"}),
+    };
+    let out = ToolCallOut::new("api-chat-1", call);
+    assert_eq!(
+        serde_json::to_value(&out).unwrap(),
+        json!({
+            "id": "api-chat-1/call_0",
+            "type": "function",
+            "function": {"name": "get_weather", "arguments": "{\"city\":\"Oslo\"}"}
+        })
+    );
+    assert_eq!(split_tool_call_id(&out.id), Some(("api-chat-1", "call_0")));
+}
@@ -64,28 +64,6 @@ fn glob_match(pattern: &str, text: &str) -> bool {
     true
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_glob_match() {
-        assert!(glob_match("*", "anything"));
-        assert!(glob_match("*", ""));
-        assert!(glob_match("user:*", "user:123"));
-        assert!(!glob_match("user:*", "session:123"));
-        assert!(glob_match("*:123", "user:123"));
-        assert!(glob_match("u*r:*", "user:123"));
-        assert!(glob_match("exact", "exact"));
-        assert!(!glob_match("exact", "exact2"));
-        assert!(!glob_match("exact", ""));
-        assert!(glob_match("*a*", "bar"));
-        assert!(!glob_match("*a*", "bor"));
-        assert!(glob_match("pre*suf", "prefixsuf"));
-        assert!(!glob_match("pre*suf", "prefix"));
-    }
-}
-
 #[async_trait]
 impl ContextStore for RedisContextStore {
     async fn get(&self, key: &str) -> Result<Option<String>, GeniusError> {
@@ -133,3 +111,25 @@ impl ContextStore for RedisContextStore {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("user:*", "user:123"));
+        assert!(!glob_match("user:*", "session:123"));
+        assert!(glob_match("*:123", "user:123"));
+        assert!(glob_match("u*r:*", "user:123"));
+        assert!(glob_match("exact", "exact"));
+        assert!(!glob_match("exact", "exact2"));
+        assert!(!glob_match("exact", ""));
+        assert!(glob_match("*a*", "bar"));
+        assert!(!glob_match("*a*", "bor"));
+        assert!(glob_match("pre*suf", "prefixsuf"));
+        assert!(!glob_match("pre*suf", "prefix"));
+    }
+}